@@ -6,6 +6,22 @@ if ! command -v zig &> /dev/null; then
     echo "❌ Zig is not installed!"
     exit 1
 fi
+
+# --minimal only changes compiler settings: it optimizes for size instead of
+# speed and strips debug info. The code and features are the same as the
+# default build; nothing is trimmed from the result path.
+C_OPT="-O2"
+ZIG_OPT="ReleaseFast"
+STRIP=""
+for arg in "$@"; do
+    if [ "$arg" = "--minimal" ]; then
+        C_OPT="-Oz"
+        ZIG_OPT="ReleaseSmall"
+        STRIP="-fstrip"
+        echo "📦 Minimal build: optimizing for size"
+    fi
+done
+
 mkdir -p .build web
 echo "🔧 Compiling Lua C sources..."
 cd src/lua
@@ -16,45 +32,104 @@ for file in lapi lauxlib lbaselib lcode lcorolib lctype ldblib ldebug ldo ldump
       printf "  %-20s" "$file.c"
      zig cc -target wasm32-freestanding \
          -I.. \
-         -c -O2 $file.c -o ../../.build/${file}.o 2>&1 && echo "✓" || {
+         -c $C_OPT $file.c -o ../../.build/${file}.o 2>&1 && echo "✓" || {
          echo ""
          echo "❌ Failed to compile $file.c"
          exit 1
      }
 done
 printf "  %-20s" "lbigint.c"
-zig cc -target wasm32-freestanding -I.. -c -O2 lbigint.c -o ../../.build/lbigint.o 2>&1 && echo "✓" || {
+zig cc -target wasm32-freestanding -I.. -c $C_OPT lbigint.c -o ../../.build/lbigint.o 2>&1 && echo "✓" || {
     echo ""
     echo "❌ Failed to compile lbigint.c"
     exit 1
 }
 cd ../..
 echo "🔧 Compiling bignum wrapper..."
-zig build-obj -target wasm32-freestanding -O ReleaseFast -Isrc -Isrc/lua \
+zig build-obj -target wasm32-freestanding -O $ZIG_OPT -Isrc -Isrc/lua \
      src/bignum.zig -femit-bin=.build/bignum.o || { echo "❌ Failed to compile bignum.zig"; exit 1; }
 echo "✓"
 
 echo "🔧 Compiling libc stubs..."
-zig build-obj -target wasm32-freestanding -O ReleaseFast \
+zig build-obj -target wasm32-freestanding -O $ZIG_OPT \
      src/libc-stubs.zig -femit-bin=.build/libc-stubs.o || { echo "❌ Failed to compile libc-stubs.zig"; exit 1; }
 echo "✓"
 
 echo "🔧 Compiling Zig main..."
-zig build-exe -target wasm32-freestanding -O ReleaseFast \
+zig build-exe -target wasm32-freestanding -O $ZIG_OPT $STRIP \
      -Isrc -Isrc/lua \
      -fno-entry \
      --export=init \
      --export=compute \
      --export=get_buffer_ptr \
      --export=get_buffer_size \
+     --export=get_features \
+     --export=buffer_generation \
      --export=get_memory_stats \
      --export=run_gc \
      --export=attach_memory_table \
      --export=get_memory_table_id \
      --export=sync_external_table_counter \
+     --export=set_next_table_id \
+     --export=get_next_table_id \
      --export=set_memory_alias_enabled \
      --export=get_io_table_id \
      --export=clear_io_table \
+     --export=free_table \
+     --export=eval_resumable \
+     --export=resume \
+     --export=describe_last_result \
+     --export=set_memory_limit \
+     --export=eval_streaming \
+     --export=read_chunk \
+     --export=set_cache_capacity \
+     --export=clear_cache \
+     --export=get_compile_count \
+     --export=get_modules_table_id \
+     --export=attach_modules_table \
+     --export=set_output_limit \
+     --export=init_with_options \
+     --export=init_with_libs \
+     --export=snapshot \
+     --export=restore \
+     --export=eval_with_env \
+     --export=eval_json \
+     --export=get_last_error_code \
+     --export=set_ext_call_limit \
+     --export=set_write_buffering \
+     --export=eval_transactional \
+     --export=profile_eval \
+     --export=eval_batch \
+     --export=attach_table \
+     --export=peek_result_type \
+     --export=set_max_deserialize_depth \
+     --export=set_log_prefix \
+     --export=read_stderr \
+     --export=gc_collect \
+     --export=get_alloc_counters \
+     --export=list_globals \
+     --export=set_max_tables \
+     --export=set_strip_bytecode \
+     --export=set_deterministic_iteration \
+     --export=set_nil_semantics \
+     --export=set_compression \
+     --export=set_capture_errors \
+     --export=get_captured_errors \
+     --export=set_deadline_ms \
+     --export=read_error_value \
+     --export=read_error_message \
+     --export=recover_from_trap \
+     --export=prepare_input \
+     --export=eval \
+     --export=append_input \
+     --export=eval_staged \
+     --export=eval_named \
+     --export=eval_with_args \
+     --export=validate_syntax \
+     --export=check_imports \
+     --export=dump_table \
+     --export=load_table \
+     --export=dump_wire_format \
      src/main.zig \
      .build/libc-stubs.o \
      .build/bignum.o \
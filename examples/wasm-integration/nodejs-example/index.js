@@ -8,6 +8,7 @@
 // - Minimal dependencies (zero npm packages!)
 
 import { readFile } from 'fs/promises';
+import { randomFillSync } from 'crypto';
 import { fileURLToPath } from 'url';
 import { dirname, join } from 'path';
 
@@ -17,6 +18,33 @@ const __dirname = dirname(__filename);
 // External table storage
 const externalTables = new Map();
 
+// Expiry timestamps (ms) for entries written with js_ext_table_set_ttl
+const entryExpiry = new Map();
+
+// Keys arrive as raw bytes and must be stored losslessly: decoding "\xff"
+// as UTF-8 would turn it into U+FFFD and merge it with every other invalid
+// key. Valid UTF-8 stays a plain string; anything else gets a marker and a
+// byte-per-char body.
+const BINARY_KEY_MARKER = '\u0000bin:';
+const strictUtf8 = new TextDecoder('utf-8', { fatal: true, ignoreBOM: true });
+
+function decodeKey(bytes) {
+  try {
+    const key = strictUtf8.decode(bytes);
+    if (!key.startsWith(BINARY_KEY_MARKER)) return key;
+  } catch (e) {
+    // Not UTF-8; fall through to the binary form
+  }
+  return BINARY_KEY_MARKER + Buffer.from(bytes).toString('latin1');
+}
+
+function encodeKey(key) {
+  if (key.startsWith(BINARY_KEY_MARKER)) {
+    return Buffer.from(key.slice(BINARY_KEY_MARKER.length), 'latin1');
+  }
+  return Buffer.from(key, 'utf8');
+}
+
 /**
  * Get or create an external table by ID
  */
@@ -37,7 +65,7 @@ function jsExtTableSet(tableId, keyPtr, keyLen, valPtr, valLen) {
 
   // Read key from WASM memory
   const keyBytes = memoryView.slice(keyPtr, keyPtr + keyLen);
-  const key = new TextDecoder().decode(keyBytes);
+  const key = decodeKey(keyBytes);
 
   // Read value from WASM memory (keep as bytes)
   const value = new Uint8Array(memoryView.slice(valPtr, valPtr + valLen));
@@ -59,7 +87,7 @@ function jsExtTableGet(tableId, keyPtr, keyLen, valPtr, maxLen) {
 
   // Read key from WASM memory
   const keyBytes = memoryView.slice(keyPtr, keyPtr + keyLen);
-  const key = new TextDecoder().decode(keyBytes);
+  const key = decodeKey(keyBytes);
 
   // Get table and value
   const table = externalTables.get(tableId);
@@ -67,6 +95,13 @@ function jsExtTableGet(tableId, keyPtr, keyLen, valPtr, maxLen) {
     return -1; // Table not found
   }
 
+  const expiresAt = entryExpiry.get(tableId)?.get(key);
+  if (expiresAt !== undefined && Date.now() >= expiresAt) {
+    table.delete(key);
+    entryExpiry.get(tableId).delete(key);
+    return -1; // Expired
+  }
+
   const value = table.get(key);
   if (!value) {
     return -1; // Key not found
@@ -74,7 +109,7 @@ function jsExtTableGet(tableId, keyPtr, keyLen, valPtr, maxLen) {
 
   // Check buffer size
   if (value.length > maxLen) {
-    return -1; // Buffer too small
+    return -value.length; // Buffer too small: report the size needed
   }
 
   // Write value to WASM memory
@@ -93,7 +128,7 @@ function jsExtTableDelete(tableId, keyPtr, keyLen) {
 
   // Read key from WASM memory
   const keyBytes = memoryView.slice(keyPtr, keyPtr + keyLen);
-  const key = new TextDecoder().decode(keyBytes);
+  const key = decodeKey(keyBytes);
 
   // Get table and delete key
   const table = externalTables.get(tableId);
@@ -113,6 +148,21 @@ function jsExtTableSize(tableId) {
   return table ? table.size : 0;
 }
 
+/**
+ * Host function: js_ext_table_seqlen
+ * Get the length of the array part: the largest n with keys 1..n all set
+ */
+function jsExtTableSeqlen(tableId) {
+  const table = externalTables.get(tableId);
+  if (!table) return 0;
+
+  const expiry = entryExpiry.get(tableId);
+  const isLive = (key) => table.has(key) && !(expiry?.get(key) <= Date.now());
+  let n = 0;
+  while (isLive(String(n + 1))) n++;
+  return n;
+}
+
 /**
  * Host function: js_ext_table_keys
  * Get all keys from an external table (newline-separated)
@@ -127,9 +177,10 @@ function jsExtTableKeys(tableId, bufPtr, maxLen) {
   }
 
   // Serialize keys
-  const keys = Array.from(table.keys());
-  const serialized = keys.join('\n');
-  const bytes = new TextEncoder().encode(serialized);
+  const newline = Buffer.from('\n');
+  const bytes = Buffer.concat(
+    Array.from(table.keys(), encodeKey).flatMap((key, i) => (i === 0 ? [key] : [newline, key])),
+  );
 
   if (bytes.length > maxLen) {
     return -1; // Buffer too small
@@ -141,6 +192,313 @@ function jsExtTableKeys(tableId, bufPtr, maxLen) {
   return bytes.length;
 }
 
+/**
+ * Host function: js_ext_table_keys_page
+ * Write [u32 next cursor][newline-separated keys] for the keys from index
+ * cursor on that fit in maxLen; the next cursor is 0 after the last key
+ */
+function jsExtTableKeysPage(tableId, cursor, outPtr, maxLen) {
+  const table = externalTables.get(tableId);
+  if (!table) {
+    return -1; // Table not found
+  }
+
+  const keys = Array.from(table.keys());
+  const parts = [];
+  let size = 4;
+  let index = cursor;
+  while (index < keys.length) {
+    const key = encodeKey(keys[index]);
+    const needed = key.length + (parts.length > 0 ? 1 : 0);
+    if (size + needed > maxLen) break;
+    parts.push(key);
+    size += needed;
+    index++;
+  }
+  if (parts.length === 0 && index < keys.length) {
+    return -1; // A single key is larger than the buffer
+  }
+
+  const header = Buffer.alloc(4);
+  header.writeUInt32LE(index < keys.length ? index : 0);
+  const newline = Buffer.from('\n');
+  const page = Buffer.concat([header, ...parts.flatMap((key, i) => (i === 0 ? [key] : [newline, key]))]);
+
+  new Uint8Array(wasmInstance.exports.memory.buffer).set(page, outPtr);
+  return page.length;
+}
+
+/**
+ * Host function: js_ext_table_free
+ * Drop an external table and all of its entries
+ */
+function jsExtTableFree(tableId) {
+  return externalTables.delete(tableId) ? 0 : -1;
+}
+
+/**
+ * Host function: js_ext_table_rename
+ * Move a value from one key to another in a single call
+ */
+function jsExtTableRename(tableId, oldPtr, oldLen, newPtr, newLen) {
+  const memory = wasmInstance.exports.memory;
+  const memoryView = new Uint8Array(memory.buffer);
+
+  const table = externalTables.get(tableId);
+  if (!table) {
+    return -1; // Table not found
+  }
+
+  const oldKey = decodeKey(memoryView.slice(oldPtr, oldPtr + oldLen));
+  const newKey = decodeKey(memoryView.slice(newPtr, newPtr + newLen));
+  if (!table.has(oldKey)) {
+    return 0; // Nothing to move
+  }
+
+  const value = table.get(oldKey);
+  table.delete(oldKey);
+  table.set(newKey, value);
+
+  return 1; // Moved
+}
+
+/**
+ * Host function: js_ext_table_cas
+ * Store a new value only if the current one matches the expected bytes
+ */
+function jsExtTableCas(tableId, keyPtr, keyLen, expectedPtr, expectedLen, newPtr, newLen) {
+  const memory = wasmInstance.exports.memory;
+  const memoryView = new Uint8Array(memory.buffer);
+
+  const key = decodeKey(memoryView.slice(keyPtr, keyPtr + keyLen));
+  const expected = memoryView.slice(expectedPtr, expectedPtr + expectedLen);
+
+  // A missing key compares equal to a serialized nil
+  const table = getOrCreateTable(tableId);
+  const current = table.get(key) ?? new Uint8Array([0x00]);
+  const matches = current.length === expected.length && current.every((b, i) => b === expected[i]);
+  if (!matches) {
+    return 0; // Someone else changed it
+  }
+
+  const newValue = new Uint8Array(memoryView.slice(newPtr, newPtr + newLen));
+  if (newValue.length === 1 && newValue[0] === 0x00) {
+    table.delete(key);
+  } else {
+    table.set(key, newValue);
+  }
+
+  return 1; // Swapped
+}
+
+/**
+ * Host function: js_ext_table_set_ttl
+ * Store a value that reads as absent once ttlMs has passed
+ */
+function jsExtTableSetTtl(tableId, keyPtr, keyLen, valPtr, valLen, ttlMs) {
+  const memory = wasmInstance.exports.memory;
+  const memoryView = new Uint8Array(memory.buffer);
+
+  const key = decodeKey(memoryView.slice(keyPtr, keyPtr + keyLen));
+  const value = new Uint8Array(memoryView.slice(valPtr, valPtr + valLen));
+  getOrCreateTable(tableId).set(key, value);
+
+  // ttlMs arrives as a BigInt (wasm i64)
+  if (!entryExpiry.has(tableId)) {
+    entryExpiry.set(tableId, new Map());
+  }
+  entryExpiry.get(tableId).set(key, Date.now() + Number(ttlMs));
+
+  return 0; // Success
+}
+
+/**
+ * Host function: js_ext_table_has
+ * Report whether a key is stored without copying its value
+ */
+function jsExtTableHas(tableId, keyPtr, keyLen) {
+  const memory = wasmInstance.exports.memory;
+  const memoryView = new Uint8Array(memory.buffer);
+
+  const table = externalTables.get(tableId);
+  if (!table) {
+    return 0; // Table not found
+  }
+
+  const key = decodeKey(memoryView.slice(keyPtr, keyPtr + keyLen));
+  const expiresAt = entryExpiry.get(tableId)?.get(key);
+  if (expiresAt !== undefined && Date.now() >= expiresAt) {
+    table.delete(key);
+    entryExpiry.get(tableId).delete(key);
+    return 0; // Expired
+  }
+
+  return table.has(key) ? 1 : 0;
+}
+
+/**
+ * Host function: js_ext_table_value_len
+ * Report the byte length of a stored value without copying it
+ */
+function jsExtTableValueLen(tableId, keyPtr, keyLen) {
+  const memory = wasmInstance.exports.memory;
+  const memoryView = new Uint8Array(memory.buffer);
+
+  const table = externalTables.get(tableId);
+  if (!table) {
+    return -1; // Table not found
+  }
+
+  const key = decodeKey(memoryView.slice(keyPtr, keyPtr + keyLen));
+  const expiresAt = entryExpiry.get(tableId)?.get(key);
+  if (expiresAt !== undefined && Date.now() >= expiresAt) {
+    table.delete(key);
+    entryExpiry.get(tableId).delete(key);
+    return -1; // Expired
+  }
+
+  const value = table.get(key);
+  return value === undefined ? -1 : value.length;
+}
+
+/**
+ * Host function: js_ext_table_swap
+ * Exchange the values of two keys; a missing key counts as nil
+ */
+function jsExtTableSwap(tableId, aPtr, aLen, bPtr, bLen) {
+  const memory = wasmInstance.exports.memory;
+  const memoryView = new Uint8Array(memory.buffer);
+
+  const table = getOrCreateTable(tableId);
+  const keyA = decodeKey(memoryView.slice(aPtr, aPtr + aLen));
+  const keyB = decodeKey(memoryView.slice(bPtr, bPtr + bLen));
+  const valueA = table.get(keyA);
+  const valueB = table.get(keyB);
+
+  if (valueB === undefined) table.delete(keyA); else table.set(keyA, valueB);
+  if (valueA === undefined) table.delete(keyB); else table.set(keyB, valueA);
+  entryExpiry.get(tableId)?.delete(keyA);
+  entryExpiry.get(tableId)?.delete(keyB);
+
+  return 0; // Success
+}
+
+/**
+ * Host function: js_ext_table_clone
+ * Copy every entry of one table into a new table id for ext.clone(proxy)
+ */
+function jsExtTableClone(srcId, dstId) {
+  externalTables.set(dstId, new Map(getOrCreateTable(srcId)));
+  const expiry = entryExpiry.get(srcId);
+  if (expiry) entryExpiry.set(dstId, new Map(expiry));
+
+  return 0; // Success
+}
+
+/**
+ * Host function: js_ext_table_bulk_dump
+ * Write every live entry as [u32 key length][key][u32 value length][value]
+ */
+function jsExtTableBulkDump(tableId, outPtr, maxLen) {
+  const table = externalTables.get(tableId);
+  if (!table) {
+    return -1; // Table not found
+  }
+
+  const records = [];
+  for (const [key, value] of table) {
+    const expiresAt = entryExpiry.get(tableId)?.get(key);
+    if (expiresAt !== undefined && Date.now() >= expiresAt) continue;
+
+    const keyBytes = encodeKey(key);
+    const keyLen = Buffer.alloc(4);
+    keyLen.writeUInt32LE(keyBytes.length);
+    const valueLen = Buffer.alloc(4);
+    valueLen.writeUInt32LE(value.length);
+    records.push(keyLen, keyBytes, valueLen, value);
+  }
+
+  const dump = Buffer.concat(records);
+  if (dump.length > maxLen) {
+    return -1; // Buffer too small
+  }
+  new Uint8Array(wasmInstance.exports.memory.buffer).set(dump, outPtr);
+
+  return dump.length;
+}
+
+/**
+ * Host function: js_ext_table_bulk_load
+ * Replace a table's entries with those of a dump
+ */
+function jsExtTableBulkLoad(tableId, inPtr, len) {
+  const memoryView = new Uint8Array(wasmInstance.exports.memory.buffer);
+  const dump = Buffer.from(memoryView.slice(inPtr, inPtr + len));
+
+  const table = new Map();
+  let pos = 0;
+  while (pos < len) {
+    const keyLen = dump.readUInt32LE(pos);
+    const key = decodeKey(dump.subarray(pos + 4, pos + 4 + keyLen));
+    pos += 4 + keyLen;
+    const valueLen = dump.readUInt32LE(pos);
+    table.set(key, new Uint8Array(dump.subarray(pos + 4, pos + 4 + valueLen)));
+    pos += 4 + valueLen;
+  }
+  externalTables.set(tableId, table);
+  entryExpiry.delete(tableId);
+
+  return table.size;
+}
+
+/**
+ * Host function: js_ext_table_delete_prefix
+ * Delete every key starting with the given bytes, for ext.delete_prefix
+ */
+function jsExtTableDeletePrefix(tableId, prefixPtr, prefixLen) {
+  const table = externalTables.get(tableId);
+  if (!table) {
+    return -1; // Table not found
+  }
+
+  const memoryView = new Uint8Array(wasmInstance.exports.memory.buffer);
+  const prefix = Buffer.from(memoryView.slice(prefixPtr, prefixPtr + prefixLen));
+
+  // Keep only the keys that don't match
+  const kept = new Map();
+  let deleted = 0;
+  for (const [key, value] of table) {
+    const keyBytes = Buffer.from(encodeKey(key));
+    if (keyBytes.length >= prefix.length && keyBytes.subarray(0, prefix.length).equals(prefix)) {
+      entryExpiry.get(tableId)?.delete(key);
+      deleted++;
+    } else {
+      kept.set(key, value);
+    }
+  }
+  externalTables.set(tableId, kept);
+
+  return deleted;
+}
+
+/**
+ * Host function: js_host_call
+ * Backs host.call(name, ...) in Lua; this example registers no functions
+ */
+function jsHostCall(namePtr, nameLen, argsPtr, argsLen, outPtr, maxLen) {
+  return -1; // No such function
+}
+
+/**
+ * Host function: js_random_bytes
+ * Fill len bytes with OS entropy for cu.random_bytes(n) and math.random
+ */
+function jsRandomBytes(bufPtr, len) {
+  const memory = wasmInstance.exports.memory;
+  randomFillSync(new Uint8Array(memory.buffer, bufPtr, len));
+  return 0; // Success
+}
+
 // Global WASM instance (for host functions to access)
 let wasmInstance = null;
 
@@ -295,7 +653,22 @@ async function main() {
       js_ext_table_get: jsExtTableGet,
       js_ext_table_delete: jsExtTableDelete,
       js_ext_table_size: jsExtTableSize,
+      js_ext_table_seqlen: jsExtTableSeqlen,
       js_ext_table_keys: jsExtTableKeys,
+      js_ext_table_keys_page: jsExtTableKeysPage,
+      js_ext_table_free: jsExtTableFree,
+      js_ext_table_rename: jsExtTableRename,
+      js_ext_table_cas: jsExtTableCas,
+      js_ext_table_set_ttl: jsExtTableSetTtl,
+      js_ext_table_has: jsExtTableHas,
+      js_ext_table_value_len: jsExtTableValueLen,
+      js_ext_table_swap: jsExtTableSwap,
+      js_ext_table_clone: jsExtTableClone,
+      js_ext_table_bulk_dump: jsExtTableBulkDump,
+      js_ext_table_bulk_load: jsExtTableBulkLoad,
+      js_ext_table_delete_prefix: jsExtTableDeletePrefix,
+      js_host_call: jsHostCall,
+      js_random_bytes: jsRandomBytes,
     },
   };
 
@@ -0,0 +1,167 @@
+// Reference decoder for the compute() return protocol; see wire.rs for the
+// layout. Function bytecode, C function refs, plain-text markers and
+// anything else this decoder doesn't interpret are returned as raw bytes.
+
+use std::fmt;
+
+use crate::wire::{
+    read_u32, read_u64, TABLE_FLAG_METATABLE, TAG_BOOLEAN, TAG_FLOAT, TAG_HANDLE, TAG_INTEGER,
+    TAG_NIL, TAG_STRING, TAG_TABLE_REF,
+};
+
+/// A decoded return value
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecodedValue {
+    Nil,
+    Boolean(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+    TableRef { id: u32, metatable_id: Option<u32> },
+    Handle(u64),
+    /// Bytes this decoder doesn't interpret, or a truncated value
+    Raw(Vec<u8>),
+}
+
+/// Captured print output plus the decoded return value
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedResult {
+    pub output: String,
+    pub value: DecodedValue,
+}
+
+/// Decode a single tagged value
+pub fn decode_value(bytes: &[u8]) -> DecodedValue {
+    let decoded = match bytes.split_first() {
+        None => Some(DecodedValue::Nil),
+        Some((&TAG_NIL, _)) => Some(DecodedValue::Nil),
+        Some((&TAG_BOOLEAN, rest)) => rest.first().map(|b| DecodedValue::Boolean(*b != 0)),
+        Some((&TAG_INTEGER, rest)) => read_u64(rest).map(|n| DecodedValue::Integer(n as i64)),
+        Some((&TAG_FLOAT, rest)) => read_u64(rest).map(|n| DecodedValue::Float(f64::from_bits(n))),
+        Some((&TAG_STRING, rest)) => read_u32(rest).and_then(|len| {
+            let data = rest.get(4..4 + len as usize)?;
+            Some(DecodedValue::String(String::from_utf8_lossy(data).into_owned()))
+        }),
+        Some((&TAG_TABLE_REF, rest)) => read_u32(rest).map(|id| {
+            let metatable_id = match rest.get(4) {
+                Some(flags) if flags & TABLE_FLAG_METATABLE != 0 => rest.get(5..).and_then(read_u32),
+                _ => None,
+            };
+            DecodedValue::TableRef { id, metatable_id }
+        }),
+        Some((&TAG_HANDLE, rest)) => read_u64(rest).map(DecodedValue::Handle),
+        Some(_) => None,
+    };
+
+    decoded.unwrap_or_else(|| DecodedValue::Raw(bytes.to_vec()))
+}
+
+/// Decode the bytes of a successful compute() call
+pub fn decode_result(bytes: &[u8]) -> DecodedResult {
+    let output_len = read_u32(bytes).unwrap_or(0) as usize;
+    let output_end = (4 + output_len).min(bytes.len());
+    let output = bytes
+        .get(4..output_end)
+        .map(|o| String::from_utf8_lossy(o).into_owned())
+        .unwrap_or_default();
+
+    DecodedResult {
+        output,
+        value: decode_value(bytes.get(output_end..).unwrap_or(&[])),
+    }
+}
+
+impl fmt::Display for DecodedValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodedValue::Nil => write!(f, "nil"),
+            DecodedValue::Boolean(b) => write!(f, "{}", b),
+            DecodedValue::Integer(n) => write!(f, "{}", n),
+            DecodedValue::Float(n) => write!(f, "{}", n),
+            DecodedValue::String(s) => write!(f, "'{}'", s),
+            DecodedValue::TableRef { id, metatable_id: Some(mt) } => {
+                write!(f, "table #{} (metatable #{})", id, mt)
+            }
+            DecodedValue::TableRef { id, metatable_id: None } => write!(f, "table #{}", id),
+            DecodedValue::Handle(h) => write!(f, "handle: {}", h),
+            DecodedValue::Raw(bytes) => write!(f, "{} raw bytes", bytes.len()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_output(output: &str, value: &[u8]) -> Vec<u8> {
+        let mut bytes = (output.len() as u32).to_le_bytes().to_vec();
+        bytes.extend_from_slice(output.as_bytes());
+        bytes.extend_from_slice(value);
+        bytes
+    }
+
+    #[test]
+    fn decodes_nil() {
+        assert_eq!(decode_value(&[0x00]), DecodedValue::Nil);
+        assert_eq!(decode_value(&[]), DecodedValue::Nil);
+    }
+
+    #[test]
+    fn decodes_booleans() {
+        assert_eq!(decode_value(&[0x01, 1]), DecodedValue::Boolean(true));
+        assert_eq!(decode_value(&[0x01, 0]), DecodedValue::Boolean(false));
+    }
+
+    #[test]
+    fn decodes_integers() {
+        let mut bytes = vec![0x02];
+        bytes.extend_from_slice(&(-42i64).to_le_bytes());
+        assert_eq!(decode_value(&bytes), DecodedValue::Integer(-42));
+    }
+
+    #[test]
+    fn decodes_floats() {
+        let mut bytes = vec![0x03];
+        bytes.extend_from_slice(&3.5f64.to_le_bytes());
+        assert_eq!(decode_value(&bytes), DecodedValue::Float(3.5));
+    }
+
+    #[test]
+    fn decodes_strings() {
+        let mut bytes = vec![0x04];
+        bytes.extend_from_slice(&5u32.to_le_bytes());
+        bytes.extend_from_slice(b"hello");
+        assert_eq!(decode_value(&bytes), DecodedValue::String("hello".into()));
+    }
+
+    #[test]
+    fn decodes_table_refs() {
+        let mut bytes = vec![0x07];
+        bytes.extend_from_slice(&3u32.to_le_bytes());
+        assert_eq!(decode_value(&bytes), DecodedValue::TableRef { id: 3, metatable_id: None });
+
+        bytes.push(0x01);
+        bytes.extend_from_slice(&9u32.to_le_bytes());
+        assert_eq!(decode_value(&bytes), DecodedValue::TableRef { id: 3, metatable_id: Some(9) });
+    }
+
+    #[test]
+    fn decodes_handles() {
+        let mut bytes = vec![0x08];
+        bytes.extend_from_slice(&77u64.to_le_bytes());
+        assert_eq!(decode_value(&bytes), DecodedValue::Handle(77));
+    }
+
+    #[test]
+    fn keeps_unknown_and_truncated_values_raw() {
+        assert_eq!(decode_value(b"table"), DecodedValue::Raw(b"table".to_vec()));
+        assert_eq!(decode_value(&[0x02, 1, 2]), DecodedValue::Raw(vec![0x02, 1, 2]));
+    }
+
+    #[test]
+    fn splits_output_from_value() {
+        let decoded = decode_result(&with_output("hi\n", &[0x01, 1]));
+        assert_eq!(decoded.output, "hi\n");
+        assert_eq!(decoded.value, DecodedValue::Boolean(true));
+    }
+}
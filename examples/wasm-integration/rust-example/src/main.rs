@@ -7,14 +7,25 @@
 // - Executing Lua code and handling results
 // - Proper error handling and memory management
 
+mod decode;
+mod wire;
+
 use anyhow::{anyhow, Result};
+use decode::decode_result;
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
 use wasmtime::*;
+use wire::{decode_table_dump, encode_table_dump, read_error};
 
 /// External table storage using HashMap
-/// Each table ID maps to a HashMap of key-value pairs
-type ExternalTables = Arc<Mutex<HashMap<u32, HashMap<String, Vec<u8>>>>>;
+/// Each table ID maps to a HashMap of key-value pairs. Keys are raw bytes:
+/// Lua strings are binary, and a lossy UTF-8 conversion would merge distinct
+/// keys such as "\xff" and "\xfe"
+type ExternalTables = Arc<Mutex<HashMap<u32, HashMap<Vec<u8>, Vec<u8>>>>>;
+
+/// Expiry times (ms since the epoch) of entries stored with js_ext_table_set_ttl
+type Expiries = Arc<Mutex<HashMap<u32, HashMap<Vec<u8>, i64>>>>;
 
 /// Main entry point
 fn main() -> Result<()> {
@@ -85,7 +96,7 @@ fn main() -> Result<()> {
     for (table_id, table) in tables_lock.iter() {
         println!("Table ID {}: {} entries", table_id, table.len());
         for (key, value) in table.iter() {
-            println!("  '{}': {} bytes", key, value.len());
+            println!("  '{}': {} bytes", String::from_utf8_lossy(key), value.len());
         }
     }
 
@@ -112,10 +123,26 @@ fn load_module(engine: &Engine) -> Result<Module> {
     Err(anyhow!("Could not find lua.wasm. Please copy it to the current directory."))
 }
 
+/// Host clock in milliseconds since the Unix epoch
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as i64)
+}
+
 /// Add all required host functions to the linker
 fn add_host_functions(linker: &mut Linker<()>, tables: ExternalTables) -> Result<()> {
+    let expiries = Expiries::default();
+
+    // js_time_now: Host clock for deadlines; the module only uses differences,
+    // so truncating to 32 bits is fine
+    linker.func_wrap("env", "js_time_now", |_caller: Caller<'_, ()>| -> i32 {
+        now_ms() as i32
+    })?;
+
     // js_ext_table_set: Store a key-value pair
     let tables_set = tables.clone();
+    let expiries_set = expiries.clone();
     linker.func_wrap(
         "env",
         "js_ext_table_set",
@@ -134,7 +161,7 @@ fn add_host_functions(linker: &mut Linker<()>, tables: ExternalTables) -> Result
             let key_bytes = memory.data(&caller)
                 .get(key_ptr as usize..(key_ptr + key_len) as usize)
                 .expect("key read");
-            let key = String::from_utf8_lossy(key_bytes).to_string();
+            let key = key_bytes.to_vec();
 
             // Read value from WASM memory
             let val_bytes = memory.data(&caller)
@@ -142,9 +169,12 @@ fn add_host_functions(linker: &mut Linker<()>, tables: ExternalTables) -> Result
                 .expect("value read")
                 .to_vec();
 
-            // Store in external table
+            // Store in external table; a plain write clears any expiry
+            if let Some(expiry) = expiries_set.lock().unwrap().get_mut(&table_id) {
+                expiry.remove(&key);
+            }
             let mut tables_lock = tables_set.lock().unwrap();
-            let table = tables_lock.entry(table_id).or_insert_with(HashMap::new);
+            let table = tables_lock.entry(table_id).or_default();
             table.insert(key, val_bytes);
 
             0 // Success
@@ -153,6 +183,7 @@ fn add_host_functions(linker: &mut Linker<()>, tables: ExternalTables) -> Result
 
     // js_ext_table_get: Retrieve a value by key
     let tables_get = tables.clone();
+    let expiries_get = expiries.clone();
     linker.func_wrap(
         "env",
         "js_ext_table_get",
@@ -171,7 +202,15 @@ fn add_host_functions(linker: &mut Linker<()>, tables: ExternalTables) -> Result
             let key_bytes = memory.data(&caller)
                 .get(key_ptr as usize..(key_ptr + key_len) as usize)
                 .expect("key read");
-            let key = String::from_utf8_lossy(key_bytes).to_string();
+            let key = key_bytes.to_vec();
+
+            // Expired entries read as missing
+            let expires_at = expiries_get.lock().unwrap()
+                .get(&table_id)
+                .and_then(|expiry| expiry.get(&key).copied());
+            if expires_at.is_some_and(|at| now_ms() >= at) {
+                return -1;
+            }
 
             // Lookup in external table
             let tables_lock = tables_get.lock().unwrap();
@@ -187,7 +226,8 @@ fn add_host_functions(linker: &mut Linker<()>, tables: ExternalTables) -> Result
 
             // Check buffer size
             if value.len() > max_len as usize {
-                return -1; // Buffer too small
+                // Buffer too small: report the size needed
+                return -i32::try_from(value.len()).unwrap_or(i32::MAX);
             }
 
             // Write value to WASM memory
@@ -202,6 +242,7 @@ fn add_host_functions(linker: &mut Linker<()>, tables: ExternalTables) -> Result
 
     // js_ext_table_delete: Delete a key
     let tables_delete = tables.clone();
+    let expiries_delete = expiries.clone();
     linker.func_wrap(
         "env",
         "js_ext_table_delete",
@@ -218,9 +259,12 @@ fn add_host_functions(linker: &mut Linker<()>, tables: ExternalTables) -> Result
             let key_bytes = memory.data(&caller)
                 .get(key_ptr as usize..(key_ptr + key_len) as usize)
                 .expect("key read");
-            let key = String::from_utf8_lossy(key_bytes).to_string();
+            let key = key_bytes.to_vec();
 
-            // Delete from external table
+            // Delete from external table, along with any expiry
+            if let Some(expiry) = expiries_delete.lock().unwrap().get_mut(&table_id) {
+                expiry.remove(&key);
+            }
             let mut tables_lock = tables_delete.lock().unwrap();
             if let Some(table) = tables_lock.get_mut(&table_id) {
                 table.remove(&key);
@@ -245,6 +289,31 @@ fn add_host_functions(linker: &mut Linker<()>, tables: ExternalTables) -> Result
         },
     )?;
 
+    // js_ext_table_seqlen: Length of the array part (largest n with keys 1..n)
+    let tables_seqlen = tables.clone();
+    linker.func_wrap(
+        "env",
+        "js_ext_table_seqlen",
+        move |_caller: Caller<'_, ()>, table_id: u32| -> i32 {
+            let tables_lock = tables_seqlen.lock().unwrap();
+            let table = match tables_lock.get(&table_id) {
+                Some(t) => t,
+                None => return 0,
+            };
+
+            // Integer keys are stored as decimal text. A tombstone (the
+            // serialized nil, 0x00) reads as nil, so it ends the run.
+            let mut n: u64 = 0;
+            while table
+                .get((n + 1).to_string().as_bytes())
+                .is_some_and(|value| value.as_slice() != [0x00])
+            {
+                n += 1;
+            }
+            n as i32
+        },
+    )?;
+
     // js_ext_table_keys: Get all keys (serialized)
     let tables_keys = tables.clone();
     linker.func_wrap(
@@ -262,8 +331,8 @@ fn add_host_functions(linker: &mut Linker<()>, tables: ExternalTables) -> Result
             };
 
             // Serialize keys (simple newline-separated format)
-            let keys: Vec<&str> = table.keys().map(|s| s.as_str()).collect();
-            let serialized = keys.join("\n");
+            let keys: Vec<&[u8]> = table.keys().map(|k| k.as_slice()).collect();
+            let serialized = keys.join(&b'\n');
 
             if serialized.len() > max_len as usize {
                 return -1; // Buffer too small
@@ -277,12 +346,442 @@ fn add_host_functions(linker: &mut Linker<()>, tables: ExternalTables) -> Result
             memory.data_mut(&mut caller)
                 .get_mut(buf_ptr as usize..(buf_ptr as usize + serialized.len()))
                 .expect("keys write")
-                .copy_from_slice(serialized.as_bytes());
+                .copy_from_slice(&serialized);
 
             serialized.len() as i32
         },
     )?;
 
+    // js_ext_table_keys_page: One page of keys for pairs(). The cursor is an
+    // index into the HashMap's iteration order, which holds while keys are
+    // only overwritten.
+    let tables_keys_page = tables.clone();
+    linker.func_wrap(
+        "env",
+        "js_ext_table_keys_page",
+        move |mut caller: Caller<'_, ()>,
+              table_id: u32,
+              cursor: u32,
+              out_ptr: i32,
+              max_len: i32|
+              -> i32 {
+            let tables_lock = tables_keys_page.lock().unwrap();
+            let table = match tables_lock.get(&table_id) {
+                Some(t) => t,
+                None => return -1,
+            };
+
+            // [u32 next cursor][newline-separated keys]
+            let mut page = vec![0u8; 4];
+            let mut index = cursor as usize;
+            for key in table.keys().skip(index) {
+                let separator = usize::from(page.len() > 4);
+                if page.len() + separator + key.len() > max_len as usize {
+                    break;
+                }
+                if separator == 1 {
+                    page.push(b'\n');
+                }
+                page.extend_from_slice(key);
+                index += 1;
+            }
+            if page.len() == 4 && index < table.len() {
+                return -1; // A single key is larger than the buffer
+            }
+            let next_cursor = if index < table.len() { index as u32 } else { 0 };
+            page[..4].copy_from_slice(&next_cursor.to_le_bytes());
+
+            let memory = caller.get_export("memory")
+                .and_then(|e| e.into_memory())
+                .expect("memory export");
+            memory.data_mut(&mut caller)
+                .get_mut(out_ptr as usize..(out_ptr as usize + page.len()))
+                .expect("keys write")
+                .copy_from_slice(&page);
+
+            page.len() as i32
+        },
+    )?;
+
+    // js_ext_table_has: Check for a key without copying its value
+    let tables_has = tables.clone();
+    linker.func_wrap(
+        "env",
+        "js_ext_table_has",
+        move |mut caller: Caller<'_, ()>,
+              table_id: u32,
+              key_ptr: i32,
+              key_len: i32|
+              -> i32 {
+            let memory = caller.get_export("memory")
+                .and_then(|e| e.into_memory())
+                .expect("memory export");
+
+            // Read key from WASM memory
+            let key_bytes = memory.data(&caller)
+                .get(key_ptr as usize..(key_ptr + key_len) as usize)
+                .expect("key read");
+
+            let tables_lock = tables_has.lock().unwrap();
+            let found = tables_lock
+                .get(&table_id)
+                .is_some_and(|t| t.contains_key(key_bytes));
+            found as i32
+        },
+    )?;
+
+    // js_ext_table_value_len: Byte length of a stored value, without copying it
+    let tables_value_len = tables.clone();
+    linker.func_wrap(
+        "env",
+        "js_ext_table_value_len",
+        move |mut caller: Caller<'_, ()>,
+              table_id: u32,
+              key_ptr: i32,
+              key_len: i32|
+              -> i32 {
+            let memory = caller.get_export("memory")
+                .and_then(|e| e.into_memory())
+                .expect("memory export");
+
+            // Read key from WASM memory
+            let key_bytes = memory.data(&caller)
+                .get(key_ptr as usize..(key_ptr + key_len) as usize)
+                .expect("key read");
+
+            let tables_lock = tables_value_len.lock().unwrap();
+            tables_lock
+                .get(&table_id)
+                .and_then(|t| t.get(key_bytes))
+                .map_or(-1, |value| i32::try_from(value.len()).unwrap_or(i32::MAX))
+        },
+    )?;
+
+    // js_ext_table_swap: Exchange two values; a missing key counts as nil
+    let tables_swap = tables.clone();
+    linker.func_wrap(
+        "env",
+        "js_ext_table_swap",
+        move |mut caller: Caller<'_, ()>,
+              table_id: u32,
+              a_ptr: i32,
+              a_len: i32,
+              b_ptr: i32,
+              b_len: i32|
+              -> i32 {
+            let memory = caller.get_export("memory")
+                .and_then(|e| e.into_memory())
+                .expect("memory export");
+
+            // Read both keys from WASM memory
+            let data = memory.data(&caller);
+            let key_a = data
+                .get(a_ptr as usize..(a_ptr + a_len) as usize)
+                .expect("key read")
+                .to_vec();
+            let key_b = data
+                .get(b_ptr as usize..(b_ptr + b_len) as usize)
+                .expect("key read")
+                .to_vec();
+
+            let mut tables_lock = tables_swap.lock().unwrap();
+            let table = tables_lock.entry(table_id).or_default();
+            let value_a = table.remove(&key_a);
+            let value_b = table.remove(&key_b);
+            if let Some(v) = value_b {
+                table.insert(key_a, v);
+            }
+            if let Some(v) = value_a {
+                table.insert(key_b, v);
+            }
+
+            0 // Success
+        },
+    )?;
+
+    // js_ext_table_clone: Copy a whole table to a new id
+    let tables_clone = tables.clone();
+    linker.func_wrap(
+        "env",
+        "js_ext_table_clone",
+        move |_caller: Caller<'_, ()>, src_id: u32, dst_id: u32| -> i32 {
+            let mut tables_lock = tables_clone.lock().unwrap();
+            let copy = tables_lock.get(&src_id).cloned().unwrap_or_default();
+            tables_lock.insert(dst_id, copy);
+            0 // Success
+        },
+    )?;
+
+    // js_ext_table_free: Drop a table and all of its entries
+    let tables_free = tables.clone();
+    let expiries_free = expiries.clone();
+    linker.func_wrap(
+        "env",
+        "js_ext_table_free",
+        move |_caller: Caller<'_, ()>, table_id: u32| -> i32 {
+            expiries_free.lock().unwrap().remove(&table_id);
+            match tables_free.lock().unwrap().remove(&table_id) {
+                Some(_) => 0,
+                None => -1, // Table not found
+            }
+        },
+    )?;
+
+    // js_ext_table_rename: Move a value to a new key in one call
+    let tables_rename = tables.clone();
+    linker.func_wrap(
+        "env",
+        "js_ext_table_rename",
+        move |mut caller: Caller<'_, ()>,
+              table_id: u32,
+              old_ptr: i32,
+              old_len: i32,
+              new_ptr: i32,
+              new_len: i32|
+              -> i32 {
+            let memory = caller.get_export("memory")
+                .and_then(|e| e.into_memory())
+                .expect("memory export");
+
+            // Read both keys from WASM memory
+            let data = memory.data(&caller);
+            let old_key = data
+                .get(old_ptr as usize..(old_ptr + old_len) as usize)
+                .expect("key read")
+                .to_vec();
+            let new_key = data
+                .get(new_ptr as usize..(new_ptr + new_len) as usize)
+                .expect("key read")
+                .to_vec();
+
+            let mut tables_lock = tables_rename.lock().unwrap();
+            let Some(table) = tables_lock.get_mut(&table_id) else {
+                return -1; // Table not found
+            };
+            match table.remove(&old_key) {
+                Some(value) => {
+                    table.insert(new_key, value);
+                    1
+                }
+                None => 0, // Nothing to move
+            }
+        },
+    )?;
+
+    // js_ext_table_cas: Store a value only if the current one matches. The
+    // table lock makes the compare and the store atomic.
+    let tables_cas = tables.clone();
+    linker.func_wrap(
+        "env",
+        "js_ext_table_cas",
+        move |mut caller: Caller<'_, ()>,
+              table_id: u32,
+              key_ptr: i32,
+              key_len: i32,
+              expected_ptr: i32,
+              expected_len: i32,
+              new_ptr: i32,
+              new_len: i32|
+              -> i32 {
+            let memory = caller.get_export("memory")
+                .and_then(|e| e.into_memory())
+                .expect("memory export");
+
+            let data = memory.data(&caller);
+            let key = data
+                .get(key_ptr as usize..(key_ptr + key_len) as usize)
+                .expect("key read")
+                .to_vec();
+            let expected = data
+                .get(expected_ptr as usize..(expected_ptr + expected_len) as usize)
+                .expect("expected read");
+            let new_value = data
+                .get(new_ptr as usize..(new_ptr + new_len) as usize)
+                .expect("value read")
+                .to_vec();
+
+            // A missing key matches a serialized nil, and storing nil deletes
+            const NIL: &[u8] = &[0x00];
+            let mut tables_lock = tables_cas.lock().unwrap();
+            let table = tables_lock.entry(table_id).or_default();
+            let current = table.get(&key).map_or(NIL, Vec::as_slice);
+            if current != expected {
+                return 0;
+            }
+            if new_value == NIL {
+                table.remove(&key);
+            } else {
+                table.insert(key, new_value);
+            }
+            1
+        },
+    )?;
+
+    // js_ext_table_set_ttl: Store a value that reads as missing after ttl_ms
+    let tables_set_ttl = tables.clone();
+    let expiries_set_ttl = expiries.clone();
+    linker.func_wrap(
+        "env",
+        "js_ext_table_set_ttl",
+        move |mut caller: Caller<'_, ()>,
+              table_id: u32,
+              key_ptr: i32,
+              key_len: i32,
+              val_ptr: i32,
+              val_len: i32,
+              ttl_ms: i64|
+              -> i32 {
+            let memory = caller.get_export("memory")
+                .and_then(|e| e.into_memory())
+                .expect("memory export");
+
+            let data = memory.data(&caller);
+            let key = data
+                .get(key_ptr as usize..(key_ptr + key_len) as usize)
+                .expect("key read")
+                .to_vec();
+            let val_bytes = data
+                .get(val_ptr as usize..(val_ptr + val_len) as usize)
+                .expect("value read")
+                .to_vec();
+
+            expiries_set_ttl.lock().unwrap()
+                .entry(table_id)
+                .or_default()
+                .insert(key.clone(), now_ms().saturating_add(ttl_ms));
+            tables_set_ttl.lock().unwrap()
+                .entry(table_id)
+                .or_default()
+                .insert(key, val_bytes);
+
+            0 // Success
+        },
+    )?;
+
+    // js_ext_table_bulk_dump: Write a whole table to WASM memory in one call
+    let tables_dump = tables.clone();
+    linker.func_wrap(
+        "env",
+        "js_ext_table_bulk_dump",
+        move |mut caller: Caller<'_, ()>,
+              table_id: u32,
+              out_ptr: i32,
+              max_len: i32|
+              -> i32 {
+            let tables_lock = tables_dump.lock().unwrap();
+            let table = match tables_lock.get(&table_id) {
+                Some(t) => t,
+                None => return -1,
+            };
+
+            let dump = encode_table_dump(table.iter().map(|(k, v)| (k.as_slice(), v.as_slice())));
+            if dump.len() > max_len as usize {
+                return -1; // Buffer too small
+            }
+
+            let memory = caller.get_export("memory")
+                .and_then(|e| e.into_memory())
+                .expect("memory export");
+            memory.data_mut(&mut caller)
+                .get_mut(out_ptr as usize..(out_ptr as usize + dump.len()))
+                .expect("dump write")
+                .copy_from_slice(&dump);
+
+            dump.len() as i32
+        },
+    )?;
+
+    // js_ext_table_bulk_load: Replace a table with the entries of a dump
+    let tables_load = tables.clone();
+    linker.func_wrap(
+        "env",
+        "js_ext_table_bulk_load",
+        move |mut caller: Caller<'_, ()>, table_id: u32, in_ptr: i32, len: i32| -> i32 {
+            let memory = caller.get_export("memory")
+                .and_then(|e| e.into_memory())
+                .expect("memory export");
+            let dump = memory.data(&caller)
+                .get(in_ptr as usize..(in_ptr + len) as usize)
+                .expect("dump read");
+
+            let Some(entries) = decode_table_dump(dump) else {
+                return -1;
+            };
+            let table: HashMap<Vec<u8>, Vec<u8>> = entries.into_iter().collect();
+            let count = table.len() as i32;
+            tables_load.lock().unwrap().insert(table_id, table);
+            count
+        },
+    )?;
+
+    // js_ext_table_delete_prefix: Delete every key starting with the given bytes
+    let tables_delete_prefix = tables.clone();
+    linker.func_wrap(
+        "env",
+        "js_ext_table_delete_prefix",
+        move |mut caller: Caller<'_, ()>,
+              table_id: u32,
+              prefix_ptr: i32,
+              prefix_len: i32|
+              -> i32 {
+            let memory = caller.get_export("memory")
+                .and_then(|e| e.into_memory())
+                .expect("memory export");
+
+            // Read prefix from WASM memory
+            let prefix = memory.data(&caller)
+                .get(prefix_ptr as usize..(prefix_ptr + prefix_len) as usize)
+                .expect("prefix read");
+
+            let mut tables_lock = tables_delete_prefix.lock().unwrap();
+            let Some(table) = tables_lock.get_mut(&table_id) else {
+                return -1; // Table not found
+            };
+            let before = table.len();
+            table.retain(|key, _| !key.starts_with(prefix));
+            (before - table.len()) as i32
+        },
+    )?;
+
+    // js_host_call: Backs host.call(name, ...); this example registers no functions
+    linker.func_wrap(
+        "env",
+        "js_host_call",
+        |_caller: Caller<'_, ()>,
+         _name_ptr: i32,
+         _name_len: i32,
+         _args_ptr: i32,
+         _args_len: i32,
+         _out_ptr: i32,
+         _max_len: i32|
+         -> i32 {
+            -1 // No such function
+        },
+    )?;
+
+    // js_random_bytes: Fill a buffer with OS entropy for cu.random_bytes(n)
+    linker.func_wrap(
+        "env",
+        "js_random_bytes",
+        |mut caller: Caller<'_, ()>, buf_ptr: i32, len: i32| -> i32 {
+            let memory = caller.get_export("memory")
+                .and_then(|e| e.into_memory())
+                .expect("memory export");
+
+            let buf = match memory.data_mut(&mut caller)
+                .get_mut(buf_ptr as usize..buf_ptr as usize + len as usize)
+            {
+                Some(buf) => buf,
+                None => return -1, // Out of bounds
+            };
+
+            match getrandom::getrandom(buf) {
+                Ok(()) => 0,
+                Err(_) => -1, // No entropy source
+            }
+        },
+    )?;
+
     Ok(())
 }
 
@@ -297,9 +796,9 @@ fn execute_lua(
     println!("Lua code: {}", code);
 
     // Get memory and compute function
-    let memory = instance.get_memory(store, "memory")
+    let memory = instance.get_memory(&mut *store, "memory")
         .ok_or_else(|| anyhow!("memory export not found"))?;
-    let compute = instance.get_typed_func::<(i32, i32), i32>(store, "compute")?;
+    let compute = instance.get_typed_func::<(i32, i32), i32>(&mut *store, "compute")?;
 
     // Write code to buffer
     let code_bytes = code.as_bytes();
@@ -307,66 +806,25 @@ fn execute_lua(
         return Err(anyhow!("Code too large for buffer"));
     }
 
-    memory.data_mut(store)[buffer_ptr..buffer_ptr + code_bytes.len()]
+    memory.data_mut(&mut *store)[buffer_ptr..buffer_ptr + code_bytes.len()]
         .copy_from_slice(code_bytes);
 
     // Execute
-    let result_len = compute.call(store, (buffer_ptr as i32, code_bytes.len() as i32))?;
+    let result_len = compute.call(&mut *store, (buffer_ptr as i32, code_bytes.len() as i32))?;
 
     // Handle result
-    if result_len < 0 {
-        // Error
-        let error_len = (-result_len - 1) as usize;
-        let error_bytes = &memory.data(store)[buffer_ptr..buffer_ptr + error_len];
-        let error_msg = String::from_utf8_lossy(error_bytes);
+    let buffer = &memory.data(&*store)[buffer_ptr..buffer_ptr + buffer_size];
+    if let Some(error_msg) = read_error(buffer, result_len) {
         println!("✗ Lua error: {}", error_msg);
     } else if result_len > 0 {
-        // Success - read result
-        let result_bytes = &memory.data(store)[buffer_ptr..buffer_ptr + result_len as usize];
-        
-        // First 4 bytes are output length
-        let output_len = u32::from_le_bytes([
-            result_bytes[0],
-            result_bytes[1],
-            result_bytes[2],
-            result_bytes[3],
-        ]) as usize;
-
-        if output_len > 0 {
-            let output = String::from_utf8_lossy(&result_bytes[4..4 + output_len]);
-            println!("Output: {}", output.trim());
-        }
+        // Success - decode captured output and the return value
+        let result_bytes = &memory.data(&*store)[buffer_ptr..buffer_ptr + result_len as usize];
+        let decoded = decode_result(result_bytes);
 
-        // Parse return value (simplified - just show bytes)
-        if result_bytes.len() > 4 + output_len {
-            let return_bytes = &result_bytes[4 + output_len..];
-            println!("✓ Result: {} bytes returned", return_bytes.len());
-            
-            // Try to parse simple number results
-            if return_bytes.len() >= 2 && return_bytes[0] == 0x03 {
-                // Type tag 0x03 = number
-                if return_bytes.len() >= 9 {
-                    let num_bytes = &return_bytes[1..9];
-                    let num = f64::from_le_bytes([
-                        num_bytes[0], num_bytes[1], num_bytes[2], num_bytes[3],
-                        num_bytes[4], num_bytes[5], num_bytes[6], num_bytes[7],
-                    ]);
-                    println!("  Number value: {}", num);
-                }
-            } else if return_bytes.len() >= 2 && return_bytes[0] == 0x04 {
-                // Type tag 0x04 = string
-                let str_len = u32::from_le_bytes([
-                    return_bytes[1],
-                    return_bytes[2],
-                    return_bytes[3],
-                    return_bytes[4],
-                ]) as usize;
-                if return_bytes.len() >= 5 + str_len {
-                    let s = String::from_utf8_lossy(&return_bytes[5..5 + str_len]);
-                    println!("  String value: '{}'", s);
-                }
-            }
+        if !decoded.output.is_empty() {
+            println!("Output: {}", decoded.output.trim());
         }
+        println!("✓ Result: {}", decoded.value);
     } else {
         println!("✓ No result");
     }
@@ -380,15 +838,15 @@ fn show_memory_stats(
     instance: &Instance,
     buffer_ptr: usize,
 ) -> Result<()> {
-    let memory = instance.get_memory(store, "memory")
+    let memory = instance.get_memory(&mut *store, "memory")
         .ok_or_else(|| anyhow!("memory export not found"))?;
-    let get_memory_stats = instance.get_typed_func::<i32, ()>(store, "get_memory_stats")?;
+    let get_memory_stats = instance.get_typed_func::<i32, ()>(&mut *store, "get_memory_stats")?;
 
     // Call get_memory_stats
-    get_memory_stats.call(store, buffer_ptr as i32)?;
+    get_memory_stats.call(&mut *store, buffer_ptr as i32)?;
 
     // Read stats structure (12 bytes: 3 × u32)
-    let stats_bytes = &memory.data(store)[buffer_ptr..buffer_ptr + 12];
+    let stats_bytes = &memory.data(&*store)[buffer_ptr..buffer_ptr + 12];
     
     let io_buffer_size = u32::from_le_bytes([
         stats_bytes[0], stats_bytes[1], stats_bytes[2], stats_bytes[3]
@@ -0,0 +1,247 @@
+// Wire format of the compute() return protocol
+//
+// This module has no dependencies so hosts can copy it as-is.
+//
+// A successful compute() returns the result length and leaves this in the
+// IO buffer:
+//   [4 bytes: output length (u32 LE)][output bytes][value]
+// where value starts with a one-byte type tag (TAG_*):
+//   0x00 nil
+//   0x01 boolean    1 byte (0 or 1)
+//   0x02 integer    8 bytes (i64 LE)
+//   0x03 float      8 bytes (f64 LE)
+//   0x04 string     4 bytes length (u32 LE) + UTF-8 bytes
+//   0x05 function   Lua bytecode
+//   0x06 function   C function reference
+//   0x07 table ref  4 bytes table id (u32 LE), then optionally a flags byte
+//                   (TABLE_FLAG_*) and, with TABLE_FLAG_METATABLE, a 4 byte
+//                   metatable id
+//   0x08 handle     8 bytes (u64 LE)
+// Values compute() can't encode are written as plain-text markers such as
+// "table" with no tag; peek_result_type() reports those as UNTAGGED.
+//
+// A negative return is an error: the message is at the start of the buffer
+// and the return value is -(message length + 1), so -1 is an empty message.
+
+// Not every host reads every tag
+#![allow(dead_code)]
+
+pub const TAG_NIL: u8 = 0x00;
+pub const TAG_BOOLEAN: u8 = 0x01;
+pub const TAG_INTEGER: u8 = 0x02;
+pub const TAG_FLOAT: u8 = 0x03;
+pub const TAG_STRING: u8 = 0x04;
+pub const TAG_FUNCTION_BYTECODE: u8 = 0x05;
+pub const TAG_FUNCTION_REF: u8 = 0x06;
+pub const TAG_TABLE_REF: u8 = 0x07;
+pub const TAG_HANDLE: u8 = 0x08;
+
+/// What peek_result_type() returns for a plain-text marker
+pub const UNTAGGED: u8 = 0xFF;
+
+/// Flags byte bit after a table ref id: a metatable id follows
+pub const TABLE_FLAG_METATABLE: u8 = 0x01;
+
+/// Flags byte bit on the value read_error_value() returns for a table the
+/// script raised with error()
+pub const TABLE_FLAG_ERROR: u8 = 0x02;
+
+pub(crate) fn read_u32(bytes: &[u8]) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(..4)?.try_into().ok()?))
+}
+
+pub(crate) fn read_u64(bytes: &[u8]) -> Option<u64> {
+    Some(u64::from_le_bytes(bytes.get(..8)?.try_into().ok()?))
+}
+
+/// Length of the error message for a negative compute() return, capped at
+/// buffer_size. None for non-negative returns.
+pub fn error_len(result: i32, buffer_size: usize) -> Option<usize> {
+    if result >= 0 {
+        return None;
+    }
+    // -(len + 1) == !len, which can't overflow even for i32::MIN
+    let len = usize::try_from(!result).unwrap_or(usize::MAX);
+    Some(len.min(buffer_size))
+}
+
+/// The tagged value of a successful compute(), skipping the captured
+/// output. buffer is the IO buffer and result what compute() returned.
+pub fn result_value(buffer: &[u8], result: i32) -> Option<&[u8]> {
+    let bytes = buffer.get(..usize::try_from(result).ok()?)?;
+    let output_len = read_u32(bytes)? as usize;
+    bytes.get(4usize.checked_add(output_len)?..)
+}
+
+/// The error message of a failed compute(), or None if it succeeded
+pub fn read_error(buffer: &[u8], result: i32) -> Option<String> {
+    let len = error_len(result, buffer.len())?;
+    Some(String::from_utf8_lossy(&buffer[..len]).into_owned())
+}
+
+/// The return value of a successful compute() if it is an integer. Floats
+/// are not converted, even when they have no fractional part.
+pub fn read_integer_result(buffer: &[u8], result: i32) -> Option<i64> {
+    match result_value(buffer, result)?.split_first()? {
+        (&TAG_INTEGER, rest) => read_u64(rest).map(|n| n as i64),
+        _ => None,
+    }
+}
+
+/// The return value of a successful compute() if it is a string
+pub fn read_string_result(buffer: &[u8], result: i32) -> Option<String> {
+    match result_value(buffer, result)?.split_first()? {
+        (&TAG_STRING, rest) => {
+            let len = read_u32(rest)? as usize;
+            let data = rest.get(4..4usize.checked_add(len)?)?;
+            Some(String::from_utf8_lossy(data).into_owned())
+        }
+        _ => None,
+    }
+}
+
+/// A table dump, as js_ext_table_bulk_dump writes and js_ext_table_bulk_load
+/// reads it: one record per entry of
+///   [4 bytes: key length (u32 LE)][key][4 bytes: value length (u32 LE)][value]
+pub fn encode_table_dump<'a>(entries: impl IntoIterator<Item = (&'a [u8], &'a [u8])>) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for (key, value) in entries {
+        bytes.extend_from_slice(&(key.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(key);
+        bytes.extend_from_slice(&(value.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(value);
+    }
+    bytes
+}
+
+/// The entries of a table dump, or None if it is truncated or has an empty key
+pub fn decode_table_dump(mut bytes: &[u8]) -> Option<Vec<(Vec<u8>, Vec<u8>)>> {
+    let mut entries = Vec::new();
+    while !bytes.is_empty() {
+        let key_len = read_u32(bytes)? as usize;
+        let key = bytes.get(4..4usize.checked_add(key_len)?)?;
+        if key.is_empty() {
+            return None;
+        }
+        bytes = &bytes[4 + key_len..];
+
+        let value_len = read_u32(bytes)? as usize;
+        let value = bytes.get(4..4usize.checked_add(value_len)?)?;
+        bytes = &bytes[4 + value_len..];
+        entries.push((key.to_vec(), value.to_vec()));
+    }
+    Some(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // An IO buffer holding output and value, padded like the real buffer;
+    // returns it with the matching compute() result
+    fn buffer_with(output: &str, value: &[u8]) -> (Vec<u8>, i32) {
+        let mut bytes = (output.len() as u32).to_le_bytes().to_vec();
+        bytes.extend_from_slice(output.as_bytes());
+        bytes.extend_from_slice(value);
+        let result = bytes.len() as i32;
+        bytes.resize(64, 0xAA);
+        (bytes, result)
+    }
+
+    fn integer(n: i64) -> Vec<u8> {
+        let mut bytes = vec![TAG_INTEGER];
+        bytes.extend_from_slice(&n.to_le_bytes());
+        bytes
+    }
+
+    fn string(s: &str) -> Vec<u8> {
+        let mut bytes = vec![TAG_STRING];
+        bytes.extend_from_slice(&(s.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(s.as_bytes());
+        bytes
+    }
+
+    #[test]
+    fn maps_negative_returns_to_error_lengths() {
+        assert_eq!(error_len(5, 64), None);
+        assert_eq!(error_len(0, 64), None);
+        assert_eq!(error_len(-1, 64), Some(0));
+        assert_eq!(error_len(-(26 + 1), 64), Some(26));
+        assert_eq!(error_len(i32::MIN, 64), Some(64));
+    }
+
+    #[test]
+    fn reads_error_messages() {
+        let mut buffer = b"attempt to call a nil value".to_vec();
+        buffer.resize(64, 0);
+        assert_eq!(read_error(&buffer, -(27 + 1)).as_deref(), Some("attempt to call a nil value"));
+        assert_eq!(read_error(&buffer, -1).as_deref(), Some(""));
+        assert_eq!(read_error(&buffer, 4), None);
+    }
+
+    #[test]
+    fn reads_integer_results() {
+        let (buffer, result) = buffer_with("", &integer(-42));
+        assert_eq!(read_integer_result(&buffer, result), Some(-42));
+
+        let (buffer, result) = buffer_with("printed\n", &integer(i64::MAX));
+        assert_eq!(read_integer_result(&buffer, result), Some(i64::MAX));
+    }
+
+    #[test]
+    fn does_not_read_floats_as_integers() {
+        let mut float = vec![TAG_FLOAT];
+        float.extend_from_slice(&4.0f64.to_le_bytes());
+        let (buffer, result) = buffer_with("", &float);
+        assert_eq!(read_integer_result(&buffer, result), None);
+    }
+
+    #[test]
+    fn reads_string_results() {
+        let (buffer, result) = buffer_with("hi\n", &string("héllo"));
+        assert_eq!(read_string_result(&buffer, result).as_deref(), Some("héllo"));
+        assert_eq!(read_integer_result(&buffer, result), None);
+    }
+
+    #[test]
+    fn ignores_bytes_past_the_result() {
+        // The length prefix claims more bytes than compute() returned
+        let (buffer, result) = buffer_with("", &string("hello"));
+        assert_eq!(read_string_result(&buffer, result - 1), None);
+
+        let (buffer, result) = buffer_with("", &integer(7));
+        assert_eq!(read_integer_result(&buffer, result - 1), None);
+    }
+
+    #[test]
+    fn rejects_error_returns_and_missing_values() {
+        let (buffer, _) = buffer_with("", &integer(7));
+        assert_eq!(read_integer_result(&buffer, -5), None);
+        assert_eq!(read_string_result(&buffer, 0), None);
+
+        let (buffer, result) = buffer_with("only output", &[]);
+        assert_eq!(read_integer_result(&buffer, result), None);
+        assert_eq!(result_value(&buffer, result), Some(&[][..]));
+    }
+
+    #[test]
+    fn round_trips_table_dumps() {
+        let entries: [(&[u8], &[u8]); 2] = [(b"name", b"\x04\x02\0\0\0hi"), (b"\xff", b"")];
+        let dump = encode_table_dump(entries);
+        assert_eq!(&dump[..8], &[4, 0, 0, 0, b'n', b'a', b'm', b'e']);
+
+        let decoded = decode_table_dump(&dump).unwrap();
+        assert_eq!(decoded.len(), 2);
+        assert_eq!(decoded[0], (b"name".to_vec(), b"\x04\x02\0\0\0hi".to_vec()));
+        assert_eq!(decoded[1], (vec![0xff], vec![]));
+        assert_eq!(decode_table_dump(&[]), Some(vec![]));
+    }
+
+    #[test]
+    fn rejects_malformed_table_dumps() {
+        let dump = encode_table_dump([(&b"key"[..], &b"value"[..])]);
+        assert_eq!(decode_table_dump(&dump[..dump.len() - 1]), None);
+        assert_eq!(decode_table_dump(&dump[..2]), None);
+        assert_eq!(decode_table_dump(&encode_table_dump([(&b""[..], &b"v"[..])])), None);
+    }
+}
@@ -0,0 +1,21 @@
+const lua = @import("lua.zig");
+
+const c = lua.c;
+
+// The host clock in milliseconds. It is 32-bit on wasm32, so the host's
+// value arrives truncated and wraps about every 49.7 days.
+extern fn js_time_now() c_long;
+
+// cu.now(): the host clock in milliseconds, as js_time_now reports it. Good
+// for timing work (subtract two readings) whatever os holds, including when
+// os is sandboxed or left out by init_with_libs.
+fn now_impl(L: *lua.lua_State) c_int {
+    lua.pushinteger(L, js_time_now());
+    return 1;
+}
+
+// Set cu.now on the table at the top of the stack
+pub fn register(L: *lua.lua_State) void {
+    lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&now_impl)));
+    lua.setfield(L, -2, "now");
+}
@@ -0,0 +1,114 @@
+const std = @import("std");
+const lua = @import("lua.zig");
+
+// Compiled chunks keyed by a hash of their source, so hosts re-running the
+// same script skip the parser. Functions, and a copy of the source that a hit
+// is checked against, are pinned in the registry.
+pub const DEFAULT_CAPACITY: usize = 16;
+pub const MAX_CAPACITY: usize = 64;
+
+const Entry = struct {
+    hash: u64,
+    code_len: usize,
+    ref: c_int,
+    source_ref: c_int,
+    last_used: u64,
+};
+
+var entries: [MAX_CAPACITY]Entry = undefined;
+var entry_count: usize = 0;
+var capacity: usize = DEFAULT_CAPACITY;
+var clock: u64 = 0;
+var compile_count: u32 = 0;
+
+fn evict(L: *lua.lua_State, index: usize) void {
+    lua.c.luaL_unref(L, lua.c.LUA_REGISTRYINDEX, entries[index].ref);
+    lua.c.luaL_unref(L, lua.c.LUA_REGISTRYINDEX, entries[index].source_ref);
+    entry_count -= 1;
+    entries[index] = entries[entry_count];
+}
+
+fn least_recently_used() usize {
+    var oldest: usize = 0;
+    for (entries[1..entry_count], 1..) |entry, i| {
+        if (entry.last_used < entries[oldest].last_used) oldest = i;
+    }
+    return oldest;
+}
+
+// Whether the entry was compiled from exactly these bytes, so a hash
+// collision never runs the wrong chunk
+fn same_source(L: *lua.lua_State, entry: *const Entry, code: []const u8) bool {
+    _ = lua.c.lua_rawgeti(L, lua.c.LUA_REGISTRYINDEX, entry.source_ref);
+    defer lua.pop(L, 1);
+    var len: usize = 0;
+    const source = lua.c.lua_tolstring(L, -1, &len) orelse return false;
+    return std.mem.eql(u8, source[0..len], code);
+}
+
+// Push the compiled chunk for code, compiling and caching it on a miss.
+// Returns the luaL_loadstring status.
+pub fn load(L: *lua.lua_State, code: [*:0]const u8, code_len: usize) c_int {
+    if (capacity == 0) {
+        compile_count += 1;
+        return lua.c.luaL_loadstring(L, code);
+    }
+
+    const hash = std.hash.Wyhash.hash(0, code[0..code_len]);
+    clock += 1;
+
+    for (entries[0..entry_count]) |*entry| {
+        if (entry.hash == hash and entry.code_len == code_len and same_source(L, entry, code[0..code_len])) {
+            entry.last_used = clock;
+            _ = lua.c.lua_rawgeti(L, lua.c.LUA_REGISTRYINDEX, entry.ref);
+            return lua.c.LUA_OK;
+        }
+    }
+
+    compile_count += 1;
+    const status = lua.c.luaL_loadstring(L, code);
+    if (status != lua.c.LUA_OK) return status;
+
+    if (entry_count >= capacity) {
+        evict(L, least_recently_used());
+    }
+
+    lua.pushvalue(L, -1);
+    const ref = lua.c.luaL_ref(L, lua.c.LUA_REGISTRYINDEX);
+    _ = lua.c.lua_pushlstring(L, code, code_len);
+    entries[entry_count] = .{
+        .hash = hash,
+        .code_len = code_len,
+        .ref = ref,
+        .source_ref = lua.c.luaL_ref(L, lua.c.LUA_REGISTRYINDEX),
+        .last_used = clock,
+    };
+    entry_count += 1;
+
+    return lua.c.LUA_OK;
+}
+
+pub fn set_capacity(L: *lua.lua_State, new_capacity: usize) void {
+    capacity = @min(new_capacity, MAX_CAPACITY);
+    while (entry_count > capacity) {
+        evict(L, least_recently_used());
+    }
+}
+
+pub fn clear(L: *lua.lua_State) void {
+    while (entry_count > 0) {
+        evict(L, entry_count - 1);
+    }
+}
+
+// Forget every entry without touching the registry; used when the Lua state
+// the refs belong to is replaced.
+pub fn reset() void {
+    entry_count = 0;
+    clock = 0;
+    compile_count = 0;
+}
+
+pub fn get_compile_count() u32 {
+    return compile_count;
+}
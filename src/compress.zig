@@ -0,0 +1,172 @@
+const std = @import("std");
+
+// LZ77 block compression for stored values, in the LZ4 block format so
+// hosts can decode it with any LZ4 block decoder. A block is a series of
+// sequences:
+//   [token][extra literal length][literals][2 bytes: offset (LE)][extra match length]
+// The token's high nibble is the literal count and its low nibble the match
+// length minus MIN_MATCH; a nibble of 15 continues in extra bytes, each
+// added to it, until one is below 255. The last sequence has literals only;
+// as LZ4 requires, matches end LAST_LITERALS bytes before the end and none
+// starts in the last MATCH_LIMIT bytes.
+
+const MIN_MATCH = 4;
+const LAST_LITERALS = 5;
+const MATCH_LIMIT = 12;
+const MAX_OFFSET = 65535;
+const HASH_BITS = 12;
+
+fn hash(sequence: u32) usize {
+    return @intCast((sequence *% 2654435761) >> (32 - HASH_BITS));
+}
+
+const Writer = struct {
+    dst: []u8,
+    len: usize = 0,
+
+    fn byte(self: *Writer, value: u8) bool {
+        if (self.len == self.dst.len) return false;
+        self.dst[self.len] = value;
+        self.len += 1;
+        return true;
+    }
+
+    fn bytes(self: *Writer, values: []const u8) bool {
+        if (self.dst.len - self.len < values.len) return false;
+        @memcpy(self.dst[self.len .. self.len + values.len], values);
+        self.len += values.len;
+        return true;
+    }
+
+    // The part of a length past its 15 in the token
+    fn extra_length(self: *Writer, length: usize) bool {
+        var rest = length - 15;
+        while (rest >= 255) : (rest -= 255) {
+            if (!self.byte(255)) return false;
+        }
+        return self.byte(@intCast(rest));
+    }
+
+    // match_len 0 writes the final, literals-only sequence
+    fn sequence(self: *Writer, literals: []const u8, offset: usize, match_len: usize) bool {
+        const literal_nibble: u8 = @intCast(@min(literals.len, 15));
+        const match_nibble: u8 = if (match_len == 0) 0 else @intCast(@min(match_len - MIN_MATCH, 15));
+        if (!self.byte(literal_nibble << 4 | match_nibble)) return false;
+        if (literals.len >= 15 and !self.extra_length(literals.len)) return false;
+        if (!self.bytes(literals)) return false;
+        if (match_len == 0) return true;
+
+        const offset_bytes = [2]u8{ @truncate(offset), @truncate(offset >> 8) };
+        if (!self.bytes(&offset_bytes)) return false;
+        if (match_len - MIN_MATCH >= 15 and !self.extra_length(match_len - MIN_MATCH)) return false;
+        return true;
+    }
+};
+
+// Compress src into dst. Returns the compressed length, or null if it
+// doesn't fit in dst.
+pub fn compress(src: []const u8, dst: []u8) ?usize {
+    var positions = [_]u32{0} ** (1 << HASH_BITS);
+    var out = Writer{ .dst = dst };
+
+    var anchor: usize = 0;
+    var i: usize = 0;
+    while (i + MATCH_LIMIT <= src.len) {
+        const sequence = std.mem.readInt(u32, src[i..][0..MIN_MATCH], .little);
+        const slot = hash(sequence);
+        const candidate: usize = positions[slot];
+        positions[slot] = @intCast(i);
+
+        if (candidate < i and i - candidate <= MAX_OFFSET and
+            std.mem.readInt(u32, src[candidate..][0..MIN_MATCH], .little) == sequence)
+        {
+            var match_len: usize = MIN_MATCH;
+            while (i + match_len < src.len - LAST_LITERALS and src[candidate + match_len] == src[i + match_len]) match_len += 1;
+
+            if (!out.sequence(src[anchor..i], i - candidate, match_len)) return null;
+            i += match_len;
+            anchor = i;
+        } else {
+            i += 1;
+        }
+    }
+
+    if (!out.sequence(src[anchor..], 0, 0)) return null;
+    return out.len;
+}
+
+// Read the part of a length past its 15 in the token
+fn read_extra_length(src: []const u8, in: *usize) ?usize {
+    var length: usize = 15;
+    while (true) {
+        if (in.* == src.len) return null;
+        const value = src[in.*];
+        in.* += 1;
+        length += value;
+        if (value != 255) return length;
+    }
+}
+
+// Decompress src into dst. Returns the decompressed length, or null if src
+// is malformed or decompresses to more than dst holds.
+pub fn decompress(src: []const u8, dst: []u8) ?usize {
+    var in: usize = 0;
+    var out: usize = 0;
+
+    while (in < src.len) {
+        const token = src[in];
+        in += 1;
+
+        var literal_len: usize = token >> 4;
+        if (literal_len == 15) literal_len = read_extra_length(src, &in) orelse return null;
+        if (src.len - in < literal_len or dst.len - out < literal_len) return null;
+        @memcpy(dst[out .. out + literal_len], src[in .. in + literal_len]);
+        in += literal_len;
+        out += literal_len;
+
+        // The last sequence ends after its literals
+        if (in == src.len) break;
+
+        if (src.len - in < 2) return null;
+        const offset = @as(usize, src[in]) | @as(usize, src[in + 1]) << 8;
+        in += 2;
+        if (offset == 0 or offset > out) return null;
+
+        var match_len: usize = (token & 0x0f) + MIN_MATCH;
+        if (token & 0x0f == 15) match_len = (read_extra_length(src, &in) orelse return null) + MIN_MATCH;
+        if (dst.len - out < match_len) return null;
+
+        // Byte by byte: a match may overlap the bytes it is copying
+        for (0..match_len) |_| {
+            dst[out] = dst[out - offset];
+            out += 1;
+        }
+    }
+
+    return out;
+}
+
+test "round trips repetitive and incompressible data" {
+    const testing = std.testing;
+    var packed_buffer: [4096]u8 = undefined;
+    var unpacked: [4096]u8 = undefined;
+
+    const repetitive = "abcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabc" ** 20;
+    const packed_len = compress(repetitive, &packed_buffer).?;
+    try testing.expect(packed_len < repetitive.len / 10);
+    try testing.expectEqual(repetitive.len, decompress(packed_buffer[0..packed_len], &unpacked).?);
+    try testing.expectEqualSlices(u8, repetitive, unpacked[0..repetitive.len]);
+
+    const short = "no";
+    const short_len = compress(short, &packed_buffer).?;
+    try testing.expectEqual(short.len, decompress(packed_buffer[0..short_len], &unpacked).?);
+    try testing.expectEqualSlices(u8, short, unpacked[0..short.len]);
+}
+
+test "rejects malformed blocks" {
+    var unpacked: [64]u8 = undefined;
+    // Offset pointing before the start of the output
+    try std.testing.expectEqual(@as(?usize, null), decompress(&.{ 0x10, 'a', 0x05, 0x00 }, &unpacked));
+    // Literal run longer than the input
+    try std.testing.expectEqual(@as(?usize, null), decompress(&.{ 0x30, 'a' }, &unpacked));
+}
@@ -0,0 +1,133 @@
+const std = @import("std");
+const lua = @import("lua.zig");
+const serializer = @import("serializer.zig");
+const error_handler = @import("error.zig");
+const result_encoder = @import("result.zig");
+
+// Set on the return value of eval_resumable/resume when the chunk yielded.
+// The low bits still carry the encoded result length.
+pub const YIELD_FLAG: i32 = 0x40000000;
+
+var thread: ?*lua.lua_State = null;
+var thread_ref: c_int = lua.c.LUA_NOREF;
+
+pub fn is_suspended() bool {
+    return thread != null;
+}
+
+fn release_thread(L: *lua.lua_State) void {
+    if (thread == null) return;
+    lua.c.luaL_unref(L, lua.c.LUA_REGISTRYINDEX, thread_ref);
+    thread = null;
+    thread_ref = lua.c.LUA_NOREF;
+}
+
+// Forget the suspended coroutine, e.g. one a trap stopped mid-run
+pub fn abandon(L: *lua.lua_State) void {
+    release_thread(L);
+}
+
+fn fail(L: *lua.lua_State, co: *lua.lua_State, status: c_int, buffer: [*]u8, max_len: usize) i32 {
+    _ = error_handler.capture_lua_error(co, status);
+    release_thread(L);
+    const error_len = error_handler.format_error_to_buffer(buffer, max_len);
+    return -@as(i32, @intCast(error_len + 1));
+}
+
+fn fail_message(L: *lua.lua_State, msg: [*:0]const u8, buffer: [*]u8, max_len: usize) i32 {
+    _ = lua.pushstring(L, msg);
+    _ = error_handler.capture_lua_error(L, lua.c.LUA_ERRRUN);
+    const error_len = error_handler.format_error_to_buffer(buffer, max_len);
+    return -@as(i32, @intCast(error_len + 1));
+}
+
+fn run(L: *lua.lua_State, co: *lua.lua_State, nargs: c_int, buffer: [*]u8, max_len: usize) i32 {
+    var nres: c_int = 0;
+    const status = lua.c.lua_resume(co, L, nargs, &nres);
+
+    if (status == lua.c.LUA_YIELD) {
+        // The yielded values sit on the coroutine stack; encode_result takes
+        // the top one and clears them so resume can push the reply.
+        const encoded_len = result_encoder.encode_result(co, buffer, max_len);
+        return YIELD_FLAG | @as(i32, @intCast(encoded_len));
+    }
+
+    if (status != lua.c.LUA_OK) {
+        return fail(L, co, status, buffer, max_len);
+    }
+
+    const encoded_len = result_encoder.encode_result(co, buffer, max_len);
+    release_thread(L);
+    return @intCast(encoded_len);
+}
+
+// Called on the coroutine before each run, to arm the eval's limits on it
+pub const ArmFn = *const fn (co: *lua.lua_State) void;
+
+// Load code into a fresh coroutine and run it until it yields or finishes.
+// Any previously suspended coroutine is discarded.
+pub fn start(L: *lua.lua_State, code: [*:0]const u8, arm: ArmFn, buffer: [*]u8, max_len: usize) i32 {
+    release_thread(L);
+
+    const new_thread = lua.c.lua_newthread(L);
+    if (new_thread == null) {
+        return fail_message(L, "Failed to create coroutine", buffer, max_len);
+    }
+    const co: *lua.lua_State = new_thread.?;
+    thread_ref = lua.c.luaL_ref(L, lua.c.LUA_REGISTRYINDEX);
+    thread = co;
+
+    const load_result = lua.c.luaL_loadstring(co, code);
+    if (load_result != 0) {
+        return fail(L, co, load_result, buffer, max_len);
+    }
+
+    arm(co);
+    return run(L, co, 0, buffer, max_len);
+}
+
+// Resume the suspended coroutine, passing the serialized value (if any) as
+// the result of the pending coroutine.yield call.
+pub fn resume_with(L: *lua.lua_State, value_ptr: [*]const u8, value_len: usize, arm: ArmFn, buffer: [*]u8, max_len: usize) i32 {
+    const co = thread orelse {
+        return fail_message(L, "No suspended coroutine to resume", buffer, max_len);
+    };
+
+    var nargs: c_int = 0;
+    if (value_len > 0) {
+        serializer.deserialize_value(co, value_ptr, value_len) catch {
+            return fail_message(L, "Invalid resume value", buffer, max_len);
+        };
+        nargs = 1;
+    }
+
+    arm(co);
+    return run(L, co, nargs, buffer, max_len);
+}
+
+// cu.yield(value): hand value to the host and wait. Under eval_resumable
+// this yields the chunk like coroutine.yield, returning the value the host
+// passes to resume. In an ordinary eval there is no host waiting on a yield,
+// so it does nothing and returns nil. Inside a script's own coroutine the
+// host can't be reached, which is an error.
+fn yield_impl(L: *lua.lua_State) c_int {
+    lua.settop(L, 1);
+    if (thread) |co| {
+        if (co == L) return lua.c.lua_yieldk(L, 1, 0, null);
+    }
+
+    const is_main = lua.c.lua_pushthread(L) == 1;
+    lua.pop(L, 1);
+    if (is_main) return 0;
+
+    const message = "cu.yield: can't yield to the host from inside a coroutine";
+    error_handler.set_error(.runtime_error, message);
+    _ = lua.pushstring(L, message);
+    return lua.c.lua_error(L);
+}
+
+// Set cu.yield on the table at the top of the stack
+pub fn register(L: *lua.lua_State) void {
+    lua.pushcfunction(L, @as(lua.c.lua_CFunction, @ptrCast(&yield_impl)));
+    lua.setfield(L, -2, "yield");
+}
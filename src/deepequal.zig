@@ -0,0 +1,148 @@
+const lua = @import("lua.zig");
+const ext_table = @import("ext_table.zig");
+
+const c = lua.c;
+
+// Nesting depth at which cu.deepequal gives up, bounding its C recursion
+const MAX_DEPTH: u32 = 200;
+
+fn raise(L: *lua.lua_State, message: [*:0]const u8) c_int {
+    _ = lua.pushstring(L, message);
+    return c.lua_error(L);
+}
+
+// Push an array of the keys of the table at idx: the host's key list for an
+// external table, its own keys for a plain one. Returns true for a plain
+// table, whose list only has keys with non-nil values.
+fn push_key_list(L: *lua.lua_State, idx: c_int) bool {
+    if (ext_table.push_proxy_keys(L, idx)) return false;
+
+    lua.newtable(L);
+    var count: c.lua_Integer = 0;
+    lua.pushnil(L);
+    while (c.lua_next(L, idx) != 0) {
+        lua.pop(L, 1);
+        lua.pushvalue(L, -1);
+        count += 1;
+        c.lua_rawseti(L, -3, count);
+    }
+    return true;
+}
+
+// Push table[key] for the key on top of the stack, replacing the key. Plain
+// tables are read raw; external tables need __index to reach the host.
+fn push_field(L: *lua.lua_State, table: c_int, plain: bool) void {
+    if (plain) {
+        _ = c.lua_rawget(L, table);
+    } else {
+        _ = c.lua_gettable(L, table);
+    }
+}
+
+// Record that the tables at a and b are being compared. Returns false if
+// they already were: a cycle, which counts as equal here since the
+// comparison further up settles it.
+fn mark_pair(L: *lua.lua_State, a: c_int, b: c_int, seen: c_int) bool {
+    lua.pushvalue(L, a);
+    if (c.lua_rawget(L, seen) != c.LUA_TTABLE) {
+        lua.pop(L, 1);
+        lua.newtable(L);
+        lua.pushvalue(L, a);
+        lua.pushvalue(L, -2);
+        c.lua_rawset(L, seen);
+    }
+
+    lua.pushvalue(L, b);
+    const already = c.lua_rawget(L, -2) != c.LUA_TNIL;
+    lua.pop(L, 1);
+    if (!already) {
+        lua.pushvalue(L, b);
+        lua.pushboolean(L, 1);
+        c.lua_rawset(L, -3);
+    }
+    lua.pop(L, 1);
+    return !already;
+}
+
+// a, b and seen are absolute stack indices; seen maps each table of a to the
+// tables of b it is being compared with
+fn values_equal(L: *lua.lua_State, a: c_int, b: c_int, seen: c_int, depth: u32) bool {
+    if (c.lua_rawequal(L, a, b) != 0) return true;
+    if (c.lua_type(L, a) != c.LUA_TTABLE or c.lua_type(L, b) != c.LUA_TTABLE) return false;
+
+    if (depth >= MAX_DEPTH) {
+        _ = raise(L, "cu.deepequal: tables nested too deeply");
+        return false;
+    }
+    c.luaL_checkstack(L, 8, "cu.deepequal");
+    if (!mark_pair(L, a, b, seen)) return true;
+
+    const top = lua.gettop(L);
+    defer lua.settop(L, top);
+
+    const a_plain = push_key_list(L, a);
+    const a_keys = lua.gettop(L);
+    const b_plain = push_key_list(L, b);
+    const b_keys = lua.gettop(L);
+
+    // External tables can list keys whose stored value is nil, so only plain
+    // tables can be told apart by their key counts
+    const a_count: c.lua_Integer = @intCast(c.lua_rawlen(L, a_keys));
+    const b_count: c.lua_Integer = @intCast(c.lua_rawlen(L, b_keys));
+    if (a_plain and b_plain and a_count != b_count) return false;
+
+    // Keys with a non-nil value in a, all of which matched b
+    lua.newtable(L);
+    const matched = lua.gettop(L);
+
+    var i: c.lua_Integer = 1;
+    while (i <= a_count) : (i += 1) {
+        _ = c.lua_rawgeti(L, a_keys, i);
+        const key = lua.gettop(L);
+        lua.pushvalue(L, key);
+        push_field(L, a, a_plain);
+        if (!lua.isnil(L, -1)) {
+            lua.pushvalue(L, key);
+            push_field(L, b, b_plain);
+            if (!values_equal(L, key + 1, key + 2, seen, depth + 1)) return false;
+
+            lua.pushvalue(L, key);
+            lua.pushboolean(L, 1);
+            c.lua_rawset(L, matched);
+        }
+        lua.settop(L, matched);
+    }
+
+    // Every other key of b must be nil
+    var j: c.lua_Integer = 1;
+    while (j <= b_count) : (j += 1) {
+        _ = c.lua_rawgeti(L, b_keys, j);
+        lua.pushvalue(L, -1);
+        if (c.lua_rawget(L, matched) == c.LUA_TNIL) {
+            lua.pop(L, 1);
+            push_field(L, b, b_plain);
+            if (!lua.isnil(L, -1)) return false;
+        }
+        lua.settop(L, matched);
+    }
+
+    return true;
+}
+
+// cu.deepequal(a, b): whether a and b hold the same values, comparing
+// tables key by key and reading external tables through the host. Cycles
+// are followed once, so cyclic structures compare without looping.
+// Metatables aren't compared.
+fn deepequal_impl(L: *lua.lua_State) c_int {
+    lua.settop(L, 2);
+    lua.newtable(L);
+    const equal = values_equal(L, 1, 2, 3, 0);
+    lua.pushboolean(L, if (equal) 1 else 0);
+    return 1;
+}
+
+// Set cu.deepequal on the table at the top of the stack
+pub fn register(L: *lua.lua_State) void {
+    lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&deepequal_impl)));
+    lua.setfield(L, -2, "deepequal");
+}
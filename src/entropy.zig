@@ -0,0 +1,58 @@
+const std = @import("std");
+const lua = @import("lua.zig");
+
+const c = lua.c;
+
+// Random bytes from the host's entropy source (crypto.getRandomValues,
+// getrandom, ...), since a freestanding module has none of its own. The
+// host fills len bytes at buf_ptr and returns 0, or a negative number if it
+// can't.
+extern fn js_random_bytes(buf_ptr: [*]u8, len: usize) c_int;
+
+fn raise(L: *lua.lua_State, message: [*:0]const u8) c_int {
+    _ = lua.pushstring(L, message);
+    return c.lua_error(L);
+}
+
+// cu.random_bytes(n): a binary string of n bytes from the host
+fn random_bytes_impl(L: *lua.lua_State) c_int {
+    var is_integer: c_int = 0;
+    const n = c.lua_tointegerx(L, 1, &is_integer);
+    if (is_integer == 0) return raise(L, "cu.random_bytes: expected an integer length");
+    if (n < 0) return raise(L, "cu.random_bytes: length must not be negative");
+    // The import reports failure through its i32 result
+    if (n > std.math.maxInt(c_int)) return raise(L, "cu.random_bytes: length too large");
+    const len: usize = @intCast(n);
+
+    var buffer: c.luaL_Buffer = undefined;
+    const bytes = c.luaL_buffinitsize(L, &buffer, len);
+    if (len > 0 and js_random_bytes(@ptrCast(bytes), len) < 0) {
+        return raise(L, "cu.random_bytes: host entropy unavailable");
+    }
+    c.luaL_pushresultsize(&buffer, len);
+    return 1;
+}
+
+// Set cu.random_bytes on the table at the top of the stack
+pub fn register(L: *lua.lua_State) void {
+    lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&random_bytes_impl)));
+    lua.setfield(L, -2, "random_bytes");
+}
+
+// Seed math.random from host entropy instead of Lua's time/address seed,
+// which is predictable under WASM. Keeps Lua's seed if the host has none.
+pub fn reseed_math_random(L: *lua.lua_State) void {
+    var seed: [16]u8 = undefined;
+    if (js_random_bytes(&seed, seed.len) < 0) return;
+
+    // math may be left out by init_with_libs
+    if (lua.getglobal(L, "math") != c.LUA_TTABLE) {
+        lua.pop(L, 1);
+        return;
+    }
+    _ = lua.getfield(L, -1, "randomseed");
+    lua.pushinteger(L, @bitCast(std.mem.readInt(u64, seed[0..8], .little)));
+    lua.pushinteger(L, @bitCast(std.mem.readInt(u64, seed[8..16], .little)));
+    c.lua_callk(L, 2, 0, 0, null);
+    lua.pop(L, 1);
+}
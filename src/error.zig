@@ -1,5 +1,6 @@
 const std = @import("std");
 const lua = @import("lua.zig");
+const serializer = @import("serializer.zig");
 
 const IO_BUFFER_SIZE = 64 * 1024;
 const MAX_ERROR_MSG_SIZE = IO_BUFFER_SIZE - 10;
@@ -10,27 +11,62 @@ pub const ErrorCode = enum(c_int) {
     compilation_error = -1,
     runtime_error = -2,
     serialization_error = -3,
+    ext_call_limit = -4,
+    vm_not_initialized = -5,
+    reentrant = -6,
+    timeout = -7,
+    host_import_error = -8,
 };
 
 var error_buffer: [MAX_ERROR_MSG_SIZE]u8 = undefined;
 var error_len: usize = 0;
 var last_error_code: ErrorCode = .success;
 
+// The serialized value passed to error() when it wasn't a string or number,
+// e.g. error({code = 500}); tables are stored as external tables
+var error_value: [32]u8 = undefined;
+var error_value_len: usize = 0;
+
 pub fn init_error_state() void {
     error_len = 0;
+    error_value_len = 0;
     last_error_code = .success;
+    serializer.reset_deserialize_depth();
 }
 
 pub fn clear_error_state(L: *lua.lua_State) void {
     error_len = 0;
+    error_value_len = 0;
     last_error_code = .success;
+    serializer.reset_deserialize_depth();
     lua.settop(L, 0);
 }
 
 pub fn capture_lua_error(L: *lua.lua_State, error_code: c_int) ErrorCode {
+    const error_enum = record_error(L, error_code);
+    lua.settop(L, 0);
+    return error_enum;
+}
+
+// Record an error as it is thrown: raising one traps on wasm32-freestanding,
+// so the call never gets to capture it. An error recorded before it was
+// raised, such as a limit, is kept. Leaves the stack alone.
+pub fn record_thrown_error(L: *lua.lua_State, status: c_int) void {
+    if (last_error_code != .success) return;
+
+    switch (status) {
+        // Nothing is pushed for these, and converting the top could allocate
+        lua.c.LUA_ERRMEM => set_error(.runtime_error, "not enough memory"),
+        lua.c.LUA_ERRERR => set_error(.runtime_error, "error in error handling"),
+        lua.c.LUA_YIELD => set_error(.runtime_error, "attempt to yield: coroutines can't be suspended on this target"),
+        else => _ = record_error(L, status),
+    }
+}
+
+fn record_error(L: *lua.lua_State, error_code: c_int) ErrorCode {
     var error_enum: ErrorCode = .runtime_error;
 
-    if (error_code == 4) {
+    if (error_code == lua.c.LUA_ERRSYNTAX) {
         error_enum = .compilation_error;
     } else if (error_code == 2) {
         error_enum = .runtime_error;
@@ -40,6 +76,12 @@ pub fn capture_lua_error(L: *lua.lua_State, error_code: c_int) ErrorCode {
 
     last_error_code = error_enum;
 
+    error_value_len = 0;
+    if (!lua.isstring(L, -1)) {
+        capture_error_object(L);
+        return error_enum;
+    }
+
     const err_str = lua.tostring(L, -1);
     if (err_str[0] != 0) {
         var i: usize = 0;
@@ -53,10 +95,61 @@ pub fn capture_lua_error(L: *lua.lua_State, error_code: c_int) ErrorCode {
         error_len = fallback.len;
     }
 
-    lua.settop(L, 0);
     return error_enum;
 }
 
+// Keep the error object on top of the stack for read_error_value, and
+// describe it the way the standalone interpreter does, or with its string
+// message field (as cu.check sets) if it has one
+fn capture_error_object(L: *lua.lua_State) void {
+    error_value_len = serializer.serialize_error_value(L, -1, &error_value, error_value.len) catch 0;
+
+    if (lua.istable(L, -1)) {
+        _ = lua.pushstring(L, "message");
+        const field_type = lua.c.lua_rawget(L, -2);
+        defer lua.pop(L, 1);
+        if (field_type == lua.c.LUA_TSTRING) {
+            var message_len: usize = 0;
+            const message = lua.tolstring(L, -1, &message_len);
+            error_len = @min(message_len, MAX_ERROR_MSG_SIZE);
+            @memcpy(error_buffer[0..error_len], message[0..error_len]);
+            return;
+        }
+    }
+
+    const message = std.fmt.bufPrint(&error_buffer, "(error object is a {s} value)", .{std.mem.span(lua.type_name(L, -1))}) catch unreachable;
+    error_len = message.len;
+}
+
+// cu.check(cond, code [, message]): returns cond if it is truthy, otherwise
+// raises the error object {code = code, message = message}. Hosts read it
+// back with read_error_value and branch on the code instead of parsing text.
+fn check_impl(L: *lua.lua_State) c_int {
+    if (lua.c.lua_toboolean(L, 1) != 0) {
+        lua.settop(L, 1);
+        return 1;
+    }
+
+    const code = lua.c.luaL_checkinteger(L, 2);
+    const has_message = lua.c.lua_type(L, 3) > lua.c.LUA_TNIL;
+    if (has_message) _ = lua.c.luaL_checklstring(L, 3, null);
+
+    lua.c.lua_createtable(L, 0, 2);
+    lua.pushinteger(L, code);
+    lua.setfield(L, -2, "code");
+    if (has_message) {
+        lua.pushvalue(L, 3);
+        lua.setfield(L, -2, "message");
+    }
+    return lua.c.lua_error(L);
+}
+
+// Set cu.check on the table at the top of the stack
+pub fn register(L: *lua.lua_State) void {
+    lua.pushcfunction(L, @as(lua.c.lua_CFunction, @ptrCast(&check_impl)));
+    lua.setfield(L, -2, "check");
+}
+
 pub fn format_error_to_buffer(buffer: [*]u8, max_len: usize) usize {
     if (max_len < 1) return 0;
 
@@ -72,10 +165,34 @@ pub fn format_error_to_buffer(buffer: [*]u8, max_len: usize) usize {
     return copy_len;
 }
 
+// Record an error raised outside Lua, such as an export called before init
+pub fn set_error(code: ErrorCode, message: []const u8) void {
+    last_error_code = code;
+    error_value_len = 0;
+    error_len = @min(message.len, MAX_ERROR_MSG_SIZE);
+    @memcpy(error_buffer[0..error_len], message[0..error_len]);
+}
+
+// Reclassify the captured error, e.g. when a limit rather than the script
+// itself caused it
+pub fn set_error_code(code: ErrorCode) void {
+    last_error_code = code;
+}
+
 pub fn get_last_error_code() ErrorCode {
     return last_error_code;
 }
 
+pub fn get_error_message() []const u8 {
+    return error_buffer[0..error_len];
+}
+
+// The serialized error object of the last error, empty if error() was
+// given a string
+pub fn get_error_value() []const u8 {
+    return error_value[0..error_value_len];
+}
+
 pub fn get_error_len() usize {
     return error_len;
 }
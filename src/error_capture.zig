@@ -0,0 +1,54 @@
+const std = @import("std");
+
+// Errors kept until the host reads them, and the bytes kept of each
+// message. Past the limit the oldest are dropped.
+const MAX_CAPTURED = 32;
+const MAX_CAPTURED_MSG = 256;
+
+var enabled: bool = false;
+var messages: [MAX_CAPTURED][MAX_CAPTURED_MSG]u8 = undefined;
+var message_lens: [MAX_CAPTURED]usize = undefined;
+var count: usize = 0;
+
+pub fn set_enabled(on: bool) void {
+    enabled = on;
+    if (!on) count = 0;
+}
+
+// Record the message of an error as it is thrown. A thrown error traps, so
+// this runs where pcall would have caught it; the list is the only trace of
+// such an error once the host has run another eval.
+pub fn record(message: []const u8) void {
+    if (!enabled) return;
+
+    if (count == MAX_CAPTURED) {
+        std.mem.copyForwards([MAX_CAPTURED_MSG]u8, messages[0 .. MAX_CAPTURED - 1], messages[1..]);
+        std.mem.copyForwards(usize, message_lens[0 .. MAX_CAPTURED - 1], message_lens[1..]);
+        count -= 1;
+    }
+
+    const len = @min(message.len, MAX_CAPTURED_MSG);
+    @memcpy(messages[count][0..len], message[0..len]);
+    message_lens[count] = len;
+    count += 1;
+}
+
+// Write the captured errors as [4 bytes: count] then per error [4 bytes:
+// length][message], all little-endian, and forget them. Errors that don't
+// fit in max_len are left out of the count. Returns the bytes written.
+pub fn take_list(buffer: [*]u8, max_len: usize) usize {
+    if (max_len < 4) return 0;
+
+    var written: u32 = 0;
+    var offset: usize = 4;
+    for (messages[0..count], message_lens[0..count]) |*message, len| {
+        if (max_len - offset < 4 + len) break;
+        std.mem.writeInt(u32, buffer[offset..][0..4], @intCast(len), .little);
+        @memcpy(buffer[offset + 4 .. offset + 4 + len], message[0..len]);
+        offset += 4 + len;
+        written += 1;
+    }
+    std.mem.writeInt(u32, buffer[0..4], written, .little);
+    count = 0;
+    return offset;
+}
@@ -1,6 +1,8 @@
 const std = @import("std");
 const lua = @import("lua.zig");
 const serializer = @import("serializer.zig");
+const compress = @import("compress.zig");
+const error_handler = @import("error.zig");
 
 const c = lua.c;
 const IO_BUFFER_SIZE = 64 * 1024;
@@ -9,31 +11,377 @@ extern fn js_ext_table_set(table_id: u32, key_ptr: [*]const u8, key_len: usize,
 extern fn js_ext_table_get(table_id: u32, key_ptr: [*]const u8, key_len: usize, val_ptr: [*]u8, max_len: usize) c_int;
 extern fn js_ext_table_delete(table_id: u32, key_ptr: [*]const u8, key_len: usize) c_int;
 extern fn js_ext_table_size(table_id: u32) usize;
+extern fn js_ext_table_seqlen(table_id: u32) usize;
 extern fn js_ext_table_keys(table_id: u32, buf_ptr: [*]u8, max_len: usize) c_int;
+extern fn js_ext_table_keys_page(table_id: u32, cursor: u32, out_ptr: [*]u8, max_len: usize) c_int;
+extern fn js_ext_table_free(table_id: u32) c_int;
+extern fn js_ext_table_rename(table_id: u32, old_ptr: [*]const u8, old_len: usize, new_ptr: [*]const u8, new_len: usize) c_int;
+extern fn js_ext_table_cas(table_id: u32, key_ptr: [*]const u8, key_len: usize, expected_ptr: [*]const u8, expected_len: usize, new_ptr: [*]const u8, new_len: usize) c_int;
+extern fn js_ext_table_set_ttl(table_id: u32, key_ptr: [*]const u8, key_len: usize, val_ptr: [*]const u8, val_len: usize, ttl_ms: i64) c_int;
+extern fn js_ext_table_has(table_id: u32, key_ptr: [*]const u8, key_len: usize) c_int;
+extern fn js_ext_table_value_len(table_id: u32, key_ptr: [*]const u8, key_len: usize) c_int;
+extern fn js_ext_table_swap(table_id: u32, a_ptr: [*]const u8, a_len: usize, b_ptr: [*]const u8, b_len: usize) c_int;
+extern fn js_ext_table_clone(src_id: u32, dst_id: u32) c_int;
+extern fn js_ext_table_bulk_dump(table_id: u32, out_ptr: [*]u8, max_len: usize) c_int;
+extern fn js_ext_table_bulk_load(table_id: u32, in_ptr: [*]const u8, len: usize) c_int;
+extern fn js_ext_table_delete_prefix(table_id: u32, prefix_ptr: [*]const u8, prefix_len: usize) c_int;
+
+// Fields on a proxy's private metatable when it carries a stored user metatable
+const USER_METATABLE_FIELD: [*:0]const u8 = "__cu_user_mt";
+const USER_METATABLE_ID_FIELD: [*:0]const u8 = "__cu_user_mt_id";
+// Registry table of buffered writes: table id -> { key bytes -> value bytes }
+const PENDING_WRITES_KEY: [*:0]const u8 = "cu_pending_writes";
 
 var io_buffer: [*]u8 = undefined;
 var io_buffer_size: usize = 0;
 var external_table_counter: u32 = 1;
 
+// Once the counter reaches its last value, ids released by free are handed
+// out again instead of wrapping around onto live tables. Until then ids are
+// never reused, so a stale reference can't alias a newer table.
+const MAX_TABLE_ID: u32 = std.math.maxInt(u32);
+const MAX_RECYCLED_IDS = 1024;
+var recycled_ids: [MAX_RECYCLED_IDS]u32 = undefined;
+var recycled_count: usize = 0;
+// Tables created since init and not yet freed; ext.table() and tables
+// copied in by assignment are refused once it reaches max_tables
+var live_table_count: u32 = 0;
+var max_tables: u32 = 0; // 0 = unlimited
+var call_count: u32 = 0;
+var call_limit: u32 = 0; // 0 = unlimited
+var call_limit_hit: bool = false;
+var write_buffering: bool = false;
+var transaction_active: bool = false;
+// Sort key lists by their stored bytes instead of keeping the host's order
+var deterministic_iteration: bool = false;
+
+// What assigning nil to a proxy key does. tombstone, the default, stores
+// the serialized nil (0x00), so the key stays listed and ext.contains
+// reports it; delete removes the key from the host.
+pub const NilSemantics = enum(u32) {
+    delete = 0,
+    tombstone = 1,
+};
+var nil_semantics: NilSemantics = .tombstone;
+
+// Values written through a proxy whose serialized form is longer than
+// compression_threshold are stored compressed; reads undo it
+var compression_enabled: bool = false;
+var compression_threshold: usize = 0;
+// Largest serialized value compression accepts, and where it is serialized
+// before being compressed into the IO buffer
+const MAX_COMPRESSIBLE_VALUE = 256 * 1024;
+var compression_scratch: [MAX_COMPRESSIBLE_VALUE]u8 = undefined;
+const COMPRESSED_HEADER_SIZE = 5;
+
 pub fn init_ext_table(buffer: [*]u8, buffer_size: usize) void {
     io_buffer = buffer;
     io_buffer_size = buffer_size;
 }
 
+pub fn set_max_tables(limit: u32) void {
+    max_tables = limit;
+}
+
+// Start counting live tables from zero, so the VM's own tables (_home, _io,
+// Modules) don't count against max_tables
+pub fn reset_live_table_count() void {
+    live_table_count = 0;
+}
+
+fn table_limit_reached() bool {
+    return max_tables != 0 and live_table_count >= max_tables;
+}
+
+pub fn set_call_limit(limit: u32) void {
+    call_limit = limit;
+}
+
+// Start a new eval: a fresh storage-call budget, and no transaction left
+// open by an eval that trapped before it could end it
+pub fn begin_eval(L: *lua.lua_State) void {
+    call_count = 0;
+    call_limit_hit = false;
+    abandon_transaction(L);
+}
+
+pub fn call_limit_exceeded() bool {
+    return call_limit_hit;
+}
+
+// Count one storage operation; true once the eval's budget is spent
+fn charge_call() bool {
+    call_count +|= 1;
+    if (call_limit == 0 or call_count <= call_limit) return false;
+    call_limit_hit = true;
+    return true;
+}
+
+// Recorded before it is raised: raising traps on wasm32-freestanding, and
+// the record is all the host gets back then
+fn raise_call_limit(L: *lua.lua_State) c_int {
+    error_handler.set_error(.ext_call_limit, "ext call limit exceeded");
+    _ = lua.pushstring(L, "ext call limit exceeded");
+    return c.lua_error(L);
+}
+
+// With buffering on, assignments to proxies are held in the VM until
+// flush_pending; reads see the held values. Turning it off flushes.
+pub fn set_write_buffering(L: *lua.lua_State, enabled: bool) void {
+    if (!enabled) _ = flush_pending(L);
+    write_buffering = enabled;
+}
+
+// Inside a transaction every assignment is buffered and nothing is flushed
+// until end_transaction decides whether the writes are kept.
+pub fn begin_transaction() void {
+    transaction_active = true;
+}
+
+// Keep (for the next flush) or drop the writes made since begin_transaction
+pub fn end_transaction(L: *lua.lua_State, commit: bool) void {
+    transaction_active = false;
+    if (!commit) discard_pending(L);
+}
+
+// Roll back a transaction a trap cut short. Its held writes are dropped,
+// as they would have been had the error unwound to eval_transactional.
+pub fn abandon_transaction(L: *lua.lua_State) void {
+    if (transaction_active) end_transaction(L, false);
+}
+
+// ext functions that act on the host's copy directly can't be held back
+// with the transaction's writes, nor see them, so they refuse to run
+fn raise_in_transaction(L: *lua.lua_State, name: [*:0]const u8) c_int {
+    _ = lua.pushstring(L, name);
+    _ = lua.pushstring(L, ": not available inside a transaction");
+    c.lua_concat(L, 2);
+    return c.lua_error(L);
+}
+
+// Store one entry written on the VM's behalf rather than through a proxy
+// (ext.merge, a table converted to external). Inside a transaction it is
+// held like an assignment, so a rollback drops it. Returns false if the
+// host refused it.
+pub fn store_entry(L: *lua.lua_State, table_id: u32, key: []const u8, value: []const u8) bool {
+    if (transaction_active) {
+        buffer_write(L, table_id, key, value);
+        return true;
+    }
+    return js_ext_table_set(table_id, key.ptr, key.len, value.ptr, value.len) == 0;
+}
+
+// Pushes the pending writes for table_id, creating the entry if asked.
+// Returns false, pushing nothing, when there is none.
+fn push_pending_writes(L: *lua.lua_State, table_id: u32, create: bool) bool {
+    _ = lua.getfield(L, c.LUA_REGISTRYINDEX, PENDING_WRITES_KEY);
+    if (!lua.istable(L, -1)) {
+        lua.pop(L, 1);
+        if (!create) return false;
+        lua.newtable(L);
+        lua.pushvalue(L, -1);
+        lua.setfield(L, c.LUA_REGISTRYINDEX, PENDING_WRITES_KEY);
+    }
+
+    _ = c.lua_rawgeti(L, -1, table_id);
+    if (!lua.istable(L, -1)) {
+        lua.pop(L, 1);
+        if (!create) {
+            lua.pop(L, 1);
+            return false;
+        }
+        lua.newtable(L);
+        lua.pushvalue(L, -1);
+        c.lua_rawseti(L, -3, table_id);
+    }
+
+    c.lua_rotate(L, -2, -1);
+    lua.pop(L, 1);
+    return true;
+}
+
+// Empty value bytes hold a buffered delete: no serialized value is empty,
+// and reading them back gives nil
+fn buffer_write(L: *lua.lua_State, table_id: u32, key: []const u8, value: []const u8) void {
+    _ = push_pending_writes(L, table_id, true);
+    _ = lua.pushlstring(L, key.ptr, key.len);
+    _ = lua.pushlstring(L, value.ptr, value.len);
+    c.lua_rawset(L, -3);
+    lua.pop(L, 1);
+}
+
+// Push the buffered value for key, deserialized. Returns false, pushing
+// nothing, if the key has no pending write.
+fn push_pending_value(L: *lua.lua_State, table_id: u32, key: []const u8) bool {
+    if (!push_pending_writes(L, table_id, false)) return false;
+
+    _ = lua.pushlstring(L, key.ptr, key.len);
+    _ = c.lua_rawget(L, -2);
+    if (!lua.isstring(L, -1)) {
+        lua.pop(L, 2);
+        return false;
+    }
+
+    var value_len: usize = 0;
+    const value = lua.tolstring(L, -1, &value_len);
+    if (value_len == 0) {
+        lua.pushnil(L);
+    } else {
+        deserialize_stored_value(L, value, value_len) catch lua.pushnil(L);
+    }
+
+    // Keep only the value: writes, bytes, value -> value
+    c.lua_rotate(L, -3, 1);
+    lua.pop(L, 2);
+    return true;
+}
+
+// Length of the buffered value bytes for key, or null if the key has no
+// pending write
+fn pending_value_len(L: *lua.lua_State, table_id: u32, key: []const u8) ?usize {
+    if (!push_pending_writes(L, table_id, false)) return null;
+
+    _ = lua.pushlstring(L, key.ptr, key.len);
+    _ = c.lua_rawget(L, -2);
+    defer lua.pop(L, 2);
+    if (!lua.isstring(L, -1)) return null;
+    return c.lua_rawlen(L, -1);
+}
+
+// Send one table's buffered writes to the host. Returns how many were sent.
+fn flush_table(L: *lua.lua_State, table_id: u32) u32 {
+    if (transaction_active) return 0;
+    if (!push_pending_writes(L, table_id, false)) return 0;
+    const writes = lua.gettop(L);
+
+    var flushed: u32 = 0;
+    lua.pushnil(L);
+    while (c.lua_next(L, writes) != 0) {
+        var key_len: usize = 0;
+        const key = lua.tolstring(L, -2, &key_len);
+        var value_len: usize = 0;
+        const value = lua.tolstring(L, -1, &value_len);
+        if (value_len == 0) {
+            _ = js_ext_table_delete(table_id, key, key_len);
+        } else {
+            _ = js_ext_table_set(table_id, key, key_len, value, value_len);
+        }
+        flushed += 1;
+        lua.pop(L, 1);
+    }
+    lua.pop(L, 1);
+
+    _ = lua.getfield(L, c.LUA_REGISTRYINDEX, PENDING_WRITES_KEY);
+    lua.pushnil(L);
+    c.lua_rawseti(L, -2, table_id);
+    lua.pop(L, 1);
+    return flushed;
+}
+
+// Flush pending writes for the proxy at idx so host-side operations see them
+fn flush_proxy(L: *lua.lua_State, idx: c_int) void {
+    const table_id = proxy_table_id(L, idx);
+    if (table_id != 0) _ = flush_table(L, table_id);
+}
+
+// Send every buffered write to the host. Returns how many were sent.
+pub fn flush_pending(L: *lua.lua_State) u32 {
+    _ = lua.getfield(L, c.LUA_REGISTRYINDEX, PENDING_WRITES_KEY);
+    if (!lua.istable(L, -1)) {
+        lua.pop(L, 1);
+        return 0;
+    }
+
+    // Collect ids first; flush_table edits the pending table
+    const pending = lua.gettop(L);
+    lua.newtable(L);
+    const ids = lua.gettop(L);
+    var id_count: c.lua_Integer = 0;
+    lua.pushnil(L);
+    while (c.lua_next(L, pending) != 0) {
+        lua.pop(L, 1);
+        id_count += 1;
+        lua.pushvalue(L, -1);
+        c.lua_rawseti(L, ids, id_count);
+    }
+
+    var flushed: u32 = 0;
+    var i: c.lua_Integer = 1;
+    while (i <= id_count) : (i += 1) {
+        _ = c.lua_rawgeti(L, ids, i);
+        const table_id: u32 = @intCast(lua.tointeger(L, -1));
+        lua.pop(L, 1);
+        flushed += flush_table(L, table_id);
+    }
+
+    lua.pop(L, 2);
+    return flushed;
+}
+
+// Drop every buffered write without sending it
+pub fn discard_pending(L: *lua.lua_State) void {
+    lua.pushnil(L);
+    lua.setfield(L, c.LUA_REGISTRYINDEX, PENDING_WRITES_KEY);
+}
+
+// The metamethods every proxy metatable has, set on the table at mt
+fn set_proxy_metamethods(L: *lua.lua_State, mt: c_int) void {
+    lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&ext_table_index_impl)));
+    lua.setfield(L, mt, "__index");
+    lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&ext_table_newindex_impl)));
+    lua.setfield(L, mt, "__newindex");
+    lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&ext_table_len_impl)));
+    lua.setfield(L, mt, "__len");
+    lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&ext_table_pairs_impl)));
+    lua.setfield(L, mt, "__pairs");
+}
+
 fn ensure_metatable(L: *lua.lua_State) void {
     const meta_type: [*:0]const u8 = "ext_table_mt";
     if (lua.luaL_newmetatable(L, meta_type) != 0) {
-        lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&ext_table_index_impl)));
-        lua.setfield(L, -2, "__index");
+        set_proxy_metamethods(L, lua.gettop(L));
+    }
+
+    _ = lua.setmetatable(L, -2);
+}
+
+// ext.readonly views share their proxy's table and namespace prefix; this
+// flag in the view's metatable makes __newindex and the ext.* functions that
+// write refuse them
+const READONLY_FIELD: [*:0]const u8 = "__ext_readonly";
+
+fn proxy_is_readonly(L: *lua.lua_State, idx: c_int) bool {
+    if (lua.getmetatable(L, idx) == 0) return false;
+    _ = lua.getfield(L, -1, READONLY_FIELD);
+    const readonly = lua.toboolean(L, -1);
+    lua.pop(L, 2);
+    return readonly;
+}
 
-        lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&ext_table_newindex_impl)));
-        lua.setfield(L, -2, "__newindex");
+fn raise_readonly(L: *lua.lua_State) c_int {
+    _ = lua.pushstring(L, "read-only table");
+    return c.lua_error(L);
+}
 
-        lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&ext_table_len_impl)));
-        lua.setfield(L, -2, "__len");
+// Make the proxy at proxy_index read-only. With a stored user metatable
+// (metatable_id) the proxy gets a private metatable carrying the flag,
+// otherwise the shared read-only one.
+fn set_readonly_metatable(L: *lua.lua_State, proxy_index: c_int, metatable_id: u32) void {
+    if (metatable_id != 0) {
+        set_user_metatable(L, proxy_index, metatable_id);
+        _ = lua.getmetatable(L, proxy_index);
+        lua.pushboolean(L, 1);
+        lua.setfield(L, -2, READONLY_FIELD);
+        lua.pop(L, 1);
+        return;
     }
 
-    _ = lua.setmetatable(L, -2);
+    if (lua.luaL_newmetatable(L, "ext_table_readonly_mt") != 0) {
+        set_proxy_metamethods(L, lua.gettop(L));
+        lua.pushboolean(L, 1);
+        lua.setfield(L, -2, READONLY_FIELD);
+    }
+    _ = lua.setmetatable(L, proxy_index);
 }
 
 fn push_ext_table(L: *lua.lua_State, table_id: u32) void {
@@ -43,9 +391,24 @@ fn push_ext_table(L: *lua.lua_State, table_id: u32) void {
     ensure_metatable(L);
 }
 
+// Returns 0 when every id is in use
+fn allocate_table_id() u32 {
+    if (external_table_counter < MAX_TABLE_ID) {
+        const table_id = external_table_counter;
+        external_table_counter += 1;
+        return table_id;
+    }
+    if (recycled_count == 0) return 0;
+    recycled_count -= 1;
+    return recycled_ids[recycled_count];
+}
+
+// Pushes a proxy for a new table and returns its id. When ids are exhausted
+// or max_tables is reached the id is 0 and the proxy is detached (reads give
+// nil, writes are dropped).
 pub fn create_table(L: *lua.lua_State) u32 {
-    const table_id = external_table_counter;
-    external_table_counter += 1;
+    const table_id = if (table_limit_reached()) 0 else allocate_table_id();
+    if (table_id != 0) live_table_count += 1;
     push_ext_table(L, table_id);
     return table_id;
 }
@@ -54,7 +417,7 @@ pub fn attach_table(L: *lua.lua_State, table_id: u32) void {
     if (table_id == 0) return;
     push_ext_table(L, table_id);
     if (table_id >= external_table_counter) {
-        external_table_counter = table_id + 1;
+        external_table_counter = @min(table_id, MAX_TABLE_ID - 1) + 1;
     }
 }
 
@@ -64,6 +427,377 @@ pub fn sync_counter(next_id: u32) void {
     }
 }
 
+// Restart the id sequence at next_id. Freed ids the new sequence will hand
+// out anyway are forgotten, so no id is issued twice.
+pub fn set_next_id(next_id: u32) void {
+    external_table_counter = next_id;
+    var kept: usize = 0;
+    for (recycled_ids[0..recycled_count]) |table_id| {
+        if (table_id < next_id) {
+            recycled_ids[kept] = table_id;
+            kept += 1;
+        }
+    }
+    recycled_count = kept;
+}
+
+pub fn next_id() u32 {
+    return external_table_counter;
+}
+
+fn proxy_table_id(L: *lua.lua_State, idx: c_int) u32 {
+    if (!lua.istable(L, idx)) return 0;
+
+    var table_id: u32 = 0;
+    _ = lua.getfield(L, idx, "__ext_table_id");
+    if (lua.isnumber(L, -1)) {
+        table_id = @intCast(lua.tointeger(L, -1));
+    }
+    lua.pop(L, 1);
+    return table_id;
+}
+
+// Namespace views (ext.namespace) are proxies over the same table id whose
+// keys are all stored under this prefix; plain proxies have none
+const KEY_PREFIX_FIELD: [*:0]const u8 = "__ext_key_prefix";
+
+fn proxy_key_prefix(L: *lua.lua_State, idx: c_int) []const u8 {
+    if (!lua.istable(L, idx)) return "";
+    const abs_idx = c.lua_absindex(L, idx);
+
+    // Raw lookup: a missing field must not fall through to the host
+    _ = lua.pushstring(L, KEY_PREFIX_FIELD);
+    _ = c.lua_rawget(L, abs_idx);
+    defer lua.pop(L, 1);
+    if (!lua.isstring(L, -1)) return "";
+
+    // The proxy keeps the string alive
+    var prefix_len: usize = 0;
+    const prefix = lua.tolstring(L, -1, &prefix_len);
+    return prefix[0..prefix_len];
+}
+
+// serialize_key for a key used on the proxy at proxy_idx, including the
+// proxy's namespace prefix
+fn serialize_proxy_key(L: *lua.lua_State, proxy_idx: c_int, key_idx: c_int, buffer: [*]u8, max_len: usize) !usize {
+    const prefix = proxy_key_prefix(L, proxy_idx);
+    if (prefix.len > max_len) return serializer.SerializationError.BufferTooSmall;
+    @memcpy(buffer[0..prefix.len], prefix);
+    return prefix.len + try serialize_key(L, key_idx, buffer + prefix.len, max_len - prefix.len);
+}
+
+// Push an array of the keys stored under prefix, with the prefix stripped and
+// integer keys restored. Reads only the host's key list. Returns the count.
+fn push_keys(L: *lua.lua_State, table_id: u32, prefix: []const u8) c.lua_Integer {
+    // The list stays on the stack, under the array, until it's read
+    const list = push_key_list(L, table_id);
+    if (!deterministic_iteration) {
+        push_key_array(L, list, prefix);
+        const count = append_keys(L, list, prefix);
+        c.lua_rotate(L, -2, 1);
+        lua.pop(L, 1);
+        return count;
+    }
+
+    // So does the sorted copy
+    const sorted = push_sorted_keys(L, list);
+    push_key_array(L, sorted, prefix);
+    const count = append_keys(L, sorted, prefix);
+    c.lua_rotate(L, -3, 1);
+    lua.pop(L, 2);
+    return count;
+}
+
+// Push every key of table_id as one newline-separated string, read a page at
+// a time so the list isn't limited to what fits in the IO buffer. The pages
+// are joined by a single lua_concat at the end rather than a luaL_Buffer,
+// which would copy the list each time it grew.
+fn push_key_list(L: *lua.lua_State, table_id: u32) []const u8 {
+    var parts: c_int = 0;
+    var cursor: u32 = 0;
+    while (true) {
+        const result = js_ext_table_keys_page(table_id, cursor, io_buffer, io_buffer_size);
+        if (result < KEYS_PAGE_HEADER_LEN) break;
+
+        const page = io_buffer[0..@intCast(result)];
+        const keys = page[KEYS_PAGE_HEADER_LEN..];
+        if (keys.len > 0) {
+            c.luaL_checkstack(L, 2, "ext table key list");
+            if (parts > 0) {
+                _ = lua.pushstring(L, "\n");
+                parts += 1;
+            }
+            _ = lua.pushlstring(L, keys.ptr, keys.len);
+            parts += 1;
+        }
+
+        cursor = std.mem.readInt(u32, page[0..KEYS_PAGE_HEADER_LEN], .little);
+        if (cursor == 0) break;
+    }
+    c.lua_concat(L, parts);
+
+    var list_len: usize = 0;
+    const list = lua.tolstring(L, -1, &list_len);
+    return list[0..list_len];
+}
+
+pub fn set_deterministic_iteration(enabled: bool) void {
+    deterministic_iteration = enabled;
+}
+
+pub fn set_nil_semantics(mode: NilSemantics) void {
+    nil_semantics = mode;
+}
+
+pub fn set_compression(enabled: bool, threshold: usize) void {
+    compression_enabled = enabled;
+    compression_threshold = threshold;
+}
+
+// Serialize the value at idx as it is stored: compressed if compression is
+// on, its serialized form is over the threshold, and compressing saves
+// space; otherwise exactly as serialize_value writes it.
+fn serialize_stored_value(L: *lua.lua_State, idx: c_int, buffer: [*]u8, max_len: usize) serializer.SerializationError!usize {
+    if (!compression_enabled) return serializer.serialize_value(L, idx, buffer, max_len);
+
+    const len = try serializer.serialize_value(L, idx, &compression_scratch, compression_scratch.len);
+    if (len > compression_threshold and max_len > COMPRESSED_HEADER_SIZE) {
+        if (compress.compress(compression_scratch[0..len], buffer[COMPRESSED_HEADER_SIZE..max_len])) |packed_len| {
+            if (COMPRESSED_HEADER_SIZE + packed_len < len) {
+                buffer[0] = serializer.COMPRESSED_TAG;
+                std.mem.writeInt(u32, buffer[1..COMPRESSED_HEADER_SIZE], @intCast(len), .little);
+                return COMPRESSED_HEADER_SIZE + packed_len;
+            }
+        }
+    }
+
+    if (len > max_len) return serializer.SerializationError.BufferTooSmall;
+    @memcpy(buffer[0..len], compression_scratch[0..len]);
+    return len;
+}
+
+// Push a stored value, decompressing it first if it was stored compressed.
+// Compressed values are read whether or not compression is on now.
+fn deserialize_stored_value(L: *lua.lua_State, bytes: [*]const u8, len: usize) serializer.SerializationError!void {
+    if (len == 0 or bytes[0] != serializer.COMPRESSED_TAG) return serializer.deserialize_value(L, bytes, len);
+    if (len < COMPRESSED_HEADER_SIZE) return serializer.SerializationError.InvalidFormat;
+
+    // The length comes from the host; nothing larger was ever compressed
+    const original_len = std.mem.readInt(u32, bytes[1..COMPRESSED_HEADER_SIZE], .little);
+    if (original_len > MAX_COMPRESSIBLE_VALUE) return serializer.SerializationError.InvalidFormat;
+    const unpacked: [*]u8 = @ptrCast(c.lua_newuserdatauv(L, original_len, 0));
+    const unpacked_len = compress.decompress(bytes[COMPRESSED_HEADER_SIZE..len], unpacked[0..original_len]);
+    if (unpacked_len != original_len) {
+        lua.pop(L, 1);
+        return serializer.SerializationError.InvalidFormat;
+    }
+
+    serializer.deserialize_value(L, unpacked, original_len) catch |err| {
+        lua.pop(L, 1);
+        return err;
+    };
+    // Drop the buffer under the value
+    c.lua_copy(L, -1, -2);
+    lua.pop(L, 1);
+}
+
+fn key_bytes_less(_: void, a: []const u8, b: []const u8) bool {
+    return std.mem.order(u8, a, b) == .lt;
+}
+
+// Push a userdata holding the newline-separated keys of list sorted by their
+// bytes, and return its contents
+fn push_sorted_keys(L: *lua.lua_State, list: []const u8) []const u8 {
+    const key_count = std.mem.count(u8, list, "\n") + 1;
+    const keys_ptr: [*][]const u8 = @ptrCast(@alignCast(c.lua_newuserdatauv(L, key_count * @sizeOf([]const u8), 0)));
+    const keys = keys_ptr[0..key_count];
+
+    var it = std.mem.splitScalar(u8, list, '\n');
+    var i: usize = 0;
+    while (it.next()) |key| : (i += 1) keys[i] = key;
+    std.mem.sort([]const u8, keys, {}, key_bytes_less);
+
+    const sorted: [*]u8 = @ptrCast(c.lua_newuserdatauv(L, list.len, 0));
+    var pos: usize = 0;
+    for (keys, 0..) |key, n| {
+        if (n > 0) {
+            sorted[pos] = '\n';
+            pos += 1;
+        }
+        @memcpy(sorted[pos .. pos + key.len], key);
+        pos += key.len;
+    }
+
+    // Drop the slices, keeping the sorted copy
+    c.lua_rotate(L, -2, 1);
+    lua.pop(L, 1);
+    return sorted[0..list.len];
+}
+
+// Push an empty array for append_keys, sized for the keys in list so
+// iterating allocates in proportion to the table rather than growing by
+// doubling. A namespace view keeps only some of the keys, so its array
+// starts empty instead of sized for the whole backing table.
+fn push_key_array(L: *lua.lua_State, list: []const u8, prefix: []const u8) void {
+    const count = if (prefix.len > 0 or list.len == 0) 0 else std.mem.count(u8, list, "\n") + 1;
+    c.lua_createtable(L, @intCast(count), 0);
+}
+
+// Add the newline-separated keys in list that start with prefix to the array
+// on top of the stack, prefix stripped and integer keys restored. Returns
+// the count added.
+fn append_keys(L: *lua.lua_State, list: []const u8, prefix: []const u8) c.lua_Integer {
+    var count: c.lua_Integer = 0;
+    var it = std.mem.splitScalar(u8, list, '\n');
+    while (it.next()) |stored_key| {
+        if (!std.mem.startsWith(u8, stored_key, prefix)) continue;
+        const key = stored_key[prefix.len..];
+        if (key.len == 0) continue;
+
+        if (parse_integer_key(key)) |int_key| {
+            lua.pushinteger(L, int_key);
+        } else {
+            _ = lua.pushlstring(L, key.ptr, key.len);
+        }
+        count += 1;
+        c.lua_rawseti(L, -2, count);
+    }
+
+    return count;
+}
+
+// js_ext_table_keys_page writes the cursor of the following page, then
+// newline-separated keys as js_ext_table_keys does
+const KEYS_PAGE_HEADER_LEN = 4;
+
+// Push an array of the keys on the page at cursor, filtered like push_keys.
+// Returns the cursor of the next page, 0 after the last one.
+fn push_keys_page(L: *lua.lua_State, table_id: u32, prefix: []const u8, cursor: u32) u32 {
+    const result = js_ext_table_keys_page(table_id, cursor, io_buffer, io_buffer_size);
+    if (result < KEYS_PAGE_HEADER_LEN) {
+        lua.newtable(L);
+        return 0;
+    }
+
+    const page = io_buffer[0..@intCast(result)];
+    push_key_array(L, page[KEYS_PAGE_HEADER_LEN..], prefix);
+    _ = append_keys(L, page[KEYS_PAGE_HEADER_LEN..], prefix);
+    return std.mem.readInt(u32, page[0..KEYS_PAGE_HEADER_LEN], .little);
+}
+
+pub fn free_table(table_id: u32) c_int {
+    if (table_id == 0) return -1;
+    const result = js_ext_table_free(table_id);
+    if (result != 0) return result;
+
+    release_table_id(table_id);
+    return result;
+}
+
+// A table dump is every live entry as a record of
+//   [4 bytes: key length (u32 LE)][key][4 bytes: value length (u32 LE)][value]
+// with keys and values exactly as the host stores them. Checks that bytes is
+// a whole number of records with non-empty keys.
+pub fn is_valid_dump(bytes: []const u8) bool {
+    var pos: usize = 0;
+    while (pos < bytes.len) {
+        const key_len = read_dump_len(bytes, pos) orelse return false;
+        if (key_len == 0) return false;
+        pos += 4;
+        if (key_len > bytes.len - pos) return false;
+        pos += key_len;
+
+        const value_len = read_dump_len(bytes, pos) orelse return false;
+        pos += 4;
+        if (value_len > bytes.len - pos) return false;
+        pos += value_len;
+    }
+    return true;
+}
+
+fn read_dump_len(bytes: []const u8, pos: usize) ?usize {
+    if (bytes.len - pos < 4) return null;
+    return std.mem.readInt(u32, bytes[pos..][0..4], .little);
+}
+
+// Have the host write a dump of table_id to the IO buffer in one call.
+// Returns its length, or -1 if the host can't or it doesn't fit.
+pub fn dump_table(table_id: u32) c_int {
+    if (table_id == 0) return -1;
+    const result = js_ext_table_bulk_dump(table_id, io_buffer, io_buffer_size);
+    if (result < 0) return -1;
+    const len: usize = @intCast(result);
+    if (len > io_buffer_size or !is_valid_dump(io_buffer[0..len])) return -1;
+    return result;
+}
+
+// Replace the entries of table_id with the dump of len bytes in the IO
+// buffer. Returns the host's entry count, or -1 for a malformed dump.
+pub fn load_table(table_id: u32, len: usize) c_int {
+    if (table_id == 0 or len > io_buffer_size) return -1;
+    if (!is_valid_dump(io_buffer[0..len])) return -1;
+    return js_ext_table_bulk_load(table_id, io_buffer, len);
+}
+
+// Stop counting a table and make its id available again
+fn release_table_id(table_id: u32) void {
+    live_table_count -|= 1;
+    if (recycled_count < MAX_RECYCLED_IDS) {
+        recycled_ids[recycled_count] = table_id;
+        recycled_count += 1;
+    }
+}
+
+// Round-trip a value through a scratch table to catch a host whose storage
+// imports misbehave: writes that don't persist, wrong lengths or counts.
+// Returns null if set, get, size, keys and delete all work, otherwise a
+// message naming the first broken one.
+pub fn check_imports() ?[]const u8 {
+    const table_id = allocate_table_id();
+    if (table_id == 0) return "no table id free for the import check";
+    live_table_count += 1;
+    defer {
+        _ = js_ext_table_free(table_id);
+        release_table_id(table_id);
+    }
+
+    const key = "cu_import_check";
+    const value = [_]u8{ @intFromEnum(serializer.SerializationType.string), 2, 0, 0, 0, 'o', 'k' };
+    var buffer: [64]u8 = undefined;
+
+    if (js_ext_table_set(table_id, key, key.len, &value, value.len) != 0) {
+        return "js_ext_table_set reported an error";
+    }
+
+    const got = js_ext_table_get(table_id, key, key.len, &buffer, buffer.len);
+    if (got < 0) return "js_ext_table_get didn't find a value js_ext_table_set stored";
+    if (got != value.len) return "js_ext_table_get returned the wrong length";
+    if (!std.mem.eql(u8, buffer[0..value.len], &value)) return "js_ext_table_get returned different bytes";
+
+    if (js_ext_table_size(table_id) != 1) return "js_ext_table_size miscounted the entries";
+
+    const listed = js_ext_table_keys(table_id, &buffer, buffer.len);
+    if (listed != key.len or !std.mem.eql(u8, buffer[0..key.len], key)) {
+        return "js_ext_table_keys listed the wrong keys";
+    }
+
+    if (js_ext_table_delete(table_id, key, key.len) != 0) return "js_ext_table_delete reported an error";
+    if (js_ext_table_get(table_id, key, key.len, &buffer, buffer.len) != -1) {
+        return "js_ext_table_delete didn't remove the key";
+    }
+    return null;
+}
+
+// Integer keys are stored as their canonical decimal text ("1", "-7").
+// Only that exact form maps back to an integer; "007", "+5" and "-0" are
+// string keys and must come back as strings.
+pub fn parse_integer_key(key: []const u8) ?i64 {
+    const digits = if (key.len > 0 and key[0] == '-') key[1..] else key;
+    if (digits.len == 0 or digits[0] == '+') return null;
+    if (digits[0] == '0' and (digits.len > 1 or digits.len != key.len)) return null;
+    return std.fmt.parseInt(i64, key, 10) catch null;
+}
+
 fn serialize_key(L: *lua.lua_State, idx: c_int, buffer: [*]u8, max_len: usize) !usize {
     if (lua.isstring(L, idx)) {
         var key_len: usize = 0;
@@ -105,10 +839,50 @@ fn serialize_key(L: *lua.lua_State, idx: c_int, buffer: [*]u8, max_len: usize) !
 }
 
 fn ext_table_new_impl(L: *lua.lua_State) c_int {
-    _ = create_table(L);
+    if (table_limit_reached()) {
+        _ = lua.pushstring(L, "ext.table: table limit reached");
+        return c.lua_error(L);
+    }
+    if (create_table(L) == 0) {
+        _ = lua.pushstring(L, "ext.table: external table ids exhausted");
+        return c.lua_error(L);
+    }
     return 1;
 }
 
+// Read key's value from the host into buffer and push it deserialized (nil
+// if it can't be). Returns false, pushing nothing, if the key is missing.
+// The host returns -(needed length) for a value that doesn't fit; it is read
+// again into a temporary Lua buffer of that size rather than looking absent.
+fn push_host_value(L: *lua.lua_State, table_id: u32, key: []const u8, buffer: [*]u8, buffer_len: usize) bool {
+    const result = js_ext_table_get(table_id, key.ptr, key.len, buffer, buffer_len);
+    if (result > 0) {
+        deserialize_stored_value(L, buffer, @intCast(result)) catch lua.pushnil(L);
+        return true;
+    }
+    if (result >= -1) return false;
+
+    const needed: usize = @intCast(-@as(i64, result));
+    const large: [*]u8 = @ptrCast(c.lua_newuserdatauv(L, needed, 0));
+    const retry = js_ext_table_get(table_id, key.ptr, key.len, large, needed);
+    if (retry == -1) {
+        lua.pop(L, 1);
+        return false;
+    }
+    if (retry <= 0) {
+        // Grew again between the two reads
+        _ = lua.pushstring(L, "ext: value changed size while being read");
+        _ = c.lua_error(L);
+        return false;
+    }
+
+    deserialize_stored_value(L, large, @intCast(retry)) catch lua.pushnil(L);
+    // Drop the temporary buffer under the value
+    c.lua_copy(L, -1, -2);
+    lua.pop(L, 1);
+    return true;
+}
+
 fn ext_table_index_impl(L: *lua.lua_State) c_int {
     if (lua.gettop(L) < 2) {
         return 0;
@@ -126,22 +900,51 @@ fn ext_table_index_impl(L: *lua.lua_State) c_int {
         return 1;
     }
 
+    if (charge_call()) return raise_call_limit(L);
+
     const key_buffer_start = io_buffer;
     const key_buffer_size = io_buffer_size / 4;
 
-    const key_len = serialize_key(L, 2, key_buffer_start, key_buffer_size) catch {
+    const key_len = serialize_proxy_key(L, 1, 2, key_buffer_start, key_buffer_size) catch {
         lua.pushnil(L);
         return 1;
     };
 
+    if (push_pending_value(L, table_id, key_buffer_start[0..key_len])) return 1;
+
     const value_buffer_start = io_buffer + io_buffer_size / 4;
     const value_buffer_size = io_buffer_size / 4;
 
-    const result = js_ext_table_get(table_id, key_buffer_start, key_len, value_buffer_start, value_buffer_size);
-    if (result > 0) {
-        serializer.deserialize_value(L, value_buffer_start, @intCast(result)) catch {
-            lua.pushnil(L);
-        };
+    if (push_host_value(L, table_id, key_buffer_start[0..key_len], value_buffer_start, value_buffer_size)) return 1;
+
+    return index_user_metatable(L);
+}
+
+// Missing keys fall through to a restored user metatable's __index, so
+// methods keep working on objects loaded from storage.
+fn index_user_metatable(L: *lua.lua_State) c_int {
+    lua.settop(L, 2);
+
+    if (lua.getmetatable(L, 1) == 0) {
+        lua.pushnil(L);
+        return 1;
+    }
+    _ = lua.getfield(L, -1, USER_METATABLE_FIELD);
+    if (!lua.istable(L, -1)) {
+        lua.pushnil(L);
+        return 1;
+    }
+
+    _ = lua.getfield(L, -1, "__index");
+    if (lua.istable(L, -1)) {
+        lua.pushvalue(L, 2);
+        _ = c.lua_gettable(L, -2);
+        return 1;
+    }
+    if (lua.isfunction(L, -1)) {
+        lua.pushvalue(L, 1);
+        lua.pushvalue(L, 2);
+        c.lua_callk(L, 2, 1, 0, null);
         return 1;
     }
 
@@ -149,10 +952,78 @@ fn ext_table_index_impl(L: *lua.lua_State) c_int {
     return 1;
 }
 
+// Give the proxy at proxy_index a private metatable that keeps the external
+// table behavior and layers the stored user metatable (table metatable_id)
+// on top: its __index answers missing keys, other metamethods are copied.
+pub fn set_user_metatable(L: *lua.lua_State, proxy_index: c_int, metatable_id: u32) void {
+    if (metatable_id == 0) return;
+    const base = lua.gettop(L);
+
+    lua.newtable(L);
+    copy_to_table(L, metatable_id, base + 1);
+    const user_mt = lua.gettop(L);
+
+    // mt.__index = mt was stored as `true`
+    _ = lua.getfield(L, user_mt, "__index");
+    if (lua.isboolean(L, -1)) {
+        lua.pushvalue(L, user_mt);
+        lua.setfield(L, user_mt, "__index");
+    }
+    lua.pop(L, 1);
+
+    lua.newtable(L);
+    const object_mt = lua.gettop(L);
+
+    lua.pushnil(L);
+    while (c.lua_next(L, user_mt) != 0) {
+        lua.pushvalue(L, -2);
+        lua.pushvalue(L, -2);
+        c.lua_rawset(L, object_mt);
+        lua.pop(L, 1);
+    }
+
+    set_proxy_metamethods(L, object_mt);
+
+    lua.pushvalue(L, user_mt);
+    lua.setfield(L, object_mt, USER_METATABLE_FIELD);
+    lua.pushinteger(L, metatable_id);
+    lua.setfield(L, object_mt, USER_METATABLE_ID_FIELD);
+
+    _ = lua.setmetatable(L, proxy_index);
+    lua.settop(L, base);
+}
+
+// Id of the stored user metatable behind the proxy at idx, or 0
+pub fn user_metatable_id(L: *lua.lua_State, idx: c_int) u32 {
+    if (lua.getmetatable(L, idx) == 0) return 0;
+
+    var metatable_id: u32 = 0;
+    _ = lua.getfield(L, -1, USER_METATABLE_ID_FIELD);
+    if (lua.isnumber(L, -1)) {
+        metatable_id = @intCast(lua.tointeger(L, -1));
+    }
+    lua.pop(L, 2);
+    return metatable_id;
+}
+
+// Set the restored user metatable of the proxy at proxy_index (if any) on
+// the table at the top of the stack.
+fn copy_user_metatable(L: *lua.lua_State, proxy_index: c_int) void {
+    if (lua.getmetatable(L, proxy_index) == 0) return;
+    _ = lua.getfield(L, -1, USER_METATABLE_FIELD);
+    if (lua.istable(L, -1)) {
+        _ = lua.setmetatable(L, -3);
+        lua.pop(L, 1);
+    } else {
+        lua.pop(L, 2);
+    }
+}
+
 fn ext_table_newindex_impl(L: *lua.lua_State) c_int {
     if (lua.gettop(L) < 3) {
         return 0;
     }
+    if (proxy_is_readonly(L, 1)) return raise_readonly(L);
 
     var table_id: u32 = 0;
     _ = lua.getfield(L, 1, "__ext_table_id");
@@ -165,43 +1036,903 @@ fn ext_table_newindex_impl(L: *lua.lua_State) c_int {
         return 0;
     }
 
+    if (charge_call()) return raise_call_limit(L);
+
     const key_buffer_start = io_buffer;
     const key_buffer_size = io_buffer_size / 4;
 
-    const key_len = serialize_key(L, 2, key_buffer_start, key_buffer_size) catch {
+    const key_len = serialize_proxy_key(L, 1, 2, key_buffer_start, key_buffer_size) catch {
         return 0;
     };
 
+    if (nil_semantics == .delete and lua.isnil(L, 3)) {
+        if (write_buffering or transaction_active) {
+            buffer_write(L, table_id, key_buffer_start[0..key_len], "");
+        } else {
+            _ = js_ext_table_delete(table_id, key_buffer_start, key_len);
+        }
+        return 0;
+    }
+
     const value_buffer_start = io_buffer + io_buffer_size / 4;
     const value_buffer_size = io_buffer_size / 4;
 
-    const value_len = serializer.serialize_value(L, 3, value_buffer_start, value_buffer_size) catch {
-        return 0;
+    const value_len = serialize_stored_value(L, 3, value_buffer_start, value_buffer_size) catch |err| {
+        return raise_unstorable(L, err, 2) orelse 0;
     };
 
-    _ = js_ext_table_set(table_id, key_buffer_start, key_len, value_buffer_start, value_len);
+    if (write_buffering or transaction_active) {
+        buffer_write(L, table_id, key_buffer_start[0..key_len], value_buffer_start[0..value_len]);
+    } else {
+        _ = js_ext_table_set(table_id, key_buffer_start, key_len, value_buffer_start, value_len);
+    }
 
     return 0;
 }
 
+// __len: a border like Lua's own tables, the largest n such that keys 1..n
+// are all set (0 without key 1), so table.insert and friends work on
+// external arrays. ext.count gives the number of entries.
 fn ext_table_len_impl(L: *lua.lua_State) c_int {
     if (lua.gettop(L) < 1) {
         return 0;
     }
 
-    var table_id: u32 = 0;
-    _ = lua.getfield(L, 1, "__ext_table_id");
-    if (lua.isnumber(L, -1)) {
-        table_id = @intCast(lua.tointeger(L, -1));
+    const table_id = proxy_table_id(L, 1);
+    if (table_id == 0) {
+        lua.pushinteger(L, 0);
+        return 1;
     }
-    lua.settop(L, -2);
 
+    if (charge_call()) return raise_call_limit(L);
+    _ = flush_table(L, table_id);
+
+    const prefix = proxy_key_prefix(L, 1);
+    if (prefix.len > 0) {
+        lua.pushinteger(L, namespace_border(L, table_id, prefix));
+        return 1;
+    }
+
+    // A host that counts tombstones as present reports a border past them
+    var border: c.lua_Integer = @intCast(js_ext_table_seqlen(table_id));
+    while (border > 0 and is_tombstone(L, table_id, "", border)) border -= 1;
+    lua.pushinteger(L, border);
+    return 1;
+}
+
+// Whether the host holds a tombstone (the serialized nil) for the integer
+// key n under prefix. The key is listed, but reads as nil, so # skips it.
+fn is_tombstone(L: *lua.lua_State, table_id: u32, prefix: []const u8, n: c.lua_Integer) bool {
+    _ = lua.pushlstring(L, prefix.ptr, prefix.len);
+    lua.pushinteger(L, n);
+    c.lua_concat(L, 2);
+    defer lua.pop(L, 1);
+
+    var key_len: usize = 0;
+    const key = lua.tolstring(L, -1, &key_len);
+    var value: [1]u8 = undefined;
+    const got = js_ext_table_get(table_id, key, key_len, &value, value.len);
+    return got == 1 and value[0] == @intFromEnum(serializer.SerializationType.nil);
+}
+
+// Border of a namespace view, worked out from its key list since the host
+// only knows whole tables
+fn namespace_border(L: *lua.lua_State, table_id: u32, prefix: []const u8) c.lua_Integer {
+    const count = push_keys(L, table_id, prefix);
+    const keys = lua.gettop(L);
+    lua.newtable(L);
+    const present = lua.gettop(L);
+
+    var i: c.lua_Integer = 1;
+    while (i <= count) : (i += 1) {
+        _ = c.lua_rawgeti(L, keys, i);
+        if (c.lua_isinteger(L, -1) != 0) {
+            lua.pushboolean(L, 1);
+            c.lua_rawset(L, present);
+        } else {
+            lua.pop(L, 1);
+        }
+    }
+
+    var border: c.lua_Integer = 0;
+    while (true) : (border += 1) {
+        const listed = c.lua_rawgeti(L, present, border + 1) != c.LUA_TNIL;
+        lua.pop(L, 1);
+        if (!listed or is_tombstone(L, table_id, prefix, border + 1)) break;
+    }
+    lua.pop(L, 2);
+    return border;
+}
+
+// ext.count(proxy): the number of entries, whatever their keys
+fn ext_table_count_impl(L: *lua.lua_State) c_int {
+    const table_id = proxy_table_id(L, 1);
     if (table_id == 0) {
-        return 0;
+        lua.pushinteger(L, 0);
+        return 1;
+    }
+
+    if (charge_call()) return raise_call_limit(L);
+    flush_proxy(L, 1);
+
+    const prefix = proxy_key_prefix(L, 1);
+    if (prefix.len > 0) {
+        const count = push_keys(L, table_id, prefix);
+        lua.pop(L, 1);
+        lua.pushinteger(L, count);
+        return 1;
+    }
+
+    lua.pushinteger(L, @intCast(js_ext_table_size(table_id)));
+    return 1;
+}
+
+// __pairs: iterate the host's keys a page at a time, so tables with more
+// keys than fit the IO buffer still iterate, fetching each value as it is
+// reached. Keys deleted in the meantime are skipped.
+fn ext_table_pairs_impl(L: *lua.lua_State) c_int {
+    if (charge_call()) return raise_call_limit(L);
+    flush_proxy(L, 1);
+    lua.settop(L, 1);
+
+    var next_cursor: u32 = 0;
+    const table_id = proxy_table_id(L, 1);
+    if (table_id == 0) {
+        lua.newtable(L);
+    } else if (deterministic_iteration) {
+        // Sorting needs every key, so they are all read up front
+        _ = push_keys(L, table_id, proxy_key_prefix(L, 1));
+    } else {
+        next_cursor = push_keys_page(L, table_id, proxy_key_prefix(L, 1), 0);
+    }
+    lua.pushinteger(L, 0);
+    lua.pushinteger(L, next_cursor);
+    c.lua_pushcclosure(L, @as(c.lua_CFunction, @ptrCast(&ext_table_pairs_next)), 3);
+
+    lua.pushvalue(L, 1);
+    lua.pushnil(L);
+    return 3;
+}
+
+fn upvalue_index(i: c_int) c_int {
+    return c.LUA_REGISTRYINDEX - i;
+}
+
+// Upvalues: the current page of keys, the position in it, and the cursor of
+// the next page (0 when there is none)
+fn ext_table_pairs_next(L: *lua.lua_State) c_int {
+    lua.settop(L, 1);
+    var position = lua.tointeger(L, upvalue_index(2));
+
+    while (true) {
+        position += 1;
+        if (c.lua_rawgeti(L, upvalue_index(1), position) == c.LUA_TNIL) {
+            const cursor: u32 = @intCast(lua.tointeger(L, upvalue_index(3)));
+            if (cursor == 0) return 1;
+            lua.pop(L, 1);
+
+            if (charge_call()) return raise_call_limit(L);
+            const next_cursor = push_keys_page(L, proxy_table_id(L, 1), proxy_key_prefix(L, 1), cursor);
+            c.lua_copy(L, -1, upvalue_index(1));
+            lua.pop(L, 1);
+            lua.pushinteger(L, next_cursor);
+            c.lua_copy(L, -1, upvalue_index(3));
+            lua.pop(L, 1);
+            position = 0;
+            continue;
+        }
+
+        lua.pushvalue(L, -1);
+        _ = c.lua_gettable(L, 1);
+        if (!lua.isnil(L, -1)) break;
+        lua.pop(L, 2);
+    }
+
+    lua.pushinteger(L, position);
+    c.lua_copy(L, -1, upvalue_index(2));
+    lua.pop(L, 1);
+    return 2;
+}
+
+// ext.free(proxy): drops the host-side storage and detaches the proxy so
+// later reads take the not-found path instead of resurrecting the table.
+fn ext_table_free_impl(L: *lua.lua_State) c_int {
+    if (proxy_is_readonly(L, 1)) return raise_readonly(L);
+    if (transaction_active) return raise_in_transaction(L, "ext.free");
+    if (charge_call()) return raise_call_limit(L);
+    flush_proxy(L, 1);
+
+    // A namespace view shares its table; freeing it would drop the others
+    const table_id = proxy_table_id(L, 1);
+    if (table_id == 0 or proxy_key_prefix(L, 1).len > 0) {
+        lua.pushboolean(L, 0);
+        return 1;
+    }
+
+    const result = free_table(table_id);
+
+    lua.pushinteger(L, 0);
+    lua.setfield(L, 1, "__ext_table_id");
+
+    lua.pushboolean(L, if (result == 0) 1 else 0);
+    return 1;
+}
+
+// ext.rename(proxy, old_key, new_key): moves a value host-side in a single
+// call. Returns true if old_key existed.
+fn ext_table_rename_impl(L: *lua.lua_State) c_int {
+    if (proxy_is_readonly(L, 1)) return raise_readonly(L);
+    if (transaction_active) return raise_in_transaction(L, "ext.rename");
+    if (charge_call()) return raise_call_limit(L);
+    flush_proxy(L, 1);
+
+    const table_id = proxy_table_id(L, 1);
+    if (table_id == 0) {
+        lua.pushboolean(L, 0);
+        return 1;
+    }
+
+    const key_buffer_size = io_buffer_size / 4;
+    const old_key_start = io_buffer;
+    const new_key_start = io_buffer + key_buffer_size;
+
+    const old_len = serialize_proxy_key(L, 1, 2, old_key_start, key_buffer_size) catch {
+        lua.pushboolean(L, 0);
+        return 1;
+    };
+    const new_len = serialize_proxy_key(L, 1, 3, new_key_start, key_buffer_size) catch {
+        lua.pushboolean(L, 0);
+        return 1;
+    };
+
+    const result = js_ext_table_rename(table_id, old_key_start, old_len, new_key_start, new_len);
+    lua.pushboolean(L, if (result > 0) 1 else 0);
+    return 1;
+}
+
+// ext.swap(proxy, key_a, key_b): exchanges two values host-side in a single
+// call; a missing key counts as nil. Returns true on success.
+fn ext_table_swap_impl(L: *lua.lua_State) c_int {
+    if (proxy_is_readonly(L, 1)) return raise_readonly(L);
+    if (transaction_active) return raise_in_transaction(L, "ext.swap");
+    if (charge_call()) return raise_call_limit(L);
+    flush_proxy(L, 1);
+
+    const table_id = proxy_table_id(L, 1);
+    if (table_id == 0) {
+        lua.pushboolean(L, 0);
+        return 1;
+    }
+
+    const key_buffer_size = io_buffer_size / 4;
+    const a_key_start = io_buffer;
+    const b_key_start = io_buffer + key_buffer_size;
+
+    const a_len = serialize_proxy_key(L, 1, 2, a_key_start, key_buffer_size) catch {
+        lua.pushboolean(L, 0);
+        return 1;
+    };
+    const b_len = serialize_proxy_key(L, 1, 3, b_key_start, key_buffer_size) catch {
+        lua.pushboolean(L, 0);
+        return 1;
+    };
+
+    const result = js_ext_table_swap(table_id, a_key_start, a_len, b_key_start, b_len);
+    lua.pushboolean(L, if (result == 0) 1 else 0);
+    return 1;
+}
+
+// ext.delete_prefix(proxy, prefix): removes every key starting with prefix
+// host-side in a single call, e.g. a whole "user:42:" group. On a namespace
+// view the prefix is within the namespace. Returns how many were removed.
+fn ext_table_delete_prefix_impl(L: *lua.lua_State) c_int {
+    const table_id = proxy_table_id(L, 1);
+    if (table_id == 0 or !lua.isstring(L, 2)) {
+        _ = lua.pushstring(L, "ext.delete_prefix: expected an external table and a prefix");
+        return c.lua_error(L);
+    }
+    if (proxy_is_readonly(L, 1)) return raise_readonly(L);
+    if (transaction_active) return raise_in_transaction(L, "ext.delete_prefix");
+    if (charge_call()) return raise_call_limit(L);
+    flush_proxy(L, 1);
+
+    const namespace_prefix = proxy_key_prefix(L, 1);
+    var prefix_len: usize = 0;
+    const prefix = lua.tolstring(L, 2, &prefix_len);
+    const total_len = namespace_prefix.len + prefix_len;
+    if (total_len > io_buffer_size) {
+        _ = lua.pushstring(L, "ext.delete_prefix: prefix too long");
+        return c.lua_error(L);
+    }
+    @memcpy(io_buffer[0..namespace_prefix.len], namespace_prefix);
+    @memcpy(io_buffer[namespace_prefix.len..total_len], prefix[0..prefix_len]);
+
+    const result = js_ext_table_delete_prefix(table_id, io_buffer, total_len);
+    if (result < 0) {
+        lua.pushboolean(L, 0);
+    } else {
+        lua.pushinteger(L, result);
+    }
+    return 1;
+}
+
+// Give dst_id a copy of src_id's buffered writes, so a clone made inside a
+// transaction sees the writes the transaction still holds
+fn copy_pending_writes(L: *lua.lua_State, src_id: u32, dst_id: u32) void {
+    if (!push_pending_writes(L, src_id, false)) return;
+    const writes = lua.gettop(L);
+    _ = push_pending_writes(L, dst_id, true);
+    const copy = lua.gettop(L);
+
+    lua.pushnil(L);
+    while (c.lua_next(L, writes) != 0) {
+        lua.pushvalue(L, -2);
+        lua.pushvalue(L, -2);
+        c.lua_rawset(L, copy);
+        lua.pop(L, 1);
+    }
+    lua.pop(L, 2);
+}
+
+// ext.clone(proxy): a new external table holding every entry of proxy,
+// copied host-side in a single call. Nested tables are shared with the
+// original, not copied.
+fn ext_table_clone_impl(L: *lua.lua_State) c_int {
+    if (charge_call()) return raise_call_limit(L);
+
+    // A namespace view is only part of its table
+    const src_id = proxy_table_id(L, 1);
+    if (src_id == 0 or proxy_key_prefix(L, 1).len > 0) {
+        _ = lua.pushstring(L, "ext.clone: expected an external table");
+        return c.lua_error(L);
+    }
+    flush_proxy(L, 1);
+
+    if (table_limit_reached()) {
+        _ = lua.pushstring(L, "ext.clone: table limit reached");
+        return c.lua_error(L);
+    }
+    lua.settop(L, 1);
+    const dst_id = create_table(L);
+    if (dst_id == 0) {
+        _ = lua.pushstring(L, "ext.clone: external table ids exhausted");
+        return c.lua_error(L);
+    }
+
+    if (js_ext_table_clone(src_id, dst_id) != 0) {
+        release_table_id(dst_id);
+        _ = lua.pushstring(L, "ext.clone: host could not copy the table");
+        return c.lua_error(L);
+    }
+
+    copy_pending_writes(L, src_id, dst_id);
+    set_user_metatable(L, 2, user_metatable_id(L, 1));
+    return 1;
+}
+
+// ext.contains(proxy, key): true if the key is stored, without fetching its
+// value from the host
+fn ext_table_contains_impl(L: *lua.lua_State) c_int {
+    if (charge_call()) return raise_call_limit(L);
+    flush_proxy(L, 1);
+
+    const table_id = proxy_table_id(L, 1);
+    if (table_id == 0) {
+        lua.pushboolean(L, 0);
+        return 1;
+    }
+
+    const key_buffer_size = io_buffer_size / 4;
+    const key_len = serialize_proxy_key(L, 1, 2, io_buffer, key_buffer_size) catch {
+        lua.pushboolean(L, 0);
+        return 1;
+    };
+
+    // Inside a transaction held writes stay in the VM; they decide
+    if (push_pending_value(L, table_id, io_buffer[0..key_len])) {
+        lua.pushboolean(L, if (lua.isnil(L, -1)) 0 else 1);
+        return 1;
+    }
+
+    const result = js_ext_table_has(table_id, io_buffer, key_len);
+    lua.pushboolean(L, if (result > 0) 1 else 0);
+    return 1;
+}
+
+// ext.value_len(proxy, key): the serialized size in bytes of the value
+// stored under key, or -1 if it is missing, without copying the value out
+// of the host. A stored nil counts as missing.
+fn ext_table_value_len_impl(L: *lua.lua_State) c_int {
+    if (charge_call()) return raise_call_limit(L);
+    flush_proxy(L, 1);
+
+    const table_id = proxy_table_id(L, 1);
+    if (table_id == 0) {
+        lua.pushinteger(L, -1);
+        return 1;
+    }
+
+    const key_buffer_size = io_buffer_size / 4;
+    const key_len = serialize_proxy_key(L, 1, 2, io_buffer, key_buffer_size) catch {
+        lua.pushinteger(L, -1);
+        return 1;
+    };
+
+    // Inside a transaction held writes stay in the VM; they decide
+    const value_len: c.lua_Integer = if (pending_value_len(L, table_id, io_buffer[0..key_len])) |len|
+        @intCast(len)
+    else
+        js_ext_table_value_len(table_id, io_buffer, key_len);
+
+    // A serialized nil is the lone nil tag; every other value is longer
+    lua.pushinteger(L, if (value_len <= 1) -1 else value_len);
+    return 1;
+}
+
+// Push the value stored under the key at key_idx of the proxy at proxy_idx,
+// or nil if it is missing. One host read at most; held writes win.
+fn push_stored_value(L: *lua.lua_State, table_id: u32, proxy_idx: c_int, key_idx: c_int) void {
+    const key_buffer_size = io_buffer_size / 4;
+    const key_len = serialize_proxy_key(L, proxy_idx, key_idx, io_buffer, key_buffer_size) catch {
+        lua.pushnil(L);
+        return;
+    };
+
+    // Inside a transaction held writes stay in the VM; they decide
+    if (push_pending_value(L, table_id, io_buffer[0..key_len])) return;
+
+    const value_buffer = io_buffer + key_buffer_size;
+    const value_buffer_size = io_buffer_size / 4;
+    if (!push_host_value(L, table_id, io_buffer[0..key_len], value_buffer, value_buffer_size)) lua.pushnil(L);
+}
+
+// ext.get_or(proxy, key, default): the stored value, or default if the key
+// is missing. Unlike `proxy[key] or default`, a stored false is returned.
+fn ext_table_get_or_impl(L: *lua.lua_State) c_int {
+    if (charge_call()) return raise_call_limit(L);
+    flush_proxy(L, 1);
+    lua.settop(L, 3);
+
+    const table_id = proxy_table_id(L, 1);
+    if (table_id == 0) {
+        lua.pushvalue(L, 3);
+        return 1;
+    }
+
+    push_stored_value(L, table_id, 1, 2);
+    if (lua.isnil(L, -1)) {
+        lua.pushvalue(L, 3);
+    }
+    return 1;
+}
+
+// Returned from an ext.update function to leave the value untouched
+var keep_sentinel: u8 = 0;
+
+fn is_keep_sentinel(L: *lua.lua_State, idx: c_int) bool {
+    return c.lua_type(L, idx) == c.LUA_TLIGHTUSERDATA and c.lua_touserdata(L, idx) == @as(?*anyopaque, @ptrCast(&keep_sentinel));
+}
+
+// ext.update(proxy, key, fn): stores fn(old value) under key, nil meaning
+// missing, and returns what is now stored. If fn returns ext.keep nothing
+// is written and the old value is returned.
+fn ext_table_update_impl(L: *lua.lua_State) c_int {
+    if (charge_call()) return raise_call_limit(L);
+    flush_proxy(L, 1);
+    lua.settop(L, 3);
+
+    const table_id = proxy_table_id(L, 1);
+    if (table_id == 0) {
+        _ = lua.pushstring(L, "ext.update: expected an external table");
+        return c.lua_error(L);
+    }
+    if (!lua.isfunction(L, 3)) {
+        _ = lua.pushstring(L, "ext.update: expected a function");
+        return c.lua_error(L);
+    }
+
+    push_stored_value(L, table_id, 1, 2);
+    lua.pushvalue(L, 3);
+    lua.pushvalue(L, 4);
+    c.lua_callk(L, 1, 1, 0, null);
+
+    if (is_keep_sentinel(L, 5)) {
+        lua.settop(L, 4);
+        return 1;
+    }
+
+    // The write goes through __newindex, so buffering and transactions apply
+    lua.pushvalue(L, 2);
+    lua.pushvalue(L, 5);
+    c.lua_settable(L, 1);
+    return 1;
+}
+
+// Values that can never be stored (a function, coroutine or userdata, a
+// cycle, an unsupported or invalid metatable) raise, naming the type and the
+// key path down to it, key_index being the key it was stored under. Returns
+// null for other failures, which the caller handles as before.
+fn raise_unstorable(L: *lua.lua_State, err: serializer.SerializationError, key_index: c_int) ?c_int {
+    switch (err) {
+        serializer.SerializationError.TypeMismatch,
+        serializer.SerializationError.CircularReference,
+        serializer.SerializationError.UnsupportedMetatable,
+        serializer.SerializationError.InvalidMetatable,
+        => {
+            serializer.note_failure_key(L, key_index);
+            var message: [serializer.MAX_FAILURE_MESSAGE]u8 = undefined;
+            const text = serializer.describe_failure(err, &message);
+            error_handler.set_error(.serialization_error, text);
+            _ = c.lua_pushlstring(L, text.ptr, text.len);
+            return c.lua_error(L);
+        },
+        else => return null,
+    }
+}
+
+// ext.cas(proxy, key, expected, new): stores new only if the current value
+// serializes to the same bytes as expected (nil matches a missing key).
+// Returns true if the swap happened.
+fn ext_table_cas_impl(L: *lua.lua_State) c_int {
+    if (proxy_is_readonly(L, 1)) return raise_readonly(L);
+    if (transaction_active) return raise_in_transaction(L, "ext.cas");
+    if (charge_call()) return raise_call_limit(L);
+    flush_proxy(L, 1);
+
+    const table_id = proxy_table_id(L, 1);
+    if (table_id == 0) {
+        lua.pushboolean(L, 0);
+        return 1;
+    }
+
+    lua.settop(L, 4);
+
+    const part_size = io_buffer_size / 4;
+    const key_start = io_buffer;
+    const expected_start = io_buffer + part_size;
+    const new_start = io_buffer + part_size * 2;
+
+    const key_len = serialize_proxy_key(L, 1, 2, key_start, part_size) catch {
+        lua.pushboolean(L, 0);
+        return 1;
+    };
+    const expected_len = serialize_stored_value(L, 3, expected_start, part_size) catch |err| {
+        if (raise_unstorable(L, err, 2)) |raised| return raised;
+        lua.pushboolean(L, 0);
+        return 1;
+    };
+    const new_len = serialize_stored_value(L, 4, new_start, part_size) catch |err| {
+        if (raise_unstorable(L, err, 2)) |raised| return raised;
+        lua.pushboolean(L, 0);
+        return 1;
+    };
+
+    const result = js_ext_table_cas(table_id, key_start, key_len, expected_start, expected_len, new_start, new_len);
+    lua.pushboolean(L, if (result == 1) 1 else 0);
+    return 1;
+}
+
+// ext.set_ttl(proxy, key, value, ms): stores value and asks the host to treat
+// it as absent once ms milliseconds have passed. Expiry is host-enforced.
+fn ext_table_set_ttl_impl(L: *lua.lua_State) c_int {
+    if (proxy_is_readonly(L, 1)) return raise_readonly(L);
+    if (transaction_active) return raise_in_transaction(L, "ext.set_ttl");
+    if (charge_call()) return raise_call_limit(L);
+    flush_proxy(L, 1);
+
+    const table_id = proxy_table_id(L, 1);
+    if (table_id == 0) {
+        lua.pushboolean(L, 0);
+        return 1;
+    }
+
+    lua.settop(L, 4);
+
+    const ttl_ms = lua.tointeger(L, 4);
+    if (!lua.isnumber(L, 4) or ttl_ms <= 0) {
+        lua.pushboolean(L, 0);
+        return 1;
+    }
+
+    const key_buffer_size = io_buffer_size / 4;
+    const key_start = io_buffer;
+    const value_start = io_buffer + key_buffer_size;
+
+    const key_len = serialize_proxy_key(L, 1, 2, key_start, key_buffer_size) catch {
+        lua.pushboolean(L, 0);
+        return 1;
+    };
+    const value_len = serialize_stored_value(L, 3, value_start, key_buffer_size) catch |err| {
+        if (raise_unstorable(L, err, 2)) |raised| return raised;
+        lua.pushboolean(L, 0);
+        return 1;
+    };
+
+    const result = js_ext_table_set_ttl(table_id, key_start, key_len, value_start, value_len, ttl_ms);
+    lua.pushboolean(L, if (result == 0) 1 else 0);
+    return 1;
+}
+
+// ext.dofile(proxy, key): loads the source string stored under key and runs
+// it in the current VM, returning whatever the chunk returns.
+fn ext_table_dofile_impl(L: *lua.lua_State) c_int {
+    if (charge_call()) return raise_call_limit(L);
+    flush_proxy(L, 1);
+
+    if (proxy_table_id(L, 1) == 0) {
+        _ = lua.pushstring(L, "ext.dofile: expected an external table");
+        return c.lua_error(L);
+    }
+
+    lua.settop(L, 2);
+    lua.pushvalue(L, 2);
+    _ = c.lua_gettable(L, 1);
+
+    if (!lua.isstring(L, -1)) {
+        var msg_buf: [128]u8 = undefined;
+        const msg = std.fmt.bufPrint(&msg_buf, "ext.dofile: value is not a source string (got {s})", .{
+            std.mem.span(lua.type_name(L, -1)),
+        }) catch "ext.dofile: value is not a source string";
+        _ = lua.pushlstring(L, msg.ptr, msg.len);
+        return c.lua_error(L);
+    }
+
+    var source_len: usize = 0;
+    const source = lua.tolstring(L, -1, &source_len);
+
+    // Text only: stored bytecode must not sneak in through this path
+    const load_result = c.luaL_loadbufferx(L, source, source_len, "=ext.dofile", "t");
+    if (load_result != c.LUA_OK) {
+        return c.lua_error(L);
+    }
+
+    const base = lua.gettop(L) - 1;
+    c.lua_callk(L, 0, c.LUA_MULTRET, 0, null);
+    return lua.gettop(L) - base;
+}
+
+// Build a plain Lua table with the contents of table_id and leave it on the
+// stack. memo_index maps table ids to copies already made, so shared and
+// cyclic references come out as shared and cyclic copies.
+fn copy_to_table(L: *lua.lua_State, table_id: u32, memo_index: c_int) void {
+    lua.pushinteger(L, table_id);
+    _ = c.lua_rawget(L, memo_index);
+    if (!lua.isnil(L, -1)) return;
+    lua.pop(L, 1);
+
+    lua.newtable(L);
+    const copy_index = lua.gettop(L);
+    lua.pushinteger(L, table_id);
+    lua.pushvalue(L, copy_index);
+    c.lua_rawset(L, memo_index);
+
+    // Keys are staged in the upper half of the IO buffer, then anchored as a
+    // Lua string because fetching values reuses the buffer.
+    const keys_start = io_buffer + io_buffer_size / 2;
+    const keys_result = js_ext_table_keys(table_id, keys_start, io_buffer_size / 2);
+    if (keys_result <= 0) return;
+
+    _ = lua.pushlstring(L, keys_start, @intCast(keys_result));
+    var keys_len: usize = 0;
+    const keys = lua.tolstring(L, -1, &keys_len);
+
+    const value_start = io_buffer + io_buffer_size / 4;
+    const value_buffer_size = io_buffer_size / 4;
+
+    var it = std.mem.splitScalar(u8, keys[0..keys_len], '\n');
+    while (it.next()) |key| {
+        if (key.len == 0) continue;
+
+        // Numeric keys were stringified on the way in; restore them so
+        // array-like tables stay arrays
+        if (parse_integer_key(key)) |int_key| {
+            lua.pushinteger(L, int_key);
+        } else {
+            _ = lua.pushlstring(L, key.ptr, key.len);
+        }
+
+        if (!push_host_value(L, table_id, key, value_start, value_buffer_size)) {
+            lua.pop(L, 1);
+            continue;
+        }
+
+        // Past the nesting limit the value stays an external table proxy
+        const nested_id = proxy_table_id(L, -1);
+        if (nested_id != 0) copy_nested: {
+            serializer.enter_deserialize() catch break :copy_nested;
+            defer serializer.leave_deserialize();
+
+            const proxy_index = lua.gettop(L);
+            copy_to_table(L, nested_id, memo_index);
+            copy_user_metatable(L, proxy_index);
+            c.lua_rotate(L, proxy_index, -1);
+            lua.pop(L, 1);
+        }
+
+        c.lua_rawset(L, copy_index);
     }
 
-    const size = js_ext_table_size(table_id);
-    lua.pushinteger(L, @intCast(size));
+    lua.pop(L, 1); // keys string
+}
+
+// Push a detached plain-table copy of the external table at idx. Returns
+// false, pushing nothing, if the value isn't an external table.
+pub fn push_plain_copy(L: *lua.lua_State, idx: c_int) bool {
+    const proxy_index = c.lua_absindex(L, idx);
+    const table_id = proxy_table_id(L, proxy_index);
+    if (table_id == 0) return false;
+
+    lua.newtable(L);
+    const memo_index = lua.gettop(L);
+    copy_to_table(L, table_id, memo_index);
+    copy_user_metatable(L, proxy_index);
+    c.lua_rotate(L, memo_index, -1);
+    lua.pop(L, 1);
+    return true;
+}
+
+// ext.keys(proxy): array of the table's keys, read from the host's key list
+// without fetching any values. Integer-looking keys come back as integers,
+// as in ext.to_table.
+fn ext_table_keys_impl(L: *lua.lua_State) c_int {
+    if (!push_proxy_keys(L, 1)) {
+        _ = lua.pushstring(L, "ext.keys: expected an external table");
+        return c.lua_error(L);
+    }
+    return 1;
+}
+
+// Push the ext.keys array of the external table (or namespace view) at idx.
+// Returns false, pushing nothing, if the value isn't an external table.
+pub fn push_proxy_keys(L: *lua.lua_State, idx: c_int) bool {
+    const proxy_index = c.lua_absindex(L, idx);
+    const table_id = proxy_table_id(L, proxy_index);
+    if (table_id == 0) return false;
+
+    if (charge_call()) _ = raise_call_limit(L);
+    flush_proxy(L, proxy_index);
+    _ = push_keys(L, table_id, proxy_key_prefix(L, proxy_index));
+    return true;
+}
+
+// ext.namespace(proxy, name): a view of the same table that stores every key
+// as "name:key". Views nest, and pairs/ext.keys/# on a view only see its own
+// keys.
+fn ext_table_namespace_impl(L: *lua.lua_State) c_int {
+    const table_id = proxy_table_id(L, 1);
+    if (table_id == 0 or !(lua.isstring(L, 2) or lua.isnumber(L, 2))) {
+        _ = lua.pushstring(L, "ext.namespace: expected an external table and a name");
+        return c.lua_error(L);
+    }
+
+    const prefix = proxy_key_prefix(L, 1);
+    _ = lua.pushlstring(L, prefix.ptr, prefix.len);
+    lua.pushvalue(L, 2);
+    _ = lua.pushstring(L, ":");
+    c.lua_concat(L, 3);
+    const prefix_index = lua.gettop(L);
+
+    // Raw set: the proxy's __newindex would send the field to the host
+    push_ext_table(L, table_id);
+    _ = lua.pushstring(L, KEY_PREFIX_FIELD);
+    lua.pushvalue(L, prefix_index);
+    c.lua_rawset(L, -3);
+
+    // Views of a read-only view stay read-only
+    if (proxy_is_readonly(L, 1)) set_readonly_metatable(L, lua.gettop(L), 0);
+    return 1;
+}
+
+// ext.readonly(proxy): a view of the same table (and namespace) whose reads
+// pass through and whose writes raise "read-only table". The original proxy
+// stays writable.
+fn ext_table_readonly_impl(L: *lua.lua_State) c_int {
+    const table_id = proxy_table_id(L, 1);
+    if (table_id == 0) {
+        _ = lua.pushstring(L, "ext.readonly: expected an external table");
+        return c.lua_error(L);
+    }
+
+    const prefix = proxy_key_prefix(L, 1);
+    _ = lua.pushlstring(L, prefix.ptr, prefix.len);
+    const prefix_index = lua.gettop(L);
+
+    push_ext_table(L, table_id);
+    const view = lua.gettop(L);
+    if (prefix.len > 0) {
+        _ = lua.pushstring(L, KEY_PREFIX_FIELD);
+        lua.pushvalue(L, prefix_index);
+        c.lua_rawset(L, view);
+    }
+    set_readonly_metatable(L, view, user_metatable_id(L, 1));
+    return 1;
+}
+
+// ext.flush(): send buffered writes to the host now. Returns how many.
+fn ext_table_flush_impl(L: *lua.lua_State) c_int {
+    lua.pushinteger(L, flush_pending(L));
+    return 1;
+}
+
+// ext.to_table(proxy): snapshot an external table (and nested proxies) into a
+// regular Lua table. The copy is detached: changing it never touches the store.
+fn ext_table_to_table_impl(L: *lua.lua_State) c_int {
+    if (charge_call()) return raise_call_limit(L);
+    flush_proxy(L, 1);
+
+    if (proxy_key_prefix(L, 1).len > 0) return copy_namespace(L);
+
+    if (!push_plain_copy(L, 1)) {
+        _ = lua.pushstring(L, "ext.to_table: expected an external table");
+        return c.lua_error(L);
+    }
+    return 1;
+}
+
+// ext.to_table for the namespace view at index 1: copy only its own keys
+fn copy_namespace(L: *lua.lua_State) c_int {
+    lua.settop(L, 1);
+    lua.newtable(L);
+    const count = push_keys(L, proxy_table_id(L, 1), proxy_key_prefix(L, 1));
+
+    var i: c.lua_Integer = 1;
+    while (i <= count) : (i += 1) {
+        _ = c.lua_rawgeti(L, 3, i);
+        lua.pushvalue(L, -1);
+        _ = c.lua_gettable(L, 1);
+        if (push_plain_copy(L, -1)) {
+            c.lua_copy(L, -1, -2);
+            lua.pop(L, 1);
+        }
+        c.lua_rawset(L, 2);
+    }
+
+    lua.settop(L, 2);
+    return 1;
+}
+
+// ext.merge(proxy, tbl): writes every pair of a plain Lua table into the
+// store, overwriting existing keys. Nested tables become nested external
+// tables through the regular value serializer. Inside a transaction the
+// writes are held like assignments. Returns the number written.
+fn ext_table_merge_impl(L: *lua.lua_State) c_int {
+    if (proxy_is_readonly(L, 1)) return raise_readonly(L);
+    if (charge_call()) return raise_call_limit(L);
+    flush_proxy(L, 1);
+
+    const table_id = proxy_table_id(L, 1);
+    if (table_id == 0 or !lua.istable(L, 2)) {
+        _ = lua.pushstring(L, "ext.merge: expected an external table and a table");
+        return c.lua_error(L);
+    }
+
+    lua.settop(L, 2);
+
+    const key_buffer_size = io_buffer_size / 4;
+    const key_start = io_buffer;
+    const value_start = io_buffer + key_buffer_size;
+
+    var written: c.lua_Integer = 0;
+    lua.pushnil(L);
+    while (c.lua_next(L, 2) != 0) {
+        // Stack: proxy, tbl, key, value
+        const key_len = serialize_proxy_key(L, 1, 3, key_start, key_buffer_size) catch {
+            lua.pop(L, 1);
+            continue;
+        };
+        const value_len = serialize_stored_value(L, 4, value_start, key_buffer_size) catch |err| {
+            if (raise_unstorable(L, err, 3)) |raised| return raised;
+            lua.pop(L, 1);
+            continue;
+        };
+
+        if (store_entry(L, table_id, key_start[0..key_len], value_start[0..value_len])) {
+            written += 1;
+        }
+        lua.pop(L, 1);
+    }
+
+    lua.pushinteger(L, written);
     return 1;
 }
 
@@ -211,5 +1942,65 @@ pub fn setup_ext_table_library(L: *lua.lua_State) void {
     lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&ext_table_new_impl)));
     lua.setfield(L, -2, "table");
 
+    lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&ext_table_free_impl)));
+    lua.setfield(L, -2, "free");
+
+    lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&ext_table_rename_impl)));
+    lua.setfield(L, -2, "rename");
+
+    lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&ext_table_contains_impl)));
+    lua.setfield(L, -2, "contains");
+
+    lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&ext_table_value_len_impl)));
+    lua.setfield(L, -2, "value_len");
+
+    lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&ext_table_get_or_impl)));
+    lua.setfield(L, -2, "get_or");
+
+    lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&ext_table_update_impl)));
+    lua.setfield(L, -2, "update");
+
+    c.lua_pushlightuserdata(L, &keep_sentinel);
+    lua.setfield(L, -2, "keep");
+
+    lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&ext_table_swap_impl)));
+    lua.setfield(L, -2, "swap");
+
+    lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&ext_table_delete_prefix_impl)));
+    lua.setfield(L, -2, "delete_prefix");
+
+    lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&ext_table_clone_impl)));
+    lua.setfield(L, -2, "clone");
+
+    lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&ext_table_cas_impl)));
+    lua.setfield(L, -2, "cas");
+
+    lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&ext_table_set_ttl_impl)));
+    lua.setfield(L, -2, "set_ttl");
+
+    lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&ext_table_dofile_impl)));
+    lua.setfield(L, -2, "dofile");
+
+    lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&ext_table_to_table_impl)));
+    lua.setfield(L, -2, "to_table");
+
+    lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&ext_table_merge_impl)));
+    lua.setfield(L, -2, "merge");
+
+    lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&ext_table_flush_impl)));
+    lua.setfield(L, -2, "flush");
+
+    lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&ext_table_keys_impl)));
+    lua.setfield(L, -2, "keys");
+
+    lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&ext_table_count_impl)));
+    lua.setfield(L, -2, "count");
+
+    lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&ext_table_namespace_impl)));
+    lua.setfield(L, -2, "namespace");
+
+    lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&ext_table_readonly_impl)));
+    lua.setfield(L, -2, "readonly");
+
     lua.setglobal(L, "ext");
 }
@@ -0,0 +1,166 @@
+const lua = @import("lua.zig");
+
+const c = lua.c;
+
+// Field of a frozen wrapper's metatable holding the table it wraps
+const TARGET_FIELD: [*:0]const u8 = "__cu_frozen";
+// Registry table from each frozen table to its wrapper, with weak keys, so a
+// table reached twice (or through a cycle) always gets the same wrapper
+const WRAPPERS_KEY: [*:0]const u8 = "cu_frozen_wrappers";
+
+fn raise(L: *lua.lua_State, message: [*:0]const u8) c_int {
+    _ = lua.pushstring(L, message);
+    return c.lua_error(L);
+}
+
+// Push the table the frozen wrapper at idx wraps. Returns false, pushing
+// nothing, for any other value.
+fn push_target(L: *lua.lua_State, idx: c_int) bool {
+    if (lua.getmetatable(L, idx) == 0) return false;
+    _ = lua.getfield(L, -1, TARGET_FIELD);
+    if (!lua.istable(L, -1)) {
+        lua.pop(L, 2);
+        return false;
+    }
+    c.lua_rotate(L, -2, 1);
+    lua.pop(L, 1);
+    return true;
+}
+
+// External table proxies and wrappers are left alone: ext.readonly covers
+// the former and the latter are frozen already
+fn needs_wrapper(L: *lua.lua_State, idx: c_int) bool {
+    if (!lua.istable(L, idx)) return false;
+
+    _ = lua.pushstring(L, "__ext_table_id");
+    const is_external = c.lua_rawget(L, idx - 1) != c.LUA_TNIL;
+    lua.pop(L, 1);
+    if (is_external) return false;
+
+    if (!push_target(L, idx)) return true;
+    lua.pop(L, 1);
+    return false;
+}
+
+fn push_wrappers(L: *lua.lua_State) void {
+    if (lua.getfield(L, c.LUA_REGISTRYINDEX, WRAPPERS_KEY) == c.LUA_TTABLE) return;
+    lua.pop(L, 1);
+
+    lua.newtable(L);
+    lua.newtable(L);
+    _ = lua.pushstring(L, "k");
+    lua.setfield(L, -2, "__mode");
+    _ = lua.setmetatable(L, -2);
+    lua.pushvalue(L, -1);
+    lua.setfield(L, c.LUA_REGISTRYINDEX, WRAPPERS_KEY);
+}
+
+// Replace the plain table on top of the stack with its frozen wrapper;
+// other values stay as they are
+fn freeze_top(L: *lua.lua_State) void {
+    if (!needs_wrapper(L, -1)) return;
+    const target = lua.gettop(L);
+
+    push_wrappers(L);
+    const wrappers = target + 1;
+    lua.pushvalue(L, target);
+    if (c.lua_rawget(L, wrappers) != c.LUA_TTABLE) {
+        lua.pop(L, 1);
+
+        lua.newtable(L);
+        c.lua_createtable(L, 0, 6);
+        lua.pushvalue(L, target);
+        lua.setfield(L, -2, TARGET_FIELD);
+        lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&frozen_index_impl)));
+        lua.setfield(L, -2, "__index");
+        lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&frozen_newindex_impl)));
+        lua.setfield(L, -2, "__newindex");
+        lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&frozen_len_impl)));
+        lua.setfield(L, -2, "__len");
+        lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&frozen_pairs_impl)));
+        lua.setfield(L, -2, "__pairs");
+        // getmetatable() returns this, and setmetatable() refuses the wrapper
+        _ = lua.pushstring(L, "frozen");
+        lua.setfield(L, -2, "__metatable");
+        _ = lua.setmetatable(L, -2);
+
+        lua.pushvalue(L, target);
+        lua.pushvalue(L, -2);
+        c.lua_rawset(L, wrappers);
+    }
+
+    c.lua_copy(L, -1, target);
+    lua.settop(L, target);
+}
+
+// Reads go to the wrapped table; tables read through a wrapper are frozen too
+fn frozen_index_impl(L: *lua.lua_State) c_int {
+    lua.settop(L, 2);
+    if (!push_target(L, 1)) {
+        lua.pushnil(L);
+        return 1;
+    }
+    lua.pushvalue(L, 2);
+    _ = c.lua_gettable(L, -2);
+    freeze_top(L);
+    return 1;
+}
+
+fn frozen_newindex_impl(L: *lua.lua_State) c_int {
+    return raise(L, "attempt to modify a frozen table");
+}
+
+fn frozen_len_impl(L: *lua.lua_State) c_int {
+    if (!push_target(L, 1)) {
+        lua.pushinteger(L, 0);
+        return 1;
+    }
+    c.lua_len(L, -1);
+    return 1;
+}
+
+fn frozen_pairs_impl(L: *lua.lua_State) c_int {
+    lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&frozen_next_impl)));
+    lua.pushvalue(L, 1);
+    lua.pushnil(L);
+    return 3;
+}
+
+// next() over the wrapped table, freezing the values it returns
+fn frozen_next_impl(L: *lua.lua_State) c_int {
+    lua.settop(L, 2);
+    if (!push_target(L, 1)) {
+        lua.pushnil(L);
+        return 1;
+    }
+    lua.pushvalue(L, 2);
+    if (c.lua_next(L, 3) == 0) {
+        lua.pushnil(L);
+        return 1;
+    }
+    freeze_top(L);
+    return 2;
+}
+
+// cu.freeze(t): a read-only view of the plain table t. Reads, #, pairs and
+// ipairs see t (and later changes made through t itself); assigning to the
+// view or any table reached through it raises an error.
+fn freeze_impl(L: *lua.lua_State) c_int {
+    lua.settop(L, 1);
+    if (!lua.istable(L, 1)) return raise(L, "cu.freeze: expected a table");
+
+    _ = lua.pushstring(L, "__ext_table_id");
+    if (c.lua_rawget(L, 1) != c.LUA_TNIL) {
+        return raise(L, "cu.freeze: expected a plain table (use ext.readonly for external tables)");
+    }
+    lua.pop(L, 1);
+
+    freeze_top(L);
+    return 1;
+}
+
+// Set cu.freeze on the table at the top of the stack
+pub fn register(L: *lua.lua_State) void {
+    lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&freeze_impl)));
+    lua.setfield(L, -2, "freeze");
+}
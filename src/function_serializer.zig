@@ -14,6 +14,15 @@ pub const FunctionSerializationError = error{
     UpvalueSerializationFailed,
 };
 
+// Dump bytecode without debug info (line numbers, local and upvalue names).
+// Smaller and doesn't reveal source structure; keep it for readable
+// tracebacks during development.
+var strip_bytecode: bool = true;
+
+pub fn set_strip_bytecode(strip: bool) void {
+    strip_bytecode = strip;
+}
+
 // C function registry entry
 const CFunctionEntry = struct {
     name: []const u8,
@@ -121,6 +130,7 @@ pub fn serialize_function(L: *lua.lua_State, stack_index: c_int, buffer: [*]u8,
     return serialize_lua_bytecode(L, stack_index, buffer, max_len) catch |err| switch (err) {
         FunctionSerializationError.BytecodeTooLarge => SerializationError.BufferTooSmall,
         FunctionSerializationError.InvalidBytecode => SerializationError.InvalidFormat,
+        SerializationError.BufferTooSmall => SerializationError.BufferTooSmall,
         else => SerializationError.TypeMismatch,
     };
 }
@@ -195,9 +205,9 @@ fn dump_function(L: *lua.lua_State, stack_index: c_int) !struct { bytecode: [*]c
     lua.c.lua_pushvalue(L, stack_index);
 
     // Push strip parameter (true to strip debug info)
-    lua.pushboolean(L, 1);
+    lua.pushboolean(L, if (strip_bytecode) 1 else 0);
 
-    // Call string.dump(func, true)
+    // Call string.dump(func, strip)
     if (lua.c.lua_pcallk(L, 2, 1, 0, 0, null) != 0) {
         // Error message is on stack - we'll pop it with defer
         return FunctionSerializationError.InvalidBytecode;
@@ -240,7 +250,11 @@ fn serialize_lua_bytecode(L: *lua.lua_State, stack_index: c_int, buffer: [*]u8,
     // Copy bytecode data
     @memcpy(buffer[5 .. 5 + dump_result.len], dump_result.bytecode[0..dump_result.len]);
 
-    return 5 + dump_result.len;
+    // Append captured upvalue values so closures survive a reload
+    const offset = 5 + dump_result.len;
+    const upvalues_len = try serialize_upvalues(L, stack_index, buffer + offset, max_len - offset);
+
+    return offset + upvalues_len;
 }
 
 // Alternative implementation that manages stack directly
@@ -281,7 +295,7 @@ pub fn serialize_function_ref(L: *lua.lua_State, stack_index: c_int, buffer: [*]
 // Deserialize a function from buffer
 pub fn deserialize_function(L: *lua.lua_State, func_type: SerializationType, buffer: [*]const u8, len: usize) SerializationError!void {
     // Note: buffer points to the type byte, skip it for data
-    if (len < 1) return SerializationError.InvalidFormat;
+    try serializer.check_len(len, 1, 0);
 
     const data_buffer = buffer + 1;
     const data_len = len - 1;
@@ -300,7 +314,7 @@ pub fn deserialize_function(L: *lua.lua_State, func_type: SerializationType, buf
 // Deserialize Lua bytecode
 fn deserialize_lua_bytecode(L: *lua.lua_State, buffer: [*]const u8, len: usize) SerializationError!void {
     // Check minimum size (4 bytes length, no type byte as it's already consumed)
-    if (len < 4) return SerializationError.InvalidFormat;
+    try serializer.check_len(len, 4, 0);
 
     // Read bytecode length (little-endian)
     const bytecode_len: u32 = @as(u32, buffer[0]) |
@@ -309,7 +323,7 @@ fn deserialize_lua_bytecode(L: *lua.lua_State, buffer: [*]const u8, len: usize)
         (@as(u32, buffer[3]) << 24);
 
     // Verify we have enough data
-    if (len < 4 + bytecode_len) return SerializationError.InvalidFormat;
+    try serializer.check_len(len, 4, bytecode_len);
 
     // Load the bytecode directly using luaL_loadbufferx
     // Mode "b" for binary (bytecode)
@@ -328,7 +342,7 @@ fn deserialize_lua_bytecode(L: *lua.lua_State, buffer: [*]const u8, len: usize)
 // Deserialize C function reference
 fn deserialize_c_function_ref(L: *lua.lua_State, buffer: [*]const u8, len: usize) SerializationError!void {
     // Check minimum size (2 bytes for index)
-    if (len < 2) return SerializationError.InvalidFormat;
+    try serializer.check_len(len, 2, 0);
 
     // Read the registry index (little-endian)
     const index: u16 = @as(u16, buffer[0]) |
@@ -435,30 +449,137 @@ pub fn init_c_function_registry(L: *lua.lua_State) void {
     // Implementation would iterate through known functions and store their pointers
 }
 
-// Helper function to get upvalue count (for future phases)
+// How a captured upvalue is encoded after the bytecode
+const UpvalueKind = enum(u8) {
+    value = 0x00, // Serialized with serializer.serialize_value
+    globals = 0x01, // The global table (_ENV), rebound on load
+    self_ref = 0x02, // The function itself (recursive local functions)
+};
+
+// Functions captured as upvalues are serialized recursively; cap the chain
+const MAX_UPVALUE_DEPTH: usize = 8;
+var upvalue_depth: usize = 0;
+
+// Get the number of upvalues captured by the function at stack_index
 fn get_upvalue_count(L: *lua.lua_State, stack_index: c_int) u8 {
-    _ = L;
-    _ = stack_index;
-    // Stub for Phase 1 - will be implemented in later phases
-    return 0;
+    lua.c.lua_pushvalue(L, stack_index);
+
+    var ar: lua.c.lua_Debug = undefined;
+    if (lua.c.lua_getinfo(L, ">u", &ar) == 0) {
+        return 0;
+    }
+
+    return ar.nups;
+}
+
+// Check whether the value at idx is the global table
+fn is_globals_table(L: *lua.lua_State, idx: c_int) bool {
+    const abs_idx = lua.c.lua_absindex(L, idx);
+    _ = lua.c.lua_rawgeti(L, lua.c.LUA_REGISTRYINDEX, lua.c.LUA_RIDX_GLOBALS);
+    defer lua.pop(L, 1);
+    return lua.c.lua_rawequal(L, abs_idx, -1) != 0;
 }
 
-// Serialize upvalues (for future phases)
+// Serialize upvalues as: count (1 byte), then per upvalue a kind byte,
+// followed for plain values by a u32 length and the serialized value.
+// Only upvalues that serialize_value supports can be captured.
 fn serialize_upvalues(L: *lua.lua_State, stack_index: c_int, buffer: [*]u8, max_len: usize) !usize {
-    _ = L;
-    _ = stack_index;
-    _ = buffer;
-    _ = max_len;
-    // Stub for Phase 1 - will be implemented in later phases
-    return 0;
+    if (max_len < 1) return SerializationError.BufferTooSmall;
+
+    const func_index = lua.c.lua_absindex(L, stack_index);
+    const count = get_upvalue_count(L, func_index);
+    buffer[0] = count;
+    var offset: usize = 1;
+
+    if (count == 0) return offset;
+
+    if (upvalue_depth >= MAX_UPVALUE_DEPTH) {
+        return FunctionSerializationError.UpvalueSerializationFailed;
+    }
+    upvalue_depth += 1;
+    defer upvalue_depth -= 1;
+
+    var i: c_int = 1;
+    while (i <= count) : (i += 1) {
+        if (lua.c.lua_getupvalue(L, func_index, i) == null) {
+            return FunctionSerializationError.UpvalueSerializationFailed;
+        }
+        defer lua.pop(L, 1);
+
+        if (max_len < offset + 1) return SerializationError.BufferTooSmall;
+
+        if (is_globals_table(L, -1)) {
+            buffer[offset] = @intFromEnum(UpvalueKind.globals);
+            offset += 1;
+            continue;
+        }
+
+        if (lua.c.lua_rawequal(L, -1, func_index) != 0) {
+            buffer[offset] = @intFromEnum(UpvalueKind.self_ref);
+            offset += 1;
+            continue;
+        }
+
+        if (max_len < offset + 5) return SerializationError.BufferTooSmall;
+        buffer[offset] = @intFromEnum(UpvalueKind.value);
+
+        const value_len = serializer.serialize_value(L, -1, buffer + offset + 5, max_len - offset - 5) catch |err| switch (err) {
+            SerializationError.BufferTooSmall => return SerializationError.BufferTooSmall,
+            else => return FunctionSerializationError.UpvalueSerializationFailed,
+        };
+
+        const len_u32: u32 = @intCast(value_len);
+        buffer[offset + 1] = @intCast(len_u32 & 0xFF);
+        buffer[offset + 2] = @intCast((len_u32 >> 8) & 0xFF);
+        buffer[offset + 3] = @intCast((len_u32 >> 16) & 0xFF);
+        buffer[offset + 4] = @intCast((len_u32 >> 24) & 0xFF);
+
+        offset += 5 + value_len;
+    }
+
+    return offset;
 }
 
-// Deserialize upvalues (for future phases)
-fn deserialize_upvalues(L: *lua.lua_State, buffer: [*]const u8, len: usize) !void {
-    _ = L;
-    _ = buffer;
-    _ = len;
-    // Stub for Phase 1 - will be implemented in later phases
+// Rebind upvalues on the function at the top of the stack
+fn deserialize_upvalues(L: *lua.lua_State, buffer: [*]const u8, len: usize) SerializationError!void {
+    try serializer.check_len(len, 1, 0);
+
+    const count = buffer[0];
+    var offset: usize = 1;
+
+    var i: c_int = 1;
+    while (i <= count) : (i += 1) {
+        try serializer.check_len(len, offset, 1);
+
+        const kind = buffer[offset];
+        offset += 1;
+
+        if (kind == @intFromEnum(UpvalueKind.globals)) {
+            _ = lua.c.lua_rawgeti(L, lua.c.LUA_REGISTRYINDEX, lua.c.LUA_RIDX_GLOBALS);
+        } else if (kind == @intFromEnum(UpvalueKind.self_ref)) {
+            lua.pushvalue(L, -1);
+        } else if (kind == @intFromEnum(UpvalueKind.value)) {
+            try serializer.check_len(len, offset, 4);
+
+            const value_len: u32 = @as(u32, buffer[offset]) |
+                (@as(u32, buffer[offset + 1]) << 8) |
+                (@as(u32, buffer[offset + 2]) << 16) |
+                (@as(u32, buffer[offset + 3]) << 24);
+            offset += 4;
+
+            try serializer.check_len(len, offset, value_len);
+
+            try serializer.deserialize_value(L, buffer + offset, value_len);
+            offset += value_len;
+        } else {
+            return SerializationError.InvalidFormat;
+        }
+
+        // lua_setupvalue pops the value on success
+        if (lua.c.lua_setupvalue(L, -2, i) == null) {
+            lua.pop(L, 1);
+        }
+    }
 }
 
 // Validate bytecode for basic security checks
@@ -484,7 +605,7 @@ pub fn validate_bytecode(bytecode: [*]const u8, len: usize) bool {
 // Public function to deserialize bytecode with validation
 pub fn deserialize_function_bytecode(L: *lua.lua_State, buffer: [*]const u8, len: usize) SerializationError!void {
     // Check minimum size (4 bytes length)
-    if (len < 4) return SerializationError.InvalidFormat;
+    try serializer.check_len(len, 4, 0);
 
     // Read bytecode length (little-endian)
     const bytecode_len: u32 = @as(u32, buffer[0]) |
@@ -493,7 +614,7 @@ pub fn deserialize_function_bytecode(L: *lua.lua_State, buffer: [*]const u8, len
         (@as(u32, buffer[3]) << 24);
 
     // Verify we have enough data
-    if (len < 4 + bytecode_len) return SerializationError.InvalidFormat;
+    try serializer.check_len(len, 4, bytecode_len);
 
     // Validate bytecode before loading
     const bytecode_ptr = buffer + 4;
@@ -511,13 +632,21 @@ pub fn deserialize_function_bytecode(L: *lua.lua_State, buffer: [*]const u8, len
         return SerializationError.InvalidFormat;
     }
 
-    // Function is now on top of stack
+    // Function is now on top of stack; restore captured upvalues if present.
+    // Blobs written before upvalue support end right after the bytecode.
+    const upvalue_offset = 4 + bytecode_len;
+    if (len > upvalue_offset) {
+        deserialize_upvalues(L, buffer + upvalue_offset, len - upvalue_offset) catch |err| {
+            lua.pop(L, 1);
+            return err;
+        };
+    }
 }
 
 // Public function to deserialize C function reference
 pub fn deserialize_function_ref(L: *lua.lua_State, buffer: [*]const u8, len: usize) SerializationError!void {
     // Check minimum size (2 bytes for index)
-    if (len < 2) return SerializationError.InvalidFormat;
+    try serializer.check_len(len, 2, 0);
 
     // Read the registry index (little-endian)
     const index: u16 = @as(u16, buffer[0]) | (@as(u16, buffer[1]) << 8);
@@ -0,0 +1,64 @@
+const std = @import("std");
+const lua = @import("lua.zig");
+
+const c = lua.c;
+
+// Opaque host handles (file descriptor ids, capability tokens, ...) live in
+// Lua as 8-byte userdata so they round-trip through storage untouched.
+const HANDLE_METATABLE: [*:0]const u8 = "cu_handle_mt";
+
+fn read_handle(L: *lua.lua_State, idx: c_int) u64 {
+    const ptr = c.lua_touserdata(L, idx);
+    var value: u64 = undefined;
+    @memcpy(std.mem.asBytes(&value), @as([*]const u8, @ptrCast(ptr.?))[0..8]);
+    return value;
+}
+
+fn handle_eq_impl(L: *lua.lua_State) c_int {
+    const a = to_handle(L, 1);
+    const b = to_handle(L, 2);
+    lua.pushboolean(L, if (a != null and b != null and a.? == b.?) 1 else 0);
+    return 1;
+}
+
+fn handle_tostring_impl(L: *lua.lua_State) c_int {
+    var buf: [32]u8 = undefined;
+    const text = std.fmt.bufPrint(&buf, "handle: {d}", .{read_handle(L, 1)}) catch "handle";
+    _ = lua.pushlstring(L, text.ptr, text.len);
+    return 1;
+}
+
+fn ensure_metatable(L: *lua.lua_State) void {
+    if (lua.luaL_newmetatable(L, HANDLE_METATABLE) != 0) {
+        lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&handle_eq_impl)));
+        lua.setfield(L, -2, "__eq");
+
+        lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&handle_tostring_impl)));
+        lua.setfield(L, -2, "__tostring");
+    }
+
+    _ = lua.setmetatable(L, -2);
+}
+
+pub fn push_handle(L: *lua.lua_State, value: u64) void {
+    const ptr = c.lua_newuserdatauv(L, 8, 0);
+    @memcpy(@as([*]u8, @ptrCast(ptr.?))[0..8], std.mem.asBytes(&value));
+    ensure_metatable(L);
+}
+
+// Returns the handle value at idx, or null if it isn't a handle. Light
+// userdata is accepted too and carries its pointer value.
+pub fn to_handle(L: *lua.lua_State, idx: c_int) ?u64 {
+    const value_type = c.lua_type(L, idx);
+
+    if (value_type == c.LUA_TLIGHTUSERDATA) {
+        return @intFromPtr(c.lua_touserdata(L, idx));
+    }
+
+    if (value_type == c.LUA_TUSERDATA) {
+        if (c.luaL_testudata(L, idx, HANDLE_METATABLE) == null) return null;
+        return read_handle(L, idx);
+    }
+
+    return null;
+}
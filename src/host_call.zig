@@ -0,0 +1,85 @@
+const std = @import("std");
+const lua = @import("lua.zig");
+const serializer = @import("serializer.zig");
+
+const c = lua.c;
+
+// host.call(name, ...) dispatches to a function the host registered under
+// name, so new capabilities (fetch, crypto, ...) don't each need an import.
+// The arguments are sent as a u32 count followed by, per argument, a u32
+// length and the serialized value. The host writes one serialized value
+// back and returns its length (0 for nil), or a negative number if it has
+// no such function or the call failed.
+extern fn js_host_call(name_ptr: [*]const u8, name_len: usize, args_ptr: [*]const u8, args_len: usize, out_ptr: [*]u8, max_len: usize) c_int;
+
+var io_buffer: [*]u8 = undefined;
+var io_buffer_size: usize = 0;
+
+pub fn init_host_call(buffer: [*]u8, buffer_size: usize) void {
+    io_buffer = buffer;
+    io_buffer_size = buffer_size;
+}
+
+fn raise(L: *lua.lua_State, message: [*:0]const u8) c_int {
+    _ = lua.pushstring(L, message);
+    return c.lua_error(L);
+}
+
+// Raise "host.call: '<name>' <reason>"
+fn raise_for_name(L: *lua.lua_State, reason: [*:0]const u8) c_int {
+    _ = lua.pushstring(L, "host.call: '");
+    lua.pushvalue(L, 1);
+    _ = lua.pushstring(L, reason);
+    c.lua_concat(L, 3);
+    return c.lua_error(L);
+}
+
+fn host_call_impl(L: *lua.lua_State) c_int {
+    if (c.lua_type(L, 1) != c.LUA_TSTRING) return raise(L, "host.call: expected a function name");
+
+    var name_len: usize = 0;
+    const name = lua.tolstring(L, 1, &name_len);
+
+    // Arguments in the first half of the IO buffer, the result in the second
+    const args = io_buffer;
+    const args_max = io_buffer_size / 2;
+    const out = io_buffer + args_max;
+    const out_max = io_buffer_size - args_max;
+
+    const top = lua.gettop(L);
+    std.mem.writeInt(u32, args[0..4], @intCast(top - 1), .little);
+    var args_len: usize = 4;
+
+    var i: c_int = 2;
+    while (i <= top) : (i += 1) {
+        if (args_max - args_len < 4) return raise_for_name(L, "' arguments too large");
+        const value_start = args + args_len + 4;
+        const value_len = serializer.serialize_value(L, i, value_start, args_max - args_len - 4) catch |err| {
+            if (err == serializer.SerializationError.BufferTooSmall) return raise_for_name(L, "' arguments too large");
+            return raise_for_name(L, "' argument can't be serialized");
+        };
+        std.mem.writeInt(u32, (args + args_len)[0..4], @intCast(value_len), .little);
+        args_len += 4 + value_len;
+    }
+
+    const result = js_host_call(name, name_len, args, args_len, out, out_max);
+    if (result < 0) return raise_for_name(L, "' failed");
+    if (result == 0) {
+        lua.pushnil(L);
+        return 1;
+    }
+    if (@as(usize, @intCast(result)) > out_max) return raise_for_name(L, "' returned too much data");
+
+    serializer.deserialize_value(L, out, @intCast(result)) catch {
+        return raise_for_name(L, "' returned an invalid value");
+    };
+    return 1;
+}
+
+// Register the host global
+pub fn setup_host_library(L: *lua.lua_State) void {
+    lua.newtable(L);
+    lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&host_call_impl)));
+    lua.setfield(L, -2, "call");
+    lua.setglobal(L, "host");
+}
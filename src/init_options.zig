@@ -0,0 +1,61 @@
+const std = @import("std");
+
+// Options blob read by init_with_options: consecutive little-endian u32
+// fields in the order below. A blob may stop after any field; the remaining
+// fields keep their defaults, so older hosts stay compatible as fields are
+// appended.
+//
+//   0  version            must be OPTIONS_VERSION
+//   4  io_buffer_size     bytes of the IO buffer to use (0 = full buffer)
+//   8  memory_limit       Lua allocator cap in bytes (0 = unlimited)
+//  12  instruction_limit  VM instructions per call (0 = unlimited)
+//  16  flags              bit0 = sandbox
+//  20  format             serialization format (0 = default)
+pub const OPTIONS_VERSION: u32 = 1;
+pub const FLAG_SANDBOX: u32 = 0x01;
+pub const FORMAT_DEFAULT: u32 = 0;
+pub const MIN_IO_BUFFER_SIZE: usize = 1024;
+
+const FIELD_SIZE = 4;
+const FIELD_COUNT = 6;
+
+pub const InitOptions = struct {
+    io_buffer_size: usize = 0,
+    memory_limit: usize = 0,
+    instruction_limit: u32 = 0,
+    sandbox: bool = false,
+    format: u32 = FORMAT_DEFAULT,
+};
+
+pub const OptionsError = error{
+    InvalidVersion,
+    InvalidBufferSize,
+    UnsupportedFormat,
+    UnknownFlags,
+};
+
+// Parse an options blob. max_io_buffer_size bounds the requested buffer size.
+pub fn parse(buffer: [*]const u8, len: usize, max_io_buffer_size: usize) OptionsError!InitOptions {
+    var fields = [_]u32{0} ** FIELD_COUNT;
+    const available = @min(len / FIELD_SIZE, FIELD_COUNT);
+    for (0..available) |i| {
+        fields[i] = std.mem.readInt(u32, buffer[i * FIELD_SIZE ..][0..FIELD_SIZE], .little);
+    }
+
+    if (available == 0 or fields[0] != OPTIONS_VERSION) return OptionsError.InvalidVersion;
+
+    const io_buffer_size: usize = fields[1];
+    if (io_buffer_size != 0 and (io_buffer_size < MIN_IO_BUFFER_SIZE or io_buffer_size > max_io_buffer_size)) {
+        return OptionsError.InvalidBufferSize;
+    }
+    if (fields[4] & ~FLAG_SANDBOX != 0) return OptionsError.UnknownFlags;
+    if (fields[5] != FORMAT_DEFAULT) return OptionsError.UnsupportedFormat;
+
+    return InitOptions{
+        .io_buffer_size = io_buffer_size,
+        .memory_limit = fields[2],
+        .instruction_limit = fields[3],
+        .sandbox = fields[4] & FLAG_SANDBOX != 0,
+        .format = fields[5],
+    };
+}
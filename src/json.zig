@@ -0,0 +1,298 @@
+const std = @import("std");
+const lua = @import("lua.zig");
+const serializer = @import("serializer.zig");
+const ext_table = @import("ext_table.zig");
+
+const c = lua.c;
+
+// Nesting depth at which encode_value gives up, which also stops cycles
+const MAX_ENCODE_DEPTH: u32 = 32;
+
+pub const EncodeError = error{
+    UnsupportedType,
+    TooDeep,
+    InvalidKey,
+};
+
+// Why the last encode_value failed, e.g. "cannot encode a function value
+// as JSON"
+var failure: [64]u8 = undefined;
+var failure_len: usize = 0;
+
+pub fn failure_message() []const u8 {
+    return failure[0..failure_len];
+}
+
+fn fail(err: EncodeError, message: []const u8) EncodeError {
+    failure_len = @min(message.len, failure.len);
+    @memcpy(failure[0..failure_len], message[0..failure_len]);
+    return err;
+}
+
+// Bounded JSON writer over a caller-provided buffer. Writes past max_len
+// are dropped and flagged so callers can report the overflow.
+pub const Writer = struct {
+    buffer: [*]u8,
+    max_len: usize,
+    len: usize = 0,
+    overflow: bool = false,
+
+    pub fn init(buffer: [*]u8, max_len: usize) Writer {
+        return .{ .buffer = buffer, .max_len = max_len };
+    }
+
+    pub fn raw(self: *Writer, data: []const u8) void {
+        if (self.overflow or self.len + data.len > self.max_len) {
+            self.overflow = true;
+            return;
+        }
+        @memcpy(self.buffer[self.len .. self.len + data.len], data);
+        self.len += data.len;
+    }
+
+    pub fn byte(self: *Writer, b: u8) void {
+        const one = [1]u8{b};
+        self.raw(&one);
+    }
+
+    pub fn string(self: *Writer, s: []const u8) void {
+        self.byte('"');
+        for (s) |ch| {
+            switch (ch) {
+                '"' => self.raw("\\\""),
+                '\\' => self.raw("\\\\"),
+                '\n' => self.raw("\\n"),
+                '\r' => self.raw("\\r"),
+                '\t' => self.raw("\\t"),
+                else => {
+                    if (ch < 0x20) {
+                        var buf: [8]u8 = undefined;
+                        const escaped = std.fmt.bufPrint(&buf, "\\u{x:0>4}", .{ch}) catch return;
+                        self.raw(escaped);
+                    } else {
+                        self.byte(ch);
+                    }
+                },
+            }
+        }
+        self.byte('"');
+    }
+
+    pub fn integer(self: *Writer, value: i64) void {
+        var buf: [32]u8 = undefined;
+        const text = std.fmt.bufPrint(&buf, "{d}", .{value}) catch return;
+        self.raw(text);
+    }
+
+    pub fn number(self: *Writer, value: f64) void {
+        // JSON has no NaN/Infinity literals
+        if (std.math.isNan(value) or std.math.isInf(value)) {
+            self.raw("null");
+            return;
+        }
+        var buf: [64]u8 = undefined;
+        const text = std.fmt.bufPrint(&buf, "{d}", .{value}) catch return;
+        self.raw(text);
+    }
+};
+
+fn read_u32(bytes: []const u8) u32 {
+    return @as(u32, bytes[0]) |
+        (@as(u32, bytes[1]) << 8) |
+        (@as(u32, bytes[2]) << 16) |
+        (@as(u32, bytes[3]) << 24);
+}
+
+// Write {"type":...,"value":...} for an encoded result value. lua_type is the
+// Lua type of the value; value holds its wire encoding (tag byte first) for
+// the scalar types and is ignored for the others. "output_truncated":true is
+// added when print output hit the capture limit.
+pub fn describe_value(w: *Writer, lua_type: c_int, value: []const u8, output_truncated: bool) void {
+    w.raw("{\"type\":");
+
+    switch (lua_type) {
+        lua.c.LUA_TNIL => w.raw("\"nil\""),
+        lua.c.LUA_TBOOLEAN => {
+            w.raw("\"boolean\",\"value\":");
+            w.raw(if (value.len >= 2 and value[1] != 0) "true" else "false");
+        },
+        lua.c.LUA_TNUMBER => {
+            w.raw("\"number\"");
+            if (value.len >= 9 and value[0] == @intFromEnum(serializer.SerializationType.integer)) {
+                var int_val: i64 = undefined;
+                @memcpy(std.mem.asBytes(&int_val), value[1..9]);
+                w.raw(",\"subtype\":\"integer\",\"value\":");
+                w.integer(int_val);
+            } else if (value.len >= 9) {
+                var float_val: f64 = undefined;
+                @memcpy(std.mem.asBytes(&float_val), value[1..9]);
+                w.raw(",\"subtype\":\"float\",\"value\":");
+                w.number(float_val);
+            }
+        },
+        lua.c.LUA_TSTRING => {
+            w.raw("\"string\"");
+            if (value.len >= 5) {
+                const str_len = read_u32(value[1..5]);
+                const available = @min(@as(usize, str_len), value.len - 5);
+                w.raw(",\"value\":");
+                w.string(value[5 .. 5 + available]);
+            }
+        },
+        lua.c.LUA_TTABLE => {
+            w.raw("\"table\"");
+            if (value.len >= 5 and value[0] == @intFromEnum(serializer.SerializationType.table_ref)) {
+                w.raw(",\"id\":");
+                w.integer(read_u32(value[1..5]));
+            } else {
+                w.raw(",\"serialized\":false");
+            }
+        },
+        lua.c.LUA_TFUNCTION => w.raw("\"function\""),
+        lua.c.LUA_TTHREAD => w.raw("\"thread\""),
+        else => w.raw("\"userdata\""),
+    }
+
+    if (output_truncated) {
+        w.raw(",\"output_truncated\":true");
+    }
+
+    w.byte('}');
+}
+
+pub fn describe_error(w: *Writer, message: []const u8) void {
+    w.raw("{\"type\":\"error\",\"message\":");
+    w.string(message);
+    w.byte('}');
+}
+
+// Push the keys of the table at idx as an array, as deepequal does: the
+// host's key list for an external table, its own keys for a plain one.
+// Returns true for a plain table.
+fn push_key_list(L: *lua.lua_State, idx: c_int) bool {
+    if (ext_table.push_proxy_keys(L, idx)) return false;
+
+    lua.newtable(L);
+    var count: c.lua_Integer = 0;
+    lua.pushnil(L);
+    while (c.lua_next(L, idx) != 0) {
+        lua.pop(L, 1);
+        lua.pushvalue(L, -1);
+        count += 1;
+        c.lua_rawseti(L, -3, count);
+    }
+    return true;
+}
+
+// Push table[key] for the key on top of the stack, replacing the key
+fn push_field(L: *lua.lua_State, table: c_int, plain: bool) void {
+    if (plain) {
+        _ = c.lua_rawget(L, table);
+    } else {
+        _ = c.lua_gettable(L, table);
+    }
+}
+
+// A table whose keys are exactly the integers 1..count is an array
+fn is_array(L: *lua.lua_State, keys: c_int, count: c.lua_Integer) bool {
+    if (count == 0) return false;
+    var i: c.lua_Integer = 1;
+    while (i <= count) : (i += 1) {
+        _ = c.lua_rawgeti(L, keys, i);
+        var is_integer: c_int = 0;
+        const key = c.lua_tointegerx(L, -1, &is_integer);
+        const numeric = c.lua_type(L, -1) == c.LUA_TNUMBER;
+        lua.pop(L, 1);
+        if (!numeric or is_integer == 0 or key < 1 or key > count) return false;
+    }
+    return true;
+}
+
+fn encode_table(w: *Writer, L: *lua.lua_State, idx: c_int, depth: u32) EncodeError!void {
+    if (depth >= MAX_ENCODE_DEPTH) {
+        return fail(error.TooDeep, "cannot encode as JSON: tables nested too deeply or cyclic");
+    }
+    c.luaL_checkstack(L, 8, "JSON encoding");
+
+    const top = lua.gettop(L);
+    defer lua.settop(L, top);
+
+    const plain = push_key_list(L, idx);
+    const keys = lua.gettop(L);
+    const count: c.lua_Integer = @intCast(c.lua_rawlen(L, keys));
+
+    if (is_array(L, keys, count)) {
+        w.byte('[');
+        var i: c.lua_Integer = 1;
+        while (i <= count) : (i += 1) {
+            if (i > 1) w.byte(',');
+            lua.pushinteger(L, i);
+            push_field(L, idx, plain);
+            try encode_value(w, L, lua.gettop(L), depth + 1);
+            lua.pop(L, 1);
+        }
+        w.byte(']');
+        return;
+    }
+
+    w.byte('{');
+    var first = true;
+    var i: c.lua_Integer = 1;
+    while (i <= count) : (i += 1) {
+        _ = c.lua_rawgeti(L, keys, i);
+        const key = lua.gettop(L);
+        lua.pushvalue(L, key);
+        push_field(L, idx, plain);
+        // External tables can list keys whose value is nil
+        if (!lua.isnil(L, -1)) {
+            if (!first) w.byte(',');
+            first = false;
+            switch (c.lua_type(L, key)) {
+                // Numbers become their text, converted on a copy so the key
+                // list isn't changed
+                c.LUA_TSTRING, c.LUA_TNUMBER => {
+                    lua.pushvalue(L, key);
+                    var key_len: usize = 0;
+                    const text = lua.tolstring(L, -1, &key_len);
+                    w.string(text[0..key_len]);
+                    lua.pop(L, 1);
+                },
+                else => return fail(error.InvalidKey, "cannot encode as JSON: table keys must be strings or numbers"),
+            }
+            w.byte(':');
+            try encode_value(w, L, key + 1, depth + 1);
+        }
+        lua.settop(L, keys);
+    }
+    w.byte('}');
+}
+
+// Write the Lua value at idx (an absolute index) as JSON: nil as null,
+// integers and floats as numbers, tables whose keys are 1..n as arrays and
+// other tables, plain or external, as objects. Functions, coroutines and
+// userdata fail, as do tables too deep to encode, with failure_message()
+// saying why. Reading an external table can still raise.
+pub fn encode_value(w: *Writer, L: *lua.lua_State, idx: c_int, depth: u32) EncodeError!void {
+    switch (c.lua_type(L, idx)) {
+        c.LUA_TNIL => w.raw("null"),
+        c.LUA_TBOOLEAN => w.raw(if (lua.toboolean(L, idx)) "true" else "false"),
+        c.LUA_TNUMBER => {
+            if (c.lua_isinteger(L, idx) != 0) {
+                w.integer(lua.tointeger(L, idx));
+            } else {
+                w.number(lua.tonumber(L, idx));
+            }
+        },
+        c.LUA_TSTRING => {
+            var len: usize = 0;
+            const text = lua.tolstring(L, idx, &len);
+            w.string(text[0..len]);
+        },
+        c.LUA_TTABLE => try encode_table(w, L, idx, depth),
+        else => {
+            const message = std.fmt.bufPrint(&failure, "cannot encode a {s} value as JSON", .{std.mem.span(lua.type_name(L, idx))}) catch unreachable;
+            failure_len = message.len;
+            return error.UnsupportedType;
+        },
+    }
+}
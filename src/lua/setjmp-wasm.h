@@ -23,6 +23,15 @@ static inline void _longjmp(jmp_buf env, int val) {
     __builtin_unreachable();
 }
 
+/* Lua can't unwind, so ldo.c throws through cu_lua_throw (main.zig), which
+ * records the error for the host and then traps */
+struct lua_State;
+extern void cu_lua_throw(struct lua_State *L, int status) __attribute__((noreturn));
+
+#define LUAI_THROW(L,c)		cu_lua_throw(L, (c)->status)
+#define LUAI_TRY(L,c,a)		{ a }
+#define luai_jmpbuf		int  /* dummy variable */
+
 #else
 #include <setjmp.h>
 #endif
@@ -1,10 +1,28 @@
 const std = @import("std");
+const builtin = @import("builtin");
 const lua = @import("lua.zig");
 const serializer = @import("serializer.zig");
 const ext_table = @import("ext_table.zig");
 const error_handler = @import("error.zig");
 const output_capture = @import("output.zig");
 const result_encoder = @import("result.zig");
+const coroutine = @import("coroutine.zig");
+const json = @import("json.zig");
+const compile_cache = @import("compile_cache.zig");
+const module_loader = @import("module_loader.zig");
+const init_options = @import("init_options.zig");
+const vm_snapshot = @import("snapshot.zig");
+const utf8_lib = @import("utf8_lib.zig");
+const function_serializer = @import("function_serializer.zig");
+const host_call = @import("host_call.zig");
+const entropy = @import("entropy.zig");
+const stdlib = @import("stdlib.zig");
+const deepequal = @import("deepequal.zig");
+const freeze = @import("freeze.zig");
+const error_capture = @import("error_capture.zig");
+const pack = @import("pack.zig");
+const clock = @import("clock.zig");
+const value_type = @import("value_type.zig");
 
 extern fn luaopen_bigint(L: *lua.lua_State) c_int;
 extern fn bigint_set_allocator(allocator: *anyopaque) void;
@@ -12,23 +30,68 @@ extern fn bigint_set_allocator(allocator: *anyopaque) void;
 const IO_BUFFER_SIZE = 64 * 1024;
 const TOTAL_MEMORY = 2 * 1024 * 1024;
 
+// Runtime version exposed to scripts as cu.version; keep in sync with package.json
+pub const CU_VERSION = "2.1.0";
+
+// What get_features reports: the optional parts of the runtime this module
+// has, so a host can check before relying on one. "minimal" marks a
+// ReleaseSmall build, which is what build.sh --minimal produces; it has the
+// same code and features, just compiled for size.
+const FEATURES = "bigint,compression,pack,resumable,snapshot,streaming" ++
+    (if (builtin.mode == .ReleaseSmall) ",minimal" else "");
+
+// Globals a chunk run by eval_with_env can read through its environment
+const SAFE_ENV_GLOBALS = [_][*:0]const u8{ "assert", "error", "ipairs", "next", "pairs", "pcall", "print", "warn", "select", "tonumber", "tostring", "type", "xpcall", "rawequal", "rawget", "rawlen", "rawset", "string", "table", "math", "utf8", "cu" };
+
 // Storage namespace constants
 const HOME_TABLE_NAME = "_home";
 const LEGACY_MEMORY_NAME = "Memory";
 
 var io_buffer: [IO_BUFFER_SIZE]u8 align(16) = undefined;
+var io_buffer_len: usize = IO_BUFFER_SIZE; // usable prefix of io_buffer
+var io_buffer_generation: i32 = 0; // bumped each time init sets up the buffer
 var heap: [TOTAL_MEMORY]u8 align(4096) = undefined;
 var global_lua_state: ?*lua.lua_State = null;
+var stream_data: [*]u8 = &io_buffer; // see new_stream_buffer
+var stream_len: usize = 0;
+var staged_len: usize = 0;
+var staged_name: [STAGED_NAME_SIZE + 1]u8 = undefined; // + 1 for the NUL
 var lua_memory_used: usize = 0;
+var lua_memory_peak: usize = 0;
+var lua_memory_limit: usize = 0; // 0 = unlimited
+var alloc_counters: AllocCounters = .{};
+var instruction_limit: u32 = 0; // 0 = unlimited
+var instructions_left: u32 = 0;
+var hook_interval: u32 = 0;
+var deadline_ms: c_long = 0; // 0 = none
+var eval_started_at: c_long = 0;
+var deadline_hit: bool = false;
+var sandbox_enabled: bool = false;
+var std_libs: u32 = stdlib.ALL;
 var memory_table_id: u32 = 0;
 var io_table_id: u32 = 0;
+var modules_table_id: u32 = 0;
 var enable_memory_alias: bool = true; // Feature flag for backward compatibility
 
+extern fn js_time_now() c_long;
 extern fn js_ext_table_set(table_id: u32, key_ptr: [*]const u8, key_len: usize, val_ptr: [*]const u8, val_len: usize) c_int;
 extern fn js_ext_table_get(table_id: u32, key_ptr: [*]const u8, key_len: usize, val_ptr: [*]u8, max_len: usize) c_int;
 extern fn js_ext_table_delete(table_id: u32, key_ptr: [*]const u8, key_len: usize) c_int;
 extern fn js_ext_table_size(table_id: u32) usize;
+extern fn js_ext_table_seqlen(table_id: u32) usize;
 extern fn js_ext_table_keys(table_id: u32, buf_ptr: [*]u8, max_len: usize) c_int;
+extern fn js_ext_table_keys_page(table_id: u32, cursor: u32, out_ptr: [*]u8, max_len: usize) c_int;
+extern fn js_ext_table_free(table_id: u32) c_int;
+extern fn js_ext_table_rename(table_id: u32, old_ptr: [*]const u8, old_len: usize, new_ptr: [*]const u8, new_len: usize) c_int;
+extern fn js_ext_table_cas(table_id: u32, key_ptr: [*]const u8, key_len: usize, expected_ptr: [*]const u8, expected_len: usize, new_ptr: [*]const u8, new_len: usize) c_int;
+extern fn js_ext_table_set_ttl(table_id: u32, key_ptr: [*]const u8, key_len: usize, val_ptr: [*]const u8, val_len: usize, ttl_ms: i64) c_int;
+extern fn js_ext_table_has(table_id: u32, key_ptr: [*]const u8, key_len: usize) c_int;
+extern fn js_ext_table_value_len(table_id: u32, key_ptr: [*]const u8, key_len: usize) c_int;
+extern fn js_ext_table_swap(table_id: u32, a_ptr: [*]const u8, a_len: usize, b_ptr: [*]const u8, b_len: usize) c_int;
+extern fn js_ext_table_clone(src_id: u32, dst_id: u32) c_int;
+extern fn js_ext_table_bulk_dump(table_id: u32, out_ptr: [*]u8, max_len: usize) c_int;
+extern fn js_ext_table_bulk_load(table_id: u32, in_ptr: [*]const u8, len: usize) c_int;
+extern fn js_ext_table_delete_prefix(table_id: u32, prefix_ptr: [*]const u8, prefix_len: usize) c_int;
 
 // Import our renamed allocators from libc-stubs.zig
 extern fn lua_malloc(size: usize) ?*anyopaque;
@@ -71,17 +134,38 @@ const lua_allocator = std.mem.Allocator{
     },
 };
 
-// Custom allocator function for Lua
+// Custom allocator function for Lua; also tracks usage, peak, and the limit
 export fn lua_alloc(ud: ?*anyopaque, ptr: ?*anyopaque, osize: usize, nsize: usize) ?*anyopaque {
     _ = ud;
-    _ = osize;
+
+    // When ptr is null, osize encodes the object type rather than a size
+    const old_size: usize = if (ptr == null) 0 else osize;
 
     if (nsize == 0) {
         lua_free(ptr);
+        lua_memory_used -|= old_size;
+        alloc_counters.total_freed += old_size;
         return null;
     }
 
-    return lua_realloc(ptr, nsize);
+    if (lua_memory_limit > 0 and nsize > old_size and (lua_memory_used -| old_size) + nsize > lua_memory_limit) {
+        return null;
+    }
+
+    const new_ptr = lua_realloc(ptr, nsize);
+    if (new_ptr == null) return null;
+
+    lua_memory_used = (lua_memory_used -| old_size) + nsize;
+    if (lua_memory_used > lua_memory_peak) {
+        lua_memory_peak = lua_memory_used;
+    }
+
+    // A realloc counts as freeing the old block and allocating the new one
+    alloc_counters.total_freed += old_size;
+    alloc_counters.total_allocated += nsize;
+    alloc_counters.alloc_count += 1;
+
+    return new_ptr;
 }
 
 export fn init() i32 {
@@ -89,6 +173,11 @@ export fn init() i32 {
         return 0;
     }
 
+    lua_memory_used = 0;
+    lua_memory_peak = 0;
+    alloc_counters = .{};
+    compile_cache.reset();
+
     // Use lua_newstate with custom allocator instead of luaL_newstate
     const L = lua.c.lua_newstate(lua_alloc, null);
     if (L == null) {
@@ -96,25 +185,141 @@ export fn init() i32 {
     }
 
     global_lua_state = L;
-    lua.openlibs(L.?);
-    lua_memory_used = 0;
+    io_buffer_generation +%= 1;
+    stdlib.open(L.?, std_libs);
 
     error_handler.init_error_state();
     output_capture.init_output_capture();
 
-    ext_table.init_ext_table(&io_buffer, IO_BUFFER_SIZE);
+    ext_table.init_ext_table(&io_buffer, io_buffer_len);
     ext_table.setup_ext_table_library(L.?);
+    host_call.init_host_call(&io_buffer, io_buffer_len);
+    host_call.setup_host_library(L.?);
     setup_print_override(L.?);
     setup_memory_global(L.?);
     setup_io_global(L.?);
     setup_bigint_library(L.?);
+    setup_modules_global(L.?);
+    setup_cu_global(L.?);
+    entropy.reseed_math_random(L.?);
+    gc_cycles = 0;
+    arm_gc_sentinel(L.?);
+    ext_table.reset_live_table_count();
+    if (sandbox_enabled) {
+        setup_sandbox(L.?);
+    }
+    vm_snapshot.capture_baseline(L.?);
 
     return 0;
 }
 
+// Initialize from an options blob written to the IO buffer (layout in
+// init_options.zig). Returns -1 if the blob is invalid or the state already
+// exists, since options can't be applied to a running state.
+export fn init_with_options(len: usize) i32 {
+    if (global_lua_state != null) return -1;
+    if (len > IO_BUFFER_SIZE) return -1;
+
+    const options = init_options.parse(&io_buffer, len, IO_BUFFER_SIZE) catch return -1;
+
+    io_buffer_len = if (options.io_buffer_size == 0) IO_BUFFER_SIZE else options.io_buffer_size;
+    lua_memory_limit = options.memory_limit;
+    instruction_limit = options.instruction_limit;
+    sandbox_enabled = options.sandbox;
+
+    return init();
+}
+
+// Initialize with only some standard libraries: flags is a mask of the
+// stdlib.zig bits (coroutine 0x01, table 0x02, io 0x04, os 0x08, string 0x10,
+// math 0x20, utf8 0x40, debug 0x80). base and package always load. Returns
+// -1 if the state already exists or flags has unknown bits.
+export fn init_with_libs(flags: u32) i32 {
+    if (global_lua_state != null) return -1;
+    if (flags & ~stdlib.ALL != 0) return -1;
+
+    std_libs = flags;
+    return init();
+}
+
+// Remove the globals that reach outside the VM
+fn setup_sandbox(L: *lua.lua_State) void {
+    const blocked_globals = [_][*:0]const u8{ "io", "debug", "dofile", "loadfile" };
+    for (blocked_globals) |name| {
+        lua.pushnil(L);
+        lua.setglobal(L, name);
+    }
+
+    const blocked_os = [_][*:0]const u8{ "execute", "exit", "remove", "rename", "tmpname", "getenv" };
+    if (lua.getglobal(L, "os") != lua.c.LUA_TTABLE) {
+        lua.pop(L, 1);
+        return;
+    }
+    for (blocked_os) |name| {
+        lua.pushnil(L);
+        lua.setfield(L, -2, name);
+    }
+    lua.pop(L, 1);
+}
+
+// Instructions between clock reads while a deadline is set. Small, so a
+// loop spinning on slow ext calls notices the deadline soon after it passes.
+const DEADLINE_CHECK_INTERVAL: u32 = 100;
+
+// The limit errors are recorded before they are raised: raising traps on
+// wasm32-freestanding, and the record is all the host gets back then
+fn limit_hook(L: ?*lua.lua_State, ar: [*c]lua.c.lua_Debug) callconv(.c) void {
+    _ = ar;
+    // js_time_now is 32-bit and wraps, so compare elapsed time
+    if (deadline_ms > 0 and js_time_now() -% eval_started_at >= deadline_ms) {
+        deadline_hit = true;
+        error_handler.set_error(.timeout, "deadline exceeded");
+        _ = lua.c.lua_pushstring(L, "deadline exceeded");
+        _ = lua.c.lua_error(L);
+    }
+
+    if (instruction_limit == 0) return;
+    instructions_left -= hook_interval;
+    if (instructions_left == 0) {
+        error_handler.set_error(.runtime_error, "instruction limit exceeded");
+        _ = lua.c.lua_pushstring(L, "instruction limit exceeded");
+        _ = lua.c.lua_error(L);
+    }
+    // Stop exactly at the limit when it isn't a multiple of the interval
+    if (instructions_left < hook_interval) {
+        hook_interval = instructions_left;
+        lua.c.lua_sethook(L, limit_hook, lua.c.LUA_MASKCOUNT, @intCast(hook_interval));
+    }
+}
+
+// Re-arm the count hook so each call gets the full instruction budget and
+// a deadline measured from now. Every eval entry point calls this on the
+// thread that runs the chunk, since hooks are per thread and one left from
+// an earlier eval would measure from its start.
+fn arm_limit_hook(L: *lua.lua_State) void {
+    deadline_hit = false;
+    eval_started_at = js_time_now();
+    if (instruction_limit == 0 and deadline_ms == 0) {
+        lua.c.lua_sethook(L, null, 0, 0);
+        return;
+    }
+
+    hook_interval = if (deadline_ms == 0)
+        instruction_limit
+    else if (instruction_limit == 0)
+        DEADLINE_CHECK_INTERVAL
+    else
+        @min(instruction_limit, DEADLINE_CHECK_INTERVAL);
+    instructions_left = instruction_limit;
+    lua.c.lua_sethook(L, limit_hook, lua.c.LUA_MASKCOUNT, @intCast(hook_interval));
+}
+
 fn setup_print_override(L: *lua.lua_State) void {
     lua.pushcfunction(L, @as(lua.c.lua_CFunction, @ptrCast(&output_capture.custom_print)));
     lua.setglobal(L, "print");
+    lua.pushcfunction(L, @as(lua.c.lua_CFunction, @ptrCast(&output_capture.custom_warn)));
+    lua.setglobal(L, "warn");
+    lua.c.lua_setwarnf(L, &output_capture.warn_handler, null);
 }
 
 fn setup_memory_global(L: *lua.lua_State) void {
@@ -135,6 +340,35 @@ fn setup_io_global(L: *lua.lua_State) void {
     lua.setglobal(L, "_io");
 }
 
+fn setup_modules_global(L: *lua.lua_State) void {
+    modules_table_id = ext_table.create_table(L);
+    lua.setglobal(L, module_loader.MODULES_TABLE_NAME);
+    module_loader.setup_module_loader(L);
+}
+
+// cu.version, cu.lua_version and cu.format_version let stored-data
+// migrations branch on the runtime that wrote or is reading the data
+fn setup_cu_global(L: *lua.lua_State) void {
+    lua.newtable(L);
+    _ = lua.pushstring(L, CU_VERSION);
+    lua.setfield(L, -2, "version");
+    _ = lua.getglobal(L, "_VERSION");
+    lua.setfield(L, -2, "lua_version");
+    lua.pushinteger(L, serializer.FORMAT_VERSION);
+    lua.setfield(L, -2, "format_version");
+    utf8_lib.push_library(L);
+    lua.setfield(L, -2, "utf8");
+    entropy.register(L);
+    error_handler.register(L);
+    deepequal.register(L);
+    freeze.register(L);
+    coroutine.register(L);
+    pack.register(L);
+    clock.register(L);
+    value_type.register(L);
+    lua.setglobal(L, "cu");
+}
+
 fn setup_bigint_library(L: *lua.lua_State) void {
     bigint_set_allocator(@ptrCast(@constCast(&lua_allocator)));
 
@@ -161,33 +395,446 @@ pub fn ext_table_size(table_id: u32) usize {
     return js_ext_table_size(table_id);
 }
 
+pub fn ext_table_seqlen(table_id: u32) usize {
+    return js_ext_table_seqlen(table_id);
+}
+
 pub fn ext_table_keys(table_id: u32, buf_ptr: [*]u8, max_len: usize) c_int {
     return js_ext_table_keys(table_id, buf_ptr, max_len);
 }
 
+pub fn ext_table_keys_page(table_id: u32, cursor: u32, out_ptr: [*]u8, max_len: usize) c_int {
+    return js_ext_table_keys_page(table_id, cursor, out_ptr, max_len);
+}
+
+pub fn ext_table_free(table_id: u32) c_int {
+    return js_ext_table_free(table_id);
+}
+
+pub fn ext_table_rename(table_id: u32, old_ptr: [*]const u8, old_len: usize, new_ptr: [*]const u8, new_len: usize) c_int {
+    return js_ext_table_rename(table_id, old_ptr, old_len, new_ptr, new_len);
+}
+
+pub fn ext_table_cas(table_id: u32, key_ptr: [*]const u8, key_len: usize, expected_ptr: [*]const u8, expected_len: usize, new_ptr: [*]const u8, new_len: usize) c_int {
+    return js_ext_table_cas(table_id, key_ptr, key_len, expected_ptr, expected_len, new_ptr, new_len);
+}
+
+pub fn ext_table_set_ttl(table_id: u32, key_ptr: [*]const u8, key_len: usize, val_ptr: [*]const u8, val_len: usize, ttl_ms: i64) c_int {
+    return js_ext_table_set_ttl(table_id, key_ptr, key_len, val_ptr, val_len, ttl_ms);
+}
+
+pub fn ext_table_has(table_id: u32, key_ptr: [*]const u8, key_len: usize) c_int {
+    return js_ext_table_has(table_id, key_ptr, key_len);
+}
+
+pub fn ext_table_value_len(table_id: u32, key_ptr: [*]const u8, key_len: usize) c_int {
+    return js_ext_table_value_len(table_id, key_ptr, key_len);
+}
+
+pub fn ext_table_swap(table_id: u32, a_ptr: [*]const u8, a_len: usize, b_ptr: [*]const u8, b_len: usize) c_int {
+    return js_ext_table_swap(table_id, a_ptr, a_len, b_ptr, b_len);
+}
+
+pub fn ext_table_clone(src_id: u32, dst_id: u32) c_int {
+    return js_ext_table_clone(src_id, dst_id);
+}
+
+pub fn ext_table_bulk_dump(table_id: u32, out_ptr: [*]u8, max_len: usize) c_int {
+    return js_ext_table_bulk_dump(table_id, out_ptr, max_len);
+}
+
+pub fn ext_table_bulk_load(table_id: u32, in_ptr: [*]const u8, len: usize) c_int {
+    return js_ext_table_bulk_load(table_id, in_ptr, len);
+}
+
+pub fn ext_table_delete_prefix(table_id: u32, prefix_ptr: [*]const u8, prefix_len: usize) c_int {
+    return js_ext_table_delete_prefix(table_id, prefix_ptr, prefix_len);
+}
+
 export fn get_buffer_ptr() [*]u8 {
     return &io_buffer;
 }
 
 export fn get_buffer_size() usize {
-    return IO_BUFFER_SIZE;
+    return io_buffer_len;
+}
+
+// Write the comma-separated names in FEATURES to the IO buffer and return
+// their length. Works before init.
+export fn get_features() i32 {
+    if (eval_active) return REENTRANT;
+    @memcpy(io_buffer[0..FEATURES.len], FEATURES);
+    return FEATURES.len;
+}
+
+// Changes whenever init sets up the IO buffer, which is when
+// init_with_options can resize it. A host that caches get_buffer_ptr and
+// get_buffer_size refetches them when this differs from what it saw last.
+export fn buffer_generation() i32 {
+    return io_buffer_generation;
 }
 
+// Run code_len bytes of code at code_ptr, which must lie inside the IO
+// buffer; 0 stands for its start, as older hosts pass. Returns -1 for a
+// range outside the buffer rather than reading arbitrary memory.
 export fn compute(code_ptr: usize, code_len: usize) i32 {
-    _ = code_ptr;
-    if (code_len > IO_BUFFER_SIZE) return -1;
+    const buffer_start = @intFromPtr(&io_buffer);
+    const offset = if (code_ptr == 0) 0 else code_ptr -% buffer_start;
+    if (offset > io_buffer_len or code_len > io_buffer_len - offset) return -1;
+
+    // run_code reads from the start of the buffer, which a nested call
+    // mustn't touch
+    if (offset > 0 and !eval_active) std.mem.copyForwards(u8, io_buffer[0..code_len], io_buffer[offset .. offset + code_len]);
+    return run_code(code_len, false, 0);
+}
+
+// Input length recorded by prepare_input for the next eval()
+var prepared_input_len: usize = 0;
+
+// Check that len bytes of code fit the IO buffer and return the address to
+// write them at, or -1 if they don't. The next eval() runs those len bytes.
+export fn prepare_input(len: usize) i32 {
+    if (len > io_buffer_len) return -1;
+    prepared_input_len = len;
+    return @intCast(@intFromPtr(&io_buffer));
+}
+
+// Run the code written after prepare_input; returns like compute. Each
+// prepared input runs once: without a new prepare_input there is nothing to
+// run and it returns 0.
+export fn eval() i32 {
+    if (eval_active) return REENTRANT;
+    const len = prepared_input_len;
+    prepared_input_len = 0;
+    return run_code(len, false, 0);
+}
+
+// The chunks append_input stages live in a registry table, so a staged script
+// takes Lua heap only while it's being assembled
+const STAGED_INPUT_REGISTRY_KEY = "cu.staged_input";
+
+// Enough of the staged source to name the chunk: messages show no more than
+// LUA_IDSIZE (60) bytes of it
+const STAGED_NAME_SIZE = 64;
+
+// Append len bytes of the IO buffer to the staged source, for scripts too
+// large to pass through the buffer in one piece. Returns the staged length
+// so far, or -1 if the chunk would take Lua past its memory limit (the stage
+// is left as it was).
+export fn append_input(len: usize) i32 {
+    if (len > io_buffer_len) return -1;
+    if (global_lua_state == null) return report_not_initialized();
+    if (eval_active) return REENTRANT;
+    if (lua_memory_limit > 0 and len > lua_memory_limit -| lua_memory_used) return -1;
+    if (len == 0) return @intCast(staged_len);
+
+    const L = global_lua_state.?;
+
+    if (lua.getfield(L, lua.c.LUA_REGISTRYINDEX, STAGED_INPUT_REGISTRY_KEY) != lua.c.LUA_TTABLE) {
+        lua.pop(L, 1);
+        lua.newtable(L);
+        lua.pushvalue(L, -1);
+        lua.setfield(L, lua.c.LUA_REGISTRYINDEX, STAGED_INPUT_REGISTRY_KEY);
+    }
+    _ = lua.pushlstring(L, &io_buffer, len);
+    lua.c.lua_rawseti(L, -2, @intCast(lua.c.lua_rawlen(L, -2) + 1));
+    lua.pop(L, 1);
+
+    if (staged_len < STAGED_NAME_SIZE) {
+        const name_len = @min(len, STAGED_NAME_SIZE - staged_len);
+        @memcpy(staged_name[staged_len .. staged_len + name_len], io_buffer[0..name_len]);
+    }
+    staged_len += len;
+    return @intCast(staged_len);
+}
+
+// Hands lua_load the staged chunks one at a time; they stay anchored in the
+// table at table_index, so the pointers outlive the pop
+const StagedReader = struct {
+    table_index: c_int,
+    next: lua.c.lua_Integer = 1,
+};
+
+fn read_staged_chunk(L: ?*lua.c.lua_State, data: ?*anyopaque, size: [*c]usize) callconv(.c) [*c]const u8 {
+    const reader: *StagedReader = @ptrCast(@alignCast(data));
+    if (lua.c.lua_rawgeti(L, reader.table_index, reader.next) != lua.c.LUA_TSTRING) {
+        lua.c.lua_settop(L, -2);
+        size.* = 0;
+        return null;
+    }
+    const chunk = lua.c.lua_tolstring(L, -1, size);
+    lua.c.lua_settop(L, -2);
+    reader.next += 1;
+    return chunk;
+}
+
+// Run the source assembled by append_input; returns like compute. The stage
+// is cleared once the source is loaded, whether it compiles or not, so each
+// staged script runs once and returns 0 if nothing is staged.
+export fn eval_staged() i32 {
+    if (global_lua_state == null) return report_not_initialized();
+    if (eval_active) return REENTRANT;
+    if (staged_len == 0) return 0;
+    eval_active = true;
+    defer eval_active = false;
+
+    const L = global_lua_state.?;
+
+    output_capture.reset_output();
+    error_handler.clear_error_state(L);
+    ext_table.begin_eval(L);
+
+    // The start of the source doubles as the chunk name, as with
+    // luaL_loadstring in compute, so error messages look the same
+    const name_len = @min(staged_len, STAGED_NAME_SIZE);
+    staged_name[name_len] = 0;
+    staged_len = 0;
+
+    _ = lua.getfield(L, lua.c.LUA_REGISTRYINDEX, STAGED_INPUT_REGISTRY_KEY);
+    lua.pushnil(L);
+    lua.setfield(L, lua.c.LUA_REGISTRYINDEX, STAGED_INPUT_REGISTRY_KEY);
+
+    var reader = StagedReader{ .table_index = lua.gettop(L) };
+    var result = lua.c.lua_load(L, read_staged_chunk, &reader, @ptrCast(&staged_name[0]), "t");
+    lua.c.lua_remove(L, reader.table_index);
+    if (result == 0) {
+        arm_limit_hook(L);
+        result = lua.c.lua_pcallk(L, 0, lua.c.LUA_MULTRET, 0, 0, null);
+    }
+    if (result != 0) {
+        return report_lua_error(L, result);
+    }
+
+    end_eval(L);
+    const encoded_len = result_encoder.encode_result(L, &io_buffer, io_buffer_len);
+    return @intCast(encoded_len);
+}
+
+// Longest name eval_named takes; messages cut it to LUA_IDSIZE anyway
+const MAX_CHUNK_NAME = 128;
+
+// Like compute, but with a name for the chunk: the IO buffer holds name_len
+// bytes of name, then code_len bytes of source, and errors and tracebacks
+// say [string "name"]:12 instead of quoting the source. As with Lua chunk
+// names, one starting with = is shown as it is ("=handler" gives
+// handler:12). Returns like compute, or -1 if the name is empty or too long
+// or the two don't fit the IO buffer.
+export fn eval_named(name_len: usize, code_len: usize) i32 {
+    if (name_len == 0 or name_len > MAX_CHUNK_NAME) return -1;
+    if (code_len > io_buffer_len or name_len > io_buffer_len - code_len) return -1;
     if (code_len == 0) return 0;
 
-    if (global_lua_state == null) {
-        const error_msg = "Lua state not initialized";
-        @memcpy(io_buffer[0..error_msg.len], error_msg);
+    if (global_lua_state == null) return report_not_initialized();
+    if (eval_active) return REENTRANT;
+    eval_active = true;
+    defer eval_active = false;
+
+    const L = global_lua_state.?;
+
+    output_capture.reset_output();
+    error_handler.clear_error_state(L);
+    ext_table.begin_eval(L);
+
+    var chunk_name: [MAX_CHUNK_NAME + 1]u8 = undefined;
+    @memcpy(chunk_name[0..name_len], io_buffer[0..name_len]);
+    chunk_name[name_len] = 0;
+
+    var result = lua.c.luaL_loadbufferx(L, io_buffer[name_len..].ptr, code_len, @ptrCast(&chunk_name), "t");
+    if (result == 0) {
+        arm_limit_hook(L);
+        result = lua.c.lua_pcallk(L, 0, lua.c.LUA_MULTRET, 0, 0, null);
+    }
+    if (result != 0) {
+        return report_lua_error(L, result);
+    }
+
+    end_eval(L);
+    const encoded_len = result_encoder.encode_result(L, &io_buffer, io_buffer_len);
+    return @intCast(encoded_len);
+}
+
+// Push the arguments eval_with_args passes its chunk: [u32 count], then per
+// argument [u32 length][serialized value], all little-endian (the layout of
+// host.call arguments). No bytes means no arguments. Returns how many were
+// pushed, or null if the bytes are malformed.
+fn push_eval_args(L: *lua.lua_State, bytes: []const u8) ?c_int {
+    if (bytes.len == 0) return 0;
+    if (bytes.len < 4) return null;
+
+    const count = std.mem.readInt(u32, bytes[0..4], .little);
+    if (count > (bytes.len - 4) / 4) return null;
+    if (lua.c.lua_checkstack(L, @intCast(count)) == 0) return null;
+
+    var offset: usize = 4;
+    var i: u32 = 0;
+    while (i < count) : (i += 1) {
+        if (bytes.len - offset < 4) return null;
+        const value_len = std.mem.readInt(u32, bytes[offset..][0..4], .little);
+        offset += 4;
+        if (value_len > bytes.len - offset) return null;
+        serializer.deserialize_value(L, bytes[offset..].ptr, value_len) catch return null;
+        offset += value_len;
+    }
+    if (offset != bytes.len) return null;
+    return @intCast(count);
+}
+
+// Like compute, but with arguments: the IO buffer holds code_len bytes of
+// source, then args_len bytes of serialized arguments (see push_eval_args),
+// which the chunk receives as ... so `local a, b = ...` reads them. Passing
+// values this way avoids splicing them into the source. Returns like
+// compute, or -1 if the two don't fit the IO buffer or the arguments are
+// malformed.
+export fn eval_with_args(code_len: usize, args_len: usize) i32 {
+    if (code_len > io_buffer_len or args_len > io_buffer_len - code_len) return -1;
+    if (code_len == 0) return 0;
+
+    if (global_lua_state == null) return report_not_initialized();
+    if (eval_active) return REENTRANT;
+    eval_active = true;
+    defer eval_active = false;
+
+    const L = global_lua_state.?;
+
+    output_capture.reset_output();
+    error_handler.clear_error_state(L);
+    ext_table.begin_eval(L);
+
+    const base = lua.gettop(L);
+    var result = lua.c.luaL_loadbufferx(L, &io_buffer, code_len, "=eval_with_args", "t");
+    if (result == 0) {
+        const arg_count = push_eval_args(L, io_buffer[code_len .. code_len + args_len]) orelse {
+            lua.settop(L, base);
+            return -1;
+        };
+        arm_limit_hook(L);
+        result = lua.c.lua_pcallk(L, arg_count, lua.c.LUA_MULTRET, 0, 0, null);
+    }
+    if (result != 0) {
+        return report_lua_error(L, result);
+    }
+
+    end_eval(L);
+    const encoded_len = result_encoder.encode_result(L, &io_buffer, io_buffer_len);
+    return @intCast(encoded_len);
+}
+
+// Check that the host's external table imports behave by round-tripping a
+// value through a scratch table. Returns 0 if they do; otherwise the
+// message names the broken import, the return is -(message length + 1) and
+// get_last_error_code() is -8.
+export fn check_imports() i32 {
+    if (global_lua_state == null) return report_not_initialized();
+    if (eval_active) return REENTRANT;
+
+    const problem = ext_table.check_imports() orelse return 0;
+    error_handler.set_error(.host_import_error, problem);
+    const error_len = error_handler.format_error_to_buffer(&io_buffer, io_buffer_len);
+    return -@as(i32, @intCast(error_len + 1));
+}
+
+// Compile len bytes of code from the IO buffer without running it. Returns 0
+// if it compiles. A syntax error traps like any Lua error, recorded first
+// with get_last_error_code() = -1 and the line in the message ("...]:3: ...").
+export fn validate_syntax(len: usize) i32 {
+    if (len > io_buffer_len) return -1;
+
+    if (global_lua_state == null) return report_not_initialized();
+    if (eval_active) return REENTRANT;
+
+    const L = global_lua_state.?;
+    error_handler.clear_error_state(L);
+
+    // Same chunk name as compute, so messages match
+    var code_with_null: [IO_BUFFER_SIZE + 1]u8 = undefined;
+    @memcpy(code_with_null[0..len], io_buffer[0..len]);
+    code_with_null[len] = 0;
+
+    // Every failure is thrown, so this returns only if the code compiled
+    _ = lua.c.luaL_loadstring(L, @ptrCast(&code_with_null[0]));
+    lua.pop(L, 1);
+    return 0;
+}
+
+// Debugging aid for host decoders: evaluate len bytes of the IO buffer as an
+// expression and write the bytes serialize_value produces for its value,
+// followed by the same bytes as hex text ("02 2a 00 ...") if that fits.
+// Returns the number of raw bytes; errors are reported like compute, and
+// -1 if the value can't be serialized.
+export fn dump_wire_format(len: usize) i32 {
+    if (len > io_buffer_len) return -1;
+
+    if (global_lua_state == null) return report_not_initialized();
+    if (eval_active) return REENTRANT;
+    eval_active = true;
+    defer eval_active = false;
+
+    const L = global_lua_state.?;
+
+    output_capture.reset_output();
+    error_handler.clear_error_state(L);
+    ext_table.begin_eval(L);
+
+    const prefix = "return ";
+    var source: [prefix.len + IO_BUFFER_SIZE]u8 = undefined;
+    @memcpy(source[0..prefix.len], prefix);
+    @memcpy(source[prefix.len .. prefix.len + len], io_buffer[0..len]);
+
+    var result = lua.c.luaL_loadbufferx(L, &source, prefix.len + len, "=dump_wire_format", "t");
+    if (result == 0) {
+        arm_limit_hook(L);
+        result = lua.c.lua_pcallk(L, 0, 1, 0, 0, null);
+    }
+    if (result != 0) {
+        return report_lua_error(L, result);
+    }
+
+    end_eval(L);
+    const raw_len = serializer.serialize_value(L, -1, &io_buffer, io_buffer_len) catch {
+        lua.settop(L, 0);
         return -1;
+    };
+    lua.settop(L, 0);
+
+    if (raw_len > 0 and raw_len + raw_len * 3 - 1 <= io_buffer_len) {
+        const hex_digits = "0123456789abcdef";
+        var out = raw_len;
+        for (io_buffer[0..raw_len], 0..) |byte, i| {
+            if (i > 0) {
+                io_buffer[out] = ' ';
+                out += 1;
+            }
+            io_buffer[out] = hex_digits[byte >> 4];
+            io_buffer[out + 1] = hex_digits[byte & 0x0f];
+            out += 2;
+        }
     }
 
+    return @intCast(raw_len);
+}
+
+// Like compute, but all-or-nothing for external tables: every ext write is
+// held until the chunk finishes. An error traps with the writes still held;
+// recover_from_trap (or the next eval) drops them.
+export fn eval_transactional(code_len: usize) i32 {
+    return run_code(code_len, true, 0);
+}
+
+// reserve bytes at the end of the IO buffer are kept free of the result
+fn run_code(code_len: usize, transactional: bool, reserve: usize) i32 {
+    if (code_len > io_buffer_len) return -1;
+    if (code_len == 0) return 0;
+
+    if (global_lua_state == null) return report_not_initialized();
+    if (eval_active) return REENTRANT;
+    eval_active = true;
+    defer eval_active = false;
+
     const L = global_lua_state.?;
 
     output_capture.reset_output();
     error_handler.clear_error_state(L);
+    ext_table.begin_eval(L);
 
     const code_bytes = io_buffer[0..code_len];
 
@@ -196,35 +843,867 @@ export fn compute(code_ptr: usize, code_len: usize) i32 {
     code_with_null[code_len] = 0;
     const code_cstr: [*:0]u8 = @ptrCast(&code_with_null[0]);
 
-    const result = lua.dostring(L, code_cstr);
+    if (transactional) ext_table.begin_transaction();
+
+    var result = compile_cache.load(L, code_cstr, code_len);
+    if (result == 0) {
+        arm_limit_hook(L);
+        result = lua.c.lua_pcallk(L, 0, lua.c.LUA_MULTRET, 0, 0, null);
+    }
+
+    if (transactional) ext_table.end_transaction(L, result == 0);
+
+    if (result != 0) {
+        return report_lua_error(L, result);
+    }
+
+    end_eval(L);
+    const encoded_len = result_encoder.encode_result(L, &io_buffer, io_buffer_len - reserve);
+    return @intCast(encoded_len);
+}
+
+// Like compute, but also measures the chunk: a ProfileStats follows the
+// result in the IO buffer, so the host reads it at ptr + the return value.
+// Nothing is written on errors, which return like compute.
+export fn profile_eval(code_len: usize) i32 {
+    const started_at = js_time_now();
+    const memory_before = lua_memory_used;
+    const allocations_before = alloc_counters.alloc_count;
+    const gc_cycles_before = gc_cycles;
+
+    const result_len = run_code(code_len, false, @sizeOf(ProfileStats));
+    if (result_len <= 0) return result_len;
+
+    const stats = ProfileStats{
+        .wall_time_ms = @bitCast(js_time_now() -% started_at),
+        .memory_delta = @intCast(@as(i64, @intCast(lua_memory_used)) - @as(i64, @intCast(memory_before))),
+        .gc_cycles = gc_cycles -% gc_cycles_before,
+        .allocations = @truncate(alloc_counters.alloc_count - allocations_before),
+    };
+    const offset: usize = @intCast(result_len);
+    @memcpy(io_buffer[offset..][0..@sizeOf(ProfileStats)], std.mem.asBytes(&stats));
+    return result_len;
+}
+
+// Copy of the eval_batch request, since the results overwrite the IO buffer
+var batch_request: [IO_BUFFER_SIZE]u8 = undefined;
+
+// Run several snippets in one call. The IO buffer holds
+//   [4 bytes: count (u32 LE)] then per snippet [4 bytes: length][source]
+// and each runs as its own compute against the same globals. Results are
+// written back as
+//   [4 bytes: count] then per snippet [4 bytes: code (i32 LE)][4 bytes: length][bytes]
+// where the bytes are what compute would have written. A Lua error traps, so
+// the batch is all-or-nothing: the first failing snippet ends the call with
+// its error recorded, the ones before it keep their side effects and the
+// rest don't run. Every code in a response is therefore 0; the field keeps
+// the layout. Returns the total length, or -1 if the request is malformed or
+// the results don't fit.
+export fn eval_batch(len: usize) i32 {
+    if (len > io_buffer_len) return -1;
+
+    if (global_lua_state == null) return report_not_initialized();
+    if (eval_active) return REENTRANT;
+    eval_active = true;
+    defer eval_active = false;
+
+    const L = global_lua_state.?;
+
+    @memcpy(batch_request[0..len], io_buffer[0..len]);
+    const request = batch_request[0..len];
+    if (request.len < 4) return -1;
+    const count = std.mem.readInt(u32, request[0..4], .little);
+
+    std.mem.writeInt(u32, io_buffer[0..4], count, .little);
+    var in: usize = 4;
+    var out: usize = 4;
+    var i: u32 = 0;
+    while (i < count) : (i += 1) {
+        if (request.len - in < 4) return -1;
+        const code_len = std.mem.readInt(u32, request[in..][0..4], .little);
+        in += 4;
+        if (code_len > request.len - in) return -1;
+
+        var code_with_null: [IO_BUFFER_SIZE + 1]u8 = undefined;
+        @memcpy(code_with_null[0..code_len], request[in .. in + code_len]);
+        code_with_null[code_len] = 0;
+        in += code_len;
+
+        // Room for the item header and the result's output length
+        if (io_buffer_len - out < 12) return -1;
+        const body = out + 8;
+        const body_max = io_buffer_len - body;
+
+        output_capture.reset_output();
+        error_handler.clear_error_state(L);
+        ext_table.begin_eval(L);
+        lua.settop(L, 0);
+
+        // Failures are thrown (see cu_lua_throw), so both calls succeed here
+        _ = compile_cache.load(L, @ptrCast(&code_with_null[0]), code_len);
+        arm_limit_hook(L);
+        _ = lua.c.lua_pcallk(L, 0, lua.c.LUA_MULTRET, 0, 0, null);
+
+        end_eval(L);
+        const body_len = result_encoder.encode_result(L, io_buffer[body..].ptr, body_max);
+
+        std.mem.writeInt(i32, io_buffer[out..][0..4], @intFromEnum(error_handler.get_last_error_code()), .little);
+        std.mem.writeInt(u32, io_buffer[out + 4 ..][0..4], @intCast(body_len), .little);
+        out = body + body_len;
+    }
+
+    return @intCast(out);
+}
+
+const VM_NOT_INITIALIZED: i32 = @intFromEnum(error_handler.ErrorCode.vm_not_initialized);
+const REENTRANT: i32 = @intFromEnum(error_handler.ErrorCode.reentrant);
+
+// Set while an eval runs. Host imports are called in the middle of one, and
+// an eval started from inside an import would overwrite the IO buffer and
+// Lua stack the outer eval is still using, so the eval exports return
+// REENTRANT instead, leaving the buffer and error state untouched.
+var eval_active: bool = false;
+
+// Set while cu_lua_throw records an error, so an error raised while
+// recording traps straight away
+var recording_throw: bool = false;
+
+// ldo.c throws every Lua error through here (see setjmp-wasm.h). There is no
+// longjmp to unwind with, so record the error where the host can read it
+// after the trap, then trap.
+export fn cu_lua_throw(L: *lua.lua_State, status: c_int) noreturn {
+    if (!recording_throw) {
+        recording_throw = true;
+        error_handler.record_thrown_error(L, status);
+        classify_limit_error();
+        error_capture.record(error_handler.get_error_message());
+        recording_throw = false;
+    }
+    @trap();
+}
+
+// lgc.h, which lua.zig doesn't import: the collector is stopped while a
+// finalizer runs
+const GCSTPGC: u8 = 2;
+
+// A Lua error can't unwind on wasm32-freestanding (longjmp traps), so it
+// ends the call with a trap that leaves the eval's call frames on the Lua
+// stack, hooks disabled if it was raised from one, and eval_active set.
+// The host calls this after catching the trap to make the instance usable
+// again. The recorded error (get_last_error_code, read_error_message) is
+// kept. Must not be called from inside a host import.
+export fn recover_from_trap() i32 {
+    if (global_lua_state == null) return not_initialized_code();
+    const L = global_lua_state.?;
+
+    _ = lua.c.lua_closethread(L, null);
+    // lua_closethread zeroes the C call count, including the mark that
+    // keeps the main thread non-yieldable
+    L.nCcalls = 0x10000;
+    L.errorJmp = null;
+    L.allowhook = 1;
+    L.l_G.*.gcstp &= ~GCSTPGC;
+
+    // The stack is usable again from here
+    coroutine.abandon(L);
+    ext_table.abandon_transaction(L);
+    // What end_eval would have sent had the error unwound
+    _ = ext_table.flush_pending(L);
+
+    deadline_hit = false;
+    recording_throw = false;
+    eval_active = false;
+    return 0;
+}
+
+// Exports that need the VM call this before init. Records the error for
+// get_last_error_code and writes its message to the IO buffer; returns the
+// negative message length, as compute does for Lua errors. Exports without
+// a result in the buffer return VM_NOT_INITIALIZED instead.
+fn report_not_initialized() i32 {
+    error_handler.set_error(.vm_not_initialized, "Lua state not initialized");
+    const error_len = error_handler.format_error_to_buffer(&io_buffer, io_buffer_len);
+    return -@as(i32, @intCast(error_len + 1));
+}
+
+fn not_initialized_code() i32 {
+    _ = report_not_initialized();
+    return VM_NOT_INITIALIZED;
+}
+
+// Runs when an eval returns to the host, on success or error: buffered
+// ext writes are sent so nothing is lost without an explicit ext.flush
+fn end_eval(L: *lua.lua_State) void {
+    _ = ext_table.flush_pending(L);
+}
+
+// Capture the Lua error on top of the stack, classifying errors a limit
+// rather than the script caused
+fn capture_eval_error(L: *lua.lua_State, status: c_int) void {
+    _ = error_handler.capture_lua_error(L, status);
+    end_eval(L);
+    classify_limit_error();
+}
+
+// Reclassify the captured error if a limit rather than the script caused it
+fn classify_limit_error() void {
+    if (ext_table.call_limit_exceeded()) {
+        error_handler.set_error_code(.ext_call_limit);
+    }
+    if (deadline_hit) {
+        deadline_hit = false;
+        error_handler.set_error_code(.timeout);
+    }
+}
+
+// Capture the Lua error on top of the stack and write its message to the IO
+// buffer. Returns the negative length compute hands back to the host.
+fn report_lua_error(L: *lua.lua_State, status: c_int) i32 {
+    capture_eval_error(L, status);
+    const error_len = error_handler.format_error_to_buffer(&io_buffer, io_buffer_len);
+    return -@as(i32, @intCast(error_len + 1));
+}
+
+// Code of the last error (0 = success, see error.ErrorCode)
+export fn get_last_error_code() i32 {
+    return @intFromEnum(error_handler.get_last_error_code());
+}
+
+// Hold proxy assignments in the VM until ext.flush or the end of the eval
+// (nonzero enables). Disabling sends anything still pending.
+export fn set_write_buffering(enabled: c_int) i32 {
+    if (global_lua_state == null) return not_initialized_code();
+    ext_table.set_write_buffering(global_lua_state.?, enabled != 0);
+    return 0;
+}
+
+// Cap the number of live external tables scripts may create; 0 = unlimited.
+// Freed tables (ext.free, free_table) no longer count. Returns -1 for a
+// negative limit.
+export fn set_max_tables(limit: i32) i32 {
+    if (limit < 0) return -1;
+    ext_table.set_max_tables(@intCast(limit));
+    return 0;
+}
+
+// Cap ext operations (reads, writes, and ext.* calls) per eval; 0 = unlimited.
+// Returns -1 for a negative limit.
+export fn set_ext_call_limit(limit: i32) i32 {
+    if (limit < 0) return -1;
+    ext_table.set_call_limit(@intCast(limit));
+    return 0;
+}
+
+// Abort evals that run longer than ms of host clock time (js_time_now) with
+// a "deadline exceeded" error and the TIMEOUT code. Unlike the instruction
+// limit this also bounds time spent waiting on slow host imports, checked
+// whenever Lua code runs between them. 0 or less removes the deadline.
+export fn set_deadline_ms(ms: i64) void {
+    deadline_ms = @intCast(std.math.clamp(ms, 0, std.math.maxInt(c_long)));
+}
+
+// Set how many compiled chunks compute keeps (0 disables the cache)
+export fn set_cache_capacity(capacity: usize) i32 {
+    if (global_lua_state == null) return not_initialized_code();
+    compile_cache.set_capacity(global_lua_state.?, capacity);
+    return 0;
+}
+
+export fn clear_cache() i32 {
+    if (global_lua_state == null) return not_initialized_code();
+    compile_cache.clear(global_lua_state.?);
+    return 0;
+}
+
+// Cap the bytes of print output captured per call (0 restores the default).
+// Returns -1 if the limit is larger than the output buffer.
+export fn set_output_limit(limit: usize) i32 {
+    if (!output_capture.set_output_limit(limit)) return -1;
+    return 0;
+}
+
+// Read a prefix of len bytes from the IO buffer to prepend to every captured
+// print and warn line (0 clears it). Returns -1 if it is too long.
+export fn set_log_prefix(len: usize) i32 {
+    if (len > io_buffer_len) return -1;
+    if (!output_capture.set_log_prefix(io_buffer[0..len])) return -1;
+    return 0;
+}
+
+// Write the warn() output of the last call to the IO buffer; returns its
+// length. Read the result first, this overwrites it.
+export fn read_stderr() i32 {
+    return @intCast(output_capture.copy_warn_output(&io_buffer, io_buffer_len));
+}
+
+// Write the value the last failed call passed to error(), when that wasn't
+// a string or number, to the IO buffer in the serialized format; returns
+// its length, or 0 if there is none. A table comes back as an external
+// table ref with TABLE_FLAG_ERROR set.
+export fn read_error_value() i32 {
+    const value = error_handler.get_error_value();
+    if (value.len > io_buffer_len) return 0;
+    @memcpy(io_buffer[0..value.len], value);
+    return @intCast(value.len);
+}
+
+// Write the message of the last error to the IO buffer; returns its length.
+// For calls that trapped before they could report it themselves.
+export fn read_error_message() i32 {
+    return @intCast(error_handler.format_error_to_buffer(&io_buffer, io_buffer_len));
+}
+
+// Number of times compute has invoked the Lua parser since init
+export fn get_compile_count() u32 {
+    return compile_cache.get_compile_count();
+}
+
+// Results and snapshots paged out with read_chunk are staged in a userdata
+// anchored in the registry, so they come out of the Lua heap only while a
+// host streams. Each call replaces (and releases) the previous one.
+const STREAM_REGISTRY_KEY = "cu.stream";
+
+fn new_stream_buffer(L: *lua.lua_State, size: usize) [*]u8 {
+    const buffer: [*]u8 = @ptrCast(lua.c.lua_newuserdatauv(L, size, 0));
+    lua.setfield(L, lua.c.LUA_REGISTRYINDEX, STREAM_REGISTRY_KEY);
+    return buffer;
+}
+
+// Like compute, but encode the result into a staging buffer sized for it, for
+// results that don't fit in the IO buffer. Returns the total encoded length;
+// the host then pages it out with read_chunk.
+export fn eval_streaming(code_len: usize) i32 {
+    if (code_len > io_buffer_len) return -1;
+    if (code_len == 0) return 0;
+
+    if (global_lua_state == null) return report_not_initialized();
+    if (eval_active) return REENTRANT;
+    eval_active = true;
+    defer eval_active = false;
+
+    const L = global_lua_state.?;
+
+    output_capture.reset_output();
+    error_handler.clear_error_state(L);
+    ext_table.begin_eval(L);
+    stream_len = 0;
+
+    var code_with_null: [IO_BUFFER_SIZE + 1]u8 = undefined;
+    @memcpy(code_with_null[0..code_len], io_buffer[0..code_len]);
+    code_with_null[code_len] = 0;
+    const code_cstr: [*:0]u8 = @ptrCast(&code_with_null[0]);
+
+    var result = lua.c.luaL_loadstring(L, code_cstr);
+    if (result == 0) {
+        arm_limit_hook(L);
+        result = lua.c.lua_pcallk(L, 0, lua.c.LUA_MULTRET, 0, 0, null);
+    }
 
     if (result != 0) {
-        _ = error_handler.capture_lua_error(L, result);
-        const error_len = error_handler.format_error_to_buffer(&io_buffer, IO_BUFFER_SIZE);
-        return -@as(i32, @intCast(error_len + 1));
+        return report_lua_error(L, result);
+    }
+
+    end_eval(L);
+
+    // Output and any other value fit in as much as compute allows them; only
+    // a string result can need more
+    var capacity: usize = IO_BUFFER_SIZE + output_capture.get_captured_len();
+    if (lua.gettop(L) > 0 and lua.c.lua_type(L, -1) == lua.c.LUA_TSTRING) {
+        capacity += lua.objlen(L, -1);
     }
+    stream_data = new_stream_buffer(L, capacity);
+    stream_len = result_encoder.encode_result(L, stream_data, capacity);
+    return @intCast(stream_len);
+}
+
+// Copy up to max_len bytes of the staged eval_streaming result, starting at
+// offset, into the IO buffer. Returns the number of bytes copied (0 once the
+// end is reached) or -1 if offset lies past the end.
+export fn read_chunk(offset: usize, max_len: usize) i32 {
+    if (offset > stream_len) return -1;
+
+    const chunk_len = @min(@min(max_len, io_buffer_len), stream_len - offset);
+    @memcpy(io_buffer[0..chunk_len], stream_data[offset .. offset + chunk_len]);
+    return @intCast(chunk_len);
+}
+
+// eval_json builds its document here and copies it over at the end, since
+// reading external tables while encoding reuses the IO buffer
+var json_output: [IO_BUFFER_SIZE]u8 = undefined;
+var json_writer: json.Writer = undefined;
+
+// Copy the JSON document to the IO buffer; an error document comes back as
+// -(length + 1) like any other error
+fn finish_json(is_error: bool) i32 {
+    @memcpy(io_buffer[0..json_writer.len], json_output[0..json_writer.len]);
+    const len: i32 = @intCast(json_writer.len);
+    return if (is_error) -len - 1 else len;
+}
+
+fn write_json_error() i32 {
+    json_writer = json.Writer.init(&json_output, io_buffer_len);
+    json_writer.raw("{\"error\":");
+    json_writer.string(error_handler.get_error_message());
+    json_writer.raw(",\"code\":");
+    json_writer.integer(@intFromEnum(error_handler.get_last_error_code()));
+    json_writer.byte('}');
+    // Only a message longer than the buffer can overflow; send it cut
+    // short rather than nothing
+    if (json_writer.overflow) {
+        error_handler.set_error(.serialization_error, "error message too long for the IO buffer");
+        return write_json_error();
+    }
+    return finish_json(true);
+}
+
+// Like compute, but the result comes back as a JSON document, for hosts
+// without a decoder for the binary format:
+//   {"result":<value>,"output":"<captured print output>"}
+// with "output_truncated":true added when the output hit its limit. Tables
+// become arrays (keys 1..n) or objects, external tables included. A value
+// JSON can't hold (a function, say) returns -(length + 1) with
+// {"error":"<message>","code":-3} in the buffer; Lua errors trap like
+// compute's.
+export fn eval_json(len: usize) i32 {
+    if (len > io_buffer_len) return -1;
+    if (len == 0) return 0;
 
-    const encoded_len = result_encoder.encode_result(L, &io_buffer, IO_BUFFER_SIZE);
+    if (global_lua_state == null) return report_not_initialized();
+    if (eval_active) return REENTRANT;
+    eval_active = true;
+    defer eval_active = false;
+
+    const L = global_lua_state.?;
+    defer lua.settop(L, 0);
+
+    output_capture.reset_output();
+    error_handler.clear_error_state(L);
+    ext_table.begin_eval(L);
+
+    var code_with_null: [IO_BUFFER_SIZE + 1]u8 = undefined;
+    @memcpy(code_with_null[0..len], io_buffer[0..len]);
+    code_with_null[len] = 0;
+
+    var result = compile_cache.load(L, @ptrCast(&code_with_null[0]), len);
+    if (result == 0) {
+        arm_limit_hook(L);
+        result = lua.c.lua_pcallk(L, 0, 1, 0, 0, null);
+    }
+    if (result != 0) {
+        capture_eval_error(L, result);
+        return write_json_error();
+    }
+
+    json_writer = json.Writer.init(&json_output, io_buffer_len);
+    json_writer.raw("{\"result\":");
+    json.encode_value(&json_writer, L, lua.gettop(L), 0) catch {
+        error_handler.set_error(.serialization_error, json.failure_message());
+        end_eval(L);
+        return write_json_error();
+    };
+
+    end_eval(L);
+    json_writer.raw(",\"output\":");
+    json_writer.string(output_capture.get_output_ptr()[0..output_capture.get_captured_len()]);
+    if (output_capture.is_overflow()) json_writer.raw(",\"output_truncated\":true");
+    json_writer.byte('}');
+
+    if (json_writer.overflow) {
+        error_handler.set_error(.serialization_error, "result too large for the IO buffer as JSON");
+        return write_json_error();
+    }
+    return finish_json(false);
+}
+
+// Run source against an environment supplied by the host. The IO buffer holds
+// a serialized string (the source) followed by a serialized environment: an
+// external table reference, nil, or nothing for an empty one. Reads fall back
+// to SAFE_ENV_GLOBALS; globals the chunk sets land in a copy of the
+// environment, which is returned as an external table reference.
+export fn eval_with_env(len: usize) i32 {
+    if (len > io_buffer_len) return -1;
+
+    if (global_lua_state == null) return report_not_initialized();
+    if (eval_active) return REENTRANT;
+    eval_active = true;
+    defer eval_active = false;
+
+    const L = global_lua_state.?;
+
+    output_capture.reset_output();
+    error_handler.clear_error_state(L);
+    ext_table.begin_eval(L);
+
+    // Copying the environment reuses the IO buffer, so work from a copy
+    var request: [IO_BUFFER_SIZE]u8 = undefined;
+    @memcpy(request[0..len], io_buffer[0..len]);
+
+    if (len < 5 or request[0] != @intFromEnum(serializer.SerializationType.string)) return -1;
+    const source_len = std.mem.readInt(u32, request[1..5], .little);
+    if (source_len > len - 5) return -1;
+    const source = request[5 .. 5 + source_len];
+    const env_bytes = request[5 + source_len .. len];
+
+    lua.settop(L, 0);
+    if (env_bytes.len > 0) {
+        serializer.deserialize_value(L, env_bytes.ptr, env_bytes.len) catch return -1;
+    } else {
+        lua.pushnil(L);
+    }
+
+    if (lua.isnil(L, 1)) {
+        lua.pop(L, 1);
+        lua.newtable(L);
+    } else if (ext_table.push_plain_copy(L, 1)) {
+        lua.c.lua_rotate(L, 1, -1);
+        lua.pop(L, 1);
+    } else {
+        lua.settop(L, 0);
+        return -1;
+    }
+    const env_index = lua.gettop(L);
+
+    push_safe_env_metatable(L);
+    _ = lua.setmetatable(L, env_index);
+
+    var result = lua.c.luaL_loadbufferx(L, source.ptr, source.len, "=eval_with_env", "t");
+    if (result == 0) {
+        lua.pushvalue(L, env_index);
+        _ = lua.c.lua_setupvalue(L, -2, 1);
+        arm_limit_hook(L);
+        result = lua.c.lua_pcallk(L, 0, 0, 0, 0, null);
+    }
+
+    if (result != 0) {
+        return report_lua_error(L, result);
+    }
+
+    // Drop the fallback so only what the chunk left behind is serialized
+    lua.settop(L, env_index);
+    lua.pushnil(L);
+    _ = lua.setmetatable(L, env_index);
+
+    end_eval(L);
+    const encoded_len = result_encoder.encode_serialized_result(L, &io_buffer, io_buffer_len) orelse return -1;
     return @intCast(encoded_len);
 }
 
+fn push_safe_env_metatable(L: *lua.lua_State) void {
+    lua.newtable(L);
+    lua.newtable(L);
+    for (SAFE_ENV_GLOBALS) |name| {
+        _ = lua.getglobal(L, name);
+        lua.setfield(L, -2, name);
+    }
+    lua.setfield(L, -2, "__index");
+}
+
+// Serialize every global changed since init into the staging buffer. Returns
+// the blob length (page it out with read_chunk) or -1 if it doesn't fit. The
+// blob is capped at the IO buffer size, the most restore accepts.
+export fn snapshot() i32 {
+    if (global_lua_state == null) return not_initialized_code();
+
+    const L = global_lua_state.?;
+    stream_len = 0;
+    stream_data = new_stream_buffer(L, io_buffer_len);
+    const len = vm_snapshot.snapshot(L, stream_data, io_buffer_len) orelse return -1;
+    stream_len = len;
+    return @intCast(len);
+}
+
+// Set globals from a snapshot blob in the IO buffer. Returns the number of
+// globals restored or -1 if the blob is malformed.
+export fn restore(len: usize) i32 {
+    if (global_lua_state == null) return not_initialized_code();
+    if (len > io_buffer_len) return -1;
+
+    const count = vm_snapshot.restore(global_lua_state.?, &io_buffer, len) orelse return -1;
+    return @intCast(count);
+}
+
+// Run the code in the IO buffer as a coroutine. A yield returns the encoded
+// yielded value with coroutine.YIELD_FLAG set; call resume() to continue.
+export fn eval_resumable(code_len: usize) i32 {
+    if (code_len > io_buffer_len) return -1;
+    if (code_len == 0) return 0;
+
+    if (global_lua_state == null) return report_not_initialized();
+    if (eval_active) return REENTRANT;
+    eval_active = true;
+    defer eval_active = false;
+
+    const L = global_lua_state.?;
+
+    output_capture.reset_output();
+    error_handler.clear_error_state(L);
+    ext_table.begin_eval(L);
+
+    var code_with_null: [IO_BUFFER_SIZE + 1]u8 = undefined;
+    @memcpy(code_with_null[0..code_len], io_buffer[0..code_len]);
+    code_with_null[code_len] = 0;
+    const code_cstr: [*:0]u8 = @ptrCast(&code_with_null[0]);
+
+    const status = coroutine.start(L, code_cstr, &arm_limit_hook, &io_buffer, io_buffer_len);
+    end_eval(L);
+    if (status < 0) classify_limit_error();
+    return status;
+}
+
+// Resume a coroutine started by eval_resumable. The IO buffer holds an
+// optional serialized value (value_len may be 0) returned from yield.
+export fn @"resume"(value_len: usize) i32 {
+    if (value_len > io_buffer_len) return -1;
+
+    if (global_lua_state == null) return report_not_initialized();
+    if (eval_active) return REENTRANT;
+    eval_active = true;
+    defer eval_active = false;
+
+    const L = global_lua_state.?;
+
+    output_capture.reset_output();
+    error_handler.clear_error_state(L);
+
+    var value_copy: [IO_BUFFER_SIZE]u8 = undefined;
+    @memcpy(value_copy[0..value_len], io_buffer[0..value_len]);
+
+    const status = coroutine.resume_with(L, &value_copy, value_len, &arm_limit_hook, &io_buffer, io_buffer_len);
+    end_eval(L);
+    if (status < 0) classify_limit_error();
+    return status;
+}
+
+// Cap how deeply a loaded value may nest (function upvalues, stored
+// metatables, ext.to_table copies). Deeper data fails to load instead of
+// exhausting the stack. Returns -1 for 0.
+export fn set_max_deserialize_depth(depth: u32) i32 {
+    if (depth == 0) return -1;
+    serializer.set_max_deserialize_depth(depth);
+    return 0;
+}
+
+// Tag byte of the last result (0 = nil, 1 = boolean, 2 = integer, 3 = float,
+// 4 = string, ...; 0xFF for a plain-text marker such as "table"), or the
+// negative error code if the last call failed
+export fn peek_result_type() i32 {
+    if (global_lua_state == null) return not_initialized_code();
+    if (error_handler.is_error()) return @intFromEnum(error_handler.get_last_error_code());
+    return result_encoder.get_last_value_tag();
+}
+
+// Write a JSON descriptor of the last compute result into the IO buffer,
+// e.g. {"type":"number","subtype":"integer","value":42}. Returns its length.
+export fn describe_last_result() i32 {
+    var writer = json.Writer.init(&io_buffer, io_buffer_len);
+
+    if (error_handler.is_error()) {
+        json.describe_error(&writer, error_handler.get_error_message());
+    } else {
+        json.describe_value(&writer, result_encoder.get_last_value_type(), result_encoder.get_last_value(), output_capture.is_overflow());
+    }
+
+    if (writer.overflow) return -1;
+    return @intCast(writer.len);
+}
+
+// Write one "name\ttype\n" line per global with a string name into the IO
+// buffer, in table order, for tooling such as autocomplete. Returns the
+// length, or -1 if the list doesn't fit.
+export fn list_globals() i32 {
+    if (global_lua_state == null) return report_not_initialized();
+
+    const L = global_lua_state.?;
+    const top = lua.gettop(L);
+    defer lua.settop(L, top);
+
+    _ = lua.c.lua_rawgeti(L, lua.c.LUA_REGISTRYINDEX, lua.c.LUA_RIDX_GLOBALS);
+    const globals = lua.gettop(L);
+
+    var len: usize = 0;
+    lua.pushnil(L);
+    while (lua.c.lua_next(L, globals) != 0) {
+        defer lua.pop(L, 1);
+        if (lua.c.lua_type(L, -2) != lua.c.LUA_TSTRING) continue;
+
+        var name_len: usize = 0;
+        const name = lua.c.lua_tolstring(L, -2, &name_len);
+        // A name containing a separator can't be listed unambiguously
+        if (std.mem.indexOfAny(u8, name[0..name_len], "\t\n") != null) continue;
+        const type_name = std.mem.span(lua.type_name(L, -1));
+
+        const line_len = name_len + 1 + type_name.len + 1;
+        if (line_len > io_buffer_len - len) return -1;
+        @memcpy(io_buffer[len..][0..name_len], name[0..name_len]);
+        len += name_len;
+        io_buffer[len] = '\t';
+        len += 1;
+        @memcpy(io_buffer[len..][0..type_name.len], type_name);
+        len += type_name.len;
+        io_buffer[len] = '\n';
+        len += 1;
+    }
+
+    return @intCast(len);
+}
+
+// Bump when fields are added; hosts read the struct at fixed offsets.
+// Version 2 appended lua_memory_peak, lua_memory_limit, and stats_version.
+pub const MEMORY_STATS_VERSION: u32 = 2;
+
+// Fixed-width fields, so the layout doesn't depend on the target's usize
 pub const MemoryStats = extern struct {
-    io_buffer_size: usize,
-    lua_memory_used: usize,
-    wasm_pages: usize,
+    io_buffer_size: u32,
+    lua_memory_used: u32,
+    wasm_pages: u32,
+    lua_memory_peak: u32,
+    lua_memory_limit: u32, // 0 = unlimited
+    stats_version: u32,
+};
+
+// What profile_eval writes after the result, covering compiling, running
+// and encoding the chunk
+pub const ProfileStats = extern struct {
+    wall_time_ms: u32, // host clock (js_time_now)
+    memory_delta: i32, // change in lua_memory_used; negative if more was freed
+    gc_cycles: u32, // garbage collection cycles completed
+    allocations: u32, // allocator calls, as in AllocCounters.alloc_count
+};
+
+// Garbage collection cycles since init. Each cycle collects the current
+// sentinel, whose finalizer counts the cycle and leaves a new one.
+var gc_cycles: u32 = 0;
+
+fn gc_sentinel_finalizer(L: *lua.lua_State) c_int {
+    gc_cycles +%= 1;
+    arm_gc_sentinel(L);
+    return 0;
+}
+
+fn arm_gc_sentinel(L: *lua.lua_State) void {
+    _ = lua.c.lua_newuserdatauv(L, 0, 0);
+    if (lua.luaL_newmetatable(L, "cu_gc_sentinel") != 0) {
+        lua.pushcfunction(L, @as(lua.c.lua_CFunction, @ptrCast(&gc_sentinel_finalizer)));
+        lua.setfield(L, -2, "__gc");
+    }
+    _ = lua.setmetatable(L, -2);
+    lua.pop(L, 1);
+}
+
+// Running totals since init; unlike MemoryStats these only ever grow.
+// total_allocated - total_freed equals lua_memory_used.
+pub const AllocCounters = extern struct {
+    total_allocated: u64 = 0,
+    total_freed: u64 = 0,
+    alloc_count: u64 = 0,
 };
 
-export fn get_memory_stats(stats_ptr: *MemoryStats) void {
-    stats_ptr.*.io_buffer_size = IO_BUFFER_SIZE;
-    stats_ptr.*.lua_memory_used = lua_memory_used;
-    stats_ptr.*.wasm_pages = (TOTAL_MEMORY + 65535) / 65536;
+// The stats structs are written in place and hosts read them at the offsets
+// documented in WASM_EXPORTS_REFERENCE.md as little-endian, which every
+// wasm32 target is. Any build with a different layout fails here.
+comptime {
+    if (builtin.cpu.arch.endian() != .little) @compileError("stats structs assume a little-endian target");
+
+    assert_layout(MemoryStats, 24, &.{
+        .{ "io_buffer_size", 0 },   .{ "lua_memory_used", 4 },  .{ "wasm_pages", 8 },
+        .{ "lua_memory_peak", 12 }, .{ "lua_memory_limit", 16 }, .{ "stats_version", 20 },
+    });
+    assert_layout(ProfileStats, 16, &.{
+        .{ "wall_time_ms", 0 }, .{ "memory_delta", 4 }, .{ "gc_cycles", 8 }, .{ "allocations", 12 },
+    });
+    assert_layout(AllocCounters, 24, &.{
+        .{ "total_allocated", 0 }, .{ "total_freed", 8 }, .{ "alloc_count", 16 },
+    });
+}
+
+fn assert_layout(comptime T: type, comptime size: usize, comptime offsets: []const struct { []const u8, usize }) void {
+    if (@sizeOf(T) != size) @compileError(std.fmt.comptimePrint("{s} is {d} bytes, not {d}", .{ @typeName(T), @sizeOf(T), size }));
+    inline for (offsets) |field| {
+        if (@offsetOf(T, field[0]) != field[1]) {
+            @compileError(std.fmt.comptimePrint("{s}.{s} is at byte {d}, not {d}", .{ @typeName(T), field[0], @offsetOf(T, field[0]), field[1] }));
+        }
+    }
+}
+
+// Current number of 64KB pages in linear memory (0 on non-wasm builds)
+fn wasm_page_count() usize {
+    if (comptime builtin.cpu.arch.isWasm()) {
+        return @wasmMemorySize(0);
+    }
+    return 0;
+}
+
+fn linear_memory_size() usize {
+    if (comptime builtin.cpu.arch.isWasm()) {
+        return wasm_page_count() * 65536;
+    }
+    return std.math.maxInt(usize);
+}
+
+// Check that [ptr, ptr + len) is a non-null range inside linear memory, so
+// exports never write through a host-supplied pointer that would trap.
+fn is_valid_memory_range(ptr: usize, len: usize) bool {
+    if (ptr == 0) return false;
+    const end = std.math.add(usize, ptr, len) catch return false;
+    return end <= linear_memory_size();
+}
+
+// Values past 4 GB only occur in native builds; they read as the maximum
+fn stat_u32(value: usize) u32 {
+    return std.math.cast(u32, value) orelse std.math.maxInt(u32);
+}
+
+export fn get_memory_stats(stats_ptr: usize) i32 {
+    if (!is_valid_memory_range(stats_ptr, @sizeOf(MemoryStats))) return -1;
+    if (stats_ptr % @alignOf(MemoryStats) != 0) return -1;
+
+    const stats: *MemoryStats = @ptrFromInt(stats_ptr);
+    stats.* = .{
+        .io_buffer_size = stat_u32(io_buffer_len),
+        .lua_memory_used = stat_u32(lua_memory_used),
+        .wasm_pages = stat_u32(wasm_page_count()),
+        .lua_memory_peak = stat_u32(lua_memory_peak),
+        .lua_memory_limit = stat_u32(lua_memory_limit),
+        .stats_version = MEMORY_STATS_VERSION,
+    };
+    return 0;
+}
+
+// Write the cumulative allocator counters to counters_ptr (8-byte aligned)
+export fn get_alloc_counters(counters_ptr: usize) i32 {
+    if (!is_valid_memory_range(counters_ptr, @sizeOf(AllocCounters))) return -1;
+    if (counters_ptr % @alignOf(AllocCounters) != 0) return -1;
+
+    const counters: *AllocCounters = @ptrFromInt(counters_ptr);
+    counters.* = alloc_counters;
+    return 0;
+}
+
+// Cap the bytes the Lua allocator may hand out (0 removes the limit)
+export fn set_memory_limit(limit: usize) void {
+    lua_memory_limit = limit;
 }
 
-export fn run_gc() void {}
+// Run a full garbage collection cycle and return how many bytes it freed
+// (clamped to maxInt(i32))
+export fn gc_collect() i32 {
+    if (global_lua_state == null) return not_initialized_code();
 
-export fn attach_memory_table(table_id: u32) void {
-    if (global_lua_state == null) return;
-    if (table_id == 0) return;
+    const before = lua_memory_used;
+    _ = lua.c.lua_gc(global_lua_state.?, lua.c.LUA_GCCOLLECT);
+    const freed = before -| lua_memory_used;
+    return @intCast(@min(freed, std.math.maxInt(i32)));
+}
+
+// Run a full garbage collection cycle
+export fn run_gc() i32 {
+    const freed = gc_collect();
+    return if (freed < 0) freed else 0;
+}
+
+export fn attach_memory_table(table_id: u32) i32 {
+    if (global_lua_state == null) return not_initialized_code();
+    if (table_id == 0) return -1;
 
     const L = global_lua_state.?;
     ext_table.attach_table(L, table_id);
@@ -238,6 +1717,30 @@ export fn attach_memory_table(table_id: u32) void {
         lua.pop(L, 1); // Clean up duplicate if alias disabled
     }
     memory_table_id = table_id;
+    return 0;
+}
+
+// Longest global name attach_table accepts
+const MAX_GLOBAL_NAME_LEN = 256;
+
+// Bind a persisted external table to a global of the given name, e.g. after
+// a restart: attach_table("profile", 7). Returns 0, -1 for an invalid name
+// or id, or VM_NOT_INITIALIZED.
+export fn attach_table(name_ptr: usize, name_len: usize, table_id: u32) i32 {
+    if (global_lua_state == null) return not_initialized_code();
+    if (table_id == 0) return -1;
+    if (name_len == 0 or name_len > MAX_GLOBAL_NAME_LEN) return -1;
+    if (!is_valid_memory_range(name_ptr, name_len)) return -1;
+
+    const L = global_lua_state.?;
+    const name: [*]const u8 = @ptrFromInt(name_ptr);
+
+    _ = lua.c.lua_rawgeti(L, lua.c.LUA_REGISTRYINDEX, lua.c.LUA_RIDX_GLOBALS);
+    _ = lua.pushlstring(L, name, name_len);
+    ext_table.attach_table(L, table_id);
+    lua.c.lua_rawset(L, -3);
+    lua.pop(L, 1);
+    return 0;
 }
 
 export fn get_memory_table_id() u32 {
@@ -248,8 +1751,8 @@ export fn get_io_table_id() u32 {
     return io_table_id;
 }
 
-export fn clear_io_table() void {
-    if (global_lua_state == null) return;
+export fn clear_io_table() i32 {
+    if (global_lua_state == null) return not_initialized_code();
     const L = global_lua_state.?;
 
     // Clear _io.input, _io.output, _io.meta
@@ -261,12 +1764,109 @@ export fn clear_io_table() void {
     lua.pushnil(L);
     lua.setfield(L, -2, "meta");
     lua.pop(L, 1);
+    return 0;
+}
+
+export fn get_modules_table_id() u32 {
+    return modules_table_id;
+}
+
+// Point the Modules global at a previously persisted table
+export fn attach_modules_table(table_id: u32) i32 {
+    if (global_lua_state == null) return not_initialized_code();
+    if (table_id == 0) return -1;
+
+    const L = global_lua_state.?;
+    ext_table.attach_table(L, table_id);
+    lua.setglobal(L, module_loader.MODULES_TABLE_NAME);
+    modules_table_id = table_id;
+    return 0;
+}
+
+export fn free_table(table_id: u32) i32 {
+    return ext_table.free_table(table_id);
+}
+
+// Write every live entry of an external table to the IO buffer in one host
+// call (format in ext_table.is_valid_dump). Returns the dump length, or -1
+// if the host can't produce it or it doesn't fit.
+export fn dump_table(table_id: u32) i32 {
+    if (eval_active) return REENTRANT;
+    return ext_table.dump_table(table_id);
+}
+
+// Replace an external table's entries with the dump of len bytes in the IO
+// buffer. Returns the number of entries loaded, or -1 for a bad dump.
+export fn load_table(table_id: u32, len: usize) i32 {
+    if (eval_active) return REENTRANT;
+    return ext_table.load_table(table_id, len);
 }
 
 export fn sync_external_table_counter(next_id: u32) void {
     ext_table.sync_counter(next_id);
 }
 
+// Make next_id the id the next new external table gets, so a host can
+// replay a known sequence or continue one after restoring persisted tables.
+// 0, the last u32 (never handed out) and ids up to the highest of the
+// memory, io and modules tables are reserved. Returns 0, or -1 for a
+// reserved id.
+export fn set_next_table_id(next_id: u32) i32 {
+    if (eval_active) return REENTRANT;
+    const bound = @max(memory_table_id, io_table_id, modules_table_id);
+    if (next_id == 0 or next_id == std.math.maxInt(u32) or next_id <= bound) return -1;
+    ext_table.set_next_id(next_id);
+    return 0;
+}
+
+// The id the next new external table gets, unless a freed one is reused
+export fn get_next_table_id() u32 {
+    return ext_table.next_id();
+}
+
+// Strip debug info from function bytecode stored in external tables
+// (nonzero, the default) or keep it for line numbers in tracebacks
+export fn set_strip_bytecode(enabled: c_int) void {
+    function_serializer.set_strip_bytecode(enabled != 0);
+}
+
+// Make pairs and ext.keys list external table keys sorted by their stored
+// bytes (nonzero) instead of in the host's order (0, the default)
+export fn set_deterministic_iteration(enabled: c_int) void {
+    ext_table.set_deterministic_iteration(enabled != 0);
+}
+
+// What assigning nil to an external table key does: 0 deletes the key, 1
+// (the default) stores an explicit nil that ext.contains still reports.
+// Returns -1 for other modes.
+export fn set_nil_semantics(mode: u32) i32 {
+    const semantics = std.meta.intToEnum(ext_table.NilSemantics, mode) catch return -1;
+    ext_table.set_nil_semantics(semantics);
+    return 0;
+}
+
+// Store values written to external tables compressed when their serialized
+// form is longer than threshold bytes (nonzero enabled). Reads decompress
+// whatever was stored compressed, so turning it off keeps old values
+// readable.
+export fn set_compression(enabled: c_int, threshold: u32) void {
+    ext_table.set_compression(enabled != 0, threshold);
+}
+
+// Keep a list of the errors raised by scripts, including those pcall or
+// xpcall would have caught, for get_captured_errors (nonzero enables)
+export fn set_capture_errors(enabled: c_int) void {
+    error_capture.set_enabled(enabled != 0);
+}
+
+// Write the errors captured since the last call to the IO buffer as
+// [4 bytes: count] then per error [4 bytes: length][message], oldest first,
+// and clear the list. Returns the bytes written.
+export fn get_captured_errors() i32 {
+    if (eval_active) return REENTRANT;
+    return @intCast(error_capture.take_list(&io_buffer, io_buffer_len));
+}
+
 export fn set_memory_alias_enabled(enabled: c_int) void {
     enable_memory_alias = enabled != 0;
 }
@@ -0,0 +1,95 @@
+const std = @import("std");
+const lua = @import("lua.zig");
+
+const c = lua.c;
+
+// Global holding the external table that require() searches for sources
+pub const MODULES_TABLE_NAME = "Modules";
+
+// Called by require as loader(name, chunk). The module gets a partial table
+// registered in package.loaded before it runs, so a circular require sees
+// that table instead of recursing; the chunk receives it as its second arg.
+fn modules_loader(L: *lua.lua_State) c_int {
+    _ = lua.getfield(L, c.LUA_REGISTRYINDEX, c.LUA_LOADED_TABLE);
+    const loaded_index = lua.gettop(L);
+
+    lua.pushvalue(L, 1);
+    _ = c.lua_rawget(L, loaded_index);
+    if (lua.isnil(L, -1)) {
+        lua.pop(L, 1);
+        lua.newtable(L);
+        lua.pushvalue(L, 1);
+        lua.pushvalue(L, -2);
+        c.lua_rawset(L, loaded_index);
+    }
+    const partial_index = lua.gettop(L);
+
+    lua.pushvalue(L, 2);
+    lua.pushvalue(L, 1);
+    lua.pushvalue(L, partial_index);
+    c.lua_callk(L, 2, 1, 0, null);
+    return 1;
+}
+
+// package.searchers entry: looks name up in the Modules external table and
+// returns the compiled chunk, or a string explaining why it wasn't found.
+fn modules_searcher(L: *lua.lua_State) c_int {
+    var name_len: usize = 0;
+    if (!lua.isstring(L, 1)) {
+        _ = lua.pushstring(L, "\n\tmodule name is not a string");
+        return 1;
+    }
+    const name = lua.tolstring(L, 1, &name_len);
+
+    _ = lua.getglobal(L, MODULES_TABLE_NAME);
+    if (!lua.istable(L, -1)) {
+        _ = lua.pushstring(L, "\n\tno " ++ MODULES_TABLE_NAME ++ " table");
+        return 1;
+    }
+
+    lua.pushvalue(L, 1);
+    _ = c.lua_gettable(L, -2);
+    if (!lua.isstring(L, -1)) {
+        _ = lua.pushstring(L, "\n\tno field " ++ MODULES_TABLE_NAME ++ "['");
+        _ = lua.pushlstring(L, name, name_len);
+        _ = lua.pushstring(L, "']");
+        c.lua_concat(L, 3);
+        return 1;
+    }
+
+    var source_len: usize = 0;
+    const source = lua.tolstring(L, -1, &source_len);
+
+    var chunk_name: [256]u8 = undefined;
+    const chunk_name_str = std.fmt.bufPrintZ(&chunk_name, "=" ++ MODULES_TABLE_NAME ++ ".{s}", .{
+        name[0..@min(name_len, 200)],
+    }) catch "=" ++ MODULES_TABLE_NAME;
+
+    if (c.luaL_loadbufferx(L, source, source_len, chunk_name_str.ptr, "t") != c.LUA_OK) {
+        return c.lua_error(L);
+    }
+
+    lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&modules_loader)));
+    lua.pushvalue(L, -2);
+    return 2;
+}
+
+// Insert the Modules searcher right after package.preload so stored modules
+// win over the (unavailable) filesystem searchers.
+pub fn setup_module_loader(L: *lua.lua_State) void {
+    _ = lua.getglobal(L, "package");
+    _ = lua.getfield(L, -1, "searchers");
+    const searchers_index = lua.gettop(L);
+
+    const count: c.lua_Integer = @intCast(c.lua_rawlen(L, searchers_index));
+    var i = count;
+    while (i >= 2) : (i -= 1) {
+        _ = c.lua_rawgeti(L, searchers_index, i);
+        c.lua_rawseti(L, searchers_index, i + 1);
+    }
+
+    lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&modules_searcher)));
+    c.lua_rawseti(L, searchers_index, 2);
+
+    lua.pop(L, 2);
+}
@@ -5,40 +5,85 @@ const IO_BUFFER_SIZE = 64 * 1024;
 const OUTPUT_BUFFER_MAX = IO_BUFFER_SIZE - 1024;
 const OVERFLOW_MARKER = "...";
 
+// warn() lines are captured apart from print so hosts can read them
+// separately (read_stderr)
+const WARN_BUFFER_MAX = 16 * 1024;
+pub const MAX_LOG_PREFIX = 256;
+
 var output_buffer: [OUTPUT_BUFFER_MAX]u8 = undefined;
 var output_len: usize = 0;
 var output_overflow: bool = false;
+var output_limit: usize = OUTPUT_BUFFER_MAX;
+
+var warn_buffer: [WARN_BUFFER_MAX]u8 = undefined;
+var warn_len: usize = 0;
+var warn_overflow: bool = false;
+
+// Whether warnings are captured, switched by the "@on" and "@off" control
+// messages as in standalone Lua (but on by default); kept across calls
+var warnings_on: bool = true;
+// Lua's own warnings arrive in pieces; set while a message is unfinished
+var warning_continues: bool = false;
+
+// Prepended to every captured print and warn line; kept across calls
+var log_prefix: [MAX_LOG_PREFIX]u8 = undefined;
+var log_prefix_len: usize = 0;
 
 pub fn init_output_capture() void {
-    output_len = 0;
-    output_overflow = false;
+    reset_output();
+    log_prefix_len = 0;
+    warnings_on = true;
+    warning_continues = false;
 }
 
 pub fn reset_output() void {
     output_len = 0;
     output_overflow = false;
+    warn_len = 0;
+    warn_overflow = false;
 }
 
-pub fn push_output(data: []const u8) bool {
-    if (output_overflow) {
+// Largest length <= max_len that does not split a UTF-8 sequence.
+// Walks back over continuation bytes (0b10xxxxxx) from the cut point.
+pub fn utf8_floor(data: []const u8, max_len: usize) usize {
+    if (data.len <= max_len) return data.len;
+
+    var end = max_len;
+    while (end > 0 and (data[end] & 0xC0) == 0x80) : (end -= 1) {}
+    return end;
+}
+
+// Append data to a capture buffer, cutting it (on a character boundary) and
+// flagging overflow once limit is reached
+fn append(buffer: []u8, len: *usize, overflow: *bool, limit: usize, data: []const u8) bool {
+    if (overflow.*) {
         return false;
     }
 
-    const remaining = OUTPUT_BUFFER_MAX - output_len;
+    const remaining = limit -| len.*;
     if (data.len > remaining) {
         if (remaining >= OVERFLOW_MARKER.len) {
-            @memcpy(output_buffer[output_len .. output_len + remaining], data[0..remaining]);
-            output_len += remaining;
+            const copy_len = utf8_floor(data, remaining);
+            @memcpy(buffer[len.* .. len.* + copy_len], data[0..copy_len]);
+            len.* += copy_len;
         }
-        output_overflow = true;
+        overflow.* = true;
         return false;
     }
 
-    @memcpy(output_buffer[output_len .. output_len + data.len], data);
-    output_len += data.len;
+    @memcpy(buffer[len.* .. len.* + data.len], data);
+    len.* += data.len;
     return true;
 }
 
+pub fn push_output(data: []const u8) bool {
+    return append(&output_buffer, &output_len, &output_overflow, output_limit, data);
+}
+
+pub fn push_warn_output(data: []const u8) bool {
+    return append(&warn_buffer, &warn_len, &warn_overflow, WARN_BUFFER_MAX, data);
+}
+
 pub fn get_output_len() usize {
     if (output_overflow) {
         return output_len + OVERFLOW_MARKER.len;
@@ -46,6 +91,10 @@ pub fn get_output_len() usize {
     return output_len;
 }
 
+pub fn get_captured_len() usize {
+    return output_len;
+}
+
 pub fn get_output_ptr() [*]u8 {
     return &output_buffer;
 }
@@ -75,18 +124,24 @@ pub fn flush_output_to_buffer(buffer: [*]u8, offset: usize, max_len: usize) usiz
     return total;
 }
 
-pub fn custom_print(L: *lua.lua_State) c_int {
+// Write the arguments of a print-style call as one prefixed,
+// tab-separated line
+fn write_line(L: *lua.lua_State, comptime push: fn ([]const u8) bool) void {
     const argc = lua.gettop(L);
 
+    if (log_prefix_len > 0) {
+        _ = push(log_prefix[0..log_prefix_len]);
+    }
+
     for (0..@intCast(argc)) |i| {
         if (i > 0) {
-            _ = push_output("\t");
+            _ = push("\t");
         }
 
         if (lua.isstring(L, @intCast(i + 1))) {
             var str_len: usize = 0;
             const str = lua.tolstring(L, @intCast(i + 1), &str_len);
-            _ = push_output(str[0..str_len]);
+            _ = push(str[0..str_len]);
         } else if (lua.isnumber(L, @intCast(i + 1))) {
             var buf: [64]u8 = undefined;
             const num = lua.tonumber(L, @intCast(i + 1));
@@ -94,17 +149,17 @@ pub fn custom_print(L: *lua.lua_State) c_int {
 
             if (@as(f64, @floatFromInt(int_val)) == num) {
                 const fmt_result = std.fmt.bufPrint(&buf, "{d}", .{int_val}) catch "";
-                _ = push_output(fmt_result);
+                _ = push(fmt_result);
             } else {
                 const fmt_result = std.fmt.bufPrint(&buf, "{d}", .{num}) catch "";
-                _ = push_output(fmt_result);
+                _ = push(fmt_result);
             }
         } else if (lua.isboolean(L, @intCast(i + 1))) {
             const val = lua.toboolean(L, @intCast(i + 1));
             const str = if (val) "true" else "false";
-            _ = push_output(str);
+            _ = push(str);
         } else if (lua.isnil(L, @intCast(i + 1))) {
-            _ = push_output("nil");
+            _ = push("nil");
         } else {
             const type_name = lua.type_name(L, @intCast(i + 1));
             var i_type: usize = 0;
@@ -113,15 +168,90 @@ pub fn custom_print(L: *lua.lua_State) c_int {
                 buf[i_type] = type_name[i_type];
             }
             if (i_type > 0) {
-                _ = push_output(type_name[0..i_type]);
+                _ = push(type_name[0..i_type]);
             }
         }
     }
 
-    _ = push_output("\n");
+    _ = push("\n");
+}
+
+pub fn custom_print(L: *lua.lua_State) c_int {
+    write_line(L, push_output);
+    return 0;
+}
+
+// Apply a control message ("@on", "@off"; others are ignored, as Lua does).
+// Returns false for a message that isn't one.
+fn warn_control(message: []const u8) bool {
+    if (message.len == 0 or message[0] != '@') return false;
+    if (std.mem.eql(u8, message, "@on")) warnings_on = true;
+    if (std.mem.eql(u8, message, "@off")) warnings_on = false;
+    return true;
+}
+
+// warn(...): like print, but captured in the separate stderr stream. A lone
+// string argument starting with @ is a control message instead.
+pub fn custom_warn(L: *lua.lua_State) c_int {
+    if (lua.gettop(L) == 1 and lua.c.lua_type(L, 1) == lua.c.LUA_TSTRING) {
+        var len: usize = 0;
+        const message = lua.tolstring(L, 1, &len);
+        if (warn_control(message[0..len])) return 0;
+    }
+    if (warnings_on) write_line(L, push_warn_output);
     return 0;
 }
 
+// Warning function for lua_setwarnf, so warnings Lua raises itself (an
+// error in a __gc metamethod, say) land in the stderr stream too instead of
+// vanishing. tocont is set on every piece but a message's last.
+pub fn warn_handler(ud: ?*anyopaque, message: [*c]const u8, tocont: c_int) callconv(.c) void {
+    _ = ud;
+    const piece = std.mem.span(message);
+    const first = !warning_continues;
+    warning_continues = tocont != 0;
+
+    if (first and tocont == 0 and warn_control(piece)) return;
+    if (!warnings_on) return;
+
+    if (first and log_prefix_len > 0) _ = push_warn_output(log_prefix[0..log_prefix_len]);
+    _ = push_warn_output(piece);
+    if (tocont == 0) _ = push_warn_output("\n");
+}
+
+// Cap captured print output at limit bytes; 0 or anything above the static
+// capture buffer restores the default (the full buffer).
+// Returns false, keeping the current limit, if limit is over the buffer size
+pub fn set_output_limit(limit: usize) bool {
+    if (limit > OUTPUT_BUFFER_MAX) return false;
+    output_limit = if (limit == 0) OUTPUT_BUFFER_MAX else limit;
+    return true;
+}
+
+pub fn get_output_limit() usize {
+    return output_limit;
+}
+
 pub fn is_overflow() bool {
     return output_overflow;
 }
+
+// Set the prefix for captured lines; an empty prefix turns it off
+pub fn set_log_prefix(prefix: []const u8) bool {
+    if (prefix.len > MAX_LOG_PREFIX) return false;
+    @memcpy(log_prefix[0..prefix.len], prefix);
+    log_prefix_len = prefix.len;
+    return true;
+}
+
+// Copy the captured warn output into buffer; returns the bytes written
+pub fn copy_warn_output(buffer: [*]u8, max_len: usize) usize {
+    var copy_len = utf8_floor(warn_buffer[0..warn_len], max_len);
+    @memcpy(buffer[0..copy_len], warn_buffer[0..copy_len]);
+
+    if (warn_overflow and copy_len + OVERFLOW_MARKER.len <= max_len) {
+        @memcpy(buffer[copy_len .. copy_len + OVERFLOW_MARKER.len], OVERFLOW_MARKER);
+        copy_len += OVERFLOW_MARKER.len;
+    }
+    return copy_len;
+}
@@ -0,0 +1,56 @@
+const lua = @import("lua.zig");
+const serializer = @import("serializer.zig");
+
+const c = lua.c;
+
+// Largest value cu.pack encodes, matching what fits the IO buffer
+const MAX_PACKED = 64 * 1024;
+
+var pack_buffer: [MAX_PACKED]u8 = undefined;
+
+fn raise(L: *lua.lua_State, message: [*:0]const u8) c_int {
+    _ = lua.pushstring(L, message);
+    return c.lua_error(L);
+}
+
+// cu.pack(value): the bytes serialize_value writes for value, as a string,
+// i.e. the same encoding external tables store. Tables are copied into a
+// new external table and packed as a reference to it.
+fn pack_impl(L: *lua.lua_State) c_int {
+    c.luaL_checkany(L, 1);
+    lua.settop(L, 1);
+
+    const len = serializer.serialize_value(L, 1, &pack_buffer, pack_buffer.len) catch |err| {
+        if (err == serializer.SerializationError.BufferTooSmall) return raise(L, "cu.pack: value too large");
+        var message: [serializer.MAX_FAILURE_MESSAGE]u8 = undefined;
+        const text = serializer.describe_failure(err, &message);
+        _ = lua.pushstring(L, "cu.pack: ");
+        _ = c.lua_pushlstring(L, text.ptr, text.len);
+        c.lua_concat(L, 2);
+        return c.lua_error(L);
+    };
+    _ = c.lua_pushlstring(L, &pack_buffer, len);
+    return 1;
+}
+
+// cu.unpack(bytes): the value cu.pack encoded. Raises on malformed bytes.
+fn unpack_impl(L: *lua.lua_State) c_int {
+    var len: usize = 0;
+    const bytes = c.luaL_checklstring(L, 1, &len);
+    lua.settop(L, 1);
+
+    if (len == 0) return raise(L, "cu.unpack: empty string");
+    serializer.deserialize_value(L, bytes, len) catch {
+        lua.settop(L, 1);
+        return raise(L, "cu.unpack: malformed data");
+    };
+    return 1;
+}
+
+// Set cu.pack and cu.unpack on the table at the top of the stack
+pub fn register(L: *lua.lua_State) void {
+    lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&pack_impl)));
+    lua.setfield(L, -2, "pack");
+    lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&unpack_impl)));
+    lua.setfield(L, -2, "unpack");
+}
@@ -7,25 +7,69 @@ const IO_BUFFER_SIZE = 64 * 1024;
 const OUTPUT_LEN_SIZE = 4;
 const RESULT_OFFSET = OUTPUT_LEN_SIZE;
 
+// Copy of the most recently encoded return value, kept for introspection
+var last_value: [IO_BUFFER_SIZE]u8 = undefined;
+var last_value_len: usize = 0;
+var last_value_type: c_int = lua.c.LUA_TNIL;
+
 pub fn encode_result(L: *lua.lua_State, buffer: [*]u8, max_len: usize) usize {
     if (max_len < OUTPUT_LEN_SIZE) {
         return 0;
     }
 
-    const output_len = output.get_output_len();
-    const output_ptr = output.get_output_ptr();
+    var offset = write_output(buffer, max_len);
+
+    const top = lua.gettop(L);
+    const value_start = offset;
 
-    const output_len_u32: u32 = @intCast(output_len);
-    const output_len_bytes = std.mem.asBytes(&output_len_u32);
-    @memcpy(buffer[0..4], output_len_bytes);
+    if (top > 0) {
+        last_value_type = lua.c.lua_type(L, -1);
+        offset = encode_stack_value(L, -1, buffer, offset, max_len);
+    } else {
+        last_value_type = lua.c.LUA_TNIL;
+        if (offset + 1 <= max_len) {
+            buffer[offset] = @intFromEnum(serializer.SerializationType.nil);
+            offset += 1;
+        }
+    }
+
+    // Streamed results can outgrow the introspection copy; keep its prefix
+    last_value_len = @min(offset - value_start, last_value.len);
+    @memcpy(last_value[0..last_value_len], buffer[value_start .. value_start + last_value_len]);
+
+    lua.settop(L, 0);
+    return offset;
+}
+
+// Like encode_result, but the value on top of the stack goes through the full
+// serializer, so tables come back as external table references. Returns null
+// if the value can't be serialized.
+pub fn encode_serialized_result(L: *lua.lua_State, buffer: [*]u8, max_len: usize) ?usize {
+    if (max_len < OUTPUT_LEN_SIZE) {
+        return null;
+    }
+
+    const value_start = write_output(buffer, max_len);
+    const value_len = serializer.serialize_value(L, -1, buffer + value_start, max_len - value_start) catch return null;
+
+    last_value_type = lua.c.lua_type(L, -1);
+    last_value_len = @min(value_len, last_value.len);
+    @memcpy(last_value[0..last_value_len], buffer[value_start .. value_start + last_value_len]);
+
+    lua.settop(L, 0);
+    return value_start + value_len;
+}
+
+// Write the length-prefixed captured output; returns where the value starts
+fn write_output(buffer: [*]u8, max_len: usize) usize {
+    const captured_len = output.get_captured_len();
+    const output_ptr = output.get_output_ptr();
 
     var offset: usize = OUTPUT_LEN_SIZE;
 
-    if (output_len > 0) {
-        const output_copy_len = if (OUTPUT_LEN_SIZE + output_len > max_len)
-            max_len - OUTPUT_LEN_SIZE
-        else
-            output_len;
+    if (captured_len > 0) {
+        // Never cut a multibyte character in half, hosts decode this as UTF-8
+        const output_copy_len = output.utf8_floor(output_ptr[0..captured_len], max_len - OUTPUT_LEN_SIZE);
 
         @memcpy(buffer[offset .. offset + output_copy_len], output_ptr[0..output_copy_len]);
         offset += output_copy_len;
@@ -36,19 +80,33 @@ pub fn encode_result(L: *lua.lua_State, buffer: [*]u8, max_len: usize) usize {
         }
     }
 
-    const top = lua.gettop(L);
+    write_encoded_output_length(buffer, offset - OUTPUT_LEN_SIZE);
+    return offset;
+}
 
-    if (top > 0) {
-        offset = encode_stack_value(L, -1, buffer, offset, max_len);
-    } else {
-        if (offset + 1 <= max_len) {
-            buffer[offset] = @intFromEnum(serializer.SerializationType.nil);
-            offset += 1;
-        }
+pub fn get_last_value() []const u8 {
+    return last_value[0..last_value_len];
+}
+
+pub fn get_last_value_type() c_int {
+    return last_value_type;
+}
+
+// Tag byte peek_result_type reports for values compute writes as a plain-text
+// marker ("table", "function", ...) rather than a serialized value
+pub const UNTAGGED_RESULT: i32 = 0xFF;
+
+// Serialization tag of the most recently encoded return value
+pub fn get_last_value_tag() i32 {
+    if (last_value_len == 0) {
+        // Empty strings are encoded as no value bytes at all
+        const tag = if (last_value_type == lua.c.LUA_TSTRING) serializer.SerializationType.string else serializer.SerializationType.nil;
+        return @intFromEnum(tag);
     }
 
-    lua.settop(L, 0);
-    return offset;
+    const tag = last_value[0];
+    if (tag > @intFromEnum(serializer.SerializationType.host_handle)) return UNTAGGED_RESULT;
+    return tag;
 }
 
 fn encode_stack_value(L: *lua.lua_State, stack_idx: c_int, buffer: [*]u8, offset: usize, max_len: usize) usize {
@@ -112,7 +170,7 @@ fn encode_stack_value(L: *lua.lua_State, stack_idx: c_int, buffer: [*]u8, offset
                 return offset + 5 + str_len;
             } else if (remaining >= 5) {
                 buffer[offset] = @intFromEnum(serializer.SerializationType.string);
-                const copy_len = remaining - 5;
+                const copy_len = output.utf8_floor(str[0..str_len], remaining - 5);
                 const len_u32: u32 = @intCast(copy_len);
                 const len_bytes = std.mem.asBytes(&len_u32);
                 @memcpy(buffer[offset + 1 .. offset + 5], len_bytes);
@@ -124,6 +182,13 @@ fn encode_stack_value(L: *lua.lua_State, stack_idx: c_int, buffer: [*]u8, offset
     }
 
     if (lua.istable(L, stack_idx)) {
+        // Tables go to the host as external tables (tag 0x07, nested tables
+        // included). One the serializer can't store (C functions outside the
+        // registry, coroutines, ...) falls back to the plain "table" marker.
+        if (serializer.serialize_value(L, stack_idx, buffer + offset, remaining)) |table_len| {
+            return offset + table_len;
+        } else |_| {}
+
         const type_marker = "table";
         if (remaining >= type_marker.len) {
             @memcpy(buffer[offset .. offset + type_marker.len], type_marker);
@@ -2,9 +2,7 @@ const std = @import("std");
 const lua = @import("lua.zig");
 const function_serializer = @import("function_serializer.zig");
 const ext_table = @import("ext_table.zig");
-
-// External function for setting values in external tables
-extern fn js_ext_table_set(table_id: u32, key_ptr: [*]const u8, key_len: usize, val_ptr: [*]const u8, val_len: usize) c_int;
+const handle = @import("handle.zig");
 
 const IO_BUFFER_SIZE = 64 * 1024;
 
@@ -12,6 +10,13 @@ const IO_BUFFER_SIZE = 64 * 1024;
 const MAX_RECURSION_DEPTH: usize = 32;
 const MAX_TABLE_ENTRIES: usize = 10000;
 
+// Loading a value can recurse (function upvalues, stored metatables, nested
+// ext.to_table copies), and the bytes come from the host's store, so nesting
+// is capped instead of trusting the data to be shallow
+pub const DEFAULT_MAX_DESERIALIZE_DEPTH: usize = 64;
+var max_deserialize_depth: usize = DEFAULT_MAX_DESERIALIZE_DEPTH;
+var deserialize_depth: usize = 0;
+
 // Context for tracking recursion
 const ConversionContext = struct {
     depth: usize,
@@ -27,8 +32,20 @@ pub const SerializationType = enum(u8) {
     function_bytecode = 0x05, // For Lua functions
     function_ref = 0x06, // For C functions
     table_ref = 0x07, // For external tables
+    host_handle = 0x08, // Opaque u64 handle owned by the host
 };
 
+// Optional header in front of a stored value: tag, format version, flags.
+// Values without it are plain little-endian, which is what serialize_value
+// writes; the header lets a host hand us data from a big-endian writer.
+pub const FORMAT_VERSION: u8 = 1;
+pub const FORMAT_HEADER_TAG: u8 = 0xF0;
+// Leads a value ext_table stored compressed (see compress.zig):
+// [tag][4 bytes: serialized length (u32 LE)][LZ4 block]
+pub const COMPRESSED_TAG: u8 = 0xF1;
+pub const FORMAT_HEADER_SIZE: usize = 3;
+pub const FORMAT_FLAG_BIG_ENDIAN: u8 = 0x01;
+
 pub const SerializationError = error{
     BufferTooSmall,
     InvalidFormat,
@@ -36,6 +53,112 @@ pub const SerializationError = error{
     CircularReference,
     MaxDepthExceeded,
     TableTooLarge,
+    UnsupportedMetatable,
+    TableIdsExhausted,
+    TruncatedValue,
+    InvalidMetatable,
+};
+
+// Set in the optional flags byte after a table_ref id; a u32 metatable table
+// id follows.
+pub const TABLE_FLAG_METATABLE: u8 = 0x01;
+
+// Set in the flags byte of a table raised with error(), so a host can tell
+// an error object from a returned table. Loading ignores it: the table comes
+// back as an ordinary table.
+pub const TABLE_FLAG_ERROR: u8 = 0x02;
+
+// What the last failed serialization tripped on, so a script storing it gets
+// a useful error: the value that can't be stored and the keys leading to it.
+// The path is built as the error unwinds, innermost key first, so it fills
+// failure_path from the end.
+const MAX_FAILURE_PATH = 256;
+// Room describe_failure needs for the longest message
+pub const MAX_FAILURE_MESSAGE = 512;
+var failure_what_buffer: [48]u8 = undefined;
+var failure_what: []const u8 = "value";
+var failure_path: [MAX_FAILURE_PATH]u8 = undefined;
+var failure_path_start: usize = MAX_FAILURE_PATH;
+var failure_path_truncated: bool = false;
+
+// Start a failure report at the value or key that can't be stored, e.g.
+// "thread" "value"
+fn begin_failure(type_name: [*:0]const u8, kind: []const u8) void {
+    failure_what = std.fmt.bufPrint(&failure_what_buffer, "{s} {s}", .{ std.mem.span(type_name), kind }) catch "value";
+    failure_path_start = MAX_FAILURE_PATH;
+    failure_path_truncated = false;
+}
+
+fn prepend_failure_path(part: []const u8) void {
+    if (failure_path_truncated) return;
+    if (part.len > failure_path_start) {
+        failure_path_truncated = true;
+        return;
+    }
+    failure_path_start -= part.len;
+    @memcpy(failure_path[failure_path_start..][0..part.len], part);
+}
+
+fn is_identifier(name: []const u8) bool {
+    if (name.len == 0 or std.ascii.isDigit(name[0])) return false;
+    for (name) |char| {
+        if (!std.ascii.isAlphanumeric(char) and char != '_') return false;
+    }
+    return true;
+}
+
+// Put the key at key_index, under which the failed value sits, in front of
+// the failure path: .name for identifiers, [n] for numbers and ["..."] for
+// other strings, cut short past 32 bytes
+pub fn note_failure_key(L: *lua.lua_State, key_index: c_int) void {
+    var part_buffer: [48]u8 = undefined;
+    const part = switch (lua.c.lua_type(L, key_index)) {
+        lua.c.LUA_TNUMBER => if (lua.c.lua_isinteger(L, key_index) != 0)
+            std.fmt.bufPrint(&part_buffer, "[{d}]", .{lua.tointeger(L, key_index)}) catch unreachable
+        else
+            std.fmt.bufPrint(&part_buffer, "[{d}]", .{lua.tonumber(L, key_index)}) catch "[?]",
+        lua.c.LUA_TSTRING => blk: {
+            var key_len: usize = 0;
+            const key = lua.tolstring(L, key_index, &key_len)[0..key_len];
+            if (key.len <= 32 and is_identifier(key)) {
+                break :blk std.fmt.bufPrint(&part_buffer, ".{s}", .{key}) catch unreachable;
+            }
+            const shown = key[0..@min(key.len, 32)];
+            const ellipsis = if (key.len > 32) "..." else "";
+            break :blk std.fmt.bufPrint(&part_buffer, "[\"{s}{s}\"]", .{ shown, ellipsis }) catch unreachable;
+        },
+        else => std.fmt.bufPrint(&part_buffer, "[{s}]", .{std.mem.span(lua.type_name(L, key_index))}) catch unreachable,
+    };
+    prepend_failure_path(part);
+}
+
+// The message for a failed serialization, naming what couldn't be stored
+// and where for the errors that record it. buffer needs
+// MAX_FAILURE_MESSAGE bytes.
+pub fn describe_failure(err: SerializationError, buffer: []u8) []const u8 {
+    var path: []const u8 = failure_path[failure_path_start..];
+    // The outermost key needs no leading dot
+    if (!failure_path_truncated and path.len > 0 and path[0] == '.') path = path[1..];
+    const at = if (path.len > 0) " at " else "";
+    const ellipsis = if (failure_path_truncated) "..." else "";
+
+    return switch (err) {
+        SerializationError.TypeMismatch => std.fmt.bufPrint(buffer, "cannot store {s}{s}{s}{s}", .{ failure_what, at, ellipsis, path }),
+        SerializationError.CircularReference => std.fmt.bufPrint(buffer, "cannot store table: circular reference{s}{s}{s}", .{ at, ellipsis, path }),
+        SerializationError.UnsupportedMetatable => std.fmt.bufPrint(buffer, "cannot store table: metatable has C functions or unsupported fields{s}{s}{s}", .{ at, ellipsis, path }),
+        SerializationError.InvalidMetatable => std.fmt.bufPrint(buffer, "cannot store table: metatable has an invalid __ext_table_id{s}{s}{s}", .{ at, ellipsis, path }),
+        else => std.fmt.bufPrint(buffer, "cannot store value: {s}", .{@errorName(err)}),
+    } catch unreachable;
+}
+
+// Metatable fields that survive a store/load round trip. Anything else
+// starting with "__" (__gc, __mode, __close, __newindex, __len, ...) is
+// rejected rather than silently dropped.
+const SUPPORTED_METAFIELDS = [_][]const u8{
+    "__index", "__tostring", "__name", "__eq",   "__lt",   "__le",
+    "__concat", "__call",    "__unm",  "__add",  "__sub",  "__mul",
+    "__div",   "__mod",      "__pow",  "__idiv", "__band", "__bor",
+    "__bxor",  "__shl",      "__shr",  "__bnot",
 };
 
 // Helper to serialize Lua table keys to external table format
@@ -106,11 +229,114 @@ fn unmark_table_visited(L: *lua.lua_State, table_index: c_int, ctx: *ConversionC
     lua.c.lua_rawset(L, ctx.visited_stack_index);
 }
 
-// Convert a regular Lua table to an external table
+fn is_supported_metafield(name: []const u8) bool {
+    for (SUPPORTED_METAFIELDS) |field| {
+        if (std.mem.eql(u8, name, field)) return true;
+    }
+    return false;
+}
+
+// Reject metatables we can't faithfully restore: C functions anywhere, unknown
+// metamethods, or an __index that is neither a table nor a function.
+fn validate_metatable(L: *lua.lua_State, mt_index: c_int) SerializationError!void {
+    lua.pushnil(L);
+    while (lua.c.lua_next(L, mt_index) != 0) {
+        if (lua.c.lua_iscfunction(L, -1) != 0) {
+            lua.pop(L, 2);
+            return SerializationError.UnsupportedMetatable;
+        }
+
+        if (lua.isstring(L, -2)) {
+            var key_len: usize = 0;
+            const key = lua.tolstring(L, -2, &key_len);
+            const name = key[0..key_len];
+
+            if (std.mem.startsWith(u8, name, "__") and !is_supported_metafield(name)) {
+                lua.pop(L, 2);
+                return SerializationError.UnsupportedMetatable;
+            }
+
+            if (std.mem.eql(u8, name, "__index") and !lua.istable(L, -1) and !lua.isfunction(L, -1)) {
+                lua.pop(L, 2);
+                return SerializationError.UnsupportedMetatable;
+            }
+        }
+
+        lua.pop(L, 1);
+    }
+}
+
+// The external table id a metatable claims with __ext_table_id. Scripts can
+// set that field themselves, so only an integer in the u32 id range counts.
+fn metatable_ref_id(L: *lua.lua_State, idx: c_int) SerializationError!u32 {
+    if (lua.c.lua_isinteger(L, idx) == 0) return SerializationError.InvalidMetatable;
+    const id = lua.tointeger(L, idx);
+    if (id <= 0 or id > std.math.maxInt(u32)) return SerializationError.InvalidMetatable;
+    return @intCast(id);
+}
+
+// Fail on a metatable serialize_metatable can't store before the table it
+// belongs to is converted, so the failure leaves no orphaned host table
+fn check_metatable(L: *lua.lua_State, table_index: c_int) SerializationError!void {
+    if (lua.getmetatable(L, table_index) == 0) return;
+    const mt_index = lua.gettop(L);
+    defer lua.settop(L, mt_index - 1);
+
+    _ = lua.getfield(L, mt_index, "__ext_table_id");
+    if (!lua.isnil(L, -1)) {
+        _ = metatable_ref_id(L, -1) catch |err| {
+            begin_failure("table", "value");
+            return err;
+        };
+        return;
+    }
+    lua.pop(L, 1);
+
+    validate_metatable(L, mt_index) catch |err| {
+        begin_failure("table", "value");
+        return err;
+    };
+}
+
+// Store the metatable of the table at table_index as an external table.
+// Returns its id, or 0 when the table has no metatable. A metatable that is
+// already an external table is referenced by its id rather than copied.
+// check_metatable has vetted it.
+fn serialize_metatable(L: *lua.lua_State, table_index: c_int, ctx: *ConversionContext) SerializationError!u32 {
+    if (lua.getmetatable(L, table_index) == 0) return 0;
+    const mt_index = lua.gettop(L);
+    defer lua.settop(L, mt_index - 1);
+
+    _ = lua.getfield(L, mt_index, "__ext_table_id");
+    if (!lua.isnil(L, -1)) return metatable_ref_id(L, -1);
+    lua.pop(L, 1);
+
+    return convert_table_to_external(L, mt_index, ctx, true);
+}
+
+fn write_table_ref(buffer: [*]u8, max_len: usize, table_id: u32, metatable_id: u32) SerializationError!usize {
+    const total_len: usize = if (metatable_id != 0) 10 else 5;
+    if (max_len < total_len) return SerializationError.BufferTooSmall;
+
+    buffer[0] = @intFromEnum(SerializationType.table_ref);
+    @memcpy(buffer[1..5], std.mem.asBytes(&table_id));
+
+    if (metatable_id != 0) {
+        buffer[5] = TABLE_FLAG_METATABLE;
+        @memcpy(buffer[6..10], std.mem.asBytes(&metatable_id));
+    }
+
+    return total_len;
+}
+
+// Convert a regular Lua table to an external table. With is_metatable set, a
+// field __index pointing back at the table itself (the usual class idiom) is
+// stored as `true` instead of failing as a circular reference.
 fn convert_table_to_external(
     L: *lua.lua_State,
     table_index: c_int,
     ctx: *ConversionContext,
+    is_metatable: bool,
 ) SerializationError!u32 {
     // Check recursion depth
     if (ctx.depth >= MAX_RECURSION_DEPTH) {
@@ -125,6 +351,7 @@ fn convert_table_to_external(
 
     // Check for circular reference
     if (is_table_visited(L, abs_table_index, ctx)) {
+        begin_failure("table", "value");
         return SerializationError.CircularReference;
     }
 
@@ -139,6 +366,10 @@ fn convert_table_to_external(
     // Create new external table
     const table_id = ext_table.create_table(L);
     _ = lua.gettop(L); // external table is now on stack
+    if (table_id == 0) {
+        lua.pop(L, 1);
+        return SerializationError.TableIdsExhausted;
+    }
 
     // Count entries and check limit
     var entry_count: usize = 0;
@@ -164,28 +395,33 @@ fn convert_table_to_external(
 
         // Serialize the key
         const key_len = serialize_table_key(L, -2, &key_buffer, key_buffer.len) catch |err| {
+            if (err == SerializationError.TypeMismatch) begin_failure(lua.type_name(L, -2), "key");
             lua.pop(L, 2); // pop value and key
             lua.pop(L, 1); // pop external table
             return err;
         };
 
-        // Serialize the value (recursive for nested tables)
-        const value_len = serialize_value_with_context(L, -1, &value_buffer, value_buffer.len, ctx) catch |err| {
-            lua.pop(L, 2); // pop value and key
-            lua.pop(L, 1); // pop external table
-            return err;
-        };
+        const is_self_index = is_metatable and
+            std.mem.eql(u8, key_buffer[0..key_len], "__index") and
+            lua.c.lua_rawequal(L, -1, abs_table_index) != 0;
 
-        // Store in external table via JavaScript bridge
-        const result = js_ext_table_set(
-            table_id,
-            &key_buffer,
-            key_len,
-            &value_buffer,
-            value_len,
-        );
+        // Serialize the value (recursive for nested tables)
+        var value_len: usize = 2;
+        if (is_self_index) {
+            value_buffer[0] = @intFromEnum(SerializationType.boolean);
+            value_buffer[1] = 1;
+        } else {
+            value_len = serialize_value_with_context(L, -1, &value_buffer, value_buffer.len, ctx) catch |err| {
+                note_failure_key(L, -2);
+                lua.pop(L, 2); // pop value and key
+                lua.pop(L, 1); // pop external table
+                return err;
+            };
+        }
 
-        if (result != 0) {
+        // Store in external table via JavaScript bridge (held back instead
+        // inside a transaction)
+        if (!ext_table.store_entry(L, table_id, key_buffer[0..key_len], value_buffer[0..value_len])) {
             lua.pop(L, 2); // pop value and key
             lua.pop(L, 1); // pop external table
             return SerializationError.InvalidFormat;
@@ -264,7 +500,11 @@ fn serialize_value_with_context(
     }
 
     if (lua.isfunction(L, stack_index)) {
-        return function_serializer.serialize_function(L, stack_index, buffer, max_len);
+        // Failing here means an upvalue can't be stored; report the function
+        return function_serializer.serialize_function(L, stack_index, buffer, max_len) catch |err| {
+            if (err == SerializationError.TypeMismatch) begin_failure(lua.type_name(L, stack_index), "value");
+            return err;
+        };
     }
 
     if (lua.istable(L, stack_index)) {
@@ -275,26 +515,27 @@ fn serialize_value_with_context(
             const table_id = lua.tointeger(L, -1);
             lua.pop(L, 1);
 
-            if (max_len < 5) return SerializationError.BufferTooSmall;
-            buffer[0] = @intFromEnum(SerializationType.table_ref);
             const id_u32: u32 = @intCast(table_id);
-            const id_bytes = std.mem.asBytes(&id_u32);
-            @memcpy(buffer[1..5], id_bytes);
-            return 5;
+            return write_table_ref(buffer, max_len, id_u32, ext_table.user_metatable_id(L, stack_index));
         }
         lua.pop(L, 1);
 
         // Regular table - convert to external table
-        const table_id = try convert_table_to_external(L, stack_index, ctx);
-
-        if (max_len < 5) return SerializationError.BufferTooSmall;
-        buffer[0] = @intFromEnum(SerializationType.table_ref);
-        const id_u32: u32 = @intCast(table_id);
-        const id_bytes = std.mem.asBytes(&id_u32);
-        @memcpy(buffer[1..5], id_bytes);
-        return 5;
+        try check_metatable(L, stack_index);
+        const table_id = try convert_table_to_external(L, stack_index, ctx, false);
+        const metatable_id = try serialize_metatable(L, stack_index, ctx);
+
+        return write_table_ref(buffer, max_len, table_id, metatable_id);
+    }
+
+    if (handle.to_handle(L, stack_index)) |handle_value| {
+        if (max_len < 9) return SerializationError.BufferTooSmall;
+        buffer[0] = @intFromEnum(SerializationType.host_handle);
+        @memcpy(buffer[1..9], std.mem.asBytes(&handle_value));
+        return 9;
     }
 
+    begin_failure(lua.type_name(L, stack_index), "value");
     return SerializationError.TypeMismatch;
 }
 
@@ -326,62 +567,123 @@ pub fn serialize_value(L: *lua.lua_State, stack_index: c_int, buffer: [*]u8, max
     return result;
 }
 
+// Serialize an error object: like serialize_value, but a table ref carries
+// TABLE_FLAG_ERROR
+pub fn serialize_error_value(L: *lua.lua_State, stack_index: c_int, buffer: [*]u8, max_len: usize) SerializationError!usize {
+    var len = try serialize_value(L, stack_index, buffer, max_len);
+    if (buffer[0] != @intFromEnum(SerializationType.table_ref)) return len;
+
+    // A bare ref (no metatable) gains a flags byte
+    if (len == 5) {
+        if (max_len < 6) return SerializationError.BufferTooSmall;
+        buffer[5] = 0;
+        len = 6;
+    }
+    buffer[5] |= TABLE_FLAG_ERROR;
+    return len;
+}
+
+// Fail with TruncatedValue unless len covers a header of `header` bytes
+// followed by `body` more. Written so a length read from the blob can't
+// overflow the comparison.
+pub fn check_len(len: usize, header: usize, body: usize) SerializationError!void {
+    if (len < header or len - header < body) return SerializationError.TruncatedValue;
+}
+
+pub fn set_max_deserialize_depth(depth: usize) void {
+    max_deserialize_depth = depth;
+}
+
+// Count one level of load nesting; pair with leave_deserialize
+pub fn enter_deserialize() SerializationError!void {
+    if (deserialize_depth >= max_deserialize_depth) return SerializationError.MaxDepthExceeded;
+    deserialize_depth += 1;
+}
+
+pub fn leave_deserialize() void {
+    deserialize_depth -= 1;
+}
+
+// A Lua error raised mid-load skips the pending leave_deserialize calls, so
+// the count starts over with each eval
+pub fn reset_deserialize_depth() void {
+    deserialize_depth = 0;
+}
+
 pub fn deserialize_value(L: *lua.lua_State, buffer: [*]const u8, len: usize) SerializationError!void {
-    if (len < 1) return SerializationError.InvalidFormat;
+    try enter_deserialize();
+    defer leave_deserialize();
+
+    if (len >= 1 and buffer[0] == FORMAT_HEADER_TAG) {
+        try check_len(len, FORMAT_HEADER_SIZE, 0);
+        if (buffer[1] == 0 or buffer[1] > FORMAT_VERSION) return SerializationError.InvalidFormat;
+
+        const endian: std.builtin.Endian = if (buffer[2] & FORMAT_FLAG_BIG_ENDIAN != 0) .big else .little;
+        return deserialize_value_with_endian(L, buffer + FORMAT_HEADER_SIZE, len - FORMAT_HEADER_SIZE, endian);
+    }
+
+    return deserialize_value_with_endian(L, buffer, len, .little);
+}
 
-    const type_byte = buffer[0];
-    const value_type: SerializationType = @enumFromInt(type_byte);
+fn deserialize_value_with_endian(L: *lua.lua_State, buffer: [*]const u8, len: usize, endian: std.builtin.Endian) SerializationError!void {
+    try check_len(len, 1, 0);
+
+    const value_type = std.meta.intToEnum(SerializationType, buffer[0]) catch return SerializationError.InvalidFormat;
 
     switch (value_type) {
         SerializationType.nil => {
             lua.pushnil(L);
         },
         SerializationType.boolean => {
-            if (len < 2) return SerializationError.InvalidFormat;
+            try check_len(len, 1, 1);
             const val = buffer[1] != 0;
             lua.pushboolean(L, if (val) 1 else 0);
         },
         SerializationType.integer => {
-            if (len < 9) return SerializationError.InvalidFormat;
-            // Read as little-endian i64
-            var int_val: i64 = undefined;
-            const bytes = buffer[1..9];
-            @memcpy(std.mem.asBytes(&int_val), bytes);
+            try check_len(len, 1, 8);
+            const int_val = std.mem.readInt(i64, buffer[1..9], endian);
             lua.pushinteger(L, int_val);
         },
         SerializationType.float => {
-            if (len < 9) return SerializationError.InvalidFormat;
-            // Read as little-endian f64
-            var float_val: f64 = undefined;
-            const bytes = buffer[1..9];
-            @memcpy(std.mem.asBytes(&float_val), bytes);
+            try check_len(len, 1, 8);
+            const float_val: f64 = @bitCast(std.mem.readInt(u64, buffer[1..9], endian));
             lua.pushnumber(L, float_val);
         },
         SerializationType.string => {
-            if (len < 5) return SerializationError.InvalidFormat;
-            const str_len: u32 = @as(u32, buffer[1]) |
-                (@as(u32, buffer[2]) << 8) |
-                (@as(u32, buffer[3]) << 16) |
-                (@as(u32, buffer[4]) << 24);
-
-            if (len < 5 + str_len) return SerializationError.InvalidFormat;
+            try check_len(len, 1, 4);
+            const str_len = std.mem.readInt(u32, buffer[1..5], endian);
+            try check_len(len, 5, str_len);
 
             _ = lua.pushlstring(L, buffer + 5, str_len);
         },
         SerializationType.function_bytecode, SerializationType.function_ref => {
+            // Lua bytecode is only loadable on the platform that dumped it
+            if (endian != .little) return SerializationError.TypeMismatch;
             // Delegate to function serializer for deserialization
             // Pass the full buffer including type byte
             try function_serializer.deserialize_function(L, value_type, buffer, len);
         },
         SerializationType.table_ref => {
-            if (len < 5) return SerializationError.InvalidFormat;
-            const table_id: u32 = @as(u32, buffer[1]) |
-                (@as(u32, buffer[2]) << 8) |
-                (@as(u32, buffer[3]) << 16) |
-                (@as(u32, buffer[4]) << 24);
+            try check_len(len, 1, 4);
+            const table_id = std.mem.readInt(u32, buffer[1..5], endian);
+            if (table_id == 0) return SerializationError.InvalidFormat;
+
+            // A metatable flag promises the id that follows it
+            const has_metatable = len >= 6 and buffer[5] & TABLE_FLAG_METATABLE != 0;
+            if (has_metatable) try check_len(len, 6, 4);
 
             // Attach the external table
             ext_table.attach_table(L, table_id);
+
+            if (has_metatable) {
+                const metatable_id = std.mem.readInt(u32, buffer[6..10], endian);
+                ext_table.set_user_metatable(L, lua.gettop(L), metatable_id);
+            }
+        },
+        SerializationType.host_handle => {
+            try check_len(len, 1, 8);
+            const handle_value = std.mem.readInt(u64, buffer[1..9], endian);
+            handle.push_handle(L, handle_value);
         },
     }
 }
@@ -0,0 +1,169 @@
+const std = @import("std");
+const lua = @import("lua.zig");
+const serializer = @import("serializer.zig");
+
+const c = lua.c;
+
+// Snapshot blob layout (integers little-endian):
+//   u8  version
+//   u32 entry count, then per entry: u16 name length, name, u32 value length, value
+//   u32 skipped count, then per skipped global: u16 name length, name
+// Globals still holding the value they had right after init (stdlib, _home,
+// ...) are left out. Globals that fail to serialize are listed as skipped;
+// restore ignores that list.
+pub const SNAPSHOT_VERSION: u8 = 1;
+const HEADER_SIZE: usize = 5;
+const BASELINE_KEY: [*:0]const u8 = "cu_snapshot_baseline";
+const MAX_NAME_LEN: usize = std.math.maxInt(u16);
+
+const Blob = struct {
+    buffer: [*]u8,
+    capacity: usize,
+    len: usize = 0,
+
+    fn reserve(self: *Blob, n: usize) ?[*]u8 {
+        if (self.capacity - self.len < n) return null;
+        const ptr = self.buffer + self.len;
+        self.len += n;
+        return ptr;
+    }
+
+    fn put_u32(self: *Blob, value: u32) bool {
+        const dest = self.reserve(4) orelse return false;
+        std.mem.writeInt(u32, dest[0..4], value, .little);
+        return true;
+    }
+
+    fn put_name(self: *Blob, name: []const u8) bool {
+        const dest = self.reserve(2 + name.len) orelse return false;
+        std.mem.writeInt(u16, dest[0..2], @intCast(name.len), .little);
+        @memcpy(dest[2 .. 2 + name.len], name);
+        return true;
+    }
+};
+
+// Remember the globals as they are after init so snapshot can leave them out
+pub fn capture_baseline(L: *lua.lua_State) void {
+    lua.newtable(L);
+    const baseline = lua.gettop(L);
+    _ = c.lua_rawgeti(L, c.LUA_REGISTRYINDEX, c.LUA_RIDX_GLOBALS);
+    const globals = lua.gettop(L);
+
+    lua.pushnil(L);
+    while (c.lua_next(L, globals) != 0) {
+        lua.pushvalue(L, -2);
+        lua.pushvalue(L, -2);
+        c.lua_rawset(L, baseline);
+        lua.pop(L, 1);
+    }
+
+    lua.pop(L, 1);
+    lua.setfield(L, c.LUA_REGISTRYINDEX, BASELINE_KEY);
+}
+
+// With a global's key at -2 and value at -1, check whether it still holds
+// its post-init value
+fn is_baseline(L: *lua.lua_State, baseline: c_int) bool {
+    if (!lua.istable(L, baseline)) return false;
+    lua.pushvalue(L, -2);
+    _ = c.lua_rawget(L, baseline);
+    const same = c.lua_rawequal(L, -1, -2) != 0;
+    lua.pop(L, 1);
+    return same;
+}
+
+// Write a snapshot of the globals into buffer. Returns its length, or null
+// if it doesn't fit.
+pub fn snapshot(L: *lua.lua_State, buffer: [*]u8, capacity: usize) ?usize {
+    const top = lua.gettop(L);
+    defer lua.settop(L, top);
+
+    var blob = Blob{ .buffer = buffer, .capacity = capacity };
+    const header = blob.reserve(HEADER_SIZE) orelse return null;
+    header[0] = SNAPSHOT_VERSION;
+
+    _ = lua.getfield(L, c.LUA_REGISTRYINDEX, BASELINE_KEY);
+    const baseline = lua.gettop(L);
+    lua.newtable(L);
+    const skipped = lua.gettop(L);
+    _ = c.lua_rawgeti(L, c.LUA_REGISTRYINDEX, c.LUA_RIDX_GLOBALS);
+    const globals = lua.gettop(L);
+
+    var entry_count: u32 = 0;
+    var skipped_count: c.lua_Integer = 0;
+
+    lua.pushnil(L);
+    while (c.lua_next(L, globals) != 0) {
+        defer lua.pop(L, 1);
+        if (c.lua_type(L, -2) != c.LUA_TSTRING or is_baseline(L, baseline)) continue;
+
+        var name_len: usize = 0;
+        const name = c.lua_tolstring(L, -2, &name_len);
+        if (name_len > MAX_NAME_LEN) continue;
+
+        const mark = blob.len;
+        if (!blob.put_name(name[0..name_len])) return null;
+        const value_len_ptr = blob.reserve(4) orelse return null;
+
+        if (serializer.serialize_value(L, -1, blob.buffer + blob.len, blob.capacity - blob.len)) |value_len| {
+            std.mem.writeInt(u32, value_len_ptr[0..4], @intCast(value_len), .little);
+            blob.len += value_len;
+            entry_count += 1;
+        } else |err| {
+            if (err == serializer.SerializationError.BufferTooSmall) return null;
+            blob.len = mark;
+            skipped_count += 1;
+            lua.pushvalue(L, -2);
+            c.lua_rawseti(L, skipped, skipped_count);
+        }
+    }
+
+    std.mem.writeInt(u32, header[1..HEADER_SIZE], entry_count, .little);
+
+    if (!blob.put_u32(@intCast(skipped_count))) return null;
+    var i: c.lua_Integer = 1;
+    while (i <= skipped_count) : (i += 1) {
+        _ = c.lua_rawgeti(L, skipped, i);
+        var name_len: usize = 0;
+        const name = c.lua_tolstring(L, -1, &name_len);
+        if (!blob.put_name(name[0..name_len])) return null;
+        lua.pop(L, 1);
+    }
+
+    return blob.len;
+}
+
+// Set the globals recorded in a snapshot blob. Returns how many were
+// restored, or null if the blob is malformed.
+pub fn restore(L: *lua.lua_State, buffer: [*]const u8, len: usize) ?u32 {
+    if (len < HEADER_SIZE or buffer[0] != SNAPSHOT_VERSION) return null;
+
+    const top = lua.gettop(L);
+    defer lua.settop(L, top);
+
+    const entry_count = std.mem.readInt(u32, buffer[1..HEADER_SIZE], .little);
+    _ = c.lua_rawgeti(L, c.LUA_REGISTRYINDEX, c.LUA_RIDX_GLOBALS);
+    const globals = lua.gettop(L);
+
+    var offset: usize = HEADER_SIZE;
+    var restored: u32 = 0;
+    while (restored < entry_count) : (restored += 1) {
+        if (len - offset < 2) return null;
+        const name_len: usize = std.mem.readInt(u16, buffer[offset..][0..2], .little);
+        offset += 2;
+
+        if (len - offset < name_len + 4) return null;
+        const name = buffer + offset;
+        offset += name_len;
+        const value_len: usize = std.mem.readInt(u32, buffer[offset..][0..4], .little);
+        offset += 4;
+
+        if (len - offset < value_len) return null;
+        _ = lua.pushlstring(L, name, name_len);
+        serializer.deserialize_value(L, buffer + offset, value_len) catch return null;
+        c.lua_rawset(L, globals);
+        offset += value_len;
+    }
+
+    return restored;
+}
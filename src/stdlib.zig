@@ -0,0 +1,49 @@
+const lua = @import("lua.zig");
+
+const c = lua.c;
+
+// Bits of the init_with_libs flags, one per optional standard library.
+// base and package always load: print, the cu setup and require("bigint")
+// depend on them.
+pub const COROUTINE: u32 = 0x01;
+pub const TABLE: u32 = 0x02;
+pub const IO: u32 = 0x04;
+pub const OS: u32 = 0x08;
+pub const STRING: u32 = 0x10;
+pub const MATH: u32 = 0x20;
+pub const UTF8: u32 = 0x40;
+pub const DEBUG: u32 = 0x80;
+
+pub const ALL: u32 = 0xFF;
+
+const Library = struct {
+    bit: u32,
+    name: [*:0]const u8,
+    open: c.lua_CFunction,
+};
+
+const optional_libraries = [_]Library{
+    .{ .bit = COROUTINE, .name = "coroutine", .open = c.luaopen_coroutine },
+    .{ .bit = TABLE, .name = "table", .open = c.luaopen_table },
+    .{ .bit = IO, .name = "io", .open = c.luaopen_io },
+    .{ .bit = OS, .name = "os", .open = c.luaopen_os },
+    .{ .bit = STRING, .name = "string", .open = c.luaopen_string },
+    .{ .bit = MATH, .name = "math", .open = c.luaopen_math },
+    .{ .bit = UTF8, .name = "utf8", .open = c.luaopen_utf8 },
+    .{ .bit = DEBUG, .name = "debug", .open = c.luaopen_debug },
+};
+
+// Open base, package and the libraries whose bits are set in flags, like
+// luaL_openlibs does for all of them
+pub fn open(L: *lua.lua_State, flags: u32) void {
+    c.luaL_requiref(L, "_G", c.luaopen_base, 1);
+    lua.pop(L, 1);
+    c.luaL_requiref(L, "package", c.luaopen_package, 1);
+    lua.pop(L, 1);
+
+    for (optional_libraries) |library| {
+        if (flags & library.bit == 0) continue;
+        c.luaL_requiref(L, library.name, library.open, 1);
+        lua.pop(L, 1);
+    }
+}
@@ -0,0 +1,197 @@
+const std = @import("std");
+const lua = @import("lua.zig");
+
+const c = lua.c;
+
+// cu.utf8: codepoint-indexed string functions. Positions count codepoints,
+// not bytes, and negative positions count back from the end as in
+// string.sub. The optional mode argument picks how invalid UTF-8 is
+// handled: "strict" (the default) raises an error, "replace" reads each
+// offending byte as one U+FFFD.
+
+const REPLACEMENT_CHAR: u21 = 0xFFFD;
+const REPLACEMENT_BYTES = "\xEF\xBF\xBD";
+
+const Mode = enum { strict, replace };
+
+const Codepoint = struct {
+    value: u21,
+    len: usize, // bytes consumed
+    valid: bool,
+};
+
+const invalid_byte = Codepoint{ .value = REPLACEMENT_CHAR, .len = 1, .valid = false };
+
+// Decode the codepoint starting at s[pos]; overlong forms, surrogates and
+// truncated sequences are invalid
+fn decode_at(s: []const u8, pos: usize) Codepoint {
+    const seq_len = std.unicode.utf8ByteSequenceLength(s[pos]) catch return invalid_byte;
+    if (seq_len > s.len - pos) return invalid_byte;
+    const value = std.unicode.utf8Decode(s[pos..][0..seq_len]) catch return invalid_byte;
+    return .{ .value = value, .len = seq_len, .valid = true };
+}
+
+const Scanner = struct {
+    s: []const u8,
+    mode: Mode,
+    pos: usize = 0,
+
+    // The next codepoint, or null at the end. In strict mode an invalid
+    // sequence is an error, with pos left at its first byte.
+    fn next(self: *Scanner) error{InvalidUtf8}!?Codepoint {
+        if (self.pos >= self.s.len) return null;
+        const cp = decode_at(self.s, self.pos);
+        if (!cp.valid and self.mode == .strict) return error.InvalidUtf8;
+        self.pos += cp.len;
+        return cp;
+    }
+};
+
+fn raise(L: *lua.lua_State, comptime name: []const u8, message: [*:0]const u8) c_int {
+    _ = lua.pushstring(L, "cu.utf8." ++ name ++ ": ");
+    _ = lua.pushstring(L, message);
+    c.lua_concat(L, 2);
+    return c.lua_error(L);
+}
+
+fn raise_invalid(L: *lua.lua_State, comptime name: []const u8, pos: usize) c_int {
+    var buf: [64]u8 = undefined;
+    const message: [:0]const u8 = std.fmt.bufPrintZ(&buf, "invalid UTF-8 at byte {d}", .{pos + 1}) catch "invalid UTF-8";
+    return raise(L, name, message.ptr);
+}
+
+fn check_string(L: *lua.lua_State, idx: c_int) ?[]const u8 {
+    if (!lua.isstring(L, idx)) return null;
+    var len: usize = 0;
+    const ptr = lua.tolstring(L, idx, &len);
+    return ptr[0..len];
+}
+
+fn opt_integer(L: *lua.lua_State, idx: c_int, default: i64) ?i64 {
+    if (c.lua_type(L, idx) <= c.LUA_TNIL) return default;
+    var is_integer: c_int = 0;
+    const value = c.lua_tointegerx(L, idx, &is_integer);
+    return if (is_integer != 0) value else null;
+}
+
+fn opt_mode(L: *lua.lua_State, idx: c_int) ?Mode {
+    if (c.lua_type(L, idx) <= c.LUA_TNIL) return .strict;
+    const name = check_string(L, idx) orelse return null;
+    return std.meta.stringToEnum(Mode, name);
+}
+
+// Count codepoints, or report where the first invalid sequence starts
+fn count_codepoints(s: []const u8, mode: Mode, bad_pos: *usize) ?i64 {
+    var scanner = Scanner{ .s = s, .mode = mode };
+    var count: i64 = 0;
+    while (scanner.next() catch {
+        bad_pos.* = scanner.pos;
+        return null;
+    }) |_| count += 1;
+    return count;
+}
+
+// First position of a range, clamped to 1 like string.sub
+fn start_position(i: i64, n: i64) i64 {
+    if (i > 0) return i;
+    if (i == 0 or i < -n) return 1;
+    return n + i + 1;
+}
+
+// Last position of a range, clamped to n like string.sub
+fn end_position(j: i64, n: i64) i64 {
+    if (j > n) return n;
+    if (j >= 0) return j;
+    if (j < -n) return 0;
+    return n + j + 1;
+}
+
+// cu.utf8.len(s [, mode]): number of codepoints in s
+fn utf8_len(L: *lua.lua_State) c_int {
+    const s = check_string(L, 1) orelse return raise(L, "len", "expected a string");
+    const mode = opt_mode(L, 2) orelse return raise(L, "len", "mode must be \"strict\" or \"replace\"");
+
+    var bad_pos: usize = 0;
+    const count = count_codepoints(s, mode, &bad_pos) orelse return raise_invalid(L, "len", bad_pos);
+    lua.pushinteger(L, count);
+    return 1;
+}
+
+// cu.utf8.sub(s, i [, j [, mode]]): codepoints i through j (default -1)
+fn utf8_sub(L: *lua.lua_State) c_int {
+    const s = check_string(L, 1) orelse return raise(L, "sub", "expected a string");
+    const i = opt_integer(L, 2, 1) orelse return raise(L, "sub", "positions must be integers");
+    const j = opt_integer(L, 3, -1) orelse return raise(L, "sub", "positions must be integers");
+    const mode = opt_mode(L, 4) orelse return raise(L, "sub", "mode must be \"strict\" or \"replace\"");
+
+    var bad_pos: usize = 0;
+    const n = count_codepoints(s, mode, &bad_pos) orelse return raise_invalid(L, "sub", bad_pos);
+    const first = start_position(i, n);
+    const last = end_position(j, n);
+    if (first > last) {
+        _ = lua.pushstring(L, "");
+        return 1;
+    }
+
+    var buffer: c.luaL_Buffer = undefined;
+    c.luaL_buffinit(L, &buffer);
+
+    // Already validated above, so next() can't fail here
+    var scanner = Scanner{ .s = s, .mode = .replace };
+    var position: i64 = 0;
+    while (scanner.next() catch unreachable) |cp| {
+        position += 1;
+        if (position < first) continue;
+        if (position > last) break;
+        if (cp.valid) {
+            c.luaL_addlstring(&buffer, s.ptr + scanner.pos - cp.len, cp.len);
+        } else {
+            c.luaL_addlstring(&buffer, REPLACEMENT_BYTES, REPLACEMENT_BYTES.len);
+        }
+    }
+
+    c.luaL_pushresult(&buffer);
+    return 1;
+}
+
+// cu.utf8.codepoint(s [, i [, j [, mode]]]): the codepoints at positions i
+// through j (both default to 1) as integers
+fn utf8_codepoint(L: *lua.lua_State) c_int {
+    const s = check_string(L, 1) orelse return raise(L, "codepoint", "expected a string");
+    const i = opt_integer(L, 2, 1) orelse return raise(L, "codepoint", "positions must be integers");
+    const j = opt_integer(L, 3, i) orelse return raise(L, "codepoint", "positions must be integers");
+    const mode = opt_mode(L, 4) orelse return raise(L, "codepoint", "mode must be \"strict\" or \"replace\"");
+
+    var bad_pos: usize = 0;
+    const n = count_codepoints(s, mode, &bad_pos) orelse return raise_invalid(L, "codepoint", bad_pos);
+    const first = start_position(i, n);
+    const last = end_position(j, n);
+    if (first > last) return 0;
+
+    if (c.lua_checkstack(L, @intCast(@min(last - first + 1, std.math.maxInt(c_int)))) == 0) {
+        return raise(L, "codepoint", "too many results");
+    }
+
+    var scanner = Scanner{ .s = s, .mode = .replace };
+    var position: i64 = 0;
+    var pushed: c_int = 0;
+    while (scanner.next() catch unreachable) |cp| {
+        position += 1;
+        if (position < first) continue;
+        if (position > last) break;
+        lua.pushinteger(L, cp.value);
+        pushed += 1;
+    }
+    return pushed;
+}
+
+// Push the cu.utf8 table
+pub fn push_library(L: *lua.lua_State) void {
+    lua.newtable(L);
+    lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&utf8_len)));
+    lua.setfield(L, -2, "len");
+    lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&utf8_sub)));
+    lua.setfield(L, -2, "sub");
+    lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&utf8_codepoint)));
+    lua.setfield(L, -2, "codepoint");
+}
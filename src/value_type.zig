@@ -0,0 +1,35 @@
+const lua = @import("lua.zig");
+
+const c = lua.c;
+
+// cu.type(value): type(value), made more specific. External table proxies
+// (including namespace views and readonly proxies) are "ext_table", and
+// numbers are "integer" or "float" as math.type reports them.
+fn type_impl(L: *lua.lua_State) c_int {
+    c.luaL_checkany(L, 1);
+
+    switch (c.lua_type(L, 1)) {
+        c.LUA_TNUMBER => {
+            _ = lua.pushstring(L, if (c.lua_isinteger(L, 1) != 0) "integer" else "float");
+            return 1;
+        },
+        c.LUA_TTABLE => {
+            // Read raw, so a proxy's __index never reaches the host
+            _ = lua.pushstring(L, "__ext_table_id");
+            const is_external = c.lua_rawget(L, 1) == c.LUA_TNUMBER;
+            lua.pop(L, 1);
+            _ = lua.pushstring(L, if (is_external) "ext_table" else "table");
+            return 1;
+        },
+        else => {
+            _ = lua.pushstring(L, lua.type_name(L, 1));
+            return 1;
+        },
+    }
+}
+
+// Set cu.type on the table at the top of the stack
+pub fn register(L: *lua.lua_State) void {
+    lua.pushcfunction(L, @as(c.lua_CFunction, @ptrCast(&type_impl)));
+    lua.setfield(L, -2, "type");
+}
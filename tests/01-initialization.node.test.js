@@ -1,6 +1,6 @@
 const { describe, it, beforeEach } = require('node:test');
 const assert = require('node:assert');
-const { loadWasm, init, compute, getBufferPtr, readResult, reset } = require('./node-test-utils');
+const { loadWasm, init, compute, getBufferPtr, getInstance, hostHooks, readRawBuffer, readResult, reset } = require('./node-test-utils');
 
 describe('Cu Initialization', () => {
   beforeEach(async () => {
@@ -21,6 +21,18 @@ describe('Cu Initialization', () => {
     assert.strictEqual(result, 0, 'init() should return 0 on success');
   });
 
+  it('get_features lists the compiled-in features before init', async () => {
+    const instance = await loadWasm();
+    const len = instance.exports.get_features();
+    assert.ok(len > 0);
+
+    const features = Buffer.from(readRawBuffer(getBufferPtr(), len)).toString('utf8').split(',');
+    for (const name of ['bigint', 'compression', 'pack', 'resumable', 'snapshot', 'streaming']) {
+      assert.ok(features.includes(name), `Reports ${name}`);
+    }
+    assert.ok(!features.includes('minimal'), 'The default build is not minimal');
+  });
+
   it('Has required exports', async () => {
     const instance = await loadWasm();
     
@@ -38,4 +50,84 @@ describe('Cu Initialization', () => {
     const ptr = getBufferPtr();
     assert.ok(ptr > 0, 'Buffer pointer should be greater than 0');
   });
+
+  it('Lists registered globals with their types', async () => {
+    await loadWasm();
+    init();
+    compute('greeting = "hi"');
+
+    const { exports } = getInstance();
+    const len = exports.list_globals();
+    assert.ok(len > 0);
+
+    const text = Buffer.from(readRawBuffer(getBufferPtr(), len)).toString('utf8');
+    const globals = new Map(text.trimEnd().split('\n').map(line => line.split('\t')));
+    assert.strictEqual(globals.get('ext'), 'table');
+    assert.strictEqual(globals.get('Memory'), 'table');
+    assert.strictEqual(globals.get('_home'), 'table');
+    assert.strictEqual(globals.get('print'), 'function');
+    assert.strictEqual(globals.get('greeting'), 'string');
+  });
+
+  it('Exposes runtime versions to scripts through cu', async () => {
+    await loadWasm();
+    init();
+
+    const bytes = compute('return table.concat({ cu.version, cu.lua_version, math.type(cu.format_version), cu.format_version }, "|")');
+    const [version, luaVersion, formatType, formatVersion] = readResult(getBufferPtr(), bytes).result.split('|');
+    assert.strictEqual(version, require('../package.json').version);
+    assert.strictEqual(luaVersion, 'Lua 5.4');
+    assert.strictEqual(formatType, 'integer');
+    assert.strictEqual(Number(formatVersion), 1, 'Matches serializer FORMAT_VERSION');
+  });
+
+  it('ext.table() ids never collide with the runtime tables', async () => {
+    await loadWasm();
+    init();
+    const { exports } = getInstance();
+    const reserved = [exports.get_memory_table_id(), exports.get_io_table_id(), exports.get_modules_table_id()];
+
+    let bytes = compute('return ext.table().__ext_table_id');
+    const fresh = readResult(getBufferPtr(), bytes).result;
+    assert.ok(!reserved.includes(fresh), `ext.table() got reserved id ${fresh}`);
+
+    // Binding a persisted table by id moves the counter past it
+    assert.strictEqual(exports.attach_memory_table(fresh + 10), 0);
+    bytes = compute('return ext.table().__ext_table_id');
+    assert.ok(readResult(getBufferPtr(), bytes).result > fresh + 10);
+  });
+
+  it('init_with_libs opens only the requested standard libraries', async () => {
+    await loadWasm();
+    const MATH = 0x20;
+    assert.strictEqual(getInstance().exports.init_with_libs(MATH), 0);
+
+    const bytes = compute('return tostring(string) .. "," .. tostring(table) .. "," .. math.max(2, 7)');
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, 'nil,nil,7');
+  });
+
+  it('init_with_libs rejects unknown bits', async () => {
+    await loadWasm();
+    assert.strictEqual(getInstance().exports.init_with_libs(0x100), -1);
+  });
+
+  it('check_imports passes with a working host', async () => {
+    await loadWasm();
+    init();
+    assert.strictEqual(getInstance().exports.check_imports(), 0);
+  });
+
+  it('check_imports names the import of a host that drops writes', async () => {
+    await loadWasm();
+    init();
+    hostHooks.dropWrites = true;
+
+    const { exports } = getInstance();
+    const result = exports.check_imports();
+    assert.ok(result < 0);
+    assert.strictEqual(exports.get_last_error_code(), -8);
+
+    const message = Buffer.from(readRawBuffer(getBufferPtr(), -result - 1)).toString('utf8');
+    assert.match(message, /js_ext_table_get/);
+  });
 });
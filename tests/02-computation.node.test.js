@@ -1,6 +1,6 @@
 const { describe, it, beforeEach } = require('node:test');
 const assert = require('node:assert');
-const { loadWasm, init, compute, getBufferPtr, readResult, reset } = require('./node-test-utils');
+const { loadWasm, init, compute, callFails, computeFails, getBufferPtr, getInstance, hostHooks, readResult, readRawBuffer, reset, serializeObject, setMockTime, writeBuffer } = require('./node-test-utils');
 
 describe('Cu Computation', () => {
   beforeEach(async () => {
@@ -114,4 +114,370 @@ describe('Cu Computation', () => {
     const result = readResult(getBufferPtr(), bytes);
     assert.strictEqual(result.result, 'The answer is 42');
   });
+
+  it('Truncates overflowing output on a UTF-8 boundary', () => {
+    // 64511 ASCII bytes put the two-byte "é" across the 64512-byte capture limit
+    const bytes = compute('print(string.rep("a", 64511) .. "é")');
+    const raw = Buffer.from(readRawBuffer(getBufferPtr(), bytes));
+    const outputLen = raw.readUInt32LE(0);
+    const outputBytes = raw.subarray(4, 4 + outputLen);
+
+    assert.strictEqual(outputBytes.toString('utf8').includes('\uFFFD'), false);
+    assert.strictEqual(Buffer.from(outputBytes.toString('utf8'), 'utf8').equals(outputBytes), true);
+    assert.ok(outputBytes.toString('utf8').endsWith('a...'));
+  });
+
+  it('Returns tables as structured data', () => {
+    const bytes = compute(`
+      return { name = "cu", tags = { "lua", "wasm" }, stats = { runs = 3, ok = true } }
+    `);
+    const result = readResult(getBufferPtr(), bytes);
+    assert.deepStrictEqual(result.result, {
+      name: 'cu',
+      tags: ['lua', 'wasm'],
+      stats: { runs: 3, ok: true },
+    });
+  });
+
+  it('Packs and unpacks binary records with string.pack', () => {
+    const bytes = compute(`
+      local record = string.pack("<I4>I8fds", 7, 1 << 40, 1.5, math.pi, "cu")
+      local id, offset, ratio, pi, name, nextpos = string.unpack("<I4>I8fds", record)
+      return { size = #record, id = id, offset = offset, ratio = ratio, pi = pi, name = name, nextpos = nextpos }
+    `);
+    const { result } = readResult(getBufferPtr(), bytes);
+    assert.deepStrictEqual(result, {
+      size: 4 + 8 + 4 + 8 + 8 + 2,
+      id: 7,
+      offset: 2 ** 40,
+      ratio: 1.5,
+      pi: Math.PI,
+      name: 'cu',
+      nextpos: 4 + 8 + 4 + 8 + 8 + 2 + 1,
+    });
+  });
+
+  it('cu.now reads the host clock even with os.time replaced', () => {
+    setMockTime(123456);
+    const bytes = compute(`
+      os.time = function() return 0 end
+      return cu.now()
+    `);
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, 123456);
+  });
+
+  it('compute rejects code ranges outside the IO buffer', () => {
+    const { exports } = getInstance();
+    const bufPtr = getBufferPtr();
+    const bufSize = exports.get_buffer_size();
+
+    assert.strictEqual(exports.compute(bufPtr + bufSize - 2, 4), -1, 'Range runs past the buffer');
+    assert.strictEqual(exports.compute(bufPtr - 16, 4), -1, 'Range starts before the buffer');
+    assert.strictEqual(exports.compute(0x7FFFFFF0, 8), -1, 'Range is nowhere near the buffer');
+
+    // Code further into the buffer still runs
+    writeBuffer('xxxxreturn 6 * 7');
+    const bytes = exports.compute(bufPtr + 4, 12);
+    assert.strictEqual(readResult(bufPtr, bytes).result, 42);
+  });
+
+  it('Refuses an eval started from inside a host import', () => {
+    const { exports } = getInstance();
+    compute('_home.value = "outer"');
+
+    let nested = null;
+    hostHooks.onGet = () => {
+      hostHooks.onGet = null;
+      // The buffer still holds the outer call's key; the guard must not read it
+      nested = exports.compute(0, 16);
+    };
+
+    const bytes = compute('return _home.value .. "!"');
+    assert.strictEqual(nested, -6, 'Reentrant compute reports REENTRANT');
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, 'outer!');
+    assert.strictEqual(exports.get_last_error_code(), 0);
+  });
+
+  it('Runs compute again after a trapped error', () => {
+    const { exports } = getInstance();
+    compute('before = 1');
+
+    assert.throws(() => compute('before = 2; error("boom")'), WebAssembly.RuntimeError);
+    assert.strictEqual(compute('return 1'), -6, 'Guard is still held until the host recovers');
+
+    assert.strictEqual(exports.recover_from_trap(), 0);
+    for (let i = 0; i < 50; i++) {
+      assert.ok(computeFails(`error("trap ${i}")`));
+    }
+
+    const bytes = compute('return before + 40');
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, 42);
+  });
+
+  it('set_deadline_ms aborts an eval stuck on slow host calls', () => {
+    const { exports } = getInstance();
+    compute('_home.value = 1');
+    exports.set_deadline_ms(50n);
+
+    // Each read takes 10ms of host clock time
+    let clock = 1000;
+    setMockTime(clock);
+    hostHooks.onGet = () => {
+      clock += 10;
+      setMockTime(clock);
+    };
+
+    assert.ok(computeFails('while true do local _ = _home.value end'));
+    assert.strictEqual(exports.get_last_error_code(), -7, 'Deadline reports TIMEOUT');
+    assert.ok(clock < 2000, 'The loop stopped soon after the deadline');
+  });
+
+  it('set_deadline_ms applies to streaming and resumable evals', async () => {
+    for (const run of [
+      (len) => getInstance().exports.eval_streaming(len),
+      (len) => getInstance().exports.eval_resumable(len),
+    ]) {
+      reset();
+      await loadWasm();
+      init();
+      const { exports } = getInstance();
+      compute('_home.value = 1');
+      exports.set_deadline_ms(50n);
+
+      let clock = 1000;
+      setMockTime(clock);
+      hostHooks.onGet = () => {
+        clock += 10;
+        setMockTime(clock);
+      };
+
+      const len = writeBuffer('while true do local _ = _home.value end');
+      assert.ok(callFails(() => run(len)));
+      assert.strictEqual(exports.get_last_error_code(), -7, 'Deadline reports TIMEOUT');
+      assert.ok(clock < 2000, 'The loop stopped soon after the deadline');
+    }
+  });
+
+  it('A resumable eval measures the deadline from its own start', () => {
+    const { exports } = getInstance();
+    exports.set_deadline_ms(50n);
+    setMockTime(1000);
+    compute('return 1');
+
+    // Long after the previous eval's deadline would have passed
+    setMockTime(5000);
+    const status = exports.eval_resumable(writeBuffer('local n = 0 for i = 1, 1000 do n = n + i end return n'));
+    assert.ok(status > 0);
+    assert.strictEqual(readResult(getBufferPtr(), status).result, 500500);
+  });
+
+  it('set_deadline_ms leaves evals that finish in time alone', () => {
+    const { exports } = getInstance();
+    exports.set_deadline_ms(50n);
+    setMockTime(1000);
+
+    const bytes = compute('local n = 0 for i = 1, 100000 do n = n + i end return n');
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, 5000050000);
+  });
+
+  it('Evaluates code written through prepare_input', () => {
+    const { exports } = getInstance();
+    const code = Buffer.from('return 6 * 7');
+    const ptr = exports.prepare_input(code.length);
+    assert.strictEqual(ptr, getBufferPtr());
+
+    new Uint8Array(exports.memory.buffer, ptr, code.length).set(code);
+    const bytes = exports.eval();
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, 42);
+    assert.strictEqual(exports.eval(), 0, 'A prepared input runs once');
+  });
+
+  it('prepare_input refuses input larger than the buffer', () => {
+    const { exports } = getInstance();
+    assert.strictEqual(exports.prepare_input(exports.get_buffer_size() + 1), -1);
+  });
+
+  it('eval_staged runs a script assembled from chunks larger than the buffer', () => {
+    const { exports } = getInstance();
+    const script = 'local n = 0\n' + 'n = n + 1\n'.repeat(8000) + 'return n';
+    assert.ok(script.length > exports.get_buffer_size(), 'The script needs staging');
+
+    // Split mid-statement: the chunks are joined byte for byte
+    const third = Math.ceil(script.length / 3);
+    let staged = 0;
+    for (let start = 0; start < script.length; start += third) {
+      staged = exports.append_input(writeBuffer(script.slice(start, start + third)));
+    }
+    assert.strictEqual(staged, script.length);
+
+    const bytes = exports.eval_staged();
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, 8000);
+    assert.strictEqual(exports.eval_staged(), 0, 'The stage is cleared after running');
+  });
+
+  it('eval_staged names the chunk after the start of the source, split or not', () => {
+    const { exports } = getInstance();
+    exports.append_input(writeBuffer('local x = '));
+    exports.append_input(writeBuffer('1\nerror("staged boom")'));
+
+    assert.ok(callFails(() => exports.eval_staged()));
+    const message = Buffer.from(readRawBuffer(getBufferPtr(), exports.read_error_message())).toString('utf8');
+    assert.match(message, /\[string "local x = 1\.\.\."\]:2: staged boom/);
+  });
+
+  it('eval_batch runs each snippet and reports per-item results', () => {
+    const { exports } = getInstance();
+    const u32 = n => {
+      const bytes = Buffer.alloc(4);
+      bytes.writeUInt32LE(n);
+      return bytes;
+    };
+    const batch = sources => {
+      const parts = sources.map(s => Buffer.from(s));
+      return writeBuffer(Buffer.concat([u32(parts.length), ...parts.flatMap(source => [u32(source.length), source])]));
+    };
+
+    const total = exports.eval_batch(batch(['shared = 20 return "set"', 'print("between")', 'return shared + 1']));
+    assert.ok(total > 0);
+
+    const response = Buffer.from(readRawBuffer(getBufferPtr(), total));
+    assert.strictEqual(response.readUInt32LE(0), 3);
+    const items = [];
+    let offset = 4;
+    for (let i = 0; i < 3; i++) {
+      const code = response.readInt32LE(offset);
+      const length = response.readUInt32LE(offset + 4);
+      items.push({ code, start: getBufferPtr() + offset + 8, length });
+      offset += 8 + length;
+    }
+    assert.strictEqual(offset, total);
+
+    assert.deepStrictEqual(items.map(item => item.code), [0, 0, 0]);
+    assert.strictEqual(readResult(items[0].start, items[0].length).result, 'set');
+    assert.match(readResult(items[1].start, items[1].length).output, /between/);
+    assert.strictEqual(readResult(items[2].start, items[2].length).result, 21, 'Snippets share globals');
+  });
+
+  it('eval_batch stops at the first failing snippet', () => {
+    const { exports } = getInstance();
+    const u32 = n => {
+      const bytes = Buffer.alloc(4);
+      bytes.writeUInt32LE(n);
+      return bytes;
+    };
+    const sources = ['first = true', 'error("second failed")', 'third = true'].map(s => Buffer.from(s));
+    const len = writeBuffer(Buffer.concat([u32(sources.length), ...sources.flatMap(source => [u32(source.length), source])]));
+
+    assert.ok(callFails(() => exports.eval_batch(len)), 'The error traps the whole batch');
+    assert.strictEqual(exports.get_last_error_code(), -2);
+    const message = Buffer.from(readRawBuffer(getBufferPtr(), exports.read_error_message())).toString('utf8');
+    assert.match(message, /second failed/);
+
+    const bytes = compute('return tostring(first) .. "," .. tostring(third)');
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, 'true,nil', 'Earlier snippets ran, later ones did not');
+  });
+
+  it('eval_named labels errors with the chunk name', () => {
+    const { exports } = getInstance();
+    const name = Buffer.from('request_handler');
+    const code = Buffer.from('local x = 1\nerror("boom")');
+    writeBuffer(Buffer.concat([name, code]));
+
+    assert.ok(callFails(() => exports.eval_named(name.length, code.length)), 'error() traps');
+    assert.strictEqual(exports.get_last_error_code(), -2);
+    const message = Buffer.from(readRawBuffer(getBufferPtr(), exports.read_error_message())).toString('utf8');
+    assert.match(message, /\[string "request_handler"\]:2: boom/);
+  });
+
+  it('eval_with_args passes arguments to the chunk as varargs', () => {
+    const { exports } = getInstance();
+    const encodeArgs = (...values) => {
+      const parts = [Buffer.alloc(4)];
+      parts[0].writeUInt32LE(values.length);
+      for (const value of values) {
+        const bytes = Buffer.from(serializeObject(value));
+        const len = Buffer.alloc(4);
+        len.writeUInt32LE(bytes.length);
+        parts.push(len, bytes);
+      }
+      return Buffer.concat(parts);
+    };
+    const run = (source, args) => {
+      const code = Buffer.from(source);
+      writeBuffer(Buffer.concat([code, args]));
+      return exports.eval_with_args(code.length, args.length);
+    };
+
+    let len = run("return select('#', ...)", encodeArgs('a', 2, true));
+    assert.strictEqual(readResult(getBufferPtr(), len).result, 3);
+
+    len = run('local name, n = ... return name .. "=" .. n', encodeArgs('x"; os.exit()', 5));
+    assert.strictEqual(readResult(getBufferPtr(), len).result, 'x"; os.exit()=5', 'Arguments are data, not code');
+
+    len = run("return select('#', ...)", Buffer.alloc(0));
+    assert.strictEqual(readResult(getBufferPtr(), len).result, 0);
+
+    const truncated = encodeArgs('a', 'b').subarray(0, 10);
+    assert.strictEqual(run('return 1', truncated), -1, 'Malformed arguments are rejected');
+  });
+
+  it('eval_json returns the result and output as JSON', () => {
+    const { exports } = getInstance();
+    const len = exports.eval_json(writeBuffer('print("hi") return { x = 1, list = { "a", "b" } }'));
+    assert.ok(len > 0);
+
+    const doc = JSON.parse(Buffer.from(readRawBuffer(getBufferPtr(), len)).toString('utf8'));
+    assert.deepStrictEqual(doc.result, { x: 1, list: ['a', 'b'] });
+    assert.strictEqual(doc.output, 'hi\n');
+  });
+
+  it('eval_json reports unencodable values as JSON', () => {
+    const { exports } = getInstance();
+    const readError = result => JSON.parse(Buffer.from(readRawBuffer(getBufferPtr(), -result - 1)).toString('utf8'));
+
+    let result = exports.eval_json(writeBuffer('return { f = print }'));
+    assert.ok(result < 0);
+    let doc = readError(result);
+    assert.match(doc.error, /cannot encode a function value as JSON/);
+    assert.strictEqual(doc.code, -3);
+    assert.strictEqual(exports.get_last_error_code(), -3);
+
+    result = exports.eval_json(writeBuffer('local t = {} t.self = t return t'));
+    doc = readError(result);
+    assert.match(doc.error, /nested too deeply or cyclic/);
+
+    result = exports.eval_json(writeBuffer('return { [{}] = 1 }'));
+    doc = readError(result);
+    assert.match(doc.error, /keys must be strings or numbers/);
+
+    // The instance is still usable: no trap was involved
+    const len = exports.eval_json(writeBuffer('return 1'));
+    assert.strictEqual(JSON.parse(Buffer.from(readRawBuffer(getBufferPtr(), len)).toString('utf8')).result, 1);
+  });
+
+  it('eval_json traps on a script error like compute', () => {
+    const { exports } = getInstance();
+    const len = writeBuffer('error("nope")');
+    assert.ok(callFails(() => exports.eval_json(len)));
+    assert.strictEqual(exports.get_last_error_code(), -2);
+    assert.match(Buffer.from(readRawBuffer(getBufferPtr(), exports.read_error_message())).toString('utf8'), /nope/);
+  });
+
+  it('validate_syntax accepts valid code without running it', () => {
+    const { exports } = getInstance();
+    assert.strictEqual(exports.validate_syntax(writeBuffer('checked = true')), 0);
+
+    const bytes = compute('return checked == nil');
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, true);
+  });
+
+  it('validate_syntax reports the line of a syntax error', () => {
+    const { exports } = getInstance();
+    const len = writeBuffer('local x = 1\nlocal y = = 2');
+    assert.ok(callFails(() => exports.validate_syntax(len)), 'A syntax error traps');
+    assert.strictEqual(exports.get_last_error_code(), -1, 'Reports a compilation error');
+
+    const message = Buffer.from(readRawBuffer(getBufferPtr(), exports.read_error_message())).toString('utf8');
+    assert.match(message, /\]:2: unexpected symbol/);
+  });
 });
@@ -1,6 +1,6 @@
 const { describe, it, beforeEach } = require('node:test');
 const assert = require('node:assert');
-const { loadWasm, init, compute, getBufferPtr, readResult, setInput, getOutput, setMetadata, clearIo, reset } = require('./node-test-utils');
+const { loadWasm, init, compute, getBufferPtr, readResult, setInput, getOutput, setMetadata, clearIo, reset, externalTables, getInstance } = require('./node-test-utils');
 
 describe('_io Table API', () => {
   beforeEach(async () => {
@@ -161,4 +161,26 @@ describe('_io Table API', () => {
     const result = readResult(getBufferPtr(), bytes);
     assert.strictEqual(result.result, 0);
   });
+
+  it('Decodes big-endian values tagged with a format header', () => {
+    const encodeBigEndian = (type, body) => Uint8Array.from([0xf0, 0x01, 0x01, type, ...body]);
+    const i64 = Buffer.alloc(8);
+    i64.writeBigInt64BE(-1234567890123n);
+    const f64 = Buffer.alloc(8);
+    f64.writeDoubleBE(2.5);
+    const str = Buffer.from('hello');
+    const len = Buffer.alloc(4);
+    len.writeUInt32BE(str.length);
+
+    setInput('placeholder');
+    const table = externalTables.get(getInstance().exports.get_io_table_id());
+    table.set('input', encodeBigEndian(0x02, i64));
+    table.set('meta', encodeBigEndian(0x03, f64));
+    table.set('text', encodeBigEndian(0x04, [...len, ...str]));
+
+    const bytes = compute(`
+      return string.format("%d|%s|%s", _io.input, _io.meta, _io.text)
+    `);
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, '-1234567890123|2.5|hello');
+  });
 });
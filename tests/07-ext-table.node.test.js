@@ -0,0 +1,957 @@
+const { describe, it, beforeEach } = require('node:test');
+const assert = require('node:assert');
+const {
+  loadWasm, init, compute, callFails, computeFails, getBufferPtr, readResult, readRawBuffer, reset, externalTables, getInstance,
+  hostCallCounts, keyIsInteger, setInput, writeBuffer, serializeObject, deserializeObject, registerHandle, resolveHandle, releaseHandle, HostHandle, setMockTime, hostHooks,
+} = require('./node-test-utils');
+
+describe('ext Table Operations', () => {
+  beforeEach(async () => {
+    reset();
+    await loadWasm();
+    init();
+  });
+
+  it('ext.free releases host storage for a table', () => {
+    let bytes = compute(`
+      scratch = ext.table()
+      scratch.a = 1
+      scratch.b = 2
+      return ext.count(scratch)
+    `);
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, 2);
+
+    bytes = compute('return scratch.__ext_table_id');
+    const tableId = readResult(getBufferPtr(), bytes).result;
+    assert.ok(externalTables.has(tableId));
+
+    bytes = compute(`
+      local freed = ext.free(scratch)
+      return freed and #scratch == 0 and scratch.a == nil
+    `);
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, true);
+    assert.ok(!externalTables.has(tableId));
+  });
+
+  it('free_table export drops a table from the host side', () => {
+    const bytes = compute(`
+      local t = ext.table()
+      t.name = "temp"
+      return t.__ext_table_id
+    `);
+    const tableId = readResult(getBufferPtr(), bytes).result;
+    assert.strictEqual(externalTables.get(tableId).size, 1);
+
+    assert.strictEqual(getInstance().exports.free_table(tableId), 0);
+    assert.ok(!externalTables.has(tableId));
+  });
+
+  it('dump_table and load_table round-trip a table through the buffer', () => {
+    const { exports } = getInstance();
+    let bytes = compute(`
+      saved = ext.table()
+      saved.name = "cu"
+      saved[1] = 42
+      saved.flag = true
+      return saved.__ext_table_id
+    `);
+    const tableId = readResult(getBufferPtr(), bytes).result;
+
+    const len = exports.dump_table(tableId);
+    assert.ok(len > 0);
+    const dump = new Uint8Array(exports.memory.buffer, getBufferPtr(), len).slice();
+
+    compute('saved.name = "changed" saved.extra = 1 saved.flag = nil');
+    writeBuffer(dump);
+    assert.strictEqual(exports.load_table(tableId, dump.length), 3);
+
+    bytes = compute('return table.concat({ saved.name, saved[1], tostring(saved.flag), tostring(saved.extra) }, "|")');
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, 'cu|42|true|nil');
+  });
+
+  it('load_table rejects a malformed dump', () => {
+    const { exports } = getInstance();
+    // Key length claims 9 bytes but only 2 follow
+    writeBuffer(Buffer.from([9, 0, 0, 0, 0x61, 0x62]));
+    assert.strictEqual(exports.load_table(exports.get_memory_table_id(), 6), -1);
+  });
+
+  it('Reads values larger than the value buffer instead of returning nil', () => {
+    compute('_home.seed = 1');
+    const homeId = getInstance().exports.get_memory_table_id();
+    externalTables.get(homeId).set('big', serializeObject('x'.repeat(40000)));
+
+    const bytes = compute(`
+      local copy = ext.to_table(_home)
+      return table.concat({ #_home.big, #ext.get_or(_home, "big", ""), #copy.big }, "|")
+    `);
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, '40000|40000|40000');
+  });
+
+  it('ext.value_len reports a stored size without transferring the value', () => {
+    compute('_home.seed = 1');
+    const homeId = getInstance().exports.get_memory_table_id();
+    const blob = serializeObject('x'.repeat(100 * 1024));
+    externalTables.get(homeId).set('blob', blob);
+
+    const getsBefore = hostCallCounts.get;
+    const bytes = compute('return ext.value_len(_home, "blob") .. "|" .. ext.value_len(_home, "missing")');
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, `${blob.length}|-1`);
+    assert.strictEqual(hostCallCounts.get, getsBefore, 'No value was read');
+  });
+
+  it('pairs visits every key once when the key list spans many pages', () => {
+    hostHooks.keysPerPage = 3;
+    const bytes = compute(`
+      local t = ext.table()
+      for i = 1, 10 do t["k" .. i] = i end
+      t[100] = "int"
+
+      local visits, sum = 0, 0
+      for k, v in pairs(t) do
+        visits = visits + 1
+        if type(v) == "number" then sum = sum + v end
+        t[k] = nil
+      end
+      return visits .. "|" .. sum .. "|" .. tostring(next(ext.to_table(t)))
+    `);
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, '11|55|nil');
+  });
+
+  it('Host handles round-trip through an ext table unchanged', () => {
+    const file = { fd: 7 };
+    const handle = registerHandle(file);
+    setInput(handle);
+
+    const bytes = compute(`
+      local h = _io.input
+      local t = ext.table()
+      t.h = h
+      assert(t.h == h, "handle should compare equal after a round trip")
+      return t.__ext_table_id
+    `);
+    const tableId = readResult(getBufferPtr(), bytes).result;
+
+    const stored = externalTables.get(tableId).get('h');
+    assert.deepStrictEqual(Array.from(stored), Array.from(serializeObject(handle)));
+
+    const readBack = deserializeObject(stored);
+    assert.ok(readBack instanceof HostHandle);
+    assert.strictEqual(readBack.id, handle.id);
+    assert.strictEqual(resolveHandle(readBack), file);
+
+    releaseHandle(handle);
+    assert.strictEqual(resolveHandle(readBack), null, 'Dangling handles resolve to null');
+  });
+
+  it('ext.rename moves a value to a new key', () => {
+    const bytes = compute(`
+      local t = ext.table()
+      t.draft = "hello"
+      local moved = ext.rename(t, "draft", "published")
+      local missing = ext.rename(t, "draft", "other")
+      return tostring(moved) .. "|" .. tostring(missing) .. "|" .. tostring(t.draft) .. "|" .. t.published
+    `);
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, 'true|false|nil|hello');
+  });
+
+  it('ext.cas only swaps when the expected value matches', () => {
+    const bytes = compute(`
+      local t = ext.table()
+      t.version = 1
+      local stale = ext.cas(t, "version", 0, 5)
+      local fresh = ext.cas(t, "version", 1, 2)
+      local created = ext.cas(t, "lock", nil, "owner")
+      return table.concat({ tostring(stale), tostring(fresh), t.version, tostring(created), t.lock }, "|")
+    `);
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, 'false|true|2|true|owner');
+  });
+
+  it('ext.set_ttl entries read as nil once they expire', () => {
+    setMockTime(1000);
+    let bytes = compute(`
+      cache = ext.table()
+      assert(ext.set_ttl(cache, "session", "abc", 50))
+      return cache.session
+    `);
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, 'abc');
+
+    setMockTime(1049);
+    bytes = compute('return cache.session');
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, 'abc');
+
+    setMockTime(1050);
+    bytes = compute('return cache.session');
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, null);
+  });
+
+  it('ext.dofile runs a chunk stored as a source string', () => {
+    const bytes = compute(`
+      scripts = ext.table()
+      scripts.seven = "return 7"
+      return ext.dofile(scripts, "seven")
+    `);
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, 7);
+
+    const failed = computeFails(`
+      scripts.bad = 42
+      return ext.dofile(scripts, "bad")
+    `);
+    assert.ok(failed, 'Non-string values should raise an error');
+  });
+
+  it('ext.to_table returns a detached copy including nested tables', () => {
+    const bytes = compute(`
+      store = ext.table()
+      store.name = "widget"
+      store.tags = { "a", "b" }
+      store.dims = { w = 2, h = 3 }
+
+      local copy = ext.to_table(store)
+      assert(getmetatable(copy) == nil and getmetatable(copy.dims) == nil)
+      copy.name = "changed"
+      copy.dims.w = 99
+      copy.tags[3] = "c"
+
+      return table.concat({ #copy.tags, store.name, store.dims.w, #store.tags, copy.dims.h }, "|")
+    `);
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, '3|widget|2|2|3');
+  });
+
+  it('ext.merge bulk-loads a nested table into storage', () => {
+    const bytes = compute(`
+      local t = ext.table()
+      t.version = 1
+      local written = ext.merge(t, {
+        version = 2,
+        owner = { name = "ada", roles = { "admin", "dev" } },
+      })
+      return table.concat({ written, t.version, t.owner.name, t.owner.roles[2], #t.owner.roles }, "|")
+    `);
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, '2|2|ada|dev|2');
+  });
+
+  it('set_ext_call_limit aborts an eval once the limit is reached', () => {
+    const { exports } = getInstance();
+    assert.strictEqual(exports.set_ext_call_limit(-1), -1, 'A negative limit is rejected');
+    assert.strictEqual(exports.set_ext_call_limit(100), 0);
+
+    assert.ok(computeFails(`
+      flood = ext.table()
+      for i = 1, 10000 do flood[i] = i end
+    `));
+    assert.strictEqual(exports.get_last_error_code(), -4, 'The limit reports EXT_CALL_LIMIT');
+    assert.ok([...externalTables.values()].some((table) => table.size === 100));
+  });
+
+  it('ext.contains checks for a key without fetching its value', () => {
+    compute('_home.bigblob = string.rep("x", 4096)');
+    hostCallCounts.get = 0;
+    hostCallCounts.has = 0;
+
+    let bytes = compute('return ext.contains(_home, "bigblob")');
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, true);
+
+    bytes = compute(`
+      _home.bigblob = nil
+      return ext.contains(_home, "bigblob")
+    `);
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, false);
+
+    assert.strictEqual(hostCallCounts.get, 0, 'ext.contains must not fetch values');
+    assert.strictEqual(hostCallCounts.has, 2);
+  });
+
+  it('ext.get_or returns stored values and the default for missing keys', () => {
+    compute('_home.counter = 5 _home.enabled = false');
+    hostCallCounts.get = 0;
+
+    const bytes = compute(`
+      return table.concat({
+        ext.get_or(_home, "counter", 0),
+        ext.get_or(_home, "missing", 0),
+        tostring(ext.get_or(_home, "enabled", true)),
+        tostring(ext.get_or(_home, "absent")),
+      }, "|")
+    `);
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, '5|0|false|nil');
+    assert.strictEqual(hostCallCounts.get, 4, 'One host read per call');
+  });
+
+  it('ext.update stores what the function returns', () => {
+    compute(`
+      ext.update(_home, "counter", function(n) return (n or 0) + 1 end)
+      ext.update(_home, "counter", function(n) return n + 1 end)
+    `);
+
+    const bytes = compute(`
+      local kept = ext.update(_home, "counter", function() return ext.keep end)
+      ext.update(_home, "gone", function() return nil end)
+      return kept .. "|" .. _home.counter .. "|" .. tostring(ext.contains(_home, "gone"))
+    `);
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, '2|2|false');
+  });
+
+  it('ext.swap exchanges two values in one host call', () => {
+    const bytes = compute(`
+      local list = ext.table()
+      list[1] = "first"
+      list[2] = { n = 2 }
+      assert(ext.swap(list, 1, 2))
+      local swapped = list[1].n .. "|" .. list[2]
+
+      assert(ext.swap(list, 2, "missing"))
+      return swapped .. "|" .. tostring(list[2]) .. "|" .. list.missing
+    `);
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, '2|first|nil|first');
+  });
+
+  it('ext.clone copies a table so changes to the copy leave the original alone', () => {
+    const bytes = compute(`
+      original = ext.table()
+      original.name = "ada"
+      original[1] = 10
+      original.tags = { "admin" }
+
+      local copy = ext.clone(original)
+      local copied = copy.name .. "|" .. copy[1] .. "|" .. copy.tags[1]
+
+      copy.name = "grace"
+      copy[1] = nil
+      copy.extra = true
+      return copied .. "|" .. original.name .. "|" .. original[1] .. "|" .. tostring(original.extra)
+    `);
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, 'ada|10|admin|ada|10|nil');
+  });
+
+  it('ext.clone refuses a namespace view', () => {
+    assert.ok(computeFails('return ext.clone(ext.namespace(_home, "users"))'));
+  });
+
+  it('# measures the array part of dense arrays', () => {
+    const bytes = compute(`
+      list = ext.table()
+      for i = 1, 5 do list[i] = i * 10 end
+      list.label = "five"
+      local before = #list
+
+      table.insert(list, 60)
+      table.remove(list, 1)
+      return before .. "|" .. #list .. "|" .. list[1] .. "|" .. list[5] .. "|" .. ext.count(list)
+    `);
+    // table.remove leaves a tombstone at 6, which ext.count still counts
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, '5|5|20|60|7');
+  });
+
+  it('# shrinks when the last element is set to nil', () => {
+    const bytes = compute(`
+      local list = ext.table()
+      for i = 1, 4 do list[i] = i end
+      list[#list] = nil
+      local after_assign = #list
+      table.remove(list)
+      local view = ext.namespace(list, "items")
+      view[1] = "a"
+      view[2] = "b"
+      view[2] = nil
+      return after_assign .. "|" .. #list .. "|" .. #view
+    `);
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, '3|2|1');
+  });
+
+  it('# stops at the first gap in sparse arrays', () => {
+    const bytes = compute(`
+      local sparse = ext.table()
+      sparse[1] = "a"
+      sparse[2] = "b"
+      sparse[4] = "d"
+      local holes = ext.table()
+      holes[2] = "b"
+      return #sparse .. "|" .. ext.count(sparse) .. "|" .. #holes
+    `);
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, '2|3|0');
+  });
+
+  it('# is 0 for string-keyed maps while ext.count counts them', () => {
+    const bytes = compute(`
+      local map = ext.table()
+      map.alice = 1
+      map.bob = 2
+      return #map .. "|" .. ext.count(map)
+    `);
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, '0|2');
+  });
+
+  it('set_max_tables caps live tables until one is freed', () => {
+    const { exports } = getInstance();
+    assert.strictEqual(exports.set_max_tables(-1), -1, 'A negative limit is rejected');
+    assert.strictEqual(exports.set_max_tables(3), 0);
+
+    let bytes = compute('live = { ext.table(), ext.table(), ext.table() } return #live');
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, 3);
+
+    bytes = compute(`
+      assert(ext.free(live[2]))
+      live[2] = ext.table()
+      live[2].ok = true
+      return live[2].ok
+    `);
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, true);
+
+    // Lua errors trap in this build, so the refusal is checked last
+    assert.ok(computeFails('extra = ext.table()'), 'A fourth live table is refused');
+  });
+
+  it('Recycles freed ids only after the id space runs out', () => {
+    const { exports } = getInstance();
+    exports.sync_external_table_counter(0xFFFFFFFC);
+
+    const bytes = compute(`
+      local live = {}
+      for i = 1, 3 do
+        local t = ext.table()
+        t.v = i
+        live[i] = t
+      end
+
+      ext.free(live[3])
+      live[3] = nil
+
+      local seen = {}
+      for round = 1, 50 do
+        local tmp = ext.table()
+        assert(tmp.v == nil, "recycled table must start empty")
+        tmp.v = -round
+        seen[tmp.__ext_table_id] = true
+        ext.free(tmp)
+      end
+
+      local reused = 0
+      for _ in pairs(seen) do reused = reused + 1 end
+      return table.concat({ live[1].v, live[2].v, reused }, "|")
+    `);
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, '1|2|1');
+  });
+
+  it('set_next_table_id picks the id of the next ext.table()', () => {
+    const { exports } = getInstance();
+    assert.strictEqual(exports.set_next_table_id(500), 0);
+    assert.strictEqual(exports.get_next_table_id(), 500);
+
+    const bytes = compute(`
+      local a, b = ext.table(), ext.table()
+      return a.__ext_table_id .. "," .. b.__ext_table_id
+    `);
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, '500,501');
+    assert.strictEqual(exports.get_next_table_id(), 502);
+
+    // Reserved ids are refused and leave the counter alone
+    assert.strictEqual(exports.set_next_table_id(0), -1);
+    assert.strictEqual(exports.set_next_table_id(exports.get_memory_table_id()), -1);
+    assert.strictEqual(exports.set_next_table_id(0xFFFFFFFF), -1);
+    assert.strictEqual(exports.get_next_table_id(), 502);
+  });
+
+  it('Buffered writes reach the host when the eval returns', () => {
+    const { exports } = getInstance();
+    assert.strictEqual(exports.set_write_buffering(1), 0);
+    const homeId = exports.get_memory_table_id();
+
+    const bytes = compute(`
+      _home.a = 1
+      _home.b = "two"
+      local flushed = ext.flush()
+      _home.c = _home.a + 2
+      return flushed
+    `);
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, 2);
+
+    const home = externalTables.get(homeId);
+    assert.strictEqual(deserializeObject(home.get('a')), 1);
+    assert.strictEqual(deserializeObject(home.get('b')), 'two');
+    assert.strictEqual(deserializeObject(home.get('c')), 3, 'Unflushed write should be sent at eval end');
+  });
+
+  it('Buffered writes made before an error are sent on recovery', () => {
+    const { exports } = getInstance();
+    exports.set_write_buffering(1);
+    const home = externalTables.get(exports.get_memory_table_id());
+
+    assert.throws(() => compute('_home.before = 1; error("boom")'), WebAssembly.RuntimeError);
+    assert.ok(!home.has('before'), 'The trap cut off the end-of-eval flush');
+
+    exports.recover_from_trap();
+    assert.strictEqual(deserializeObject(home.get('before')), 1);
+  });
+
+  it('eval_transactional discards writes from a chunk that errors', () => {
+    const { exports } = getInstance();
+    const homeId = exports.get_memory_table_id();
+
+    const run = (code) => {
+      try {
+        return exports.eval_transactional(writeBuffer(code));
+      } catch (e) {
+        if (e instanceof WebAssembly.RuntimeError) return -1;
+        throw e;
+      }
+    };
+
+    assert.ok(run('_home.committed = true') >= 0);
+    assert.ok(run('_home.partial = 1; error("boom")') < 0);
+
+    const home = externalTables.get(homeId);
+    assert.strictEqual(deserializeObject(home.get('committed')), true);
+    assert.ok(!home.has('partial'), 'Writes from a failed transaction must never reach the host');
+  });
+
+  it('eval_transactional rolls back a trapped chunk before the next eval', () => {
+    const { exports } = getInstance();
+    const home = externalTables.get(exports.get_memory_table_id());
+    assert.ok(callFails(() => exports.eval_transactional(writeBuffer('_home.partial = 1; error("boom")'))));
+
+    compute('_home.after = 2');
+    assert.ok(!home.has('partial'), 'The trapped transaction\'s writes were dropped');
+    assert.strictEqual(deserializeObject(home.get('after')), 2, 'Later writes are no longer held');
+
+    compute('ext.rename(_home, "after", "renamed")');
+    assert.strictEqual(deserializeObject(home.get('renamed')), 2, 'ext.rename works outside the transaction');
+  });
+
+  it('eval_transactional refuses a rename and leaves the host untouched', () => {
+    const { exports } = getInstance();
+    const home = externalTables.get(exports.get_memory_table_id());
+    compute('_home.a = "kept"');
+
+    const code = '_home.b = 1; _home.nested = { x = 1 }; ext.merge(_home, { m = 1 }); ext.rename(_home, "a", "c")';
+    assert.ok(callFails(() => exports.eval_transactional(writeBuffer(code))));
+
+    assert.strictEqual(deserializeObject(home.get('a')), 'kept', 'The rename never reached the host');
+    for (const key of ['b', 'c', 'nested', 'm']) {
+      assert.ok(!home.has(key), `${key} was rolled back`);
+    }
+    for (const [id, table] of externalTables) {
+      assert.ok(!table.has('x'), `The nested table's entries stayed out of table ${id}`);
+    }
+  });
+
+  it('ext.keys lists keys without fetching values', () => {
+    compute(`
+      inventory = ext.table()
+      inventory.apples = 3
+      inventory.pears = 5
+      inventory[7] = "lucky"
+    `);
+
+    hostCallCounts.get = 0;
+    const bytes = compute(`
+      local keys = ext.keys(inventory)
+      local names, ints = {}, 0
+      for i, k in ipairs(keys) do
+        names[i] = tostring(k)
+        if math.type(k) == "integer" then ints = ints + 1 end
+      end
+      table.sort(names)
+      return table.concat(names, ",") .. "|" .. ints
+    `);
+
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, '7,apples,pears|1');
+    assert.strictEqual(hostCallCounts.get, 0, 'ext.keys must not fetch values');
+  });
+
+  it('Integer and string values serialize to different tags', () => {
+    compute('_home.int = 1; _home.str = "1"');
+
+    const entries = externalTables.get(getInstance().exports.get_memory_table_id());
+    assert.strictEqual(entries.get('int')[0], 0x02);
+    assert.strictEqual(entries.get('str')[0], 0x04);
+    assert.notDeepStrictEqual(entries.get('int'), entries.get('str'));
+  });
+
+  it('Only canonical integer text comes back as an integer key', () => {
+    const bytes = compute(`
+      local t = ext.table()
+      t[1] = "one"
+      t["007"] = "bond"
+      t["-0"] = "neg"
+      local kinds = {}
+      for _, k in ipairs(ext.keys(t)) do
+        kinds[#kinds + 1] = tostring(k) .. ":" .. type(k)
+      end
+      table.sort(kinds)
+      return table.concat(kinds, ",") .. "|" .. tostring(t["1"])
+    `);
+
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, '-0:string,007:string,1:number|one');
+  });
+
+  it('keyIsInteger matches the canonical integer form', () => {
+    for (const key of ['0', '1', '-7', '9223372036854775807']) {
+      assert.ok(keyIsInteger(key), key);
+    }
+    for (const key of ['007', '+5', '-0', '1.5', '', 'abc', '9223372036854775808']) {
+      assert.ok(!keyIsInteger(key), key);
+    }
+  });
+
+  it('Deeply nested stored metatables stop loading at the depth limit', () => {
+    // Table ref to `id` whose stored metatable is `id` too
+    const refWithMetatable = (id) => {
+      const bytes = new Uint8Array(10);
+      const view = new DataView(bytes.buffer);
+      bytes[0] = 0x07;
+      view.setUint32(1, id, true);
+      bytes[5] = 0x01;
+      view.setUint32(6, id, true);
+      return bytes;
+    };
+
+    // Each table's `next` field points at the following one, 500 levels deep
+    const firstId = 1000;
+    for (let id = firstId; id < firstId + 500; id++) {
+      externalTables.set(id, new Map([['next', refWithMetatable(id + 1)]]));
+    }
+    externalTables.set(firstId + 500, new Map());
+    externalTables.get(getInstance().exports.get_memory_table_id()).set('deep', refWithMetatable(firstId));
+
+    const chainLength = () => readResult(getBufferPtr(), compute(`
+      local v, n = _home.deep, 0
+      while v do
+        n = n + 1
+        local mt = getmetatable(v)
+        v = mt and mt.next
+      end
+      return n
+    `)).result;
+
+    const defaultLength = chainLength();
+    assert.ok(defaultLength > 1 && defaultLength <= 64, `chain of ${defaultLength}`);
+
+    assert.strictEqual(getInstance().exports.set_max_deserialize_depth(4), 0);
+    assert.ok(chainLength() < defaultLength);
+    assert.strictEqual(getInstance().exports.set_max_deserialize_depth(0), -1);
+  });
+
+  it('Truncated stored values read as nil instead of trapping', () => {
+    const home = externalTables.get(getInstance().exports.get_memory_table_id());
+    const stringHeader = (len) => {
+      const bytes = new Uint8Array(5);
+      bytes[0] = 0x04;
+      new DataView(bytes.buffer).setUint32(1, len, true);
+      return bytes;
+    };
+
+    home.set('int', new Uint8Array([0x02]));
+    home.set('float', new Uint8Array([0x03, 0, 0, 0]));
+    home.set('str', new Uint8Array([...stringHeader(10), 0x61, 0x62, 0x63]));
+    home.set('huge', stringHeader(0xFFFFFFFF));
+    home.set('ref', new Uint8Array([0x07, 5, 0, 0, 0, 0x01, 9]));
+    home.set('fn', new Uint8Array([0x05, 200, 0, 0, 0, 0x1B]));
+    home.set('unknown', new Uint8Array([0x42, 1, 2, 3]));
+    home.set('ok', serializeObject('fine'));
+
+    const bytes = compute(`
+      local found = {}
+      for _, k in ipairs({ "int", "float", "str", "huge", "ref", "fn", "unknown" }) do
+        if _home[k] ~= nil then found[#found + 1] = k end
+      end
+      return table.concat(found, ",") .. "|" .. _home.ok
+    `);
+
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, '|fine');
+  });
+
+  it('ext.namespace partitions one table between views', () => {
+    const bytes = compute(`
+      users = ext.namespace(_home, "users")
+      orders = ext.namespace(_home, "orders")
+      users.alice = 1
+      users.bob = 2
+      orders.alice = "pending"
+      orders[1] = "first"
+
+      local seen = {}
+      for k, v in pairs(users) do seen[#seen + 1] = k .. "=" .. v end
+      table.sort(seen)
+      return table.concat(seen, ",") .. "|" .. orders.alice .. "|" .. tostring(users[1]) .. "|" .. ext.count(orders) .. "/" .. #orders
+    `);
+
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, 'alice=1,bob=2|pending|nil|2/1');
+
+    const home = externalTables.get(getInstance().exports.get_memory_table_id());
+    assert.deepStrictEqual(
+      [...home.keys()].filter((k) => k.includes(':')).sort(),
+      ['orders:1', 'orders:alice', 'users:alice', 'users:bob'],
+    );
+  });
+
+  it('ext.keys on a namespace strips the prefix and restores integer keys', () => {
+    const bytes = compute(`
+      local queue = ext.namespace(_home, "queue")
+      queue[1] = "a"
+      queue[2] = "b"
+      _home.unrelated = true
+      local keys = ext.keys(queue)
+      table.sort(keys)
+      return math.type(keys[1]) .. ":" .. keys[1] .. "," .. keys[2] .. "|" .. #keys
+    `);
+
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, 'integer:1,2|2');
+  });
+
+  it('set_deterministic_iteration makes pairs visit keys in byte order', () => {
+    getInstance().exports.set_deterministic_iteration(1);
+    const bytes = compute(`
+      local t = ext.table()
+      for _, k in ipairs({"pear", "apple", "Zebra", "fig", "banana"}) do t[k] = true end
+      t[10] = true
+      t[2] = true
+
+      local seen = {}
+      for k in pairs(t) do seen[#seen + 1] = tostring(k) end
+      return table.concat(seen, ",") .. "|" .. table.concat(ext.keys(t), ",")
+    `);
+
+    const order = '10,2,Zebra,apple,banana,fig,pear';
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, `${order}|${order}`);
+  });
+
+  it('Sorted pairs visits every key of a table whose key list outgrows the IO buffer', () => {
+    const { exports } = getInstance();
+    exports.set_deterministic_iteration(1);
+
+    // Filled from the host, so the keys take Lua memory only while listed
+    compute('big = ext.table()');
+    const table = externalTables.get([...externalTables.keys()].pop());
+    const pad = 'k'.repeat(600);
+    for (let i = 1; i <= 120; i++) table.set(pad + String(i).padStart(4, '0'), serializeObject(i));
+    assert.ok(120 * 605 > exports.get_buffer_size(), 'The key list is larger than the buffer');
+
+    const bytes = compute(`
+      local count, sum, previous, ordered = 0, 0, "", true
+      for k, v in pairs(big) do
+        count = count + 1
+        sum = sum + v
+        if k < previous then ordered = false end
+        previous = k
+      end
+      return count .. "," .. sum .. "," .. tostring(ordered)
+    `);
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, '120,7260,true');
+  });
+
+  it('set_nil_semantics chooses between storing nil and deleting the key', () => {
+    const { exports } = getInstance();
+    const script = `
+      local t = ext.table()
+      t.gone = "x"
+      t.gone = nil
+      return tostring(t.gone) .. "," .. tostring(ext.contains(t, "gone")) .. "," .. ext.count(t)
+    `;
+    const hostKeys = () => {
+      const id = [...externalTables.keys()].pop();
+      return [...externalTables.get(id).keys()];
+    };
+
+    assert.strictEqual(readResult(getBufferPtr(), compute(script)).result, 'nil,true,1', 'Stores a tombstone by default');
+    assert.deepStrictEqual(hostKeys(), ['gone']);
+
+    assert.strictEqual(exports.set_nil_semantics(0), 0);
+    assert.strictEqual(readResult(getBufferPtr(), compute(script)).result, 'nil,false,0', 'Mode 0 deletes');
+    assert.deepStrictEqual(hostKeys(), []);
+
+    assert.strictEqual(exports.set_nil_semantics(1), 0);
+    assert.strictEqual(readResult(getBufferPtr(), compute(script)).result, 'nil,true,1');
+    assert.strictEqual(exports.set_nil_semantics(2), -1);
+  });
+
+  it('pairs skips tombstoned keys that ext.keys still lists', () => {
+    const bytes = compute(`
+      local t = ext.table()
+      t.kept = 1
+      t.gone = 2
+      t.gone = nil
+      local seen = {}
+      for k, v in pairs(t) do seen[#seen + 1] = k .. "=" .. tostring(v) end
+      local listed = ext.keys(t)
+      table.sort(listed)
+      return table.concat(seen, ",") .. "|" .. table.concat(listed, ",")
+    `);
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, 'kept=1|gone,kept');
+  });
+
+  it('set_compression stores large values compressed and reads them back', () => {
+    getInstance().exports.set_compression(1, 1024);
+    const bytes = compute(`
+      local t = ext.table()
+      local blob = string.rep("compress me ", 8500)
+      t.blob = blob
+      t.small = "tiny"
+      return tostring(t.blob == blob) .. "," .. #t.blob .. "," .. t.small
+    `);
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, 'true,102000,tiny');
+
+    const stored = [...externalTables.values()].find(table => table.has('blob'));
+    const blob = stored.get('blob');
+    assert.strictEqual(blob[0], 0xF1, 'Flagged as compressed');
+    assert.ok(blob.length < 100000 / 10, `stored ${blob.length} bytes`);
+    assert.strictEqual(stored.get('small')[0], 0x04, 'Values under the threshold are stored as is');
+  });
+
+  it('A compressed value claiming an oversized length reads as nil', () => {
+    const { exports } = getInstance();
+    const home = externalTables.get(exports.get_memory_table_id());
+    // 0xF1 tag, then an original length of 2 GB and a few bytes of LZ4 data
+    home.set('forged', new Uint8Array([0xF1, 0x00, 0x00, 0x00, 0x80, 0x10, 0x41]));
+
+    const bytes = compute('return tostring(_home.forged) .. "," .. collectgarbage("count") // 1024');
+    const [value, megabytes] = readResult(getBufferPtr(), bytes).result.split(',');
+    assert.strictEqual(value, 'nil');
+    assert.ok(Number(megabytes) < 64, 'Nothing was allocated for the claimed length');
+  });
+
+  it('ext.readonly views read through and refuse writes', async () => {
+    const bytes = compute(`
+      _home.score = 1
+      local view = ext.readonly(_home)
+
+      -- The original proxy stays writable and the view sees its writes
+      _home.score = _home.score + 10
+      return view.score
+    `);
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, 11);
+
+    const writes = [
+      'view.score = 2',
+      'ext.update(view, "score", function(n) return n + 1 end)',
+    ];
+    for (const write of writes) {
+      reset();
+      await loadWasm();
+      init();
+      compute('_home.score = 1');
+      assert.ok(computeFails(`local view = ext.readonly(_home); ${write}`), `${write} is refused`);
+      assert.strictEqual(readResult(getBufferPtr(), compute('return _home.score')).result, 1, `${write} left the table alone`);
+    }
+  });
+
+  it('Binary strings survive storage byte-for-byte', () => {
+    const bytes = compute(`
+      _home.bin = "a\\0b\\xff"
+      local s = _home.bin
+      return tostring(s == "a\\0b\\xff") .. ":" .. #s
+    `);
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, 'true:4');
+
+    const home = externalTables.get(getInstance().exports.get_memory_table_id());
+    assert.deepStrictEqual(Array.from(home.get('bin')), [0x04, 4, 0, 0, 0, 0x61, 0x00, 0x62, 0xFF]);
+  });
+
+  it('Keys that are not valid UTF-8 stay distinct', () => {
+    const bytes = compute(`
+      local t = ext.table()
+      t["\\xff"] = 1
+      t["\\xfe"] = 2
+      t["\\xef\\xbf\\xbd"] = 3
+      local found = 0
+      for _, k in ipairs(ext.keys(t)) do
+        if k == "\\xff" or k == "\\xfe" or k == "\\xef\\xbf\\xbd" then found = found + 1 end
+      end
+      return t["\\xff"] .. t["\\xfe"] .. t["\\xef\\xbf\\xbd"] .. "|" .. found
+    `);
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, '123|3');
+  });
+
+  it('Storing an unsupported value names its type and key path', async () => {
+    const cases = [
+      [
+        '_home.jobs = { pending = { worker = coroutine.create(function() end) } }',
+        'cannot store thread value at jobs.pending.worker',
+      ],
+      [
+        'local tree = { children = {} }; tree.children[1] = { parent = tree }; _home.tree = tree',
+        'cannot store table: circular reference at tree.children[1].parent',
+      ],
+      ['ext.merge(_home, { ok = 1, handler = coroutine.create(function() end) })', 'cannot store thread value at handler'],
+      ['ext.merge(_home, { config = { worker = coroutine.create(print) } })', 'cannot store thread value at config.worker'],
+      ['ext.cas(_home, "job", nil, coroutine.create(function() end))', 'cannot store thread value at job'],
+      ['ext.set_ttl(_home, "session", { worker = coroutine.create(function() end) }, 1000)', 'cannot store thread value at session.worker'],
+    ];
+    for (const [code, message] of cases) {
+      reset();
+      await loadWasm();
+      init();
+      const { exports } = getInstance();
+      assert.ok(computeFails(code));
+      const len = exports.read_error_message();
+      const text = Buffer.from(readRawBuffer(getBufferPtr(), len)).toString('utf8');
+      assert.ok(text.includes(message), `${code} reports ${text}`);
+    }
+  });
+
+  it('cu.pack blobs survive storage and cu.unpack restores them', () => {
+    const bytes = compute(`
+      local blob = cu.pack(42.5)
+      _home.blob = blob
+      local copy = ext.table()
+      copy.blob = _home.blob
+      local restored = cu.unpack(copy.blob)
+      return type(blob) .. ":" .. #blob .. ":" .. tostring(restored == 42.5)
+    `);
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, 'string:9:true');
+
+    const home = externalTables.get(getInstance().exports.get_memory_table_id());
+    assert.strictEqual(home.get('blob')[0], 0x04, 'The blob is stored as a string');
+  });
+
+  it('Writes through a nested table read from _home reach the host', () => {
+    compute('_home.config = { retries = 3 }');
+    compute('_home.config.timeout = 5');
+
+    // A later eval reads through a fresh proxy
+    const bytes = compute('return _home.config.timeout .. ":" .. _home.config.retries');
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, '5:3');
+
+    const home = externalTables.get(getInstance().exports.get_memory_table_id());
+    const ref = home.get('config');
+    assert.strictEqual(ref[0], 0x07, 'config is stored as a table reference');
+    const configId = Buffer.from(ref).readUInt32LE(1);
+    assert.ok(externalTables.get(configId).has('timeout'), 'The write landed in the nested table');
+  });
+
+  it('cu.type tells external tables and number subtypes apart', () => {
+    const bytes = compute(`
+      _home.nested = { x = 1 }
+      return table.concat({
+        cu.type(Memory), cu.type(_home.nested), cu.type({}),
+        cu.type(1), cu.type(1.5), cu.type("s"), cu.type(nil),
+      }, ",")
+    `);
+    assert.strictEqual(
+      readResult(getBufferPtr(), bytes).result,
+      'ext_table,ext_table,table,integer,float,string,nil'
+    );
+  });
+
+  it('ext.delete_prefix removes only the keys under the prefix', () => {
+    const bytes = compute(`
+      local t = ext.table()
+      t["a:1"] = 1
+      t["a:2"] = 2
+      t["b:1"] = 3
+      local deleted = ext.delete_prefix(t, "a:")
+      local keys = ext.keys(t)
+      return deleted .. ":" .. #keys .. ":" .. keys[1] .. ":" .. tostring(t["a:1"])
+    `);
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, '2:1:b:1:nil');
+  });
+
+  it('ext.delete_prefix returns false when the host fails', () => {
+    compute('gone = ext.table() gone.marker = 1');
+    const [id] = [...externalTables].find(([, table]) => table.has('marker'));
+    externalTables.delete(id);
+
+    const bytes = compute('return tostring(ext.delete_prefix(gone, ""))');
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, 'false');
+  });
+});
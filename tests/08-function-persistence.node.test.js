@@ -0,0 +1,123 @@
+const { describe, it, beforeEach } = require('node:test');
+const assert = require('node:assert');
+const { loadWasm, init, compute, computeFails, getBufferPtr, getInstance, externalTables, readRawBuffer, readResult, reset } = require('./node-test-utils');
+
+// Reload the WASM module while keeping the host-side external tables,
+// which is what a page refresh with restored persistence looks like.
+async function restart() {
+  await loadWasm();
+  init();
+}
+
+describe('Function Persistence', () => {
+  beforeEach(async () => {
+    reset();
+    await loadWasm();
+    init();
+  });
+
+  it('set_strip_bytecode keeps debug info only when asked', async () => {
+    const { exports } = getInstance();
+    const home = () => externalTables.get(exports.get_memory_table_id());
+    const source = `
+      local function describe_point(x, y)
+        local distance = math.sqrt(x * x + y * y)
+        return string.format("%d,%d at %.1f", x, y, distance)
+      end
+    `;
+
+    compute(`${source} _home.stripped = describe_point`);
+    exports.set_strip_bytecode(0);
+    compute(`${source} _home.debug = describe_point`);
+    exports.set_strip_bytecode(1);
+
+    const strippedSize = home().get('stripped').length;
+    const debugSize = home().get('debug').length;
+    assert.ok(debugSize > strippedSize, `debug ${debugSize} bytes vs stripped ${strippedSize}`);
+
+    await restart();
+    const bytes = compute('return _home.stripped(3, 4) .. "|" .. _home.debug(3, 4)');
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, '3,4 at 5.0|3,4 at 5.0');
+  });
+
+  it('Restores captured upvalues after reload', async () => {
+    compute(`
+      local x = 42
+      _home.getX = function() return x end
+    `);
+
+    await restart();
+
+    const bytes = compute('return _home.getX()');
+    const result = readResult(getBufferPtr(), bytes);
+    assert.strictEqual(result.result, 42);
+  });
+
+  it('Restores closures that reference globals and themselves', async () => {
+    compute(`
+      local base = 10
+      local function countdown(n)
+        if n == 0 then return base end
+        return countdown(n - 1) + 1
+      end
+      _home.countdown = countdown
+      _home.describe = function() return type(string.format) end
+    `);
+
+    await restart();
+
+    let bytes = compute('return _home.countdown(3)');
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, 13);
+
+    bytes = compute('return _home.describe()');
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, 'function');
+  });
+
+  it('Restores an object metatable so methods work after reload', async () => {
+    compute(`
+      local Point = {}
+      Point.__index = Point
+      function Point:sum() return self.x + self.y end
+      Point.__tostring = function(p) return "(" .. p.x .. "," .. p.y .. ")" end
+      _home.p = setmetatable({ x = 2, y = 3 }, Point)
+    `);
+
+    await restart();
+
+    const bytes = compute('return _home.p:sum() .. " " .. tostring(_home.p)');
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, '5 (2,3)');
+  });
+
+  it('Reuses a metatable that is already an external table', () => {
+    compute('Shape = ext.table() Shape.kind = "shape"');
+    const before = externalTables.size;
+
+    compute('_home.a = setmetatable({}, Shape) _home.b = setmetatable({}, Shape)');
+    assert.strictEqual(externalTables.size, before + 2, 'Only the two objects are new tables');
+
+    const bytes = compute('return getmetatable(_home.a).kind .. "," .. getmetatable(_home.b).kind');
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, 'shape,shape');
+  });
+
+  it('Rejects metatables with C functions or unsupported fields', async () => {
+    for (const code of [
+      '_home.a = setmetatable({}, { __tostring = print })',
+      '_home.b = setmetatable({}, { __gc = function() end })',
+    ]) {
+      await restart();
+      assert.ok(computeFails(code), code);
+    }
+  });
+
+  it('Rejects a metatable with a bogus __ext_table_id before creating any host table', () => {
+    const { exports } = getInstance();
+    for (const id of ['"7"', '0', '-1', '1 << 40', '1.5']) {
+      const before = externalTables.size;
+      assert.ok(computeFails(`_home.a = setmetatable({ x = 1 }, { __ext_table_id = ${id} })`), id);
+      assert.strictEqual(externalTables.size, before, `No table is left behind for ${id}`);
+
+      const message = Buffer.from(readRawBuffer(getBufferPtr(), exports.read_error_message())).toString('utf8');
+      assert.match(message, /cannot store table: metatable has an invalid __ext_table_id/);
+    }
+  });
+});
@@ -0,0 +1,75 @@
+const { describe, it, beforeEach } = require('node:test');
+const assert = require('node:assert');
+const {
+  loadWasm, init, compute, computeFails, getBufferPtr, getInstance, writeBuffer, readResult, readRawBuffer, serializeObject, reset,
+} = require('./node-test-utils');
+
+const YIELD_FLAG = 0x40000000;
+
+describe('Resumable Evaluation', () => {
+  beforeEach(async () => {
+    reset();
+    await loadWasm();
+    init();
+  });
+
+  it('Yields a value to the host and resumes with a reply', () => {
+    const { exports } = getInstance();
+
+    const len = writeBuffer(`
+      local reply = coroutine.yield(20)
+      return reply + 1
+    `);
+    let status = exports.eval_resumable(len);
+    assert.ok((status & YIELD_FLAG) !== 0, 'Should report a yield');
+
+    const yielded = readResult(getBufferPtr(), status & ~YIELD_FLAG);
+    assert.strictEqual(yielded.result, 20);
+
+    status = exports.resume(writeBuffer(serializeObject(yielded.result * 2)));
+    assert.ok(status > 0 && (status & YIELD_FLAG) === 0, 'Should finish');
+    assert.strictEqual(readResult(getBufferPtr(), status).result, 41);
+  });
+
+  it('cu.yield hands control to the host until it resumes', () => {
+    const { exports } = getInstance();
+
+    const len = writeBuffer(`
+      local done = 0
+      for i = 1, 2 do
+        done = done + (cu.yield(i) or 0)
+      end
+      return done
+    `);
+    let status = exports.eval_resumable(len);
+    assert.ok((status & YIELD_FLAG) !== 0, 'Should report a yield');
+    assert.strictEqual(readResult(getBufferPtr(), status & ~YIELD_FLAG).result, 1);
+
+    status = exports.resume(writeBuffer(serializeObject(10)));
+    assert.ok((status & YIELD_FLAG) !== 0, 'Should yield again');
+    assert.strictEqual(readResult(getBufferPtr(), status & ~YIELD_FLAG).result, 2);
+
+    status = exports.resume(0);
+    assert.ok(status > 0 && (status & YIELD_FLAG) === 0, 'Should finish');
+    assert.strictEqual(readResult(getBufferPtr(), status).result, 10);
+  });
+
+  it('cu.yield does nothing outside eval_resumable', async () => {
+    assert.strictEqual(readResult(getBufferPtr(), compute('return tostring(cu.yield(5))')).result, 'nil');
+
+    // From inside a coroutine it fails the call, in a fresh instance
+    reset();
+    await loadWasm();
+    init();
+    const { exports } = getInstance();
+    assert.ok(computeFails('coroutine.wrap(function() cu.yield(1) end)()'));
+    assert.strictEqual(exports.get_last_error_code(), -2);
+    const text = Buffer.from(readRawBuffer(getBufferPtr(), exports.read_error_message())).toString('utf8');
+    assert.ok(text.includes("cu.yield: can't yield to the host from inside a coroutine"), text);
+  });
+
+  it('Rejects resume without a suspended coroutine', () => {
+    const status = getInstance().exports.resume(0);
+    assert.ok(status < 0);
+  });
+});
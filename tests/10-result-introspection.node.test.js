@@ -0,0 +1,169 @@
+const { describe, it, beforeEach } = require('node:test');
+const assert = require('node:assert');
+const { loadWasm, init, compute, computeFails, getBufferPtr, getInstance, readRawBuffer, readResult, deserializeObject, reset, writeBuffer } = require('./node-test-utils');
+
+function lastErrorMessage() {
+  const len = getInstance().exports.read_error_message();
+  return Buffer.from(readRawBuffer(getBufferPtr(), len)).toString('utf8');
+}
+
+function describeLast() {
+  const len = getInstance().exports.describe_last_result();
+  assert.ok(len > 0, 'Descriptor should be written');
+  return JSON.parse(Buffer.from(readRawBuffer(getBufferPtr(), len)).toString('utf8'));
+}
+
+describe('Result Introspection', () => {
+  beforeEach(async () => {
+    reset();
+    await loadWasm();
+    init();
+  });
+
+  it('describe_last_result reports integers and floats as numbers', () => {
+    compute('return 42');
+    assert.deepStrictEqual(describeLast(), { type: 'number', subtype: 'integer', value: 42 });
+
+    compute('return 2.5');
+    assert.deepStrictEqual(describeLast(), { type: 'number', subtype: 'float', value: 2.5 });
+  });
+
+  it('describe_last_result reports strings with escaping', () => {
+    compute('return "say \\"hi\\"\\n"');
+    assert.deepStrictEqual(describeLast(), { type: 'string', value: 'say "hi"\n' });
+  });
+
+  it('describe_last_result reports booleans, nil, and tables', () => {
+    compute('return true');
+    assert.deepStrictEqual(describeLast(), { type: 'boolean', value: true });
+
+    compute('return nil');
+    assert.deepStrictEqual(describeLast(), { type: 'nil' });
+
+    compute('x = 1');
+    assert.deepStrictEqual(describeLast(), { type: 'nil' });
+
+    compute('return {}');
+    const table = describeLast();
+    assert.strictEqual(table.type, 'table');
+    assert.ok(table.id > 0);
+
+    compute('return { worker = coroutine.create(function() end) }');
+    assert.deepStrictEqual(describeLast(), { type: 'table', serialized: false });
+  });
+
+  it('describe_last_result reports errors', () => {
+    assert.ok(computeFails('local x = = 1'));
+    const descriptor = describeLast();
+    assert.strictEqual(descriptor.type, 'error');
+    assert.match(descriptor.message, /unexpected symbol/);
+  });
+
+  it('describe_last_result flags print output cut off by the output limit', () => {
+    const { exports } = getInstance();
+    assert.strictEqual(exports.set_output_limit(exports.get_buffer_size()), -1, 'Larger than the output buffer');
+    assert.strictEqual(exports.set_output_limit(1000), 0);
+
+    const bytes = compute(`
+      for i = 1, 10000 do print("line " .. i) end
+      return 1
+    `);
+    const { output, result } = readResult(getBufferPtr(), bytes);
+    assert.strictEqual(result, 1);
+    assert.ok(output.length <= 1000 + 3, 'Captured output should stay within the limit');
+    assert.ok(output.endsWith('...'));
+    assert.deepStrictEqual(describeLast(), { type: 'number', subtype: 'integer', value: 1, output_truncated: true });
+
+    compute('print("short") return 1');
+    assert.strictEqual(describeLast().output_truncated, undefined);
+  });
+
+  it('peek_result_type returns the tag of each result type', () => {
+    const { exports } = getInstance();
+    const cases = [
+      ['return nil', 0x00],
+      ['x = 1', 0x00],
+      ['return true', 0x01],
+      ['return 42', 0x02],
+      ['return 2.5', 0x03],
+      ['return "hi"', 0x04],
+      ['return ""', 0x04],
+      ['return {}', 0x07],
+      ['return coroutine.create(function() end)', 0xFF],
+    ];
+
+    for (const [code, tag] of cases) {
+      compute(code);
+      assert.strictEqual(exports.peek_result_type(), tag, code);
+    }
+  });
+
+  it('peek_result_type returns the error code after a failed eval', () => {
+    assert.ok(computeFails('error("boom")'));
+    const { exports } = getInstance();
+    assert.strictEqual(exports.peek_result_type(), -2);
+    assert.strictEqual(exports.peek_result_type(), exports.get_last_error_code());
+
+    compute('return 1');
+    assert.strictEqual(exports.peek_result_type(), 0x02, 'The next successful eval clears it');
+  });
+
+  it('read_error_value returns a table raised with error()', () => {
+    assert.ok(computeFails('error({code = 500, msg = "x"})'));
+    const { exports } = getInstance();
+    assert.strictEqual(lastErrorMessage(), '(error object is a table value)');
+
+    const len = exports.read_error_value();
+    const bytes = readRawBuffer(getBufferPtr(), len);
+    assert.strictEqual(bytes[0], 0x07, 'Error objects are table refs');
+    assert.ok(bytes[5] & 0x02, 'Error objects carry the error flag');
+    assert.deepStrictEqual(deserializeObject(bytes), { code: 500, msg: 'x' });
+  });
+
+  it('cu.check raises an error object the host can branch on', () => {
+    assert.ok(computeFails('cu.check(true, 500) cu.check(false, 404, "missing")'));
+    const { exports } = getInstance();
+    assert.strictEqual(lastErrorMessage(), 'missing');
+
+    const bytes = readRawBuffer(getBufferPtr(), exports.read_error_value());
+    assert.deepStrictEqual(deserializeObject(bytes), { code: 404, message: 'missing' });
+  });
+
+  it('dump_wire_format shows the serialized bytes of a value', () => {
+    const { exports } = getInstance();
+    const n = exports.dump_wire_format(writeBuffer('42'));
+    assert.deepStrictEqual(Array.from(readRawBuffer(getBufferPtr(), n)), [0x02, 0x2a, 0, 0, 0, 0, 0, 0, 0]);
+
+    const hex = Buffer.from(readRawBuffer(getBufferPtr() + n, 3 * n - 1)).toString('utf8');
+    assert.strictEqual(hex, '02 2a 00 00 00 00 00 00 00');
+  });
+
+  it('read_error_value returns nothing for string errors', () => {
+    assert.ok(computeFails('error({code = 1})'));
+    assert.ok(getInstance().exports.read_error_value() > 0);
+
+    assert.ok(computeFails('error("boom")'));
+    assert.strictEqual(getInstance().exports.read_error_value(), 0, 'A string error replaces the earlier object');
+    assert.match(lastErrorMessage(), /:1: boom$/);
+  });
+
+  it('get_captured_errors lists errors raised under pcall', () => {
+    const { exports } = getInstance();
+    assert.ok(computeFails('error("not captured")'), 'Capture is off by default');
+
+    exports.set_capture_errors(1);
+    assert.ok(computeFails('local ok = pcall(function() error("swallowed") end) return ok'));
+    assert.ok(computeFails('xpcall(function() cu.check(false, 404, "not found") end, function(e) return e end)'));
+    compute('return 1');
+
+    const total = exports.get_captured_errors();
+    const list = Buffer.from(readRawBuffer(getBufferPtr(), total));
+    assert.strictEqual(list.readUInt32LE(0), 2, 'The list outlives the evals that followed');
+    const firstLength = list.readUInt32LE(4);
+    assert.match(list.subarray(8, 8 + firstLength).toString('utf8'), /:1: swallowed$/);
+    const second = 8 + firstLength;
+    assert.strictEqual(list.subarray(second + 4, second + 4 + list.readUInt32LE(second)).toString('utf8'), 'not found');
+
+    assert.strictEqual(Buffer.from(readRawBuffer(getBufferPtr(), exports.get_captured_errors())).readUInt32LE(0), 0, 'Reading clears the list');
+  });
+});
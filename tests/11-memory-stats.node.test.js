@@ -0,0 +1,233 @@
+const { describe, it, beforeEach } = require('node:test');
+const assert = require('node:assert');
+const { loadWasm, init, compute, callFails, getBufferPtr, getInstance, readResult, reset, writeBuffer } = require('./node-test-utils');
+
+describe('Memory Statistics', () => {
+  beforeEach(async () => {
+    reset();
+    await loadWasm();
+    init();
+  });
+
+  it('get_memory_stats writes into a valid pointer', () => {
+    const { exports } = getInstance();
+    assert.strictEqual(exports.get_memory_stats(getBufferPtr()), 0);
+
+    const view = new DataView(exports.memory.buffer, getBufferPtr(), 12);
+    assert.strictEqual(view.getUint32(0, true), exports.get_buffer_size());
+  });
+
+  it('get_memory_stats writes exactly the documented 24-byte layout', () => {
+    const { exports } = getInstance();
+    exports.set_memory_limit(3 * 65536);
+    const bytes = new Uint8Array(exports.memory.buffer, getBufferPtr(), 32);
+    bytes.fill(0xAA);
+
+    assert.strictEqual(exports.get_memory_stats(getBufferPtr()), 0);
+    const view = new DataView(exports.memory.buffer, getBufferPtr(), 32);
+    assert.strictEqual(view.getUint32(0, true), exports.get_buffer_size(), 'io_buffer_size at 0');
+    assert.ok(view.getUint32(4, true) > 0, 'lua_memory_used at 4');
+    assert.strictEqual(view.getUint32(8, true), exports.memory.buffer.byteLength / 65536, 'wasm_pages at 8');
+    assert.ok(view.getUint32(12, true) >= view.getUint32(4, true), 'lua_memory_peak at 12');
+    assert.strictEqual(view.getUint32(16, true), 3 * 65536, 'lua_memory_limit at 16');
+    assert.strictEqual(view.getUint32(20, true), 2, 'stats_version at 20');
+    assert.deepStrictEqual([...bytes.subarray(24)], new Array(8).fill(0xAA), 'Nothing past byte 24');
+  });
+
+  it('get_memory_stats rejects out-of-range pointers without trapping', () => {
+    const { exports } = getInstance();
+    const memorySize = exports.memory.buffer.byteLength;
+
+    assert.strictEqual(exports.get_memory_stats(0), -1);
+    assert.strictEqual(exports.get_memory_stats(memorySize - 4), -1);
+    assert.strictEqual(exports.get_memory_stats(memorySize + 1024), -1);
+    assert.strictEqual(exports.get_memory_stats(0xFFFFFFF0 | 0), -1);
+  });
+
+  it('get_memory_stats reports the live WASM page count', () => {
+    const { exports } = getInstance();
+    compute('local t = {} for i = 1, 1000 do t[i] = string.rep("x", 64) end');
+
+    assert.strictEqual(exports.get_memory_stats(getBufferPtr()), 0);
+    const view = new DataView(exports.memory.buffer, getBufferPtr(), 12);
+    const pages = view.getUint32(8, true);
+
+    assert.ok(pages > 0);
+    assert.strictEqual(pages, exports.memory.buffer.byteLength / 65536);
+  });
+
+  it('get_memory_stats tracks peak usage and the configured limit', () => {
+    const { exports } = getInstance();
+    compute(`
+      local spike = {}
+      for i = 1, 2000 do spike[i] = { i, tostring(i) } end
+      spike = nil
+      collectgarbage()
+    `);
+
+    exports.set_memory_limit(1024 * 1024);
+    assert.strictEqual(exports.get_memory_stats(getBufferPtr()), 0);
+    const view = new DataView(exports.memory.buffer, getBufferPtr(), 24);
+    const used = view.getUint32(4, true);
+    const peak = view.getUint32(12, true);
+
+    assert.ok(used > 0);
+    assert.ok(peak >= used);
+    assert.strictEqual(view.getUint32(16, true), 1024 * 1024);
+    assert.strictEqual(view.getUint32(20, true), 2);
+  });
+
+  it('gc_collect reports the bytes a collection freed', () => {
+    const { exports } = getInstance();
+    exports.gc_collect();
+
+    compute('local garbage = {} for i = 1, 2000 do garbage[i] = string.rep("x", 64) .. i end');
+    const freed = exports.gc_collect();
+    assert.ok(freed > 2000 * 64, `freed ${freed} bytes`);
+
+    assert.ok(exports.gc_collect() < freed, 'Nothing left to free the second time');
+    assert.strictEqual(exports.run_gc(), 0);
+  });
+
+  it('profile_eval reports the memory an allocating chunk keeps', () => {
+    const { exports } = getInstance();
+    const code = Buffer.from('kept = {} for i = 1, 2000 do kept[i] = "item" .. i end return #kept');
+    new Uint8Array(exports.memory.buffer, getBufferPtr(), code.length).set(code);
+
+    const result = exports.profile_eval(code.length);
+    assert.strictEqual(readResult(getBufferPtr(), result).result, 2000);
+
+    const stats = new DataView(exports.memory.buffer, getBufferPtr() + result, 16);
+    assert.ok(stats.getInt32(4, true) > 2000 * 8, `memory delta ${stats.getInt32(4, true)}`);
+    assert.ok(stats.getUint32(12, true) > 2000, 'Counts the allocations');
+  });
+
+  it('get_alloc_counters only ever grows', () => {
+    const { exports } = getInstance();
+    const readCounters = () => {
+      assert.strictEqual(exports.get_alloc_counters(getBufferPtr()), 0);
+      const view = new DataView(exports.memory.buffer, getBufferPtr(), 24);
+      return {
+        allocated: view.getBigUint64(0, true),
+        freed: view.getBigUint64(8, true),
+        count: view.getBigUint64(16, true),
+      };
+    };
+
+    let previous = readCounters();
+    assert.ok(previous.count > 0n, 'init itself allocates');
+
+    for (let round = 0; round < 3; round++) {
+      compute('local t = {} for i = 1, 500 do t[i] = tostring(i) .. "x" end');
+      exports.gc_collect();
+      const current = readCounters();
+      assert.ok(current.allocated > previous.allocated);
+      assert.ok(current.freed > previous.freed);
+      assert.ok(current.count > previous.count);
+      previous = current;
+    }
+
+    assert.strictEqual(exports.get_memory_stats(getBufferPtr()), 0);
+    const used = new DataView(exports.memory.buffer, getBufferPtr(), 8).getUint32(4, true);
+    assert.strictEqual(previous.allocated - previous.freed, BigInt(used));
+
+    assert.strictEqual(exports.get_alloc_counters(0), -1);
+    assert.strictEqual(exports.get_alloc_counters(getBufferPtr() + 4), -1);
+  });
+
+  it('Iterating an external table sizes its key array up front', () => {
+    const { exports } = getInstance();
+    const allocationCount = () => {
+      assert.strictEqual(exports.get_alloc_counters(getBufferPtr()), 0);
+      return new DataView(exports.memory.buffer, getBufferPtr(), 24).getBigUint64(16, true);
+    };
+    compute(`
+      _home.tiny = ext.table()
+      _home.tiny[1] = 1
+      _home.large = ext.table()
+      for i = 1, 4096 do _home.large[i] = i end
+    `);
+
+    const iterate = name => {
+      compute(`target = _home.${name}`);
+      const before = allocationCount();
+      const bytes = compute('local n = 0 for k in pairs(target) do n = n + 1 end return n');
+      const allocations = allocationCount() - before;
+      return { n: readResult(getBufferPtr(), bytes).result, allocations };
+    };
+    iterate('tiny');
+    const tiny = iterate('tiny');
+    const large = iterate('large');
+    assert.strictEqual(tiny.n, 1);
+    assert.strictEqual(large.n, 4096);
+
+    // Growing the array by doubling would take a dozen reallocations
+    assert.ok(large.allocations - tiny.allocations < 4n,
+      `4096 keys took ${large.allocations} allocations, 1 key ${tiny.allocations}`);
+  });
+
+  it('init_with_options applies buffer size and memory limit', async () => {
+    reset();
+    await loadWasm();
+    const { exports } = getInstance();
+
+    const options = new DataView(exports.memory.buffer, getBufferPtr(), 12);
+    options.setUint32(0, 1, true);
+    options.setUint32(4, 16 * 1024, true);
+    options.setUint32(8, 1024 * 1024, true);
+    assert.strictEqual(exports.init_with_options(12), 0);
+
+    assert.strictEqual(exports.get_memory_stats(getBufferPtr()), 0);
+    const view = new DataView(exports.memory.buffer, getBufferPtr(), 24);
+    assert.strictEqual(view.getUint32(0, true), 16 * 1024);
+    assert.strictEqual(view.getUint32(16, true), 1024 * 1024);
+    assert.strictEqual(exports.get_buffer_size(), 16 * 1024);
+  });
+
+  it('buffer_generation changes when init resizes the buffer', async () => {
+    reset();
+    await loadWasm();
+    const { exports } = getInstance();
+    const before = exports.buffer_generation();
+
+    const options = new DataView(exports.memory.buffer, getBufferPtr(), 12);
+    options.setUint32(0, 1, true);
+    options.setUint32(4, 16 * 1024, true);
+    options.setUint32(8, 0, true);
+    assert.strictEqual(exports.init_with_options(12), 0);
+
+    assert.notStrictEqual(exports.buffer_generation(), before);
+    assert.strictEqual(exports.get_buffer_size(), 16 * 1024);
+  });
+
+  it('The instruction limit stops loops in streaming and resumable evals', async () => {
+    for (const run of [
+      (len) => getInstance().exports.eval_streaming(len),
+      (len) => getInstance().exports.eval_resumable(len),
+    ]) {
+      reset();
+      await loadWasm();
+      const { exports } = getInstance();
+
+      const options = new DataView(exports.memory.buffer, getBufferPtr(), 16);
+      options.setUint32(0, 1, true);
+      options.setUint32(4, 0, true);
+      options.setUint32(8, 0, true);
+      options.setUint32(12, 100000, true);
+      assert.strictEqual(exports.init_with_options(16), 0);
+
+      const len = writeBuffer('while true do end');
+      assert.ok(callFails(() => run(len)));
+      assert.strictEqual(exports.get_last_error_code(), -2);
+    }
+  });
+
+  it('init_with_options rejects a bad version', async () => {
+    reset();
+    await loadWasm();
+    const { exports } = getInstance();
+
+    new DataView(exports.memory.buffer, getBufferPtr(), 4).setUint32(0, 99, true);
+    assert.strictEqual(exports.init_with_options(4), -1);
+  });
+});
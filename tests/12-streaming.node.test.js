@@ -0,0 +1,41 @@
+const { describe, it, beforeEach } = require('node:test');
+const assert = require('node:assert');
+const { loadWasm, init, getBufferPtr, getInstance, writeBuffer, readRawBuffer, reset } = require('./node-test-utils');
+
+describe('Streaming Results', () => {
+  beforeEach(async () => {
+    reset();
+    await loadWasm();
+    init();
+  });
+
+  it('Reassembles a result larger than the IO buffer across read_chunk calls', () => {
+    const { exports } = getInstance();
+    const bufSize = exports.get_buffer_size();
+    const size = 80 * 1024;
+
+    const total = exports.eval_streaming(writeBuffer(`return string.rep("x", ${size})`));
+    assert.ok(total > bufSize, 'Result should exceed the IO buffer');
+
+    const assembled = new Uint8Array(total);
+    let offset = 0;
+    let chunks = 0;
+    while (offset < total) {
+      const n = exports.read_chunk(offset, bufSize);
+      assert.ok(n > 0);
+      assembled.set(readRawBuffer(getBufferPtr(), n), offset);
+      offset += n;
+      chunks++;
+    }
+    assert.ok(chunks > 1, 'Should take multiple chunks');
+    assert.strictEqual(exports.read_chunk(total, bufSize), 0);
+    assert.strictEqual(exports.read_chunk(total + 1, bufSize), -1);
+
+    const view = new DataView(assembled.buffer);
+    const outputLen = view.getUint32(0, true);
+    assert.strictEqual(outputLen, 0);
+    assert.strictEqual(assembled[4], 0x04);
+    assert.strictEqual(view.getUint32(5, true), size);
+    assert.ok(assembled.subarray(9).every((b) => b === 0x78));
+  });
+});
@@ -0,0 +1,43 @@
+const { describe, it, beforeEach } = require('node:test');
+const assert = require('node:assert');
+const { loadWasm, init, compute, getBufferPtr, getInstance, readResult, reset } = require('./node-test-utils');
+
+describe('Compile Cache', () => {
+  beforeEach(async () => {
+    reset();
+    await loadWasm();
+    init();
+  });
+
+  it('Re-running the same source skips the parser', () => {
+    const { exports } = getInstance();
+    const source = 'counter = (counter or 0) + 1; return counter';
+
+    const before = exports.get_compile_count();
+    assert.strictEqual(readResult(getBufferPtr(), compute(source)).result, 1);
+    assert.strictEqual(exports.get_compile_count(), before + 1);
+
+    assert.strictEqual(readResult(getBufferPtr(), compute(source)).result, 2);
+    assert.strictEqual(exports.get_compile_count(), before + 1, 'Second run should hit the cache');
+
+    compute('return 0');
+    assert.strictEqual(exports.get_compile_count(), before + 2);
+  });
+
+  it('clear_cache and a zero capacity force recompilation', () => {
+    const { exports } = getInstance();
+    const source = 'return 40 + 2';
+
+    compute(source);
+    const afterFirst = exports.get_compile_count();
+
+    exports.clear_cache();
+    compute(source);
+    assert.strictEqual(exports.get_compile_count(), afterFirst + 1);
+
+    exports.set_cache_capacity(0);
+    compute(source);
+    compute(source);
+    assert.strictEqual(exports.get_compile_count(), afterFirst + 3);
+  });
+});
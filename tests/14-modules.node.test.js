@@ -0,0 +1,39 @@
+const { describe, it, beforeEach } = require('node:test');
+const assert = require('node:assert');
+const { loadWasm, init, compute, computeFails, getBufferPtr, readResult, reset } = require('./node-test-utils');
+
+describe('Modules Loader', () => {
+  beforeEach(async () => {
+    reset();
+    await loadWasm();
+    init();
+  });
+
+  it('require resolves stored modules that require each other', () => {
+    compute(`
+      Modules.util = "local M = {} function M.double(x) return x * 2 end return M"
+      Modules.app = "local util = require('util') return { run = function() return util.double(21) end }"
+    `);
+
+    const bytes = compute(`
+      local app = require("app")
+      assert(package.loaded.util ~= nil)
+      return app.run()
+    `);
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, 42);
+  });
+
+  it('Circular requires see the partial module table', () => {
+    const bytes = compute(`
+      Modules.a = "local M = select(2, ...) M.name = 'a' M.peer = require('b').name return M"
+      Modules.b = "local M = select(2, ...) M.name = 'b' M.peer = require('a').name return M"
+      local a = require("a")
+      return a.peer .. "|" .. require("b").peer
+    `);
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, 'b|a');
+  });
+
+  it('Missing modules report the Modules lookup', () => {
+    assert.ok(computeFails('return require("nope")'));
+  });
+});
@@ -0,0 +1,78 @@
+const { describe, it, beforeEach } = require('node:test');
+const assert = require('node:assert');
+const { loadWasm, init, compute, getBufferPtr, getInstance, writeBuffer, readRawBuffer, readResult, reset } = require('./node-test-utils');
+
+function takeSnapshot() {
+  const { exports } = getInstance();
+  const total = exports.snapshot();
+  assert.ok(total > 0);
+
+  const blob = new Uint8Array(total);
+  let offset = 0;
+  while (offset < total) {
+    const n = exports.read_chunk(offset, exports.get_buffer_size());
+    blob.set(readRawBuffer(getBufferPtr(), n), offset);
+    offset += n;
+  }
+  return blob;
+}
+
+function skippedNames(blob) {
+  const view = new DataView(blob.buffer);
+  let offset = 5;
+  for (let i = view.getUint32(1, true); i > 0; i--) {
+    offset += 2 + view.getUint16(offset, true);
+    offset += 4 + view.getUint32(offset, true);
+  }
+
+  const names = [];
+  for (let i = view.getUint32(offset, true); i > 0; i--) {
+    const len = view.getUint16(offset + 4, true);
+    names.push(Buffer.from(blob.subarray(offset + 6, offset + 6 + len)).toString('utf8'));
+    offset += 2 + len;
+  }
+  return names;
+}
+
+describe('VM Snapshots', () => {
+  beforeEach(async () => {
+    reset();
+    await loadWasm();
+    init();
+  });
+
+  it('Restores globals into a fresh VM', async () => {
+    compute(`
+      counter = 42
+      greeting = "hello"
+      config = { debug = true, level = 3 }
+      double = function(x) return x * 2 end
+      worker = coroutine.create(function() end)
+    `);
+
+    const blob = takeSnapshot();
+    assert.deepStrictEqual(skippedNames(blob), ['worker']);
+
+    await loadWasm();
+    init();
+    assert.strictEqual(readResult(getBufferPtr(), compute('return counter')).result, null);
+
+    const { exports } = getInstance();
+    assert.strictEqual(exports.restore(writeBuffer(blob)), 4);
+
+    assert.strictEqual(readResult(getBufferPtr(), compute('return counter')).result, 42);
+    assert.strictEqual(readResult(getBufferPtr(), compute('return greeting')).result, 'hello');
+    assert.strictEqual(readResult(getBufferPtr(), compute('return config.level')).result, 3);
+    assert.strictEqual(readResult(getBufferPtr(), compute('return double(21)')).result, 42);
+  });
+
+  it('Leaves out unchanged stdlib globals', () => {
+    const blob = takeSnapshot();
+    assert.strictEqual(new DataView(blob.buffer).getUint32(1, true), 0);
+  });
+
+  it('Rejects a malformed blob', () => {
+    const { exports } = getInstance();
+    assert.strictEqual(exports.restore(writeBuffer(new Uint8Array([9, 0, 0, 0, 0]))), -1);
+  });
+});
@@ -0,0 +1,74 @@
+const { describe, it, beforeEach } = require('node:test');
+const assert = require('node:assert');
+const {
+  loadWasm, init, compute, getBufferPtr, getInstance, writeBuffer, readRawBuffer, readResult, reset,
+  externalTables, deserializeObject,
+} = require('./node-test-utils');
+
+function envRequest(source, envBytes = new Uint8Array(0)) {
+  const src = Buffer.from(source, 'utf8');
+  const request = new Uint8Array(5 + src.length + envBytes.length);
+  const view = new DataView(request.buffer);
+  request[0] = 0x04;
+  view.setUint32(1, src.length, true);
+  request.set(src, 5);
+  request.set(envBytes, 5 + src.length);
+  return request;
+}
+
+function tableRef(id) {
+  const bytes = new Uint8Array(5);
+  bytes[0] = 0x07;
+  new DataView(bytes.buffer).setUint32(1, id, true);
+  return bytes;
+}
+
+// Decode the returned environment reference into a plain object
+function returnedEnv(len) {
+  const bytes = readRawBuffer(getBufferPtr(), len);
+  const view = new DataView(bytes.buffer, bytes.byteOffset, bytes.byteLength);
+  const valueStart = 4 + view.getUint32(0, true);
+  assert.strictEqual(bytes[valueStart], 0x07);
+
+  const entries = externalTables.get(view.getUint32(valueStart + 1, true));
+  const env = {};
+  for (const [key, value] of entries) {
+    env[key] = deserializeObject(value);
+  }
+  return env;
+}
+
+describe('eval_with_env', () => {
+  beforeEach(async () => {
+    reset();
+    await loadWasm();
+    init();
+  });
+
+  it('Sets globals in the returned env, not the real globals', () => {
+    const { exports } = getInstance();
+    const len = exports.eval_with_env(writeBuffer(envRequest('x = 1')));
+    assert.ok(len > 0);
+
+    assert.deepStrictEqual(returnedEnv(len), { x: 1 });
+    assert.strictEqual(readResult(getBufferPtr(), compute('return x')).result, null);
+  });
+
+  it('Reads from a host-supplied env and the safe base', () => {
+    const { exports } = getInstance();
+    compute('_home.base = 10');
+
+    const request = envRequest('y = base + math.floor(2.5)', tableRef(exports.get_memory_table_id()));
+    const len = exports.eval_with_env(writeBuffer(request));
+    assert.ok(len > 0);
+
+    assert.deepStrictEqual(returnedEnv(len), { base: 10, y: 12 });
+    assert.strictEqual(readResult(getBufferPtr(), compute('return _home.y')).result, null);
+  });
+
+  it('Does not expose globals outside the safe base', () => {
+    const { exports } = getInstance();
+    const len = exports.eval_with_env(writeBuffer(envRequest('found = os ~= nil or _home ~= nil')));
+    assert.deepStrictEqual(returnedEnv(len), { found: false });
+  });
+});
@@ -0,0 +1,55 @@
+const { describe, it, beforeEach } = require('node:test');
+const assert = require('node:assert');
+const { loadWasm, getBufferPtr, getInstance, readRawBuffer, reset } = require('./node-test-utils');
+
+const VM_NOT_INITIALIZED = -5;
+const MESSAGE = 'Lua state not initialized';
+
+describe('Exports called before init', () => {
+  beforeEach(async () => {
+    reset();
+    await loadWasm();
+  });
+
+  function assertReported() {
+    const { exports } = getInstance();
+    assert.strictEqual(exports.get_last_error_code(), VM_NOT_INITIALIZED);
+    const message = Buffer.from(readRawBuffer(getBufferPtr(), MESSAGE.length)).toString('utf8');
+    assert.strictEqual(message, MESSAGE);
+  }
+
+  it('Result-returning exports write the error to the buffer', () => {
+    const { exports } = getInstance();
+    const calls = {
+      compute: () => exports.compute(getBufferPtr(), 8),
+      eval_streaming: () => exports.eval_streaming(8),
+      eval_resumable: () => exports.eval_resumable(8),
+      resume: () => exports.resume(0),
+      eval_with_env: () => exports.eval_with_env(8),
+    };
+
+    for (const [name, call] of Object.entries(calls)) {
+      assert.strictEqual(call(), -(MESSAGE.length + 1), name);
+      assertReported();
+    }
+  });
+
+  it('Status exports return VM_NOT_INITIALIZED', () => {
+    const { exports } = getInstance();
+    const calls = {
+      run_gc: () => exports.run_gc(),
+      snapshot: () => exports.snapshot(),
+      restore: () => exports.restore(5),
+      attach_memory_table: () => exports.attach_memory_table(1),
+      attach_modules_table: () => exports.attach_modules_table(1),
+      clear_io_table: () => exports.clear_io_table(),
+      set_cache_capacity: () => exports.set_cache_capacity(4),
+      clear_cache: () => exports.clear_cache(),
+    };
+
+    for (const [name, call] of Object.entries(calls)) {
+      assert.strictEqual(call(), VM_NOT_INITIALIZED, name);
+      assertReported();
+    }
+  });
+});
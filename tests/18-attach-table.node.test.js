@@ -0,0 +1,41 @@
+const { describe, it, beforeEach } = require('node:test');
+const assert = require('node:assert');
+const { loadWasm, init, compute, getBufferPtr, getInstance, writeBuffer, readResult, reset } = require('./node-test-utils');
+
+function attach(name, tableId) {
+  const { exports } = getInstance();
+  const len = writeBuffer(name);
+  return exports.attach_table(getBufferPtr(), len, tableId);
+}
+
+describe('attach_table', () => {
+  beforeEach(async () => {
+    reset();
+    await loadWasm();
+    init();
+  });
+
+  it('Reconnects a persisted table to a global after a restart', async () => {
+    const bytes = compute(`
+      profile = ext.table()
+      profile.name = "ada"
+      profile.greet = function(who) return "hello " .. who end
+      return profile.__ext_table_id
+    `);
+    const tableId = readResult(getBufferPtr(), bytes).result;
+    assert.ok(tableId > 0);
+
+    await loadWasm();
+    init();
+    assert.strictEqual(readResult(getBufferPtr(), compute('return profile')).result, null);
+
+    assert.strictEqual(attach('profile', tableId), 0);
+    const greeting = compute('return profile.greet(profile.name)');
+    assert.strictEqual(readResult(getBufferPtr(), greeting).result, 'hello ada');
+  });
+
+  it('Rejects an empty name or a zero id', () => {
+    assert.strictEqual(attach('', 3), -1);
+    assert.strictEqual(attach('profile', 0), -1);
+  });
+});
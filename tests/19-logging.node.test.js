@@ -0,0 +1,69 @@
+const { describe, it, beforeEach } = require('node:test');
+const assert = require('node:assert');
+const { loadWasm, init, compute, getBufferPtr, getInstance, writeBuffer, readRawBuffer, readResult, reset } = require('./node-test-utils');
+
+function setLogPrefix(prefix) {
+  return getInstance().exports.set_log_prefix(writeBuffer(Buffer.from(prefix, 'utf8')));
+}
+
+function readStderr() {
+  const len = getInstance().exports.read_stderr();
+  return Buffer.from(readRawBuffer(getBufferPtr(), len)).toString('utf8');
+}
+
+describe('Structured Logging', () => {
+  beforeEach(async () => {
+    reset();
+    await loadWasm();
+    init();
+  });
+
+  it('Captures print and warn in separate streams', () => {
+    const bytes = compute('print("hello") warn("disk", "low") return 1');
+    const { output, result } = readResult(getBufferPtr(), bytes);
+
+    assert.strictEqual(result, 1);
+    assert.strictEqual(output, 'hello\n');
+    assert.strictEqual(readStderr(), 'disk\tlow\n');
+  });
+
+  it('Prefixes every captured line', () => {
+    assert.strictEqual(setLogPrefix('[vm-7] '), 0);
+
+    const bytes = compute('print("one") print("two") warn("careful")');
+    assert.strictEqual(readResult(getBufferPtr(), bytes).output, '[vm-7] one\n[vm-7] two\n');
+    assert.strictEqual(readStderr(), '[vm-7] careful\n');
+
+    assert.strictEqual(setLogPrefix(''), 0);
+    const plain = compute('print("three")');
+    assert.strictEqual(readResult(getBufferPtr(), plain).output, 'three\n');
+  });
+
+  it('Starts each call with an empty stderr stream', () => {
+    compute('warn("first")');
+    compute('print("quiet")');
+    assert.strictEqual(readStderr(), '');
+  });
+
+  it('Follows the @on and @off warning controls', () => {
+    compute('warn("@on") warn("hi")');
+    assert.strictEqual(readStderr(), 'hi\n', 'Control messages are not captured');
+
+    compute('warn("@off") warn("hidden") warn("@on") warn("shown")');
+    assert.strictEqual(readStderr(), 'shown\n');
+  });
+
+  it('Captures warnings Lua raises itself', () => {
+    compute(`
+      setmetatable({}, { __gc = function() error("gc boom") end })
+      collectgarbage()
+    `);
+    const stderr = readStderr();
+    assert.match(stderr, /error in __gc/);
+    assert.match(stderr, /gc boom/);
+  });
+
+  it('Rejects an overlong prefix', () => {
+    assert.strictEqual(setLogPrefix('x'.repeat(257)), -1);
+  });
+});
@@ -0,0 +1,43 @@
+const { describe, it, beforeEach } = require('node:test');
+const assert = require('node:assert');
+const { loadWasm, init, compute, computeFails, getBufferPtr, readResult, reset } = require('./node-test-utils');
+
+function evaluate(code) {
+  return readResult(getBufferPtr(), compute(code)).result;
+}
+
+describe('cu.utf8', () => {
+  beforeEach(async () => {
+    reset();
+    await loadWasm();
+    init();
+  });
+
+  it('len counts codepoints, not bytes', () => {
+    assert.strictEqual(evaluate('return cu.utf8.len("héllo, 世界 🌍")'), 11);
+    assert.strictEqual(evaluate('return #"héllo, 世界 🌍"'), Buffer.byteLength('héllo, 世界 🌍'));
+    assert.strictEqual(evaluate('return cu.utf8.len("")'), 0);
+  });
+
+  it('sub slices by codepoint with string.sub positions', () => {
+    assert.strictEqual(evaluate('return cu.utf8.sub("naïve café", 3, 5)'), 'ïve');
+    assert.strictEqual(evaluate('return cu.utf8.sub("naïve café", -4)'), 'café');
+    assert.strictEqual(evaluate('return cu.utf8.sub("世界", 0, 100)'), '世界');
+    assert.strictEqual(evaluate('return cu.utf8.sub("世界", 2, 1)'), '');
+  });
+
+  it('codepoint returns the values at codepoint positions', () => {
+    assert.strictEqual(evaluate('return table.concat({ cu.utf8.codepoint("a世🌍", 1, -1) }, ",")'), '97,19990,127757');
+    assert.strictEqual(evaluate('return cu.utf8.codepoint("a世🌍", 2)'), 0x4E16);
+  });
+
+  it('replace mode reads invalid bytes as U+FFFD', () => {
+    assert.strictEqual(evaluate('return cu.utf8.len("ab\\xFFc\\xC3", "replace")'), 5);
+    assert.strictEqual(evaluate('return cu.utf8.sub("ab\\xFFc", 2, 3, "replace")'), 'b�');
+    assert.strictEqual(evaluate('return cu.utf8.codepoint("\\xED\\xA0\\x80", 1, 1, "replace")'), 0xFFFD);
+  });
+
+  it('strict mode rejects invalid UTF-8', () => {
+    assert.ok(computeFails('return cu.utf8.len("ab\\xFF")'));
+  });
+});
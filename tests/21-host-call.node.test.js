@@ -0,0 +1,41 @@
+const { describe, it, beforeEach } = require('node:test');
+const assert = require('node:assert');
+const { loadWasm, init, compute, computeFails, getBufferPtr, readResult, registerHostFunction, reset } = require('./node-test-utils');
+
+describe('host.call', () => {
+  beforeEach(async () => {
+    reset();
+    await loadWasm();
+    init();
+  });
+
+  it('Calls a host function by name and returns its result', () => {
+    registerHostFunction('reverse', (text) => [...text].reverse().join(''));
+
+    const bytes = compute('return host.call("reverse", "stressed")');
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, 'desserts');
+  });
+
+  it('Passes every argument through in order', () => {
+    let received = null;
+    registerHostFunction('record', (...args) => {
+      received = args;
+      return args.length;
+    });
+
+    const bytes = compute('return host.call("record", 1, "two", true, nil, 4.5)');
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, 5);
+    assert.deepStrictEqual(received, [1, 'two', true, null, 4.5]);
+  });
+
+  it('Returns nil when the host function returns nothing', () => {
+    registerHostFunction('noop', () => undefined);
+
+    const bytes = compute('return host.call("noop") == nil');
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, true);
+  });
+
+  it('Raises an error for an unknown function', () => {
+    assert.ok(computeFails('return host.call("missing")'));
+  });
+});
@@ -0,0 +1,47 @@
+const { describe, it, beforeEach } = require('node:test');
+const assert = require('node:assert');
+const { loadWasm, init, compute, computeFails, getBufferPtr, readResult, hostHooks, reset } = require('./node-test-utils');
+
+describe('cu.random_bytes', () => {
+  beforeEach(async () => {
+    reset();
+    await loadWasm();
+    init();
+  });
+
+  it('Returns the bytes the host provides', () => {
+    const bytes = compute('local s = cu.random_bytes(5) return #s .. ":" .. table.concat({s:byte(1, -1)}, ",")');
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, '5:0,1,2,3,4');
+  });
+
+  it('Returns exactly n bytes for lengths past one byte value', () => {
+    const bytes = compute('local s = cu.random_bytes(300) return #s .. ":" .. s:byte(256) .. "," .. s:byte(300)');
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, '300:255,43');
+  });
+
+  it('Returns an empty string for zero bytes', () => {
+    const bytes = compute('return cu.random_bytes(0) == ""');
+    assert.strictEqual(readResult(getBufferPtr(), bytes).result, true);
+  });
+
+  it('Seeds math.random from the host at init', async () => {
+    const first = readResult(getBufferPtr(), compute('return math.random(1, 1000000)')).result;
+
+    reset();
+    await loadWasm();
+    init();
+    const second = readResult(getBufferPtr(), compute('return math.random(1, 1000000)')).result;
+
+    // The mock host hands out the same bytes every time
+    assert.strictEqual(first, second);
+  });
+
+  it('Rejects negative lengths', () => {
+    assert.ok(computeFails('return cu.random_bytes(-1)'));
+  });
+
+  it('Raises an error when the host has no entropy', () => {
+    hostHooks.onRandomBytes = () => null;
+    assert.ok(computeFails('return cu.random_bytes(8)'));
+  });
+});
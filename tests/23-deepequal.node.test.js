@@ -0,0 +1,61 @@
+const { describe, it, beforeEach } = require('node:test');
+const assert = require('node:assert');
+const { loadWasm, init, compute, getBufferPtr, readResult, reset } = require('./node-test-utils');
+
+describe('cu.deepequal', () => {
+  beforeEach(async () => {
+    reset();
+    await loadWasm();
+    init();
+  });
+
+  function run(code) {
+    return readResult(getBufferPtr(), compute(code)).result;
+  }
+
+  it('Compares nested tables by value', () => {
+    const result = run(`
+      local a = {1, 2, {x = "y", list = {true, false}}}
+      local b = {1, 2, {x = "y", list = {true, false}}}
+      local c = {1, 2, {x = "y", list = {true}}}
+      return tostring(cu.deepequal(a, b)) .. "," .. tostring(cu.deepequal(a, c)) .. ","
+        .. tostring(cu.deepequal(1, 1.0)) .. "," .. tostring(cu.deepequal({}, "x"))
+    `);
+    assert.strictEqual(result, 'true,false,true,false');
+  });
+
+  it('Reads external tables through the host', () => {
+    const result = run(`
+      local plain = {name = "cu", tags = {"a", "b"}}
+      local stored = ext.table()
+      stored.name = "cu"
+      stored.tags = {"a", "b"}
+      local before = cu.deepequal(plain, stored) and cu.deepequal(stored, plain)
+
+      stored.extra = 1
+      local after = cu.deepequal(plain, stored)
+      stored.extra = nil
+      return tostring(before) .. "," .. tostring(after) .. "," .. tostring(cu.deepequal(stored, plain))
+    `);
+    assert.strictEqual(result, 'true,false,true');
+  });
+
+  it('Terminates on cyclic tables', () => {
+    const result = run(`
+      local a = {name = "node"}
+      a.self = a
+      local b = {name = "node"}
+      b.self = b
+
+      -- Same shape unrolled over two tables
+      local c1 = {name = "node"}
+      local c2 = {name = "node", self = c1}
+      c1.self = c2
+
+      local d = {name = "other"}
+      d.self = d
+      return tostring(cu.deepequal(a, b)) .. "," .. tostring(cu.deepequal(a, c1)) .. "," .. tostring(cu.deepequal(a, d))
+    `);
+    assert.strictEqual(result, 'true,true,false');
+  });
+});
@@ -0,0 +1,71 @@
+const { describe, it, beforeEach } = require('node:test');
+const assert = require('node:assert');
+const { loadWasm, init, compute, computeFails, getBufferPtr, readResult, reset } = require('./node-test-utils');
+
+// Lua errors trap in this build and take the instance with them, so each
+// refused write gets an instance of its own
+async function restart() {
+  reset();
+  await loadWasm();
+  init();
+}
+
+describe('cu.freeze', () => {
+  beforeEach(restart);
+
+  function run(code) {
+    return readResult(getBufferPtr(), compute(code)).result;
+  }
+
+  it('Reads through every level', () => {
+    const result = run(`
+      local frozen = cu.freeze({name = "cu", limits = {max = 10, tiers = {"a", "b"}}})
+      return table.concat({frozen.name, frozen.limits.max, frozen.limits.tiers[2], #frozen.limits.tiers}, ",")
+    `);
+    assert.strictEqual(result, 'cu,10,b,2');
+  });
+
+  it('Rejects writes at every level', async () => {
+    const setup = 'local frozen = cu.freeze({name = "cu", limits = {max = 10, tiers = {"a", "b"}}})\n';
+    for (const write of [
+      'frozen.name = "other"',
+      'frozen.added = 1',
+      'frozen.limits.max = 0',
+      'frozen.limits.tiers[3] = "c"',
+      'table.insert(frozen.limits.tiers, "c")',
+    ]) {
+      await restart();
+      assert.ok(computeFails(setup + write), write);
+    }
+  });
+
+  it('Iterates with pairs and ipairs and handles cycles', () => {
+    const result = run(`
+      local t = {list = {1, 2, 3}}
+      t.self = t
+      local frozen = cu.freeze(t)
+
+      local sum = 0
+      for _, v in ipairs(frozen.list) do sum = sum + v end
+      local keys = {}
+      for k in pairs(frozen) do keys[#keys + 1] = k end
+      table.sort(keys)
+
+      return sum .. "," .. table.concat(keys, ",") .. "," .. tostring(frozen.self == frozen)
+        .. "," .. tostring(cu.freeze(frozen) == frozen)
+    `);
+    assert.strictEqual(result, '6,list,self,true,true');
+
+    // Tables reached through pairs are frozen too
+    assert.ok(computeFails(`
+      local frozen = cu.freeze({list = {1, 2, 3}})
+      for _, v in pairs(frozen) do v.x = 1 end
+    `));
+  });
+
+  it('Refuses external tables and non-tables', async () => {
+    assert.ok(computeFails('cu.freeze(ext.table())'), 'external table');
+    await restart();
+    assert.ok(computeFails('cu.freeze("text")'), 'string');
+  });
+});
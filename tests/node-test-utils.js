@@ -8,11 +8,66 @@ const path = require('path');
 // Storage for external tables (mimics browser Map)
 const externalTables = new Map();
 let nextTableId = 1;
+
+// Host import call counts, for tests that check how often storage is hit
+const hostCallCounts = { get: 0, has: 0 };
+
+// Optional callbacks run inside host imports, e.g. to call back into the
+// module the way a misbehaving host would. dropWrites makes js_ext_table_set
+// report success without storing anything.
+const hostHooks = { onGet: null, onRandomBytes: null, keysPerPage: Infinity, dropWrites: false };
+
+// Deterministic stand-in for host entropy: 0, 1, 2, ... wrapping at 256.
+// hostHooks.onRandomBytes(len) can return other bytes, or null to fail.
+function mockRandomBytes(len) {
+  if (hostHooks.onRandomBytes) return hostHooks.onRandomBytes(len);
+  return Uint8Array.from({ length: len }, (_, i) => i & 0xff);
+}
+
+// Functions Lua can call with host.call(name, ...)
+const hostFunctions = new Map();
+
+// host.call arguments: u32 count, then per argument a u32 length and the
+// serialized value
+function decodeHostCallArgs(bytes) {
+  const view = new DataView(bytes.buffer, bytes.byteOffset, bytes.byteLength);
+  const count = view.getUint32(0, true);
+  const args = [];
+  let offset = 4;
+  for (let i = 0; i < count; i++) {
+    const len = view.getUint32(offset, true);
+    args.push(deserializeObject(bytes.subarray(offset + 4, offset + 4 + len)));
+    offset += 4 + len;
+  }
+  return args;
+}
+
+// Opaque host handles (wire tag 0x08); Lua only ever sees the u64 id
+class HostHandle {
+  constructor(id) {
+    this.id = BigInt(id);
+  }
+}
+const hostHandles = new Map();
+let nextHandleId = 1n;
 let homeTableId = null;
 let ioTableId = null;
 
 let wasmInstance = null;
 let wasmMemory = null;
+let mockTime = null;
+
+// Clock seen by the module through js_time_now; tests can pin it
+function now() {
+  return mockTime ?? Date.now();
+}
+
+/**
+ * Pin the host clock to a fixed time in ms (null restores Date.now)
+ */
+function setMockTime(ms) {
+  mockTime = ms;
+}
 
 function ensureExternalTable(tableId) {
   const id = Number(tableId);
@@ -25,6 +80,88 @@ function ensureExternalTable(tableId) {
   return externalTables.get(id);
 }
 
+// Expiry timestamps (ms) for entries written with js_ext_table_set_ttl
+const entryExpiry = new Map();
+
+function isExpired(tableId, key) {
+  const expiresAt = entryExpiry.get(Number(tableId))?.get(key);
+  return expiresAt !== undefined && now() >= expiresAt;
+}
+
+function clearExpiry(tableId, key) {
+  entryExpiry.get(Number(tableId))?.delete(key);
+}
+
+// Keys arrive as raw bytes. Valid UTF-8 keys are kept as plain strings;
+// anything else (e.g. "\xff") gets a marker and a byte-per-char body so
+// distinct byte strings never collapse into the same Map key.
+const BINARY_KEY_MARKER = '\u0000bin:';
+const strictUtf8 = new TextDecoder('utf-8', { fatal: true, ignoreBOM: true });
+
+function decodeKey(bytes) {
+  try {
+    const key = strictUtf8.decode(bytes);
+    if (!key.startsWith(BINARY_KEY_MARKER)) return key;
+  } catch (e) {
+    // Not UTF-8; fall through to the binary form
+  }
+  return BINARY_KEY_MARKER + Buffer.from(bytes).toString('latin1');
+}
+
+function encodeKey(key) {
+  if (key.startsWith(BINARY_KEY_MARKER)) {
+    return Buffer.from(key.slice(BINARY_KEY_MARKER.length), 'latin1');
+  }
+  return Buffer.from(key, 'utf8');
+}
+
+// One page for js_ext_table_keys_page: [u32 LE next cursor][newline-separated
+// keys], as many keys from index cursor on as fit in maxLen, at most maxKeys.
+// The next cursor is 0 after the last key. Returns null if even the first
+// key doesn't fit.
+function encodeKeysPage(keys, cursor, maxLen, maxKeys = Infinity) {
+  const parts = [];
+  let size = 4;
+  let index = cursor;
+  while (index < keys.length && parts.length < maxKeys) {
+    const key = encodeKey(keys[index]);
+    const needed = key.length + (parts.length > 0 ? 1 : 0);
+    if (size + needed > maxLen) break;
+    parts.push(key);
+    size += needed;
+    index++;
+  }
+  if (parts.length === 0 && index < keys.length) return null;
+
+  const bytes = new Uint8Array(size);
+  new DataView(bytes.buffer).setUint32(0, index < keys.length ? index : 0, true);
+  let offset = 4;
+  parts.forEach((part, i) => {
+    if (i > 0) bytes[offset++] = 0x0A;
+    bytes.set(part, offset);
+    offset += part.length;
+  });
+  return bytes;
+}
+
+// The exports build.sh asks for; a module missing any of them predates the
+// current sources
+const EXPECTED_EXPORTS = [
+  ...fs.readFileSync(path.join(__dirname, '../build.sh'), 'utf8').matchAll(/--export=(\w+)/g),
+].map((match) => match[1]);
+
+/**
+ * Fail fast with a clear message when the module wasn't rebuilt after the
+ * sources gained exports, instead of letting tests die on "not a function"
+ */
+function checkExports(instance, wasmPath) {
+  const missing = EXPECTED_EXPORTS.filter((name) => typeof instance.exports[name] !== 'function');
+  if (missing.length > 0) {
+    const shown = path.relative(path.join(__dirname, '..'), wasmPath);
+    throw new Error(`${shown} is stale; run ./build.sh (missing exports: ${missing.join(', ')})`);
+  }
+}
+
 /**
  * Load Cu WASM module
  */
@@ -33,14 +170,16 @@ async function loadWasm(wasmPath = path.join(__dirname, '../web/cu.wasm')) {
 
   const imports = {
     env: {
-      js_time_now: () => Date.now(),
+      js_time_now: () => now(),
       js_ext_table_set: (table_id, key_ptr, key_len, val_ptr, val_len) => {
+        if (hostHooks.dropWrites) return 0;
         try {
           const table = ensureExternalTable(table_id);
-          const key = Buffer.from(wasmMemory.slice(key_ptr, key_ptr + key_len)).toString('utf8');
+          const key = decodeKey(wasmMemory.slice(key_ptr, key_ptr + key_len));
           const valueBytes = wasmMemory.slice(val_ptr, val_ptr + val_len);
           const valueCopy = new Uint8Array(valueBytes);
           table.set(key, valueCopy);
+          clearExpiry(table_id, key);
           return 0;
         } catch (e) {
           console.error('js_ext_table_set error:', e);
@@ -48,11 +187,19 @@ async function loadWasm(wasmPath = path.join(__dirname, '../web/cu.wasm')) {
         }
       },
       js_ext_table_get: (table_id, key_ptr, key_len, val_ptr, max_len) => {
+        hostCallCounts.get++;
+        hostHooks.onGet?.();
         try {
           const table = externalTables.get(table_id);
           if (!table) return -1;
 
-          const key = Buffer.from(wasmMemory.slice(key_ptr, key_ptr + key_len)).toString('utf8');
+          const key = decodeKey(wasmMemory.slice(key_ptr, key_ptr + key_len));
+          if (isExpired(table_id, key)) {
+            table.delete(key);
+            clearExpiry(table_id, key);
+            return -1;
+          }
+
           const value = table.get(key);
 
           if (value === undefined) return -1;
@@ -66,7 +213,8 @@ async function loadWasm(wasmPath = path.join(__dirname, '../web/cu.wasm')) {
             return -1;
           }
 
-          if (valueBytes.length > max_len) return -1;
+          // Too large: report the size so the module can read it again
+          if (valueBytes.length > max_len) return -valueBytes.length;
 
           for (let i = 0; i < valueBytes.length; i++) {
             wasmMemory[val_ptr + i] = valueBytes[i];
@@ -83,8 +231,9 @@ async function loadWasm(wasmPath = path.join(__dirname, '../web/cu.wasm')) {
           const table = externalTables.get(table_id);
           if (!table) return -1;
 
-          const key = Buffer.from(wasmMemory.slice(key_ptr, key_ptr + key_len)).toString('utf8');
+          const key = decodeKey(wasmMemory.slice(key_ptr, key_ptr + key_len));
           table.delete(key);
+          clearExpiry(table_id, key);
           return 0;
         } catch (e) {
           console.error('js_ext_table_delete error:', e);
@@ -95,13 +244,29 @@ async function loadWasm(wasmPath = path.join(__dirname, '../web/cu.wasm')) {
         const table = externalTables.get(table_id);
         return table ? table.size : 0;
       },
+      js_ext_table_seqlen: (table_id) => {
+        // Border: the largest n with keys 1..n all set, as Lua's # finds. A
+        // tombstone (the serialized nil, 0x00) reads as nil, so it ends the run.
+        const table = externalTables.get(table_id);
+        if (!table) return 0;
+        const present = key => {
+          const value = table.get(key);
+          if (value === undefined || isExpired(table_id, key)) return false;
+          return !(value instanceof Uint8Array && value.length === 1 && value[0] === 0x00);
+        };
+        let n = 0;
+        while (present(String(n + 1))) n++;
+        return n;
+      },
       js_ext_table_keys: (table_id, buf_ptr, max_len) => {
         try {
           const table = externalTables.get(table_id);
           if (!table) return -1;
 
-          const keys = Array.from(table.keys()).join('\n');
-          const keysBytes = Buffer.from(keys, 'utf8');
+          const newline = Buffer.from('\n');
+          const keysBytes = Buffer.concat(
+            Array.from(table.keys(), encodeKey).flatMap((key, i) => (i === 0 ? [key] : [newline, key])),
+          );
 
           if (keysBytes.length > max_len) return -1;
 
@@ -115,10 +280,247 @@ async function loadWasm(wasmPath = path.join(__dirname, '../web/cu.wasm')) {
           return -1;
         }
       },
+      js_ext_table_keys_page: (table_id, cursor, out_ptr, max_len) => {
+        try {
+          const table = externalTables.get(table_id);
+          if (!table) return -1;
+
+          // hostHooks.keysPerPage forces small pages
+          const page = encodeKeysPage(Array.from(table.keys()), cursor, max_len, hostHooks.keysPerPage);
+          if (!page) return -1;
+          wasmMemory.set(page, out_ptr);
+          return page.length;
+        } catch (e) {
+          console.error('js_ext_table_keys_page error:', e);
+          return -1;
+        }
+      },
+      js_ext_table_free: (table_id) => {
+        entryExpiry.delete(Number(table_id));
+        return externalTables.delete(Number(table_id)) ? 0 : -1;
+      },
+      js_ext_table_rename: (table_id, old_ptr, old_len, new_ptr, new_len) => {
+        try {
+          const table = externalTables.get(table_id);
+          if (!table) return -1;
+
+          const oldKey = decodeKey(wasmMemory.slice(old_ptr, old_ptr + old_len));
+          const newKey = decodeKey(wasmMemory.slice(new_ptr, new_ptr + new_len));
+          if (!table.has(oldKey)) return 0;
+
+          const value = table.get(oldKey);
+          table.delete(oldKey);
+          table.set(newKey, value);
+          return 1;
+        } catch (e) {
+          console.error('js_ext_table_rename error:', e);
+          return -1;
+        }
+      },
+      js_ext_table_cas: (table_id, key_ptr, key_len, expected_ptr, expected_len, new_ptr, new_len) => {
+        try {
+          const table = ensureExternalTable(table_id);
+          const key = decodeKey(wasmMemory.slice(key_ptr, key_ptr + key_len));
+          const expected = wasmMemory.slice(expected_ptr, expected_ptr + expected_len);
+          const current = table.get(key) ?? new Uint8Array([0x00]);
+
+          if (!Buffer.from(current).equals(Buffer.from(expected))) return 0;
+
+          const newValue = new Uint8Array(wasmMemory.slice(new_ptr, new_ptr + new_len));
+          if (newValue.length === 1 && newValue[0] === 0x00) {
+            table.delete(key);
+          } else {
+            table.set(key, newValue);
+          }
+          return 1;
+        } catch (e) {
+          console.error('js_ext_table_cas error:', e);
+          return -1;
+        }
+      },
+      js_ext_table_swap: (table_id, a_ptr, a_len, b_ptr, b_len) => {
+        try {
+          const table = ensureExternalTable(table_id);
+          const keyA = decodeKey(wasmMemory.slice(a_ptr, a_ptr + a_len));
+          const keyB = decodeKey(wasmMemory.slice(b_ptr, b_ptr + b_len));
+          const valueA = isExpired(table_id, keyA) ? undefined : table.get(keyA);
+          const valueB = isExpired(table_id, keyB) ? undefined : table.get(keyB);
+
+          // A missing key is nil: its partner ends up missing too
+          if (valueB === undefined) table.delete(keyA); else table.set(keyA, valueB);
+          if (valueA === undefined) table.delete(keyB); else table.set(keyB, valueA);
+          clearExpiry(table_id, keyA);
+          clearExpiry(table_id, keyB);
+          return 0;
+        } catch (e) {
+          console.error('js_ext_table_swap error:', e);
+          return -1;
+        }
+      },
+      js_ext_table_clone: (src_id, dst_id) => {
+        try {
+          const src = ensureExternalTable(src_id);
+          const dst = ensureExternalTable(dst_id);
+          for (const [key, value] of src) {
+            if (!isExpired(src_id, key)) dst.set(key, value);
+          }
+          // Copied entries expire when the originals do
+          const expiry = entryExpiry.get(Number(src_id));
+          if (expiry) entryExpiry.set(Number(dst_id), new Map(expiry));
+          return 0;
+        } catch (e) {
+          console.error('js_ext_table_clone error:', e);
+          return -1;
+        }
+      },
+      js_ext_table_bulk_dump: (table_id, out_ptr, max_len) => {
+        try {
+          const table = externalTables.get(table_id);
+          if (!table) return -1;
+
+          const records = [];
+          for (const [key, value] of table) {
+            if (isExpired(table_id, key)) continue;
+            const keyBytes = encodeKey(key);
+            const valueBytes = typeof value === 'string' ? Buffer.from(value, 'utf8') : value;
+            const header = Buffer.alloc(4);
+            header.writeUInt32LE(keyBytes.length);
+            const valueHeader = Buffer.alloc(4);
+            valueHeader.writeUInt32LE(valueBytes.length);
+            records.push(header, keyBytes, valueHeader, valueBytes);
+          }
+
+          const dump = Buffer.concat(records);
+          if (dump.length > max_len) return -1;
+          wasmMemory.set(dump, out_ptr);
+          return dump.length;
+        } catch (e) {
+          console.error('js_ext_table_bulk_dump error:', e);
+          return -1;
+        }
+      },
+      js_ext_table_bulk_load: (table_id, in_ptr, len) => {
+        try {
+          const dump = Buffer.from(wasmMemory.slice(in_ptr, in_ptr + len));
+          const table = ensureExternalTable(table_id);
+          table.clear();
+          entryExpiry.delete(Number(table_id));
+
+          let pos = 0;
+          while (pos < len) {
+            const keyLen = dump.readUInt32LE(pos);
+            const key = decodeKey(dump.subarray(pos + 4, pos + 4 + keyLen));
+            pos += 4 + keyLen;
+            const valueLen = dump.readUInt32LE(pos);
+            table.set(key, new Uint8Array(dump.subarray(pos + 4, pos + 4 + valueLen)));
+            pos += 4 + valueLen;
+          }
+          return table.size;
+        } catch (e) {
+          console.error('js_ext_table_bulk_load error:', e);
+          return -1;
+        }
+      },
+      js_ext_table_delete_prefix: (table_id, prefix_ptr, prefix_len) => {
+        try {
+          const table = externalTables.get(table_id);
+          if (!table) return -1;
+
+          // Compare bytes, so binary keys match as they were stored
+          const prefix = Buffer.from(wasmMemory.slice(prefix_ptr, prefix_ptr + prefix_len));
+          let deleted = 0;
+          for (const key of [...table.keys()]) {
+            const keyBytes = encodeKey(key);
+            if (keyBytes.length < prefix.length || !keyBytes.subarray(0, prefix.length).equals(prefix)) continue;
+            if (!isExpired(table_id, key)) deleted++;
+            table.delete(key);
+            clearExpiry(table_id, key);
+          }
+          return deleted;
+        } catch (e) {
+          console.error('js_ext_table_delete_prefix error:', e);
+          return -1;
+        }
+      },
+      js_host_call: (name_ptr, name_len, args_ptr, args_len, out_ptr, max_len) => {
+        try {
+          const name = Buffer.from(wasmMemory.slice(name_ptr, name_ptr + name_len)).toString('utf8');
+          const fn = hostFunctions.get(name);
+          if (!fn) return -1;
+
+          const args = decodeHostCallArgs(wasmMemory.slice(args_ptr, args_ptr + args_len));
+          const resultBytes = serializeObject(fn(...args));
+          if (resultBytes.length > max_len) return -1;
+
+          wasmMemory.set(resultBytes, out_ptr);
+          return resultBytes.length;
+        } catch (e) {
+          console.error('js_host_call error:', e);
+          return -1;
+        }
+      },
+      js_random_bytes: (buf_ptr, len) => {
+        const bytes = mockRandomBytes(len);
+        if (!bytes || bytes.length !== len) return -1;
+        wasmMemory.set(bytes, buf_ptr);
+        return 0;
+      },
+      js_ext_table_has: (table_id, key_ptr, key_len) => {
+        hostCallCounts.has++;
+        try {
+          const table = externalTables.get(table_id);
+          if (!table) return 0;
+
+          const key = decodeKey(wasmMemory.slice(key_ptr, key_ptr + key_len));
+          if (isExpired(table_id, key)) {
+            table.delete(key);
+            clearExpiry(table_id, key);
+            return 0;
+          }
+          return table.has(key) ? 1 : 0;
+        } catch (e) {
+          console.error('js_ext_table_has error:', e);
+          return -1;
+        }
+      },
+      js_ext_table_value_len: (table_id, key_ptr, key_len) => {
+        try {
+          const table = externalTables.get(table_id);
+          if (!table) return -1;
+
+          const key = decodeKey(wasmMemory.slice(key_ptr, key_ptr + key_len));
+          if (isExpired(table_id, key)) {
+            table.delete(key);
+            clearExpiry(table_id, key);
+            return -1;
+          }
+          const value = table.get(key);
+          return value === undefined ? -1 : value.length;
+        } catch (e) {
+          console.error('js_ext_table_value_len error:', e);
+          return -1;
+        }
+      },
+      js_ext_table_set_ttl: (table_id, key_ptr, key_len, val_ptr, val_len, ttl_ms) => {
+        try {
+          const table = ensureExternalTable(table_id);
+          const key = decodeKey(wasmMemory.slice(key_ptr, key_ptr + key_len));
+          table.set(key, new Uint8Array(wasmMemory.slice(val_ptr, val_ptr + val_len)));
+
+          const id = Number(table_id);
+          if (!entryExpiry.has(id)) entryExpiry.set(id, new Map());
+          entryExpiry.get(id).set(key, now() + Number(ttl_ms));
+          return 0;
+        } catch (e) {
+          console.error('js_ext_table_set_ttl error:', e);
+          return -1;
+        }
+      },
     },
   };
 
   const wasmModule = await WebAssembly.instantiate(wasmBuffer, imports);
+  checkExports(wasmModule.instance, wasmPath);
   wasmInstance = wasmModule.instance;
   wasmMemory = new Uint8Array(wasmInstance.exports.memory.buffer);
 
@@ -178,6 +580,57 @@ function compute(code) {
   return wasmInstance.exports.compute(bufPtr, codeBytes.length);
 }
 
+/**
+ * Make an export call that is expected to fail. Lua errors unwind with
+ * longjmp, which traps on wasm32-freestanding, so a RuntimeError counts as a
+ * failure just like a negative return. The instance is recovered after a trap.
+ */
+function callFails(call) {
+  try {
+    return call() < 0;
+  } catch (e) {
+    if (e instanceof WebAssembly.RuntimeError) {
+      wasmInstance.exports.recover_from_trap();
+      return true;
+    }
+    throw e;
+  }
+}
+
+/**
+ * Run Lua code that is expected to fail (see callFails)
+ */
+function computeFails(code) {
+  return callFails(() => compute(code));
+}
+
+/**
+ * Get the instantiated WASM module (for calling exports directly)
+ */
+function getInstance() {
+  return wasmInstance;
+}
+
+/**
+ * Copy raw bytes (or a UTF-8 string) into the IO buffer
+ * @returns {number} Number of bytes written
+ */
+function writeBuffer(data) {
+  const bytes = typeof data === 'string' ? Buffer.from(data, 'utf8') : data;
+  const bufPtr = wasmInstance.exports.get_buffer_ptr();
+  const bufSize = wasmInstance.exports.get_buffer_size();
+
+  if (bytes.length > bufSize) {
+    throw new Error(`Data too large (${bytes.length} > ${bufSize})`);
+  }
+
+  for (let i = 0; i < bytes.length; i++) {
+    wasmMemory[bufPtr + i] = bytes[i];
+  }
+
+  return bytes.length;
+}
+
 /**
  * Get buffer pointer
  */
@@ -197,6 +650,13 @@ function readResult(ptr, len) {
   return deserializeResult(buffer, len);
 }
 
+/**
+ * Copy raw bytes out of WASM memory
+ */
+function readRawBuffer(ptr, len) {
+  return new Uint8Array(wasmMemory.slice(ptr, ptr + len));
+}
+
 /**
  * Deserialize result (simplified version from cu-deserializer.js)
  */
@@ -266,6 +726,10 @@ function deserializeResult(buffer, len) {
         }
       }
       break;
+
+    case 0x07: // table_ref, decoded from the host-side table
+      result = deserializeObject(buffer.slice(offset - 1, len));
+      break;
     
     default:
       result = null;
@@ -304,6 +768,14 @@ function serializeObject(obj) {
     return new Uint8Array(buffer);
   }
   
+  if (obj instanceof HostHandle) {
+    const buffer = new ArrayBuffer(9);
+    const view = new DataView(buffer);
+    view.setUint8(0, 0x08); // host_handle type
+    view.setBigUint64(1, obj.id, true);
+    return new Uint8Array(buffer);
+  }
+  
   if (Array.isArray(obj)) {
     const arrayTableId = nextTableId++;
     ensureExternalTable(arrayTableId);
@@ -401,6 +873,10 @@ function deserializeObject(buffer) {
         return result;
       }
     
+    case 0x08: // host_handle
+      if (buffer.length < 9) return null;
+      return new HostHandle(view.getBigUint64(1, true));
+    
     default:
       return null;
   }
@@ -471,12 +947,63 @@ function clearIo() {
   }
 }
 
+/**
+ * Register a host resource and get an opaque handle Lua can store
+ */
+function registerHandle(resource) {
+  const handle = new HostHandle(nextHandleId++);
+  hostHandles.set(handle.id, resource);
+  return handle;
+}
+
+/**
+ * Look up the resource behind a handle; dangling handles resolve to null
+ */
+function resolveHandle(handle) {
+  if (!(handle instanceof HostHandle)) return null;
+  return hostHandles.has(handle.id) ? hostHandles.get(handle.id) : null;
+}
+
+/**
+ * Release a handle; Lua copies of it become dangling
+ */
+function releaseHandle(handle) {
+  return handle instanceof HostHandle && hostHandles.delete(handle.id);
+}
+
+/**
+ * Make fn callable from Lua as host.call(name, ...). It receives the
+ * deserialized arguments and its return value is serialized back.
+ */
+function registerHostFunction(name, fn) {
+  hostFunctions.set(name, fn);
+}
+
+/**
+ * Integer keys are stored as canonical decimal text; anything else is a
+ * string key (mirrors parse_integer_key in ext_table.zig)
+ */
+function keyIsInteger(key) {
+  return /^(0|-?[1-9][0-9]*)$/.test(key) && BigInt.asIntN(64, BigInt(key)) === BigInt(key);
+}
+
 /**
  * Reset state for next test
  */
 function reset() {
   externalTables.clear();
+  entryExpiry.clear();
+  hostCallCounts.get = 0;
+  hostCallCounts.has = 0;
+  hostHooks.onGet = null;
+  hostHooks.onRandomBytes = null;
+  hostHooks.keysPerPage = Infinity;
+  hostHooks.dropWrites = false;
+  hostFunctions.clear();
+  mockTime = null;
   nextTableId = 1;
+  hostHandles.clear();
+  nextHandleId = 1n;
   homeTableId = null;
   ioTableId = null;
   wasmInstance = null;
@@ -487,12 +1014,28 @@ module.exports = {
   loadWasm,
   init,
   compute,
+  callFails,
+  computeFails,
   getBufferPtr,
+  getInstance,
+  writeBuffer,
   readResult,
+  readRawBuffer,
+  serializeObject,
+  deserializeObject,
   setInput,
   getOutput,
   setMetadata,
   clearIo,
+  registerHandle,
+  resolveHandle,
+  releaseHandle,
+  HostHandle,
+  setMockTime,
   reset,
   externalTables,
+  hostCallCounts,
+  hostHooks,
+  registerHostFunction,
+  keyIsInteger,
 };
@@ -18,6 +18,18 @@ let wasmMemory = null;
 // External table storage
 const externalTables = new Map();
 let nextTableId = 1;
+
+// Opaque host handles (wire tag 0x08); Lua only ever sees the u64 id
+export class HostHandle {
+  constructor(id) {
+    this.id = BigInt(id);
+  }
+}
+const hostHandles = new Map();
+let nextHandleId = 1n;
+
+// Functions Lua can call with host.call(name, ...)
+const hostFunctions = new Map();
 let homeTableId = null; // Renamed from memoryTableId
 let ioTableId = null; // For _io external table
 let stateRestored = false;
@@ -40,6 +52,119 @@ function ensureExternalTable(tableId) {
   return externalTables.get(id);
 }
 
+// Expiry timestamps (ms) for entries written with js_ext_table_set_ttl
+// Keys arrive as raw bytes. Valid UTF-8 keys are kept as plain strings;
+// anything else (e.g. "\xff") gets a marker and a byte-per-char body so
+// distinct byte strings never collapse into the same Map key.
+const BINARY_KEY_MARKER = '\u0000bin:';
+const strictUtf8 = new TextDecoder('utf-8', { fatal: true, ignoreBOM: true });
+
+function decodeKey(bytes) {
+  try {
+    const key = strictUtf8.decode(bytes);
+    if (!key.startsWith(BINARY_KEY_MARKER)) return key;
+  } catch (e) {
+    // Not UTF-8; fall through to the binary form
+  }
+  let binary = BINARY_KEY_MARKER;
+  for (const byte of bytes) binary += String.fromCharCode(byte);
+  return binary;
+}
+
+function encodeKey(key) {
+  if (key.startsWith(BINARY_KEY_MARKER)) {
+    return Uint8Array.from(key.slice(BINARY_KEY_MARKER.length), (ch) => ch.charCodeAt(0));
+  }
+  return new TextEncoder().encode(key);
+}
+
+// One page for js_ext_table_keys_page: [u32 LE next cursor][newline-separated
+// keys], as many keys from index cursor on as fit in maxLen, at most maxKeys.
+// The next cursor is 0 after the last key. Returns null if even the first
+// key doesn't fit.
+function encodeKeysPage(keys, cursor, maxLen, maxKeys = Infinity) {
+  const parts = [];
+  let size = 4;
+  let index = cursor;
+  while (index < keys.length && parts.length < maxKeys) {
+    const key = encodeKey(keys[index]);
+    const needed = key.length + (parts.length > 0 ? 1 : 0);
+    if (size + needed > maxLen) break;
+    parts.push(key);
+    size += needed;
+    index++;
+  }
+  if (parts.length === 0 && index < keys.length) return null;
+
+  const bytes = new Uint8Array(size);
+  new DataView(bytes.buffer).setUint32(0, index < keys.length ? index : 0, true);
+  let offset = 4;
+  parts.forEach((part, i) => {
+    if (i > 0) bytes[offset++] = 0x0A;
+    bytes.set(part, offset);
+    offset += part.length;
+  });
+  return bytes;
+}
+
+// Records of [u32 LE key length][key][u32 LE value length][value], the
+// table dump format of js_ext_table_bulk_dump and js_ext_table_bulk_load
+function encodeTableDump(entries) {
+  const records = entries.map(([key, value]) => [encodeKey(key), value]);
+  const size = records.reduce((n, [key, value]) => n + 8 + key.length + value.length, 0);
+  const bytes = new Uint8Array(size);
+  const view = new DataView(bytes.buffer);
+  let offset = 0;
+  for (const [key, value] of records) {
+    view.setUint32(offset, key.length, true);
+    bytes.set(key, offset + 4);
+    offset += 4 + key.length;
+    view.setUint32(offset, value.length, true);
+    bytes.set(value, offset + 4);
+    offset += 4 + value.length;
+  }
+  return bytes;
+}
+
+function decodeTableDump(bytes) {
+  const view = new DataView(bytes.buffer, bytes.byteOffset, bytes.byteLength);
+  const entries = [];
+  let offset = 0;
+  while (offset < bytes.length) {
+    const keyLen = view.getUint32(offset, true);
+    const key = decodeKey(bytes.slice(offset + 4, offset + 4 + keyLen));
+    offset += 4 + keyLen;
+    const valueLen = view.getUint32(offset, true);
+    entries.push([key, bytes.slice(offset + 4, offset + 4 + valueLen)]);
+    offset += 4 + valueLen;
+  }
+  return entries;
+}
+
+// Newline-separated key list as js_ext_table_keys returns it
+function encodeKeyList(keys) {
+  const parts = Array.from(keys, encodeKey);
+  const bytes = new Uint8Array(parts.reduce((n, part) => n + part.length, 0) + Math.max(parts.length - 1, 0));
+  let offset = 0;
+  parts.forEach((part, i) => {
+    if (i > 0) bytes[offset++] = 0x0A;
+    bytes.set(part, offset);
+    offset += part.length;
+  });
+  return bytes;
+}
+
+const entryExpiry = new Map();
+
+function isExpired(tableId, key) {
+  const expiresAt = entryExpiry.get(Number(tableId))?.get(key);
+  return expiresAt !== undefined && Date.now() >= expiresAt;
+}
+
+function clearExpiry(tableId, key) {
+  entryExpiry.get(Number(tableId))?.delete(key);
+}
+
 function getMaxTableId() {
   let maxId = 0;
   for (const id of externalTables.keys()) {
@@ -68,6 +193,7 @@ async function restorePersistedTables() {
     const { tables, metadata } = await persistence.loadTables();
 
     externalTables.clear();
+    entryExpiry.clear();
     nextTableId = 1;
     homeTableId = null;
 
@@ -127,6 +253,7 @@ export async function load(options = {}) {
       await restorePersistedTables();
     } else {
       externalTables.clear();
+      entryExpiry.clear();
       nextTableId = 1;
       homeTableId = null;
       stateRestored = false;
@@ -148,12 +275,13 @@ export async function load(options = {}) {
           try {
             const table = ensureExternalTable(table_id);
 
-            const key = new TextDecoder().decode(wasmMemory.slice(key_ptr, key_ptr + key_len));
+            const key = decodeKey(wasmMemory.slice(key_ptr, key_ptr + key_len));
             // Store raw binary data to preserve function bytecode
             const valueBytes = wasmMemory.slice(val_ptr, val_ptr + val_len);
             const valueCopy = new Uint8Array(valueBytes);
 
             table.set(key, valueCopy);
+            clearExpiry(table_id, key);
             return 0;
           } catch (e) {
             console.error('js_ext_table_set error:', e);
@@ -165,7 +293,13 @@ export async function load(options = {}) {
             const table = externalTables.get(table_id);
             if (!table) return -1;
 
-            const key = new TextDecoder().decode(wasmMemory.slice(key_ptr, key_ptr + key_len));
+            const key = decodeKey(wasmMemory.slice(key_ptr, key_ptr + key_len));
+            if (isExpired(table_id, key)) {
+              table.delete(key);
+              clearExpiry(table_id, key);
+              return -1;
+            }
+
             const value = table.get(key);
 
             if (value === undefined) return -1;
@@ -181,7 +315,8 @@ export async function load(options = {}) {
               return -1;
             }
 
-            if (valueBytes.length > max_len) return -1;
+            // Too large: report the size so the module can read it again
+            if (valueBytes.length > max_len) return -valueBytes.length;
 
             for (let i = 0; i < valueBytes.length; i++) {
               wasmMemory[val_ptr + i] = valueBytes[i];
@@ -198,8 +333,9 @@ export async function load(options = {}) {
             const table = externalTables.get(table_id);
             if (!table) return -1;
 
-            const key = new TextDecoder().decode(wasmMemory.slice(key_ptr, key_ptr + key_len));
+            const key = decodeKey(wasmMemory.slice(key_ptr, key_ptr + key_len));
             table.delete(key);
+            clearExpiry(table_id, key);
             return 0;
           } catch (e) {
             console.error('js_ext_table_delete error:', e);
@@ -210,13 +346,26 @@ export async function load(options = {}) {
           const table = externalTables.get(table_id);
           return table ? table.size : 0;
         },
+        js_ext_table_seqlen: (table_id) => {
+          // Border: the largest n with keys 1..n all set, as Lua's # finds. A
+          // tombstone (the serialized nil, 0x00) reads as nil, so it ends the run.
+          const table = externalTables.get(table_id);
+          if (!table) return 0;
+          const present = key => {
+            const value = table.get(key);
+            if (value === undefined || isExpired(table_id, key)) return false;
+            return !(value instanceof Uint8Array && value.length === 1 && value[0] === 0x00);
+          };
+          let n = 0;
+          while (present(String(n + 1))) n++;
+          return n;
+        },
         js_ext_table_keys: (table_id, buf_ptr, max_len) => {
           try {
             const table = externalTables.get(table_id);
             if (!table) return -1;
 
-            const keys = Array.from(table.keys()).join('\n');
-            const keysBytes = new TextEncoder().encode(keys);
+            const keysBytes = encodeKeyList(table.keys());
 
             if (keysBytes.length > max_len) return -1;
 
@@ -230,6 +379,230 @@ export async function load(options = {}) {
             return -1;
           }
         },
+        js_ext_table_keys_page: (table_id, cursor, out_ptr, max_len) => {
+          try {
+            const table = externalTables.get(table_id);
+            if (!table) return -1;
+
+            const page = encodeKeysPage(Array.from(table.keys()), cursor, max_len);
+            if (!page) return -1;
+            wasmMemory.set(page, out_ptr);
+            return page.length;
+          } catch (e) {
+            console.error('js_ext_table_keys_page error:', e);
+            return -1;
+          }
+        },
+        js_ext_table_free: (table_id) => {
+          entryExpiry.delete(Number(table_id));
+          return externalTables.delete(Number(table_id)) ? 0 : -1;
+        },
+        js_ext_table_rename: (table_id, old_ptr, old_len, new_ptr, new_len) => {
+          try {
+            const table = externalTables.get(table_id);
+            if (!table) return -1;
+
+            const oldKey = decodeKey(wasmMemory.slice(old_ptr, old_ptr + old_len));
+            const newKey = decodeKey(wasmMemory.slice(new_ptr, new_ptr + new_len));
+            if (!table.has(oldKey)) return 0;
+
+            const value = table.get(oldKey);
+            table.delete(oldKey);
+            table.set(newKey, value);
+            return 1;
+          } catch (e) {
+            console.error('js_ext_table_rename error:', e);
+            return -1;
+          }
+        },
+        js_ext_table_cas: (table_id, key_ptr, key_len, expected_ptr, expected_len, new_ptr, new_len) => {
+          try {
+            const table = ensureExternalTable(table_id);
+            const key = decodeKey(wasmMemory.slice(key_ptr, key_ptr + key_len));
+            const expected = wasmMemory.slice(expected_ptr, expected_ptr + expected_len);
+            const current = table.get(key) ?? new Uint8Array([0x00]);
+
+            const matches = current.length === expected.length && current.every((b, i) => b === expected[i]);
+            if (!matches) return 0;
+
+            const newValue = new Uint8Array(wasmMemory.slice(new_ptr, new_ptr + new_len));
+            if (newValue.length === 1 && newValue[0] === 0x00) {
+              table.delete(key);
+            } else {
+              table.set(key, newValue);
+            }
+            return 1;
+          } catch (e) {
+            console.error('js_ext_table_cas error:', e);
+            return -1;
+          }
+        },
+        js_ext_table_set_ttl: (table_id, key_ptr, key_len, val_ptr, val_len, ttl_ms) => {
+          try {
+            const table = ensureExternalTable(table_id);
+            const key = decodeKey(wasmMemory.slice(key_ptr, key_ptr + key_len));
+            table.set(key, new Uint8Array(wasmMemory.slice(val_ptr, val_ptr + val_len)));
+
+            const id = Number(table_id);
+            if (!entryExpiry.has(id)) entryExpiry.set(id, new Map());
+            entryExpiry.get(id).set(key, Date.now() + Number(ttl_ms));
+            return 0;
+          } catch (e) {
+            console.error('js_ext_table_set_ttl error:', e);
+            return -1;
+          }
+        },
+        js_ext_table_swap: (table_id, a_ptr, a_len, b_ptr, b_len) => {
+          try {
+            const table = ensureExternalTable(table_id);
+            const keyA = decodeKey(wasmMemory.slice(a_ptr, a_ptr + a_len));
+            const keyB = decodeKey(wasmMemory.slice(b_ptr, b_ptr + b_len));
+            const valueA = isExpired(table_id, keyA) ? undefined : table.get(keyA);
+            const valueB = isExpired(table_id, keyB) ? undefined : table.get(keyB);
+
+            // A missing key is nil: its partner ends up missing too
+            if (valueB === undefined) table.delete(keyA); else table.set(keyA, valueB);
+            if (valueA === undefined) table.delete(keyB); else table.set(keyB, valueA);
+            clearExpiry(table_id, keyA);
+            clearExpiry(table_id, keyB);
+            return 0;
+          } catch (e) {
+            console.error('js_ext_table_swap error:', e);
+            return -1;
+          }
+        },
+        js_ext_table_clone: (src_id, dst_id) => {
+          try {
+            const src = ensureExternalTable(src_id);
+            const dst = ensureExternalTable(dst_id);
+            for (const [key, value] of src) {
+              if (!isExpired(src_id, key)) dst.set(key, value);
+            }
+            // Copied entries expire when the originals do
+            const expiry = entryExpiry.get(Number(src_id));
+            if (expiry) entryExpiry.set(Number(dst_id), new Map(expiry));
+            return 0;
+          } catch (e) {
+            console.error('js_ext_table_clone error:', e);
+            return -1;
+          }
+        },
+        js_ext_table_bulk_dump: (table_id, out_ptr, max_len) => {
+          try {
+            const table = externalTables.get(table_id);
+            if (!table) return -1;
+
+            const live = Array.from(table).filter(([key]) => !isExpired(table_id, key));
+            const dump = encodeTableDump(live);
+            if (dump.length > max_len) return -1;
+            wasmMemory.set(dump, out_ptr);
+            return dump.length;
+          } catch (e) {
+            console.error('js_ext_table_bulk_dump error:', e);
+            return -1;
+          }
+        },
+        js_ext_table_bulk_load: (table_id, in_ptr, len) => {
+          try {
+            const entries = decodeTableDump(wasmMemory.slice(in_ptr, in_ptr + len));
+            const table = ensureExternalTable(table_id);
+            table.clear();
+            entryExpiry.delete(Number(table_id));
+            for (const [key, value] of entries) table.set(key, value);
+            return table.size;
+          } catch (e) {
+            console.error('js_ext_table_bulk_load error:', e);
+            return -1;
+          }
+        },
+        js_ext_table_delete_prefix: (table_id, prefix_ptr, prefix_len) => {
+          try {
+            const table = externalTables.get(table_id);
+            if (!table) return -1;
+
+            // Compare bytes, so binary keys match as they were stored
+            const prefix = wasmMemory.slice(prefix_ptr, prefix_ptr + prefix_len);
+            const matches = (key) => {
+              const bytes = encodeKey(key);
+              return bytes.length >= prefix.length && prefix.every((byte, i) => bytes[i] === byte);
+            };
+
+            let deleted = 0;
+            for (const key of Array.from(table.keys()).filter(matches)) {
+              if (!isExpired(table_id, key)) deleted++;
+              table.delete(key);
+              clearExpiry(table_id, key);
+            }
+            return deleted;
+          } catch (e) {
+            console.error('js_ext_table_delete_prefix error:', e);
+            return -1;
+          }
+        },
+        js_host_call: (name_ptr, name_len, args_ptr, args_len, out_ptr, max_len) => {
+          try {
+            const name = new TextDecoder().decode(wasmMemory.slice(name_ptr, name_ptr + name_len));
+            const fn = hostFunctions.get(name);
+            if (!fn) return -1;
+
+            const args = decodeHostCallArgs(wasmMemory.slice(args_ptr, args_ptr + args_len));
+            const resultBytes = serializeObject(fn(...args));
+            if (resultBytes.length > max_len) return -1;
+
+            wasmMemory.set(resultBytes, out_ptr);
+            return resultBytes.length;
+          } catch (e) {
+            console.error('js_host_call error:', e);
+            return -1;
+          }
+        },
+        js_random_bytes: (buf_ptr, len) => {
+          try {
+            // getRandomValues fills at most 65536 bytes per call
+            for (let offset = 0; offset < len; offset += 65536) {
+              crypto.getRandomValues(wasmMemory.subarray(buf_ptr + offset, buf_ptr + Math.min(len, offset + 65536)));
+            }
+            return 0;
+          } catch (e) {
+            console.error('js_random_bytes error:', e);
+            return -1;
+          }
+        },
+        js_ext_table_has: (table_id, key_ptr, key_len) => {
+          try {
+            const table = externalTables.get(table_id);
+            if (!table) return 0;
+
+            const key = decodeKey(wasmMemory.slice(key_ptr, key_ptr + key_len));
+            if (isExpired(table_id, key)) {
+              table.delete(key);
+              clearExpiry(table_id, key);
+              return 0;
+            }
+            return table.has(key) ? 1 : 0;
+          } catch (e) {
+            console.error('js_ext_table_has error:', e);
+            return -1;
+          }
+        },
+        js_ext_table_value_len: (table_id, key_ptr, key_len) => {
+          try {
+            const table = externalTables.get(table_id);
+            if (!table) return -1;
+
+            const key = decodeKey(wasmMemory.slice(key_ptr, key_ptr + key_len));
+            if (isExpired(table_id, key)) {
+              table.delete(key);
+              clearExpiry(table_id, key);
+              return -1;
+            }
+            const value = table.get(key);
+            return value === undefined ? -1 : value.length;
+          } catch (e) {
+            console.error('js_ext_table_value_len error:', e);
+            return -1;
+          }
+        },
       },
     };
 
@@ -357,6 +730,10 @@ export async function compute(code) {
     return outputBytes.length;
   } catch (error) {
     console.error('compute() error:', error);
+    // A Lua error traps the instance; recover it so the next call works
+    if (error instanceof WebAssembly.RuntimeError && wasmInstance?.exports.recover_from_trap) {
+      wasmInstance.exports.recover_from_trap();
+    }
     // Return error message as buffer content
     const bufPtr = getBufferPtr();
     const encoder = new TextEncoder();
@@ -482,7 +859,7 @@ export function readResult(ptr, len) {
       throw new Error('Invalid buffer range');
     }
     const buffer = wasmMemory.slice(ptr, ptr + len);
-    return deserializeResult(buffer, len);
+    return deserializeResult(buffer, len, externalTableToObject);
   } catch (error) {
     console.error('readResult() error:', error);
     return { output: '', result: null };
@@ -543,6 +920,7 @@ export async function loadState() {
     const { tables, metadata } = await persistence.loadTables();
 
     externalTables.clear();
+    entryExpiry.clear();
     nextTableId = 1;
     homeTableId = null;
 
@@ -702,6 +1080,14 @@ function serializeObject(obj) {
     return new Uint8Array(buffer);
   }
   
+  if (obj instanceof HostHandle) {
+    const buffer = new ArrayBuffer(9);
+    const view = new DataView(buffer);
+    view.setUint8(0, 0x08); // host_handle type
+    view.setBigUint64(1, obj.id, true);
+    return new Uint8Array(buffer);
+  }
+  
   if (Array.isArray(obj)) {
     // Create external table for array
     const arrayTableId = nextTableId++;
@@ -743,6 +1129,39 @@ function serializeObject(obj) {
   return new Uint8Array([0x00]); // fallback to nil
 }
 
+/**
+ * Decode an external table into a JavaScript array or object
+ * @param {number} tableId - External table id
+ * @returns {Array|Object|null} Decoded table, or null if it doesn't exist
+ */
+function externalTableToObject(tableId) {
+  const table = externalTables.get(tableId);
+  if (!table) return null;
+  
+  // Check if it's an array (all keys are sequential numbers starting from 1)
+  const keys = Array.from(table.keys());
+  const isArray = keys.every((key, idx) => key === String(idx + 1));
+  
+  if (isArray) {
+    // Deserialize as array
+    const result = [];
+    for (let i = 1; i <= keys.length; i++) {
+      const value = table.get(String(i));
+      if (value !== undefined) {
+        result.push(deserializeObject(value));
+      }
+    }
+    return result;
+  }
+
+  // Deserialize as object
+  const result = {};
+  for (const [key, value] of table) {
+    result[key] = deserializeObject(value);
+  }
+  return result;
+}
+
 /**
  * Helper to deserialize Lua binary data to JavaScript objects
  * Reconstructs nested objects/arrays from external tables
@@ -782,32 +1201,11 @@ function deserializeObject(buffer) {
     
     case 0x07: // table_ref
       if (buffer.length < 5) return null;
-      const tableId = view.getUint32(1, true);
-      const table = externalTables.get(tableId);
-      if (!table) return null;
-      
-      // Check if it's an array (all keys are sequential numbers starting from 1)
-      const keys = Array.from(table.keys());
-      const isArray = keys.every((key, idx) => key === String(idx + 1));
-      
-      if (isArray) {
-        // Deserialize as array
-        const result = [];
-        for (let i = 1; i <= keys.length; i++) {
-          const value = table.get(String(i));
-          if (value !== undefined) {
-            result.push(deserializeObject(value));
-          }
-        }
-        return result;
-      } else {
-        // Deserialize as object
-        const result = {};
-        for (const [key, value] of table) {
-          result[key] = deserializeObject(value);
-        }
-        return result;
-      }
+      return externalTableToObject(view.getUint32(1, true));
+    
+    case 0x08: // host_handle
+      if (buffer.length < 9) return null;
+      return new HostHandle(view.getBigUint64(1, true));
     
     default:
       return null;
@@ -870,6 +1268,82 @@ export function clearIo() {
   }
 }
 
+/**
+ * Register a host resource and get an opaque handle Lua can store
+ * @param {*} resource - Host object the handle stands for
+ * @returns {HostHandle} Handle to pass to Lua (e.g. via setInput)
+ */
+export function registerHandle(resource) {
+  const handle = new HostHandle(nextHandleId++);
+  hostHandles.set(handle.id, resource);
+  return handle;
+}
+
+/**
+ * Look up the resource behind a handle; dangling handles resolve to null
+ * @param {HostHandle} handle - Handle read back from Lua
+ * @returns {*} The registered resource or null
+ */
+export function resolveHandle(handle) {
+  if (!(handle instanceof HostHandle)) return null;
+  return hostHandles.has(handle.id) ? hostHandles.get(handle.id) : null;
+}
+
+/**
+ * Release a handle; Lua copies of it become dangling
+ * @param {HostHandle} handle - Handle to release
+ * @returns {boolean} True if the handle was registered
+ */
+export function releaseHandle(handle) {
+  return handle instanceof HostHandle && hostHandles.delete(handle.id);
+}
+
+// host.call arguments: u32 count, then per argument a u32 length and the
+// serialized value
+function decodeHostCallArgs(bytes) {
+  const view = new DataView(bytes.buffer, bytes.byteOffset, bytes.byteLength);
+  const count = view.getUint32(0, true);
+  const args = [];
+  let offset = 4;
+  for (let i = 0; i < count; i++) {
+    const len = view.getUint32(offset, true);
+    args.push(deserializeObject(bytes.subarray(offset + 4, offset + 4 + len)));
+    offset += 4 + len;
+  }
+  return args;
+}
+
+/**
+ * Make a function callable from Lua as host.call(name, ...)
+ * @param {string} name - Name scripts call it by
+ * @param {Function} fn - Receives the deserialized arguments; its return
+ *   value is serialized back to Lua (synchronously, so no promises)
+ */
+export function registerHostFunction(name, fn) {
+  hostFunctions.set(name, fn);
+}
+
+/**
+ * Remove a function registered with registerHostFunction
+ * @param {string} name - Registered name
+ * @returns {boolean} True if it was registered
+ */
+export function unregisterHostFunction(name) {
+  return hostFunctions.delete(name);
+}
+
+/**
+ * Check whether an external table key is an integer key. Integer keys are
+ * stored as canonical decimal text, so "7" is the integer 7 while "007",
+ * "+7" and "-0" are plain string keys. Keys are opaque otherwise: never
+ * normalize them (case-fold, trim, re-encode) before storing.
+ * @param {string} key - Key as received from a js_ext_table_* import
+ * @returns {boolean} True if Lua reads this key back as an integer
+ */
+export function keyIsInteger(key) {
+  return /^(0|-?[1-9][0-9]*)$/.test(key) && BigInt.asIntN(64, BigInt(key)) === BigInt(key);
+}
+
 /**
  * Enable or disable legacy "Memory" name alias
  * @param {boolean} enabled - Whether to allow accessing _home via "Memory" name
@@ -903,5 +1377,14 @@ export default {
   setInput,
   getOutput,
   setMetadata,
-  clearIo
+  clearIo,
+  // Host handles
+  registerHandle,
+  resolveHandle,
+  releaseHandle,
+  HostHandle,
+  // Host functions
+  registerHostFunction,
+  unregisterHostFunction,
+  keyIsInteger
 };
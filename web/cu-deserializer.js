@@ -11,6 +11,7 @@ const SerializationType = {
   STRING: 4,
   TABLE: 5,
   FUNCTION: 6,
+  TABLE_REF: 7,
   ERROR: 255
 };
 
@@ -18,9 +19,11 @@ const SerializationType = {
  * Deserialize the result buffer from Lua compute()
  * @param {Uint8Array} buffer - Raw buffer data
  * @param {number} totalLength - Total bytes to read
+ * @param {function(number): any} [resolveTable] - Turns a returned external
+ *   table id into a value; without it tables come back as {tableId}
  * @returns {{output: string, result: any}} Deserialized result
  */
-export function deserializeResult(buffer, totalLength) {
+export function deserializeResult(buffer, totalLength, resolveTable) {
   if (totalLength <= 0) {
     return { output: '', result: null };
   }
@@ -58,7 +61,7 @@ export function deserializeResult(buffer, totalLength) {
   // Deserialize the return value
   let result = null;
   if (offset < totalLength) {
-    const decoded = deserializeValue(buffer, offset, totalLength);
+    const decoded = deserializeValue(buffer, offset, totalLength, resolveTable);
     result = decoded.value;
   }
 
@@ -70,9 +73,10 @@ export function deserializeResult(buffer, totalLength) {
  * @param {Uint8Array} buffer
  * @param {number} offset
  * @param {number} maxLen
+ * @param {function(number): any} [resolveTable]
  * @returns {{value: any, bytesRead: number}}
  */
-function deserializeValue(buffer, offset, maxLen) {
+function deserializeValue(buffer, offset, maxLen, resolveTable) {
   if (offset >= maxLen) {
     return { value: null, bytesRead: 0 };
   }
@@ -139,6 +143,18 @@ function deserializeValue(buffer, offset, maxLen) {
     case SerializationType.FUNCTION:
       return { value: '<function>', bytesRead: 1 };
 
+    case SerializationType.TABLE_REF:
+      if (offset + 4 <= maxLen) {
+        const tableId =
+          (buffer[offset] |
+          (buffer[offset + 1] << 8) |
+          (buffer[offset + 2] << 16) |
+          (buffer[offset + 3] << 24)) >>> 0;
+        const value = resolveTable ? resolveTable(tableId) : { tableId };
+        return { value, bytesRead: 5 };
+      }
+      return { value: null, bytesRead: 1 };
+
     case SerializationType.ERROR:
       if (offset + 4 <= maxLen) {
         // Error message follows same format as string